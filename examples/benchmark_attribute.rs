@@ -0,0 +1,21 @@
+use mpirion::prelude::*;
+
+fn scan_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "prefix-sum", "scan_kernel", |b| {
+        mpirion_bench!(scan_kernel, b, ctx)
+    });
+}
+
+fn setup(comm: &dyn Communicator) -> u64 {
+    comm.rank() as u64
+}
+
+// the attribute registers the kernel, so it doesn't have to be listed in mpirion_main!
+#[mpirion::kernel(setup = setup)]
+fn scan_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.scan_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+mpirion_group!(benches, scan_benchmark);
+mpirion_main!(benches);
@@ -0,0 +1,51 @@
+use mpirion::prelude::*;
+use mpirion::tuning::{sweep, TuningSet};
+
+/// The algorithm of ``MPI_Bcast`` in the tuned collectives of Open MPI, selected by
+/// ``OMPI_MCA_coll_tuned_bcast_algorithm``. The choice only applies with dynamic rules, otherwise
+/// the built-in decision function picks the algorithm by message size. MPICH has the same with
+/// ``MPIR_CVAR_BCAST_INTRA_ALGORITHM``.
+fn algorithm(name: &str, id: &str) -> TuningSet {
+    TuningSet::new(
+        name,
+        [
+            ("OMPI_MCA_coll_tuned_use_dynamic_rules", "1"),
+            ("OMPI_MCA_coll_tuned_bcast_algorithm", id),
+        ],
+    )
+}
+
+// every algorithm at every message size, on a fresh child world each, since the algorithm is
+// fixed when the children initialize MPI
+fn bcast_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let algorithms = [
+        TuningSet::new("default", Vec::<(String, String)>::new()),
+        algorithm("linear", "1"),
+        algorithm("pipeline", "3"),
+        algorithm("binomial", "6"),
+        algorithm("scatter-allgather", "7"),
+    ];
+    let mut group = ctx.benchmark_group(c, "bcast");
+    for (algorithm, bytes) in sweep(&algorithms, [1usize << 6, 1 << 12, 1 << 16, 1 << 20]) {
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_with_input(
+            format!("{}/{}", algorithm.name, bytes),
+            "bcast_kernel",
+            &bytes,
+            |b, &bytes| mpirion_bench!(bcast_kernel, b, ctx; world_size = 8, arg = bytes, tuning = algorithm),
+        );
+    }
+    group.finish();
+}
+
+fn setup(comm: &dyn Communicator, bytes: usize) -> Vec<u8> {
+    vec![comm.rank() as u8; bytes]
+}
+
+fn bcast_kernel(comm: &dyn Communicator, data: &mut Vec<u8>) {
+    comm.process_at_rank(0).broadcast_into(data);
+}
+
+mpirion_kernel!(bcast_kernel, setup, usize);
+mpirion_group!(benches, bcast_benchmark);
+mpirion_main!(benches, bcast_kernel);
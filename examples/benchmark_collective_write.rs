@@ -0,0 +1,70 @@
+use mpirion::io::SharedFile;
+use mpirion::prelude::*;
+
+/// The number of children that write the file.
+const WORLD_SIZE: usize = 4;
+
+// the bandwidth of MPI_File_write_all into one file, in which every rank writes a block of the
+// given size next to the blocks of the other ranks
+fn collective_write_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "collective-write");
+    for block in [1 << 12, 1 << 16, 1 << 20, 1 << 24] {
+        group.throughput(Throughput::Bytes((block * WORLD_SIZE) as u64));
+        group.bench_with_input(
+            block,
+            "write_all_kernel",
+            &block,
+            |b, &block| mpirion_bench!(write_all_kernel, b, ctx; world_size = WORLD_SIZE, arg = block),
+        );
+    }
+    group.finish();
+}
+
+/// The file in the scratch directory of the run, and the block this rank writes into it.
+struct Blocks<'w> {
+    file: SharedFile<'w>,
+    block: Vec<u8>,
+}
+
+// creating the file is not measured, it is deleted after the last iteration
+fn init(comm: &dyn Communicator, block: usize) -> Blocks<'_> {
+    Blocks {
+        file: SharedFile::create(comm),
+        block: vec![comm.rank() as u8; block],
+    }
+}
+
+// every iteration writes into an empty file, so the filesystem allocates the blocks again
+fn reset<'s, 'w>(comm: &dyn Communicator, blocks: &'s Blocks<'w>) -> &'s Blocks<'w> {
+    blocks.file.truncate();
+    blocks
+        .file
+        .seek((comm.rank() as usize * blocks.block.len()) as u64);
+    blocks
+}
+
+fn teardown(_comm: &dyn Communicator, blocks: Blocks<'_>) {
+    blocks.file.delete();
+}
+
+// the write only counts once it reached the storage, not the caches of the filesystem
+fn write_all_kernel(_comm: &dyn Communicator, blocks: &&Blocks) {
+    blocks.file.write_all(&blocks.block);
+    blocks.file.sync();
+}
+
+fn wrote_every_block(comm: &dyn Communicator, blocks: &&Blocks) -> bool {
+    blocks.file.size() == (blocks.block.len() * comm.size() as usize) as u64
+}
+
+mpirion_kernel!(
+    kernel = write_all_kernel,
+    init = init,
+    reset = reset,
+    teardown = teardown,
+    arg = usize,
+    data = ref,
+    validate = wrote_every_block
+);
+mpirion_group!(benches, collective_write_benchmark);
+mpirion_main!(benches, write_all_kernel);
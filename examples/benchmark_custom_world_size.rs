@@ -1,20 +1,22 @@
-use criterion::{BenchmarkId, Criterion};
-use mpi::collective::CommunicatorCollectives;
-use mpi::traits::Communicator;
-use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main};
+use mpirion::prelude::*;
 
-fn simple_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-    let mut group = c.benchmark_group("gossiping");
+fn simple_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "gossiping");
     for size in 2..=8 {
-        group.bench_with_input(BenchmarkId::new("all-to-all", size), &size, |b, &size|
+        group.bench_with_input(
+            ctx.benchmark_name("all-to-all", size, None),
+            "simple_kernel",
+            &size,
+            |b, &size|
             // when altering world size, named parameters syntax needs to be used to avoid
             // ambiguity with input arguments passed to clients
             mpirion_bench! {
                 kernel = simple_kernel,
                 bencher = b,
-                world = world,
+                world = ctx,
                 world_size = size
-            });
+            },
+        );
     }
     group.finish();
 }
@@ -28,6 +30,6 @@ fn simple_kernel(comm: &dyn Communicator, data: &[u64]) {
     comm.all_to_all_into(data, &mut recv_buffer);
 }
 
-mpirion_kernel!(simple_kernel, setup);
+mpirion_kernel!(kernel = simple_kernel, setup = setup, data = ref);
 mpirion_group!(benches, simple_benchmark);
 mpirion_main!(benches, simple_kernel);
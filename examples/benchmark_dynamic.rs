@@ -0,0 +1,61 @@
+use mpirion::prelude::*;
+use mpirion::registry::BootstrapKernel;
+use mpirion::runner::DEFAULT_WORLD_SIZE;
+use mpirion::{bench_dyn, register_kernel};
+
+/// The benchmark matrix, which a real suite would read from a configuration file at startup. Each
+/// line names a benchmark and the collective it runs.
+const SUITE: &str = "
+    all-reduce-small all_reduce
+    all-reduce-large all_reduce
+    scan scan
+";
+
+/// The benchmarks of the suite, as pairs of name and collective.
+fn suite() -> impl Iterator<Item = (&'static str, &'static str)> {
+    SUITE.lines().filter_map(|line| {
+        let mut words = line.split_whitespace();
+        Some((words.next()?, words.next()?))
+    })
+}
+
+/// Register the kernels of the suite. Runs in the parent and in the children.
+fn register_suite() {
+    for (name, collective) in suite() {
+        let run = match collective {
+            "all_reduce" => execute_kernel_all_reduce_kernel,
+            "scan" => execute_kernel_scan_kernel,
+            _ => panic!("unknown collective `{}`", collective),
+        };
+        register_kernel(Box::new(BootstrapKernel::new(name, run)));
+    }
+}
+
+fn dynamic_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "dynamic");
+    for (name, _) in suite() {
+        group.bench_function(name, name, |b| {
+            bench_dyn(name, b, ctx, DEFAULT_WORLD_SIZE, None)
+        });
+    }
+    group.finish();
+}
+
+fn setup(comm: &dyn Communicator) -> u64 {
+    comm.rank() as u64
+}
+
+fn all_reduce_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.all_reduce_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+fn scan_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.scan_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = all_reduce_kernel, setup = setup, data = ref);
+mpirion_kernel!(kernel = scan_kernel, setup = setup, data = ref);
+mpirion_group!(benches, dynamic_benchmark);
+mpirion_main!(init = register_suite; groups = benches);
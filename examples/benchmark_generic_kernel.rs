@@ -0,0 +1,40 @@
+use mpirion::prelude::*;
+
+fn element_type_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "all-reduce-element-type");
+    group.bench_function("u32", "all_reduce_kernel_u32", |b| {
+        mpirion_bench!(all_reduce_kernel_u32, b, ctx)
+    });
+    group.bench_function("u64", "all_reduce_kernel_u64", |b| {
+        mpirion_bench!(all_reduce_kernel_u64, b, ctx)
+    });
+    group.bench_function("f64", "all_reduce_kernel_f64", |b| {
+        mpirion_bench!(all_reduce_kernel_f64, b, ctx)
+    });
+    group.finish();
+}
+
+fn setup<T: From<u8>>(comm: &dyn Communicator) -> Vec<T> {
+    (0..1024)
+        .map(|i| T::from((i + comm.rank() as usize) as u8))
+        .collect()
+}
+
+fn all_reduce_kernel<T: Equivalence + Default + Clone>(comm: &dyn Communicator, data: &mut Vec<T>) {
+    let mut recv_buffer = vec![T::default(); data.len()];
+    comm.all_reduce_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+// registers all_reduce_kernel_u32, all_reduce_kernel_u64 and all_reduce_kernel_f64
+mpirion_kernel!(
+    kernel = all_reduce_kernel,
+    setup = setup,
+    types = [u32, u64, f64]
+);
+mpirion_group!(benches, element_type_benchmark);
+mpirion_main!(
+    benches,
+    all_reduce_kernel_u32,
+    all_reduce_kernel_u64,
+    all_reduce_kernel_f64
+);
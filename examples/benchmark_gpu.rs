@@ -0,0 +1,65 @@
+use mpi::ffi;
+use mpi::raw::AsRaw;
+use mpirion::gpu::{Device, DeviceBuffer};
+use mpirion::prelude::*;
+
+/// The number of floats reduced by each rank.
+const ELEMENTS: usize = 1 << 20;
+
+fn gpu_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    // the children would panic without a GPU, so the benchmark is not even started
+    if !mpirion::gpu::available() {
+        eprintln!("CUDA not available, skipping the device allreduce benchmark");
+        return;
+    }
+    ctx.bench_function(c, "device-allreduce", "device_allreduce_kernel", |b| {
+        mpirion_bench!(device_allreduce_kernel, b, ctx)
+    });
+}
+
+struct Buffers {
+    send: DeviceBuffer<f32>,
+    receive: DeviceBuffer<f32>,
+}
+
+// runs after the context selected the GPU of this child, so the buffers are allocated on it
+fn setup(comm: &dyn Communicator) -> Buffers {
+    Buffers {
+        send: DeviceBuffer::from_host(&vec![comm.rank() as f32; ELEMENTS]),
+        receive: DeviceBuffer::zeros(ELEMENTS),
+    }
+}
+
+// the device pointers are passed to CUDA-aware MPI, which reduces the buffers without copying them
+// to the host first
+fn device_allreduce_kernel(comm: &dyn Communicator, buffers: &mut Buffers, _device: &Device) {
+    unsafe {
+        ffi::MPI_Allreduce(
+            buffers.send.as_ptr(),
+            buffers.receive.as_mut_ptr(),
+            ELEMENTS as i32,
+            ffi::RSMPI_FLOAT,
+            ffi::RSMPI_SUM,
+            comm.as_raw(),
+        );
+    }
+}
+
+fn summed_all_ranks(comm: &dyn Communicator, buffers: &Buffers) -> bool {
+    let size = comm.size() as f32;
+    let expected = size * (size - 1.0) / 2.0;
+    buffers
+        .receive
+        .to_host()
+        .iter()
+        .all(|value| *value == expected)
+}
+
+mpirion_kernel!(
+    kernel = device_allreduce_kernel,
+    setup = setup,
+    context = Device::select,
+    validate = summed_all_ranks
+);
+mpirion_group!(benches, gpu_benchmark);
+mpirion_main!(benches, device_allreduce_kernel);
@@ -0,0 +1,72 @@
+use mpi::point_to_point::send_receive_into;
+use mpi::Rank;
+use mpirion::prelude::*;
+use mpirion::topology::grid;
+
+/// The number of cells along each border of a tile.
+const EDGE: usize = 1024;
+
+fn halo_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "halo-exchange", "halo_kernel", |b| {
+        mpirion_bench!(halo_kernel, b, ctx)
+    });
+}
+
+/// The four borders of the tile of a rank, the halos received from its neighbours, and the
+/// neighbours to exchange them with, in the order (to, from).
+struct Tile {
+    borders: Vec<f64>,
+    halos: Vec<f64>,
+    neighbours: [(Rank, Rank); 4],
+}
+
+// the communicator is the Cartesian one, so the neighbours are looked up outside the measured time
+fn setup(comm: &dyn Communicator) -> Tile {
+    let grid = grid();
+    let mut neighbours = [(0, 0); 4];
+    for dimension in 0..2 {
+        // both dimensions wrap around, so every rank has all four neighbours
+        let (below, above) = grid.shift(comm.rank(), dimension, 1);
+        let (below, above) = (below.unwrap(), above.unwrap());
+        neighbours[2 * dimension] = (above, below);
+        neighbours[2 * dimension + 1] = (below, above);
+    }
+    Tile {
+        borders: vec![comm.rank() as f64; 4 * EDGE],
+        halos: vec![-1.0; 4 * EDGE],
+        neighbours,
+    }
+}
+
+// send each border to the neighbour on its side, and receive the halo of the opposite side
+fn halo_kernel(comm: &dyn Communicator, tile: &mut Tile) {
+    for (direction, (to, from)) in tile.neighbours.iter().enumerate() {
+        let range = direction * EDGE..(direction + 1) * EDGE;
+        send_receive_into(
+            &tile.borders[range.clone()],
+            &comm.process_at_rank(*to),
+            &mut tile.halos[range],
+            &comm.process_at_rank(*from),
+        );
+    }
+}
+
+fn received_all_halos(_comm: &dyn Communicator, tile: &Tile) -> bool {
+    tile.neighbours
+        .iter()
+        .enumerate()
+        .all(|(direction, (_, from))| {
+            tile.halos[direction * EDGE..(direction + 1) * EDGE]
+                .iter()
+                .all(|halo| *halo == *from as f64)
+        })
+}
+
+mpirion_kernel!(
+    kernel = halo_kernel,
+    setup = setup,
+    topology = cart(dims = [0, 0], periodic = [true, true]),
+    validate = received_all_halos
+);
+mpirion_group!(benches, halo_benchmark);
+mpirion_main!(benches, halo_kernel);
@@ -0,0 +1,35 @@
+use mpirion::prelude::*;
+use mpirion::rayon::prelude::*;
+
+/// The number of elements of the vector, over all ranks.
+const ELEMENTS: usize = 1 << 24;
+
+// the same eight cores, split differently between ranks and threads, like sweeping the ranks and
+// OMP_NUM_THREADS of an MPI+OpenMP code
+fn hybrid_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "hybrid-norm");
+    for (ranks, threads) in [(8, 1), (4, 2), (2, 4), (1, 8)] {
+        group.bench_function(
+            format!("p={}/t={}", ranks, threads),
+            "norm_kernel",
+            |b| mpirion_bench!(norm_kernel, b, ctx; world_size = ranks, threads = threads),
+        );
+    }
+    group.finish();
+}
+
+fn setup(comm: &dyn Communicator) -> Vec<f64> {
+    let len = ELEMENTS / comm.size() as usize;
+    (0..len).map(|index| index as f64).collect()
+}
+
+// the squares are summed on the Rayon pool of the child, then the main thread alone calls MPI
+fn norm_kernel(comm: &dyn Communicator, data: &Vec<f64>) {
+    let local: f64 = data.par_iter().map(|value| value * value).sum();
+    let mut global = 0.0;
+    comm.all_reduce_into(&local, &mut global, SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = norm_kernel, setup = setup, data = ref);
+mpirion_group!(benches, hybrid_benchmark);
+mpirion_main!(benches, norm_kernel);
@@ -1,14 +1,17 @@
-use criterion::Criterion;
-use mpi::collective::SystemOperation;
-use mpi::traits::{Communicator, Root};
-use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main};
+// Run only one of the groups with `-- --bench --mpirion-group reduce_bench`.
+use mpirion::prelude::*;
+use std::time::Duration;
 
-fn broadcast_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-    c.bench_function("broadcast", |b| mpirion_bench!(broadcast_kernel, b, world));
+fn broadcast_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "broadcast", "broadcast_kernel", |b| {
+        mpirion_bench!(broadcast_kernel, b, ctx)
+    });
 }
 
-fn reduce_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-    c.bench_function("reduce", |b| mpirion_bench!(reduce_kernel, b, world));
+fn reduce_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "reduce", "reduce_kernel", |b| {
+        mpirion_bench!(reduce_kernel, b, ctx)
+    });
 }
 
 fn setup(comm: &dyn Communicator) -> u64 {
@@ -31,8 +34,18 @@ fn reduce_kernel(comm: &dyn Communicator, data: &u64) {
 }
 
 mpirion_kernel!(broadcast_kernel, setup);
-mpirion_kernel!(reduce_kernel, setup);
+mpirion_kernel!(kernel = reduce_kernel, setup = setup, data = ref);
 mpirion_group!(broadcast_bench, broadcast_benchmark);
-mpirion_group!(reduce_bench, reduce_benchmark);
-// named parameters syntax is required to avoid ambiguity when multiple groups are used
-mpirion_main!(groups = broadcast_bench, reduce_bench; kernels = broadcast_kernel, reduce_kernel);
+// the reduction is slower, so it only takes a few samples. This only affects this group.
+mpirion_group! {
+    name = reduce_bench;
+    config = |c: Criterion| c.sample_size(10);
+    target = reduce_benchmark
+}
+// named parameters syntax is required to avoid ambiguity when multiple groups are used. The config
+// applies to all groups and to the final summary.
+mpirion_main! {
+    config = Criterion::default().measurement_time(Duration::from_secs(10));
+    groups = broadcast_bench, reduce_bench;
+    kernels = broadcast_kernel, reduce_kernel
+}
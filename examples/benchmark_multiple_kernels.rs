@@ -1,12 +1,13 @@
-use criterion::Criterion;
-use mpi::collective::{CommunicatorCollectives, SystemOperation};
-use mpi::traits::Communicator;
-use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main};
+use mpirion::prelude::*;
 
-fn simple_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-    let mut group = c.benchmark_group("cmp-psum-reduce");
-    group.bench_function("prefix-sum", |b| mpirion_bench!(first_kernel, b, world));
-    group.bench_function("all-reduce", |b| mpirion_bench!(second_kernel, b, world));
+fn simple_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "cmp-psum-reduce");
+    group.bench_function("prefix-sum", "first_kernel", |b| {
+        mpirion_bench!(first_kernel, b, ctx)
+    });
+    group.bench_function("all-reduce", "second_kernel", |b| {
+        mpirion_bench!(second_kernel, b, ctx)
+    });
     group.finish();
 }
 
@@ -24,7 +25,7 @@ fn second_kernel(comm: &dyn Communicator, data: &u64) {
     comm.all_reduce_into(data, &mut recv_buffer, SystemOperation::sum());
 }
 
-mpirion_kernel!(first_kernel, setup);
-mpirion_kernel!(second_kernel, setup);
+mpirion_kernel!(kernel = first_kernel, setup = setup, data = ref);
+mpirion_kernel!(kernel = second_kernel, setup = setup, data = ref);
 mpirion_group!(benches, simple_benchmark);
 mpirion_main!(benches, first_kernel, second_kernel);
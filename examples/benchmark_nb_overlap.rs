@@ -0,0 +1,153 @@
+use std::os::raw::c_void;
+
+use mpi::ffi;
+use mpirion::nb::{measure_overlap, overlap, COMPUTE, TOTAL};
+use mpirion::prelude::*;
+use mpirion::reporter::{BenchRecord, MpirionReporter, RunSummary};
+
+/// How often the computation passes over the local vector between starting and completing the
+/// reduction.
+const COMPUTE_PASSES: usize = 8;
+
+fn overlap_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut g = ctx.benchmark_group(c, "iallreduce");
+    for elements in [1u64 << 10, 1 << 14, 1 << 18, 1 << 20] {
+        g.bench_with_input(
+            format!("no-compute/{}", elements),
+            "iallreduce_kernel",
+            &elements,
+            |b, &elements| mpirion_bench!(iallreduce_kernel, b, ctx, elements),
+        );
+        g.bench_with_input(
+            format!("compute/{}", elements),
+            "iallreduce_compute_kernel",
+            &elements,
+            |b, &elements| mpirion_bench!(iallreduce_compute_kernel, b, ctx, elements),
+        );
+    }
+    g.finish();
+}
+
+struct Buffers {
+    send: Vec<f64>,
+    receive: Vec<f64>,
+    local: Vec<f64>,
+}
+
+fn setup(comm: &dyn Communicator, elements: u64) -> Buffers {
+    let elements = elements as usize;
+    Buffers {
+        send: vec![comm.rank() as f64; elements],
+        receive: vec![0.0; elements],
+        local: vec![1.0; elements],
+    }
+}
+
+/// Start an ``MPI_Iallreduce`` of ``send`` into ``receive``, and return the closure that waits for
+/// it. The buffers stay borrowed until the reduction is complete.
+fn iallreduce<'a>(
+    comm: &dyn Communicator,
+    send: &'a [f64],
+    receive: &'a mut [f64],
+) -> impl FnOnce() + 'a {
+    let mut request = std::mem::MaybeUninit::uninit();
+    unsafe {
+        ffi::MPI_Iallreduce(
+            send.as_ptr() as *const c_void,
+            receive.as_mut_ptr() as *mut c_void,
+            send.len() as i32,
+            ffi::RSMPI_DOUBLE,
+            ffi::RSMPI_SUM,
+            comm.as_raw(),
+            request.as_mut_ptr(),
+        );
+    }
+    let mut request = unsafe { request.assume_init() };
+    move || unsafe {
+        ffi::MPI_Wait(&mut request, ffi::RSMPI_STATUS_IGNORE);
+    }
+}
+
+fn smooth(local: &mut [f64]) -> f64 {
+    for _ in 0..COMPUTE_PASSES {
+        for value in local.iter_mut() {
+            *value = value.mul_add(0.5, 0.25);
+        }
+    }
+    local.iter().sum()
+}
+
+// the reduction alone, the time of the communication the computation could hide
+fn iallreduce_kernel(comm: &dyn Communicator, buffers: &mut Buffers) {
+    let Buffers { send, receive, .. } = buffers;
+    measure_overlap(comm, |comm| iallreduce(comm, send, receive), |_| ());
+}
+
+// the reduction with independent computation while it is in flight
+fn iallreduce_compute_kernel(comm: &dyn Communicator, buffers: &mut Buffers) {
+    let Buffers {
+        send,
+        receive,
+        local,
+    } = buffers;
+    let sum = measure_overlap(
+        comm,
+        |comm| iallreduce(comm, send, receive),
+        |_| smooth(local),
+    );
+    std::hint::black_box(sum);
+}
+
+/// Prints how much of the reduction the computation hid at each message size, from the counters of
+/// the benchmarks with and without computation.
+struct OverlapReporter;
+
+impl MpirionReporter for OverlapReporter {
+    fn on_benchmark_complete(&mut self, _record: &BenchRecord) {}
+
+    fn on_group_complete(&mut self, group: &str, records: &[BenchRecord]) {
+        let counter = |record: &BenchRecord, name: &str| {
+            record
+                .metrics
+                .counters
+                .iter()
+                .find(|(counter, _)| counter == name)
+                .map(|(_, mean)| *mean)
+        };
+        for record in records
+            .iter()
+            .filter(|record| record.metrics.id.contains("/compute/"))
+        {
+            let alone_id = record.metrics.id.replace("/compute/", "/no-compute/");
+            let Some(alone) = records.iter().find(|alone| alone.metrics.id == alone_id) else {
+                continue;
+            };
+            if let (Some(communication), Some(compute), Some(total)) = (
+                counter(alone, TOTAL),
+                counter(record, COMPUTE),
+                counter(record, TOTAL),
+            ) {
+                eprintln!(
+                    "{}: {} hid {:.0}% of the reduction ({:?} alone, {:?} compute, {:?} together)",
+                    group,
+                    record.metrics.id,
+                    overlap(communication, compute, total) * 100.0,
+                    communication,
+                    compute,
+                    total
+                );
+            }
+        }
+    }
+
+    fn on_run_complete(&mut self, _summary: &RunSummary) {}
+}
+
+fn register_reporters() {
+    mpirion::register_reporter(Box::new(OverlapReporter));
+}
+
+mpirion_kernel!(kernel = iallreduce_kernel, setup = setup);
+mpirion_kernel!(kernel = iallreduce_compute_kernel, setup = setup);
+mpirion_group!(benches, overlap_benchmark);
+mpirion_main!(init = register_reporters; groups = benches; kernels = iallreduce_kernel, iallreduce_compute_kernel);
@@ -0,0 +1,110 @@
+use mpi::Rank;
+use mpirion::prelude::*;
+use mpirion::topology::{graph, neighbor_all_to_all_into, reported_neighbours, CartesianGrid};
+
+/// The number of values each rank sends to each of its neighbours.
+const BLOCK: usize = 4096;
+
+/// The number of ranks each rank of the random graph sends to.
+const RANDOM_DEGREE: usize = 3;
+
+fn neighbor_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "neighbor-alltoall");
+    for world_size in [4, 8, 16] {
+        group.bench_function(
+            format!("stencil/{}", world_size),
+            "stencil_kernel",
+            |b| mpirion_bench!(stencil_kernel, b, ctx; world_size = world_size),
+        );
+        group.bench_function(
+            format!("random/{}", world_size),
+            "random_kernel",
+            |b| mpirion_bench!(random_kernel, b, ctx; world_size = world_size),
+        );
+    }
+    group.finish();
+}
+
+// the four neighbours of a 5-point stencil on a periodic 2D grid, in the order -x, +x, -y, +y
+fn stencil(rank: Rank, size: Rank) -> Vec<Rank> {
+    let grid = CartesianGrid::new(size, &[0, 0], &[true, true]).unwrap();
+    (0..2)
+        .flat_map(|dimension| {
+            let (below, above) = grid.shift(rank, dimension, 1);
+            [below.unwrap(), above.unwrap()]
+        })
+        .collect()
+}
+
+// a sparse graph with a few distinct destinations per rank, drawn from a generator seeded with the
+// rank, so every rank derives the same graph
+fn random(rank: Rank, size: Rank) -> Vec<Rank> {
+    let mut state = rank as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut mixed = state;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        mixed ^ (mixed >> 31)
+    };
+    let mut destinations = Vec::new();
+    while destinations.len() < RANDOM_DEGREE.min(size as usize - 1) {
+        let destination = (next() % size as u64) as Rank;
+        if destination != rank && !destinations.contains(&destination) {
+            destinations.push(destination);
+        }
+    }
+    destinations
+}
+
+struct Blocks {
+    send: Vec<f64>,
+    receive: Vec<f64>,
+}
+
+// the communicator is the graph one, so the degrees are known before the first iteration
+fn setup(comm: &dyn Communicator) -> Blocks {
+    let neighbours = graph();
+    Blocks {
+        send: vec![comm.rank() as f64; BLOCK * neighbours.destinations.len()],
+        receive: vec![-1.0; BLOCK * neighbours.sources.len()],
+    }
+}
+
+fn exchange(comm: &dyn Communicator, blocks: &mut Blocks) {
+    neighbor_all_to_all_into(comm, &blocks.send[..], &mut blocks.receive[..]);
+}
+
+fn stencil_kernel(comm: &dyn Communicator, blocks: &mut Blocks) {
+    exchange(comm, blocks);
+}
+
+fn random_kernel(comm: &dyn Communicator, blocks: &mut Blocks) {
+    exchange(comm, blocks);
+}
+
+// MPI must report the neighbours of the graph, and every block must come from its source
+fn received_from_sources(comm: &dyn Communicator, blocks: &Blocks) -> bool {
+    let neighbours = graph();
+    reported_neighbours(comm) == neighbours
+        && blocks
+            .receive
+            .chunks(BLOCK)
+            .zip(&neighbours.sources)
+            .all(|(block, source)| block.iter().all(|value| *value == *source as f64))
+}
+
+mpirion_kernel!(
+    kernel = stencil_kernel,
+    setup = setup,
+    topology = graph(stencil),
+    validate = received_from_sources
+);
+mpirion_kernel!(
+    kernel = random_kernel,
+    setup = setup,
+    topology = graph(random),
+    validate = received_from_sources
+);
+mpirion_group!(benches, neighbor_benchmark);
+mpirion_main!(benches, stencil_kernel, random_kernel);
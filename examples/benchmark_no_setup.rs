@@ -0,0 +1,16 @@
+use mpirion::prelude::*;
+
+fn barrier_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "barrier", "barrier_kernel", |b| {
+        mpirion_bench!(barrier_kernel, b, ctx)
+    });
+}
+
+// a kernel without input does not need a setup function
+fn barrier_kernel(comm: &dyn Communicator) {
+    comm.barrier();
+}
+
+mpirion_kernel!(barrier_kernel);
+mpirion_group!(benches, barrier_benchmark);
+mpirion_main!(benches, barrier_kernel);
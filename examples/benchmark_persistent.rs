@@ -0,0 +1,99 @@
+use mpirion::persistent::Persistent;
+use mpirion::prelude::*;
+
+/// The number of cells of each halo.
+const HALO: usize = 4096;
+
+fn persistent_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(
+        c,
+        "persistent-halo-exchange",
+        "persistent_halo_kernel",
+        |b| mpirion_bench!(persistent_halo_kernel, b, ctx),
+    );
+}
+
+/// The persistent requests of a rank on a ring, created once per child process. Each request owns
+/// its buffer, and all of them borrow the communicator they were created on.
+struct Exchange<'w> {
+    send_left: Persistent<'w, f64>,
+    send_right: Persistent<'w, f64>,
+    receive_left: Persistent<'w, f64>,
+    receive_right: Persistent<'w, f64>,
+}
+
+impl Exchange<'_> {
+    fn requests(&self) -> [&Persistent<'_, f64>; 4] {
+        [
+            &self.receive_left,
+            &self.receive_right,
+            &self.send_left,
+            &self.send_right,
+        ]
+    }
+}
+
+// creating the requests is not measured, and they live until the teardown
+fn init(comm: &dyn Communicator) -> Exchange<'_> {
+    let (rank, size) = (comm.rank(), comm.size());
+    let (left, right) = ((rank + size - 1) % size, (rank + 1) % size);
+    let border = vec![rank as f64; HALO];
+    Exchange {
+        send_left: Persistent::send_init(comm, border.clone(), left, 0),
+        send_right: Persistent::send_init(comm, border, right, 1),
+        receive_left: Persistent::receive_init(comm, vec![-1.0; HALO], left, 1),
+        receive_right: Persistent::receive_init(comm, vec![-1.0; HALO], right, 0),
+    }
+}
+
+fn reset<'s, 'w>(_comm: &dyn Communicator, state: &'s Exchange<'w>) -> &'s Exchange<'w> {
+    state
+}
+
+// the requests are freed on all ranks after the last iteration, before the communicator
+fn teardown(_comm: &dyn Communicator, state: Exchange<'_>) {
+    for request in [
+        state.receive_left,
+        state.receive_right,
+        state.send_left,
+        state.send_right,
+    ] {
+        request.free();
+    }
+}
+
+// start all four requests, then complete them
+fn persistent_halo_kernel(_comm: &dyn Communicator, exchange: &&Exchange) {
+    for request in exchange.requests() {
+        request.start();
+    }
+    for request in exchange.requests() {
+        request.wait();
+    }
+}
+
+fn received_both_halos(comm: &dyn Communicator, exchange: &&Exchange) -> bool {
+    let (rank, size) = (comm.rank(), comm.size());
+    let (left, right) = ((rank + size - 1) % size, (rank + 1) % size);
+    exchange
+        .receive_left
+        .buffer()
+        .iter()
+        .all(|cell| *cell == left as f64)
+        && exchange
+            .receive_right
+            .buffer()
+            .iter()
+            .all(|cell| *cell == right as f64)
+}
+
+mpirion_kernel!(
+    kernel = persistent_halo_kernel,
+    init = init,
+    reset = reset,
+    teardown = teardown,
+    data = ref,
+    validate = received_both_halos
+);
+mpirion_group!(benches, persistent_benchmark);
+mpirion_main!(benches, persistent_halo_kernel);
@@ -0,0 +1,95 @@
+use mpi::Rank;
+use mpirion::pair::{remote_size, Report};
+use mpirion::prelude::*;
+
+/// The number of values in each message.
+const MESSAGE: usize = 1 << 16;
+
+/// The number of messages each producer sends in an iteration.
+const MESSAGES: usize = 8;
+
+// four producers stream to one, two and four consumers, reporting the time of the consumers, which
+// wait for the producers
+fn stream_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "stream");
+    for consumers in [1, 2, 4] {
+        group.bench_with_input(
+            format!("4-to/{}", consumers),
+            "produce_kernel",
+            &consumers,
+            |b, &consumers| {
+                mpirion_bench!(
+                    produce_kernel, b, ctx;
+                    world_size = 4, consumer = consume_kernel, consumers = consumers, report = Report::Consumers
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+/// The value a producer sends at ``index`` of every message, so the consumers can check the sum of
+/// what they received.
+fn value(producer: Rank, index: usize) -> u64 {
+    producer as u64 * MESSAGE as u64 + index as u64
+}
+
+fn producer_setup(comm: &dyn Communicator) -> Vec<u64> {
+    (0..MESSAGE)
+        .map(|index| value(comm.rank(), index))
+        .collect()
+}
+
+/// The messages a consumer received and their checksum.
+struct Received {
+    message: Vec<u64>,
+    checksum: u64,
+}
+
+fn consumer_setup(_comm: &dyn Communicator) -> Received {
+    Received {
+        message: vec![0; MESSAGE],
+        checksum: 0,
+    }
+}
+
+// each producer sends to the consumer with its rank modulo the number of consumers
+fn produce_kernel(comm: &dyn Communicator, message: &Vec<u64>) {
+    let consumer = comm.process_at_rank(comm.rank() % remote_size());
+    for _ in 0..MESSAGES {
+        consumer.send(message);
+    }
+}
+
+// the producers of a consumer are its rank plus multiples of the number of consumers
+fn consume_kernel(comm: &dyn Communicator, received: &mut Received) {
+    received.checksum = 0;
+    for producer in (comm.rank()..remote_size()).step_by(comm.size() as usize) {
+        let producer = comm.process_at_rank(producer);
+        for _ in 0..MESSAGES {
+            producer.receive_into(&mut received.message[..]);
+            received.checksum = received
+                .message
+                .iter()
+                .fold(received.checksum, |sum, value| sum.wrapping_add(*value));
+        }
+    }
+}
+
+fn received_everything(comm: &dyn Communicator, received: &Received) -> bool {
+    let expected = (comm.rank()..remote_size())
+        .step_by(comm.size() as usize)
+        .flat_map(|producer| (0..MESSAGE).map(move |index| value(producer, index)))
+        .fold(0u64, |sum, value| sum.wrapping_add(value))
+        .wrapping_mul(MESSAGES as u64);
+    received.checksum == expected
+}
+
+mpirion_kernel!(kernel = produce_kernel, setup = producer_setup, data = ref);
+mpirion_kernel!(
+    kernel = consume_kernel,
+    setup = consumer_setup,
+    validate = received_everything
+);
+mpirion_group!(benches, stream_benchmark);
+mpirion_main!(benches, produce_kernel, consume_kernel);
@@ -0,0 +1,84 @@
+use mpi::point_to_point::send_receive_into;
+use mpi::Rank;
+use mpirion::prelude::*;
+use mpirion::topology::{compare_reorder, grid};
+
+/// The number of cells of each face of a block.
+const FACE: usize = 1 << 14;
+
+// the same halo exchange on a grid without and with reordered ranks, whose ids end in /noreorder
+// and /reorder. The mpirion.json of both records whether MPI permuted the ranks.
+fn reorder_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "reorder");
+    for world_size in [8, 16] {
+        compare_reorder(
+            &mut group,
+            world_size,
+            "halo_kernel",
+            |b, reorder| mpirion_bench!(halo_kernel, b, ctx; world_size = world_size, reorder = reorder),
+        );
+    }
+    group.finish();
+}
+
+/// The six faces of the block of a rank, the halos received from its neighbours, and the
+/// neighbours to exchange them with, in the order (to, from).
+struct Block {
+    faces: Vec<f64>,
+    halos: Vec<f64>,
+    neighbours: [(Rank, Rank); 6],
+}
+
+// the ranks of the grid are the ones of the selected communicator, so they are looked up on it
+fn setup(comm: &dyn Communicator) -> Block {
+    let grid = grid();
+    let mut neighbours = [(0, 0); 6];
+    for dimension in 0..3 {
+        let (below, above) = grid.shift(comm.rank(), dimension, 1);
+        let (below, above) = (below.unwrap(), above.unwrap());
+        neighbours[2 * dimension] = (above, below);
+        neighbours[2 * dimension + 1] = (below, above);
+    }
+    Block {
+        faces: vec![comm.rank() as f64; 6 * FACE],
+        halos: vec![-1.0; 6 * FACE],
+        neighbours,
+    }
+}
+
+fn halo_kernel(comm: &dyn Communicator, block: &mut Block) {
+    for (direction, (to, from)) in block.neighbours.iter().enumerate() {
+        let range = direction * FACE..(direction + 1) * FACE;
+        send_receive_into(
+            &block.faces[range.clone()],
+            &comm.process_at_rank(*to),
+            &mut block.halos[range],
+            &comm.process_at_rank(*from),
+        );
+    }
+}
+
+fn received_all_halos(_comm: &dyn Communicator, block: &Block) -> bool {
+    block
+        .neighbours
+        .iter()
+        .enumerate()
+        .all(|(direction, (_, from))| {
+            block.halos[direction * FACE..(direction + 1) * FACE]
+                .iter()
+                .all(|halo| *halo == *from as f64)
+        })
+}
+
+mpirion_kernel!(
+    kernel = halo_kernel,
+    setup = setup,
+    topology = cart(
+        dims = [0, 0, 0],
+        periodic = [true, true, true],
+        reorder = compare
+    ),
+    validate = received_all_halos
+);
+mpirion_group!(benches, reorder_benchmark);
+mpirion_main!(benches, halo_kernel);
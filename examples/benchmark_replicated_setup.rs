@@ -0,0 +1,59 @@
+use mpirion::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn stencil_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "random-stencil", "stencil_kernel", |b| {
+        mpirion_bench!(stencil_kernel, b, ctx)
+    });
+}
+
+// drawn at random before every iteration, but must be the same on all ranks
+#[derive(Equivalence, Default, Clone, Copy)]
+struct Stencil {
+    weights: [f64; 8],
+    offsets: [u32; 8],
+    scale: f64,
+}
+
+// only called on rank 0, the other ranks receive its stencil
+fn setup(_comm: &dyn Communicator) -> Stencil {
+    let mut state = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+        | 1;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut stencil = Stencil {
+        scale: 1.0 / 8.0,
+        ..Stencil::default()
+    };
+    for (weight, offset) in stencil.weights.iter_mut().zip(stencil.offsets.iter_mut()) {
+        *weight = (next() >> 11) as f64 / (1u64 << 53) as f64;
+        *offset = (next() % 1024) as u32;
+    }
+    stencil
+}
+
+fn stencil_kernel(comm: &dyn Communicator, stencil: &Stencil) {
+    let base = comm.rank() as f64;
+    let local: f64 = stencil
+        .weights
+        .iter()
+        .zip(stencil.offsets.iter())
+        .map(|(weight, offset)| weight * (base + *offset as f64))
+        .sum::<f64>()
+        * stencil.scale;
+
+    let mut global = 0.0;
+    comm.all_reduce_into(&local, &mut global, SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = stencil_kernel, replicated_setup = setup, data = ref);
+mpirion_group!(benches, stencil_benchmark);
+mpirion_main!(benches, stencil_kernel);
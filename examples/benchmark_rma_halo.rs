@@ -0,0 +1,129 @@
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+use mpi::ffi;
+use mpi::Rank;
+use mpirion::prelude::*;
+
+/// The number of cells of each halo.
+const HALO: usize = 1024;
+
+fn rma_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "put-halo-exchange", "put_halo_kernel", |b| {
+        mpirion_bench!(put_halo_kernel, b, ctx)
+    });
+}
+
+/// An RMA window over the two halos of a rank, the left one at displacement 0 and the right one
+/// at displacement ``HALO``. It borrows the communicator it was created on, and must be freed on
+/// all ranks with ``free`` before MPI is finalized.
+struct Window<'a> {
+    raw: ffi::MPI_Win,
+    halos: Vec<f64>,
+    _comm: PhantomData<&'a dyn Communicator>,
+}
+
+impl<'a> Window<'a> {
+    fn create(comm: &'a dyn Communicator) -> Self {
+        let mut halos = vec![-1.0; 2 * HALO];
+        let mut raw = std::mem::MaybeUninit::uninit();
+        // the halos live on the heap, so moving the window does not move the exposed memory
+        unsafe {
+            ffi::MPI_Win_create(
+                halos.as_mut_ptr() as *mut c_void,
+                (halos.len() * std::mem::size_of::<f64>()) as ffi::MPI_Aint,
+                std::mem::size_of::<f64>() as i32,
+                ffi::RSMPI_INFO_NULL,
+                comm.as_raw(),
+                raw.as_mut_ptr(),
+            );
+        }
+        Self {
+            raw: unsafe { raw.assume_init() },
+            halos,
+            _comm: PhantomData,
+        }
+    }
+
+    fn fence(&self) {
+        unsafe {
+            ffi::MPI_Win_fence(0, self.raw);
+        }
+    }
+
+    /// Write ``values`` into the window of ``target`` at the given displacement.
+    fn put(&self, values: &[f64], target: Rank, displacement: usize) {
+        unsafe {
+            ffi::MPI_Put(
+                values.as_ptr() as *const c_void,
+                values.len() as i32,
+                ffi::RSMPI_DOUBLE,
+                target,
+                displacement as ffi::MPI_Aint,
+                values.len() as i32,
+                ffi::RSMPI_DOUBLE,
+                self.raw,
+            );
+        }
+    }
+
+    /// Free the window, which is collective over its communicator.
+    fn free(mut self) {
+        unsafe {
+            ffi::MPI_Win_free(&mut self.raw);
+        }
+    }
+}
+
+/// The window and the borders of a rank, created once per child process.
+struct Exchange<'a> {
+    window: Window<'a>,
+    borders: Vec<f64>,
+}
+
+// the window borrows the communicator, so it lives in the state of the child
+fn init(comm: &dyn Communicator) -> Exchange<'_> {
+    Exchange {
+        window: Window::create(comm),
+        borders: vec![comm.rank() as f64; HALO],
+    }
+}
+
+fn reset<'s, 'a>(_comm: &dyn Communicator, state: &'s Exchange<'a>) -> &'s Exchange<'a> {
+    state
+}
+
+// freeing the window is collective, so it happens on all ranks after the last iteration
+fn teardown(_comm: &dyn Communicator, state: Exchange<'_>) {
+    state.window.free();
+}
+
+// put the border of this rank into the halos of both neighbours on a ring, between two fences
+fn put_halo_kernel(comm: &dyn Communicator, exchange: &&Exchange) {
+    let (rank, size) = (comm.rank(), comm.size());
+    exchange.window.fence();
+    exchange.window.put(&exchange.borders, (rank + 1) % size, 0);
+    exchange
+        .window
+        .put(&exchange.borders, (rank + size - 1) % size, HALO);
+    exchange.window.fence();
+}
+
+fn received_both_halos(comm: &dyn Communicator, exchange: &&Exchange) -> bool {
+    let (rank, size) = (comm.rank(), comm.size());
+    let (left, right) = exchange.window.halos.split_at(HALO);
+    left.iter()
+        .all(|cell| *cell == ((rank + size - 1) % size) as f64)
+        && right.iter().all(|cell| *cell == ((rank + 1) % size) as f64)
+}
+
+mpirion_kernel!(
+    kernel = put_halo_kernel,
+    init = init,
+    reset = reset,
+    teardown = teardown,
+    data = ref,
+    validate = received_both_halos
+);
+mpirion_group!(benches, rma_benchmark);
+mpirion_main!(benches, put_halo_kernel);
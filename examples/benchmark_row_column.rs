@@ -0,0 +1,56 @@
+use mpi::Rank;
+use mpirion::prelude::*;
+use mpirion::split::{Split, SplitSpec};
+
+/// The number of elements of a panel of the matrix.
+const PANEL: usize = 4096;
+
+fn row_column_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "row-broadcast-column-reduce", "row_column_kernel", |b| {
+        mpirion_bench!(row_column_kernel, b, ctx)
+    });
+}
+
+// arrange the children in a grid that is as square as possible, the last row may be shorter
+fn square_grid(world: &dyn Communicator) -> SplitSpec {
+    let columns = ((world.size() as f64).sqrt() as Rank).max(1);
+    SplitSpec::rows_and_columns(world.rank(), columns)
+}
+
+struct Panels {
+    panel: Vec<f64>,
+    sum: Vec<f64>,
+}
+
+fn setup(comm: &dyn Communicator) -> Panels {
+    Panels {
+        panel: vec![comm.rank() as f64; PANEL],
+        sum: vec![0.0; PANEL],
+    }
+}
+
+// one step of SUMMA: the first rank of each row broadcasts its panel along the row, then the
+// panels are summed up the columns into the first row
+fn row_column_kernel(_comm: &dyn Communicator, data: &mut Panels, split: &Split) {
+    let (row, column) = (split.part(0), split.part(1));
+    row.process_at_rank(0).broadcast_into(&mut data.panel[..]);
+    if column.rank() == 0 {
+        column.this_process().reduce_into_root(
+            &data.panel[..],
+            &mut data.sum[..],
+            SystemOperation::sum(),
+        );
+    } else {
+        column
+            .process_at_rank(0)
+            .reduce_into(&data.panel[..], SystemOperation::sum());
+    }
+}
+
+mpirion_kernel!(
+    kernel = row_column_kernel,
+    setup = setup,
+    split = square_grid
+);
+mpirion_group!(benches, row_column_benchmark);
+mpirion_main!(benches, row_column_kernel);
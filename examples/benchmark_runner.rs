@@ -0,0 +1,57 @@
+use mpirion::prelude::*;
+use mpirion::registry::KernelRegistry;
+use mpirion::runner::{BenchRunner, ChildRunner};
+
+fn setup(comm: &dyn Communicator) -> u64 {
+    comm.rank() as u64
+}
+
+fn scan_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.scan_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+fn all_reduce_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.all_reduce_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = scan_kernel, setup = setup, data = ref);
+mpirion_kernel!(kernel = all_reduce_kernel, setup = setup, data = ref);
+
+// the suite could just as well be read from a configuration file
+fn kernels() -> KernelRegistry {
+    let mut registry = KernelRegistry::new();
+    registry
+        .register("scan_kernel", execute_kernel_scan_kernel)
+        .register("all_reduce_kernel", execute_kernel_all_reduce_kernel);
+    registry
+}
+
+fn main() {
+    let registry = kernels();
+    if let Some(kernel) = ChildRunner::kernel_from_args() {
+        ChildRunner::new(registry).run(&kernel);
+        return;
+    }
+
+    let universe = mpirion::threading::initialize();
+    let world = universe.world();
+    let mut criterion = Criterion::default().configure_from_args();
+
+    let mut group = criterion.benchmark_group("runtime-suite");
+    for kernel in registry.names() {
+        for world_size in [2, 4, 8] {
+            group.bench_function(format!("{}/{}", kernel, world_size), |b| {
+                b.iter_custom(|iterations| {
+                    BenchRunner::new(kernel, &world)
+                        .world_size(world_size)
+                        .run(iterations)
+                })
+            });
+        }
+    }
+    group.finish();
+
+    criterion.final_summary();
+}
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+
+use mpirion::prelude::*;
+use mpirion::shared::SharedWindow;
+
+/// The number of values the root distributes.
+const VALUES: usize = 1 << 16;
+
+// the copy out of a window shared by the ranks of one node against a broadcast of the same data,
+// both from rank 0, on worlds that fit on one node
+fn shared_window_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "node-broadcast");
+    group.throughput(Throughput::Bytes(
+        (VALUES * std::mem::size_of::<f64>()) as u64,
+    ));
+    for world_size in [2, 4, 8] {
+        group.bench_function(
+            format!("shared-window/{}", world_size),
+            "shared_copy_kernel",
+            |b| mpirion_bench!(shared_copy_kernel, b, ctx; world_size = world_size),
+        );
+        group.bench_function(
+            format!("bcast/{}", world_size),
+            "bcast_kernel",
+            |b| mpirion_bench!(bcast_kernel, b, ctx; world_size = world_size),
+        );
+    }
+    group.finish();
+}
+
+/// The value the root distributes at ``index``.
+fn value(index: usize) -> f64 {
+    index as f64 * 0.5
+}
+
+/// The window, in which only the slice of the root has values, and the private copy of them.
+struct Shared {
+    window: SharedWindow<f64>,
+    received: RefCell<Vec<f64>>,
+}
+
+// allocating the window is not measured, and the root of each node fills its slice once
+fn init(comm: &dyn Communicator) -> Shared {
+    let window = SharedWindow::allocate(comm, VALUES, 0.0);
+    if window.node().rank() == 0 {
+        window
+            .local_mut()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, cell)| *cell = value(index));
+    }
+    window.synchronize();
+    Shared {
+        window,
+        received: RefCell::new(vec![-1.0; VALUES]),
+    }
+}
+
+fn reset<'s>(_comm: &dyn Communicator, shared: &'s Shared) -> &'s Shared {
+    shared.received.borrow_mut().fill(-1.0);
+    shared
+}
+
+fn teardown(_comm: &dyn Communicator, shared: Shared) {
+    shared.window.free();
+}
+
+// the barrier stands for the root announcing its data, then every rank copies it out of the
+// memory of the root
+fn shared_copy_kernel(_comm: &dyn Communicator, shared: &&Shared) {
+    shared.window.synchronize();
+    shared
+        .received
+        .borrow_mut()
+        .copy_from_slice(&shared.window.slice(0));
+}
+
+fn received_shared(_comm: &dyn Communicator, shared: &&Shared) -> bool {
+    shared
+        .received
+        .borrow()
+        .iter()
+        .enumerate()
+        .all(|(index, received)| *received == value(index))
+}
+
+fn setup(comm: &dyn Communicator) -> Vec<f64> {
+    if comm.rank() == 0 {
+        (0..VALUES).map(value).collect()
+    } else {
+        vec![-1.0; VALUES]
+    }
+}
+
+fn bcast_kernel(comm: &dyn Communicator, data: &mut Vec<f64>) {
+    comm.process_at_rank(0).broadcast_into(data);
+}
+
+fn received_broadcast(_comm: &dyn Communicator, data: &[f64]) -> bool {
+    data.iter()
+        .enumerate()
+        .all(|(index, received)| *received == value(index))
+}
+
+mpirion_kernel!(
+    kernel = shared_copy_kernel,
+    init = init,
+    reset = reset,
+    teardown = teardown,
+    data = ref,
+    validate = received_shared
+);
+mpirion_kernel!(
+    kernel = bcast_kernel,
+    setup = setup,
+    validate = received_broadcast
+);
+mpirion_group!(benches, shared_window_benchmark);
+mpirion_main!(benches, shared_copy_kernel, bcast_kernel);
@@ -1,10 +1,9 @@
-use criterion::Criterion;
-use mpi::collective::{CommunicatorCollectives, SystemOperation};
-use mpi::traits::Communicator;
-use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main};
+use mpirion::prelude::*;
 
-fn simple_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-    c.bench_function("prefix-sum", |b| mpirion_bench!(simple_kernel, b, world));
+fn simple_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "prefix-sum", "simple_kernel", |b| {
+        mpirion_bench!(simple_kernel, b, ctx)
+    });
 }
 
 fn setup(comm: &dyn Communicator) -> u64 {
@@ -16,6 +15,6 @@ fn simple_kernel(comm: &dyn Communicator, data: &u64) {
     comm.scan_into(data, &mut recv_buffer, SystemOperation::sum());
 }
 
-mpirion_kernel!(simple_kernel, setup);
+mpirion_kernel!(kernel = simple_kernel, setup = setup, data = ref);
 mpirion_group!(benches, simple_benchmark);
 mpirion_main!(benches, simple_kernel);
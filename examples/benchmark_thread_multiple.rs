@@ -0,0 +1,98 @@
+use mpi::point_to_point::send_receive_into_with_tags;
+use mpirion::prelude::*;
+use mpirion::threading::ThreadSafe;
+
+/// The number of threads of each rank that exchange messages at the same time.
+const THREADS: usize = 2;
+
+/// The number of values each thread sends.
+const VALUES: usize = 1 << 12;
+
+// the ranks exchange messages in pairs, each thread with the same thread of the partner rank
+fn thread_multiple_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "thread-multiple");
+    group.throughput(Throughput::Bytes(
+        (THREADS * VALUES * std::mem::size_of::<u64>()) as u64,
+    ));
+    for world_size in [2, 4] {
+        group.bench_function(
+            world_size,
+            "exchange_kernel",
+            |b| mpirion_bench!(exchange_kernel, b, ctx; world_size = world_size),
+        );
+    }
+    group.finish();
+}
+
+/// The value the given thread of ``rank`` sends at ``index``.
+fn value(rank: i32, thread: usize, index: usize) -> u64 {
+    ((rank as u64) << 48) | ((thread as u64) << 32) | index as u64
+}
+
+/// The messages each thread sends to the partner rank, and the buffers it receives into.
+struct Messages {
+    sent: Vec<Vec<u64>>,
+    received: Vec<Vec<u64>>,
+}
+
+fn setup(comm: &dyn Communicator) -> Messages {
+    Messages {
+        sent: (0..THREADS)
+            .map(|thread| {
+                (0..VALUES)
+                    .map(|index| value(comm.rank(), thread, index))
+                    .collect()
+            })
+            .collect(),
+        received: vec![vec![0; VALUES]; THREADS],
+    }
+}
+
+/// The rank the given rank exchanges its messages with, on worlds of even size.
+fn partner(rank: i32) -> i32 {
+    rank ^ 1
+}
+
+// the threads send and receive concurrently, told apart by the tag, which needs MPI_THREAD_MULTIPLE.
+// The scope joins them before the kernel returns, so the barrier of mpirion never overlaps them.
+fn exchange_kernel(comm: &dyn Communicator, messages: &mut Messages) {
+    let comm = ThreadSafe::new(comm);
+    std::thread::scope(|scope| {
+        for (thread, (sent, received)) in messages
+            .sent
+            .iter()
+            .zip(messages.received.iter_mut())
+            .enumerate()
+        {
+            scope.spawn(move || {
+                let other = comm.process_at_rank(partner(comm.rank()));
+                let tag = thread as i32;
+                send_receive_into_with_tags(&sent[..], &other, tag, &mut received[..], &other, tag);
+            });
+        }
+    });
+}
+
+// every thread received the message of its own counterpart, not the one of another thread
+fn received_intact(comm: &dyn Communicator, messages: &Messages) -> bool {
+    let partner = partner(comm.rank());
+    messages
+        .received
+        .iter()
+        .enumerate()
+        .all(|(thread, received)| {
+            received
+                .iter()
+                .enumerate()
+                .all(|(index, value_received)| *value_received == value(partner, thread, index))
+        })
+}
+
+mpirion_kernel!(
+    kernel = exchange_kernel,
+    setup = setup,
+    threading = multiple,
+    validate = received_intact
+);
+mpirion_group!(benches, thread_multiple_benchmark);
+mpirion_main!(benches, exchange_kernel);
@@ -0,0 +1,84 @@
+use mpirion::op::{Reduction, UserOperation};
+use mpirion::prelude::*;
+
+// the overhead of a reduction MPI calls back into Rust for, against the built-in sum of the same
+// values at several message sizes
+fn user_operation_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut group = ctx.benchmark_group(c, "user-operation");
+    for len in [1usize << 4, 1 << 10, 1 << 16] {
+        group.throughput(Throughput::Bytes((len * std::mem::size_of::<f64>()) as u64));
+        group.bench_with_input(
+            format!("user-sum/{}", len),
+            "user_sum_kernel",
+            &len,
+            |b, &len| mpirion_bench!(user_sum_kernel, b, ctx; world_size = 4, arg = len),
+        );
+        group.bench_with_input(
+            format!("system-sum/{}", len),
+            "system_sum_kernel",
+            &len,
+            |b, &len| mpirion_bench!(system_sum_kernel, b, ctx; world_size = 4, arg = len),
+        );
+    }
+    group.finish();
+}
+
+/// The same sum as ``SystemOperation::sum()``, computed by Rust instead of MPI.
+struct Sum;
+
+impl Reduction for Sum {
+    type Value = f64;
+
+    fn reduce(input: &[f64], inout: &mut [f64]) {
+        for (input, inout) in input.iter().zip(inout.iter_mut()) {
+            *inout += *input;
+        }
+    }
+}
+
+/// The values of this rank, and the sums of the values of all ranks.
+struct Sums {
+    values: Vec<f64>,
+    sums: Vec<f64>,
+}
+
+// small integers, so the sum is exact in any order
+fn setup(comm: &dyn Communicator, len: usize) -> Sums {
+    Sums {
+        values: (0..len)
+            .map(|index| (comm.rank() as usize + index) as f64)
+            .collect(),
+        sums: vec![0.0; len],
+    }
+}
+
+// created once per child and passed as the context, it is freed when the context is dropped
+fn user_sum(_comm: &dyn Communicator) -> UserOperation<Sum> {
+    UserOperation::new()
+}
+
+fn user_sum_kernel(comm: &dyn Communicator, data: &mut Sums, sum: &UserOperation<Sum>) {
+    sum.all_reduce_into(comm, &data.values, &mut data.sums);
+}
+
+fn system_sum_kernel(comm: &dyn Communicator, data: &mut Sums) {
+    comm.all_reduce_into(&data.values[..], &mut data.sums[..], SystemOperation::sum());
+}
+
+fn summed(comm: &dyn Communicator, data: &Sums) -> bool {
+    let size = comm.size() as usize;
+    data.sums
+        .iter()
+        .enumerate()
+        .all(|(index, sum)| *sum == (size * (size - 1) / 2 + size * index) as f64)
+}
+
+mpirion_kernel!(
+    kernel = user_sum_kernel,
+    setup = setup,
+    context = user_sum,
+    validate = summed
+);
+mpirion_kernel!(kernel = system_sum_kernel, setup = setup, validate = summed);
+mpirion_group!(benches, user_operation_benchmark);
+mpirion_main!(benches, user_sum_kernel, system_sum_kernel);
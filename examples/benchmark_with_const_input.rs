@@ -0,0 +1,39 @@
+use mpirion::prelude::*;
+
+const FILL_VALUE: u64 = 7;
+
+fn all_reduce_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut g = ctx.benchmark_group(c, "all-reduce");
+    for size in [1u32, 16, 256, 4096].into_iter() {
+        g.bench_with_input(
+            format!("message-size/{}", size),
+            "all_reduce_kernel",
+            &size,
+            |b, &size| {
+                // the fill value stays the same for the whole run, only the size is swept
+                mpirion_bench!(
+                    kernel = all_reduce_kernel,
+                    bencher = b,
+                    world = ctx,
+                    world_size = 4,
+                    const_arg = FILL_VALUE,
+                    arg = size
+                )
+            },
+        );
+    }
+    g.finish();
+}
+
+fn setup(_comm: &dyn Communicator, fill: u64, size: u32) -> Vec<u64> {
+    vec![fill; size as usize]
+}
+
+fn all_reduce_kernel(comm: &dyn Communicator, data: &mut Vec<u64>) {
+    let mut recv_buffer = vec![0u64; data.len()];
+    comm.all_reduce_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+mpirion_kernel!(all_reduce_kernel, setup);
+mpirion_group!(benches, all_reduce_benchmark);
+mpirion_main!(benches, all_reduce_kernel);
@@ -0,0 +1,27 @@
+use mpirion::mpi::topology::SimpleCommunicator;
+use mpirion::prelude::*;
+
+fn scan_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "isolated-scan", "scan_kernel", |b| {
+        mpirion_bench!(scan_kernel, b, ctx)
+    });
+}
+
+// created once per child process, and shared by all iterations
+fn make_context(comm: &dyn Communicator) -> SimpleCommunicator {
+    comm.duplicate()
+}
+
+fn setup(comm: &dyn Communicator) -> u64 {
+    comm.rank() as u64
+}
+
+// the kernel communicates on its own communicator, so it cannot interfere with mpirion's messages
+fn scan_kernel(_comm: &dyn Communicator, data: &mut u64, isolated: &SimpleCommunicator) {
+    let mut recv_buffer = 0u64;
+    isolated.scan_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = scan_kernel, setup = setup, context = make_context);
+mpirion_group!(benches, scan_benchmark);
+mpirion_main!(benches, scan_kernel);
@@ -0,0 +1,36 @@
+use mpirion::partition::block_shard;
+use mpirion::prelude::*;
+
+const TOTAL_ELEMENTS: u64 = 1 << 24;
+
+fn histogram_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "histogram", "histogram_kernel", |b| {
+        mpirion_bench!(histogram_kernel, b, ctx)
+    });
+}
+
+// expensive, only called once per child process
+fn init(comm: &dyn Communicator) -> Vec<u64> {
+    block_shard(TOTAL_ELEMENTS, comm, |i| {
+        i.wrapping_mul(0x9e3779b97f4a7c15) >> 60
+    })
+}
+
+// cheap, called before every iteration: the input is borrowed from the state, and only the
+// histogram is reset to zero
+fn reset<'a>(_comm: &dyn Communicator, state: &'a [u64]) -> (&'a [u64], [u64; 16]) {
+    (state, [0; 16])
+}
+
+fn histogram_kernel(comm: &dyn Communicator, data: &mut (&[u64], [u64; 16])) {
+    let (input, histogram) = data;
+    let mut local = [0u64; 16];
+    for value in input.iter() {
+        local[*value as usize] += 1;
+    }
+    comm.all_reduce_into(&local[..], &mut histogram[..], SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = histogram_kernel, init = init, reset = reset);
+mpirion_group!(benches, histogram_benchmark);
+mpirion_main!(benches, histogram_kernel);
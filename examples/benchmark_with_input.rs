@@ -1,20 +1,23 @@
-use criterion::{BenchmarkId, Criterion};
-use mpi::collective::CommunicatorCollectives;
-use mpi::traits::Communicator;
-use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main};
+use mpirion::partition::block_shard;
+use mpirion::prelude::*;
 
-fn collective_comm_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-    let mut g = c.benchmark_group("collective-comm");
+fn collective_comm_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    let mut g = ctx.benchmark_group(c, "collective-comm");
     for size in [1, 2, 4, 8, 16, 32, 64, 128, 256].into_iter() {
-        g.bench_with_input(BenchmarkId::new("message-size", size), &size, |b, &size| {
-            mpirion_bench!(collective_comm_kernel, b, world, size)
-        });
+        g.bench_with_input(
+            format!("message-size/{}", size),
+            "collective_comm_kernel",
+            &size,
+            |b, &size| mpirion_bench!(collective_comm_kernel, b, ctx, size),
+        );
     }
     g.finish();
 }
 
 fn setup(comm: &dyn Communicator, size: u32) -> Vec<u64> {
-    (0u64..comm.size() as u64 * size as u64).collect()
+    // every rank sends `size` elements to every other rank
+    let ranks = comm.size() as u64;
+    block_shard(ranks * ranks * size as u64, comm, |i| i)
 }
 
 fn collective_comm_kernel(comm: &dyn Communicator, data: &mut Vec<u64>) {
@@ -22,6 +25,6 @@ fn collective_comm_kernel(comm: &dyn Communicator, data: &mut Vec<u64>) {
     comm.all_to_all_into(data, &mut recv_buffer);
 }
 
-mpirion_kernel!(collective_comm_kernel, setup, u32);
+mpirion_kernel!(collective_comm_kernel, setup);
 mpirion_group!(benches, collective_comm_benchmark);
 mpirion_main!(benches, collective_comm_kernel);
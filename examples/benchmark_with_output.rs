@@ -0,0 +1,40 @@
+use mpirion::prelude::*;
+
+fn prefix_sum_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "prefix-sum", "prefix_sum_kernel", |b| {
+        mpirion_bench!(
+            kernel = prefix_sum_kernel,
+            bencher = b,
+            world = ctx,
+            world_size = 4,
+            output = |prefix_sums: &[u64]| {
+                // cross-check against a sequential reference computed on the parent
+                let reference: Vec<u64> = (0..prefix_sums.len() as u64)
+                    .scan(0, |sum, rank| {
+                        *sum += rank;
+                        Some(*sum)
+                    })
+                    .collect();
+                assert_eq!(prefix_sums, &reference[..]);
+            }
+        )
+    });
+}
+
+// the first element holds the input, the second element receives the prefix sum
+fn setup(comm: &dyn Communicator) -> [u64; 2] {
+    [comm.rank() as u64, 0]
+}
+
+fn prefix_sum_kernel(comm: &dyn Communicator, data: &mut [u64; 2]) {
+    let input = data[0];
+    comm.scan_into(&input, &mut data[1], SystemOperation::sum());
+}
+
+fn project(data: &[u64; 2]) -> u64 {
+    data[1]
+}
+
+mpirion_kernel!(kernel = prefix_sum_kernel, setup = setup, output = project);
+mpirion_group!(benches, prefix_sum_benchmark);
+mpirion_main!(benches, prefix_sum_kernel);
@@ -0,0 +1,34 @@
+use mpirion::prelude::*;
+
+fn all_reduce_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "all-reduce", "all_reduce_kernel", |b| {
+        mpirion_bench!(all_reduce_kernel, b, ctx)
+    });
+}
+
+// the first element holds the input, the second element receives the reduction result
+fn setup(comm: &dyn Communicator) -> [u64; 2] {
+    [comm.rank() as u64, 0]
+}
+
+fn all_reduce_kernel(comm: &dyn Communicator, data: &mut [u64; 2]) {
+    let input = data[0];
+    comm.all_reduce_into(&input, &mut data[1], SystemOperation::sum());
+}
+
+// the sum of all ranks 0..p is p * (p - 1) / 2
+fn validate(comm: &dyn Communicator, data: &[u64; 2]) -> bool {
+    // all ranks must have received the same result, regardless of whether it is correct
+    mpirion::assert_agreement(comm, &data[1]);
+
+    let size = comm.size() as u64;
+    data[1] == size * (size - 1) / 2
+}
+
+mpirion_kernel!(
+    kernel = all_reduce_kernel,
+    setup = setup,
+    validate = validate
+);
+mpirion_group!(benches, all_reduce_benchmark);
+mpirion_main!(benches, all_reduce_kernel);
@@ -0,0 +1,47 @@
+use mpirion::prelude::*;
+
+fn setup(comm: &dyn Communicator) -> [u64; 2] {
+    [comm.rank() as u64, 0]
+}
+
+fn prefix_sum_kernel(comm: &dyn Communicator, data: &mut [u64; 2]) {
+    let input = data[0];
+    comm.scan_into(&input, &mut data[1], SystemOperation::sum());
+}
+
+fn all_reduce_kernel(comm: &dyn Communicator, data: &mut [u64; 2]) {
+    let input = data[0];
+    comm.all_reduce_into(&input, &mut data[1], SystemOperation::sum());
+}
+
+fn check_prefix_sum(comm: &dyn Communicator, data: &[u64; 2]) -> bool {
+    let rank = comm.rank() as u64;
+    data[1] == rank * (rank + 1) / 2
+}
+
+fn check_all_reduce(comm: &dyn Communicator, data: &[u64; 2]) -> bool {
+    let size = comm.size() as u64;
+    data[1] == size * (size - 1) / 2
+}
+
+mpirion_test!(
+    prefix_sum,
+    prefix_sum_kernel,
+    setup,
+    check = check_prefix_sum
+);
+mpirion_test!(
+    all_reduce_small,
+    all_reduce_kernel,
+    setup,
+    world_size = 2,
+    check = check_all_reduce
+);
+mpirion_test!(
+    all_reduce_large,
+    all_reduce_kernel,
+    setup,
+    world_size = 16,
+    check = check_all_reduce
+);
+mpirion_test_main!(prefix_sum, all_reduce_small, all_reduce_large);
@@ -0,0 +1,345 @@
+//! Attribute macros for mpirion. Use them through the re-exports in the ``mpirion`` crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, Expr, ExprCall, FnArg, Ident, ItemFn, Path, Token, Type};
+
+/// The options of the ``kernel`` attribute. They mirror the named parameters of
+/// ``mpirion_kernel!``.
+#[derive(Default)]
+struct KernelOptions {
+    name: Option<Ident>,
+    setup: Option<Path>,
+    try_setup: Option<Path>,
+    replicated_setup: Option<Path>,
+    init: Option<Path>,
+    reset: Option<Path>,
+    teardown: Option<Path>,
+    const_arg: Option<Type>,
+    arg: Option<Type>,
+    data: Option<Ident>,
+    threading: Option<Ident>,
+    topology: Option<ExprCall>,
+    split: Option<Expr>,
+    context: Option<Path>,
+    output: Option<Path>,
+    validate: Option<Path>,
+}
+
+impl Parse for KernelOptions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = KernelOptions::default();
+
+        while !input.is_empty() {
+            let key = Ident::parse_any(input)?;
+            input.parse::<Token![=]>()?;
+
+            let duplicate = match key.to_string().as_str() {
+                "name" => options.name.replace(input.parse()?).is_some(),
+                "setup" => options.setup.replace(input.parse()?).is_some(),
+                "try_setup" => options.try_setup.replace(input.parse()?).is_some(),
+                "replicated_setup" => options.replicated_setup.replace(input.parse()?).is_some(),
+                "init" => options.init.replace(input.parse()?).is_some(),
+                "reset" => options.reset.replace(input.parse()?).is_some(),
+                "teardown" => options.teardown.replace(input.parse()?).is_some(),
+                "const_arg" => options.const_arg.replace(input.parse()?).is_some(),
+                "arg" => options.arg.replace(input.parse()?).is_some(),
+                "data" => {
+                    let kind = Ident::parse_any(input)?;
+                    if kind != "ref" && kind != "mut" {
+                        return Err(Error::new(kind.span(), "expected `ref` or `mut`"));
+                    }
+                    options.data.replace(kind).is_some()
+                }
+                "threading" => {
+                    let level = Ident::parse_any(input)?;
+                    if level != "funneled" && level != "serialized" && level != "multiple" {
+                        return Err(Error::new(
+                            level.span(),
+                            "expected `funneled`, `serialized` or `multiple`",
+                        ));
+                    }
+                    options.threading.replace(level).is_some()
+                }
+                "topology" => options.topology.replace(input.parse()?).is_some(),
+                "split" => options.split.replace(input.parse()?).is_some(),
+                "context" => options.context.replace(input.parse()?).is_some(),
+                "output" => options.output.replace(input.parse()?).is_some(),
+                "validate" => options.validate.replace(input.parse()?).is_some(),
+                _ => {
+                    return Err(Error::new(
+                        key.span(),
+                        format!("unknown kernel option `{}`", key),
+                    ))
+                }
+            };
+
+            if duplicate {
+                return Err(Error::new(
+                    key.span(),
+                    format!("duplicate kernel option `{}`", key),
+                ));
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+impl KernelOptions {
+    /// Whether the kernel receives a data parameter.
+    fn has_data(&self) -> bool {
+        self.setup.is_some()
+            || self.try_setup.is_some()
+            || self.replicated_setup.is_some()
+            || self.init.is_some()
+    }
+
+    /// Whether the kernel receives a context parameter, which is the ``Split`` of a kernel with a
+    /// ``split``.
+    fn has_context(&self) -> bool {
+        self.context.is_some() || self.split.is_some()
+    }
+
+    /// The lifecycle part of the ``mpirion_kernel!`` invocation.
+    fn lifecycle(&self, span: proc_macro2::Span) -> syn::Result<TokenStream2> {
+        if let (Some(teardown), None) = (&self.teardown, &self.init) {
+            return Err(Error::new(
+                teardown.span(),
+                "`teardown` can only be declared with `init` and `reset`",
+            ));
+        }
+        match (&self.setup, &self.try_setup, &self.replicated_setup, &self.init, &self.reset) {
+            (Some(setup), None, None, None, None) => Ok(quote!(, setup = #setup)),
+            (None, Some(setup), None, None, None) => Ok(quote!(, try_setup = #setup)),
+            (None, None, Some(setup), None, None) => Ok(quote!(, replicated_setup = #setup)),
+            (None, None, None, Some(init), Some(reset)) => {
+                let teardown = self.teardown.as_ref().map(|path| quote!(, teardown = #path));
+                Ok(quote!(, init = #init, reset = #reset #teardown))
+            }
+            (None, None, None, None, None) => Ok(TokenStream2::new()),
+            (None, None, None, Some(_), None) | (None, None, None, None, Some(_)) => Err(Error::new(
+                span,
+                "`init` and `reset` must be declared together",
+            )),
+            _ => Err(Error::new(
+                span,
+                "only one of `setup`, `try_setup`, `replicated_setup`, or `init` and `reset` can be declared",
+            )),
+        }
+    }
+}
+
+/// Whether ``ty`` is ``&dyn Communicator`` (with any path to the trait).
+fn is_communicator_ref(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_none() => match &*reference.elem {
+            Type::TraitObject(object) => object.bounds.iter().any(|bound| match bound {
+                syn::TypeParamBound::Trait(bound) => bound
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "Communicator"),
+                _ => false,
+            }),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Validate the signature of the kernel function against its options, and infer the reference
+/// kind of the data parameter if it wasn't declared.
+fn validate_signature(function: &ItemFn, options: &mut KernelOptions) -> syn::Result<()> {
+    let signature = &function.sig;
+    if !signature.generics.params.is_empty() {
+        return Err(Error::new(
+            signature.generics.span(),
+            "generic kernels cannot be registered with an attribute, use `mpirion_kernel!` with `types = [...]` instead",
+        ));
+    }
+    if let Some(asyncness) = &signature.asyncness {
+        return Err(Error::new(asyncness.span(), "kernels cannot be async"));
+    }
+
+    if let (Some(_), Some(split)) = (&options.context, &options.split) {
+        return Err(Error::new(
+            split.span(),
+            "only one of `context` and `split` can be declared",
+        ));
+    }
+
+    let inputs: Vec<&FnArg> = signature.inputs.iter().collect();
+    let expected = 1 + options.has_data() as usize + options.has_context() as usize;
+    let description = match (options.has_data(), options.has_context()) {
+        (false, false) => "`&dyn Communicator`",
+        (true, false) => "`&dyn Communicator` and a reference to the data",
+        (false, true) => "`&dyn Communicator` and a reference to the context",
+        (true, true) => {
+            "`&dyn Communicator`, a reference to the data, and a reference to the context"
+        }
+    };
+
+    if inputs.len() != expected {
+        let span = inputs
+            .get(expected)
+            .map(|input| input.span())
+            .unwrap_or_else(|| signature.inputs.span());
+        return Err(Error::new(
+            span,
+            format!(
+                "the kernel must take {} parameter(s): {}",
+                expected, description
+            ),
+        ));
+    }
+
+    let mut types = Vec::new();
+    for input in inputs {
+        match input {
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "kernels cannot take `self`"))
+            }
+            FnArg::Typed(typed) => types.push(&*typed.ty),
+        }
+    }
+
+    if !is_communicator_ref(types[0]) {
+        return Err(Error::new(
+            types[0].span(),
+            "the first parameter of a kernel must be `&dyn Communicator`",
+        ));
+    }
+
+    if options.has_data() {
+        match types[1] {
+            Type::Reference(reference) => {
+                let shared = reference.mutability.is_none();
+                let declared = options.data.as_ref().map(|kind| kind.to_string());
+                match declared.as_deref() {
+                    Some("mut") if shared => {
+                        return Err(Error::new(
+                            reference.span(),
+                            "the data is declared as `data = mut`, but the parameter is a shared reference",
+                        ))
+                    }
+                    Some("ref") if !shared => {
+                        return Err(Error::new(
+                            reference.span(),
+                            "the data is declared as `data = ref`, but the parameter is a mutable reference",
+                        ))
+                    }
+                    Some(_) => {}
+                    None if shared => options.data = Some(format_ident!("ref")),
+                    None => {}
+                }
+            }
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "the data parameter of a kernel must be a reference to the data returned by the setup function",
+                ))
+            }
+        }
+    }
+
+    if options.has_context() {
+        let context = types[types.len() - 1];
+        if !matches!(context, Type::Reference(reference) if reference.mutability.is_none()) {
+            return Err(Error::new(
+                context.span(),
+                "the context parameter of a kernel must be a shared reference",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Register a function as an mpirion kernel. This generates the same bootstrap function as
+/// ``mpirion_kernel!`` and registers it, so ``mpirion_main!`` does not need to list the kernel.
+/// The options are the named parameters of ``mpirion_kernel!``, e.g.
+/// ``#[mpirion::kernel(setup = my_setup, arg = u32)]``. If the data parameter is a shared
+/// reference, ``data = ref`` is inferred.
+///
+/// The kernel is registered under the name of the function, unless another name is given with
+/// ``name = other_name``, like ``kernel = my_kernel as other_name`` of ``mpirion_kernel!``. Kernels
+/// with the same function name in different modules need distinct names, because the children
+/// look them up by name.
+#[proc_macro_attribute]
+pub fn kernel(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = parse_macro_input!(attr as KernelOptions);
+    let function = parse_macro_input!(item as ItemFn);
+
+    match expand_kernel(&mut options, &function) {
+        Ok(expanded) => expanded.into(),
+        Err(error) => {
+            let mut tokens = function.into_token_stream();
+            tokens.extend(error.into_compile_error());
+            tokens.into()
+        }
+    }
+}
+
+fn expand_kernel(options: &mut KernelOptions, function: &ItemFn) -> syn::Result<TokenStream2> {
+    validate_signature(function, options)?;
+
+    let function_name = &function.sig.ident;
+    let name = options.name.as_ref().unwrap_or(function_name);
+    let kernel = match &options.name {
+        Some(alias) => quote!(#function_name as #alias),
+        None => quote!(#function_name),
+    };
+    let lifecycle = options.lifecycle(function.sig.span())?;
+    let const_arg = options
+        .const_arg
+        .as_ref()
+        .map(|ty| quote!(, const_arg = #ty));
+    let arg = options.arg.as_ref().map(|ty| quote!(, arg = #ty));
+    let data = options.data.as_ref().map(|kind| quote!(, data = #kind));
+    let threading = options
+        .threading
+        .as_ref()
+        .map(|level| quote!(, threading = #level));
+    let topology = options
+        .topology
+        .as_ref()
+        .map(|topology| quote!(, topology = #topology));
+    let split = options.split.as_ref().map(|split| quote!(, split = #split));
+    let context = options
+        .context
+        .as_ref()
+        .map(|path| quote!(, context = #path));
+    let output = options.output.as_ref().map(|path| quote!(, output = #path));
+    let validate = options
+        .validate
+        .as_ref()
+        .map(|path| quote!(, validate = #path));
+
+    let bootstrap = format_ident!("execute_kernel_{}", name);
+    let info = format_ident!("kernel_info_{}", name);
+    let entry = format_ident!("__MPIRION_KERNEL_{}", name.to_string().to_uppercase());
+
+    Ok(quote! {
+        #function
+
+        ::mpirion::mpirion_kernel!(kernel = #kernel #lifecycle #const_arg #arg #data #threading #topology #split #context #output #validate);
+
+        #[::mpirion::registry::linkme::distributed_slice(::mpirion::registry::KERNELS)]
+        #[linkme(crate = ::mpirion::registry::linkme)]
+        #[doc(hidden)]
+        static #entry: ::mpirion::registry::KernelEntry = ::mpirion::registry::KernelEntry {
+            name: stringify!(#name),
+            run: #bootstrap,
+            info: #info,
+        };
+    })
+}
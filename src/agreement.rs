@@ -0,0 +1,107 @@
+//! Helpers to check that all ranks of a communicator hold the same value, which is the expected
+//! outcome of most collective kernels.
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::traits::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Panic if ``value`` differs between the ranks of ``comm``. The values are compared by their
+/// hash, so if they agree, the check costs a single all-reduce of two integers. Only if they
+/// disagree, the checksums of all ranks are gathered to list the divergent ranks in the panic
+/// message. This function must be called collectively by all ranks of ``comm``.
+///
+/// It is meant to be called from a validation function of a kernel (see ``mpirion_kernel!``) or
+/// at the end of a setup function.
+///
+/// Floating point values are not hashable; convert them with ``f64::to_bits`` first, or compute a
+/// custom checksum and call [`assert_checksum_agreement`].
+pub fn assert_agreement<T: Hash + ?Sized>(comm: &dyn Communicator, value: &T) {
+    assert_checksum_agreement(comm, checksum(value));
+}
+
+/// The checksum [`assert_agreement`] compares between the ranks.
+pub fn checksum<T: Hash + ?Sized>(value: &T) -> u64 {
+    // the default hasher uses fixed keys, so all processes of the same binary agree on it
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Panic if ``checksum`` differs between the ranks of ``comm``. See [`assert_agreement`].
+pub fn assert_checksum_agreement(comm: &dyn Communicator, checksum: u64) {
+    // the maximum of the inverted checksum is the inverted minimum, so one reduction suffices
+    let local = [checksum, !checksum];
+    let mut global = [0u64; 2];
+    comm.all_reduce_into(&local[..], &mut global[..], SystemOperation::max());
+
+    if global[0] != !global[1] {
+        let mut checksums = vec![0u64; comm.size() as usize];
+        comm.all_gather_into(&checksum, &mut checksums[..]);
+        if let Some(message) = disagreement(&checksums) {
+            panic!("{}", message);
+        }
+    }
+}
+
+/// The message [`assert_checksum_agreement`] panics with for the checksums of all ranks, ordered
+/// by rank, or ``None`` if they agree.
+///
+/// # Example
+/// ```rust
+/// use mpirion::agreement::{checksum, disagreement};
+///
+/// // rank 2 scanned a different input than the others
+/// let inputs = [vec![1u64, 2, 3], vec![1, 2, 3], vec![1, 2, 4], vec![1, 2, 3]];
+/// let checksums: Vec<u64> = inputs.iter().map(|input| checksum(input)).collect();
+/// assert_eq!(
+///     disagreement(&checksums).unwrap(),
+///     "ranks [2] disagree with the majority of the 4 ranks"
+/// );
+/// assert!(disagreement(&[checksum("scan"); 4]).is_none());
+/// ```
+pub fn disagreement(checksums: &[u64]) -> Option<String> {
+    let divergent = divergent_ranks(checksums);
+    if divergent.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "ranks {:?} disagree with the majority of the {} ranks",
+        divergent,
+        checksums.len()
+    ))
+}
+
+/// Find the ranks whose checksum differs from the most common checksum. If multiple checksums are
+/// equally common, the one of the lowest rank is considered correct.
+///
+/// # Example
+/// ```rust
+/// use mpirion::agreement::divergent_ranks;
+///
+/// assert_eq!(divergent_ranks(&[7, 7, 7, 7]), Vec::<usize>::new());
+/// assert_eq!(divergent_ranks(&[7, 3, 7, 7]), vec![1]);
+/// assert_eq!(divergent_ranks(&[3, 7, 7, 5]), vec![0, 3]);
+/// assert_eq!(divergent_ranks(&[3, 3, 7, 7]), vec![2, 3]);
+/// assert_eq!(divergent_ranks(&[]), Vec::<usize>::new());
+/// ```
+pub fn divergent_ranks(checksums: &[u64]) -> Vec<usize> {
+    let mut counts = HashMap::new();
+    for checksum in checksums {
+        *counts.entry(*checksum).or_insert(0usize) += 1;
+    }
+
+    let majority = checksums
+        .iter()
+        .enumerate()
+        .max_by_key(|(rank, checksum)| (counts[*checksum], std::cmp::Reverse(*rank)))
+        .map(|(_, checksum)| *checksum);
+
+    checksums
+        .iter()
+        .enumerate()
+        .filter(|(_, checksum)| Some(**checksum) != majority)
+        .map(|(rank, _)| rank)
+        .collect()
+}
@@ -0,0 +1,331 @@
+//! Statistical comparison of two benchmarks. Criterion compares a benchmark against its own
+//! baseline, while [`compare`] answers whether benchmark B is faster than benchmark A, e.g. two
+//! kernels at the same world size, and by how much.
+//!
+//! The comparison bootstraps the relative difference of the mean times per iteration: the samples
+//! criterion saved for both benchmarks are resampled with replacement [`RESAMPLES`] times, and the
+//! relative difference of the means of each resample gives the [`CONFIDENCE`] interval of the
+//! difference. B is faster or slower than A if the interval does not contain zero. The resamples
+//! are drawn from a fixed seed, so comparing the same samples always gives the same interval.
+//!
+//! Benchmarks of a group that are listed in ``compare = [..]`` of ``mpirion_group!`` are compared
+//! automatically when the group ends, see [`compare_set`]. Each comparison is printed and written
+//! into ``compare-<a>-vs-<b>.json`` in the output directory of criterion.
+//!
+//! Both benchmarks should be measured in the same run, as the comparison cannot tell apart a
+//! faster kernel from a less busy machine.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::reporter::BenchRecord;
+
+/// The number of resamples of the bootstrap.
+pub const RESAMPLES: usize = 10_000;
+
+/// The confidence level of the interval of the difference.
+pub const CONFIDENCE: f64 = 0.95;
+
+/// The seed of the resamples, so the comparison of the same samples is reproducible.
+const SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Whether benchmark B of a [`Comparison`] is faster or slower than benchmark A.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// the whole confidence interval of the difference is below zero
+    Faster,
+    /// the whole confidence interval of the difference is above zero
+    Slower,
+    /// the confidence interval of the difference contains zero
+    NoDifference,
+}
+
+/// The comparison of benchmark B to benchmark A.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    /// the id of benchmark A, the reference
+    pub a: String,
+    /// the id of benchmark B
+    pub b: String,
+    /// the mean time per iteration of benchmark A
+    pub mean_a: Duration,
+    /// the mean time per iteration of benchmark B
+    pub mean_b: Duration,
+    /// the relative difference of the mean of B to the mean of A, negative if B is faster
+    pub difference: f64,
+    /// the lower bound of the confidence interval of the difference
+    pub lower: f64,
+    /// the upper bound of the confidence interval of the difference
+    pub upper: f64,
+    /// whether B is faster or slower than A
+    pub verdict: Verdict,
+}
+
+impl Comparison {
+    /// Compare the times per iteration of benchmark B to those of benchmark A, in nanoseconds.
+    /// Returns ``None`` if either has no times or the mean of A is zero.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::analysis::{Comparison, Verdict};
+    ///
+    /// let ring: Vec<f64> = (0..50).map(|i| 100.0 + (i % 5) as f64).collect();
+    /// let tree: Vec<f64> = (0..50).map(|i| 80.0 + (i % 5) as f64).collect();
+    /// let comparison = Comparison::of("scan/ring/p=16", &ring, "scan/tree/p=16", &tree).unwrap();
+    /// assert_eq!(comparison.verdict, Verdict::Faster);
+    /// assert!(comparison.lower < comparison.difference && comparison.difference < comparison.upper);
+    /// assert!((comparison.difference + 0.196).abs() < 0.001);
+    ///
+    /// let again = Comparison::of("scan/ring/p=16", &ring, "scan/ring/p=16", &ring).unwrap();
+    /// assert_eq!(again.verdict, Verdict::NoDifference);
+    /// ```
+    pub fn of(a: &str, times_a: &[f64], b: &str, times_b: &[f64]) -> Option<Self> {
+        let (mean_a, mean_b) = (mean(times_a)?, mean(times_b)?);
+        if mean_a <= 0.0 {
+            return None;
+        }
+
+        let mut random = SEED;
+        let mut resample = |times: &[f64]| {
+            let sum: f64 = (0..times.len())
+                .map(|_| times[next(&mut random) as usize % times.len()])
+                .sum();
+            sum / times.len() as f64
+        };
+        let mut differences: Vec<f64> = (0..RESAMPLES)
+            .map(|_| {
+                let (resampled_a, resampled_b) = (resample(times_a), resample(times_b));
+                resampled_b / resampled_a - 1.0
+            })
+            .filter(|difference| difference.is_finite())
+            .collect();
+        if differences.is_empty() {
+            return None;
+        }
+        differences.sort_by(f64::total_cmp);
+
+        let tail = (1.0 - CONFIDENCE) / 2.0;
+        let last = differences.len() - 1;
+        let lower = differences[(tail * last as f64).floor() as usize];
+        let upper = differences[((1.0 - tail) * last as f64).ceil() as usize];
+        let verdict = if upper < 0.0 {
+            Verdict::Faster
+        } else if lower > 0.0 {
+            Verdict::Slower
+        } else {
+            Verdict::NoDifference
+        };
+        let nanos = |mean: f64| Duration::from_nanos(mean.round() as u64);
+        Some(Comparison {
+            a: a.to_string(),
+            b: b.to_string(),
+            mean_a: nanos(mean_a),
+            mean_b: nanos(mean_b),
+            difference: mean_b / mean_a - 1.0,
+            lower,
+            upper,
+            verdict,
+        })
+    }
+
+    /// The comparison as a flat JSON object, as written by [`save`].
+    pub fn to_json(&self) -> String {
+        let verdict = match self.verdict {
+            Verdict::Faster => "faster",
+            Verdict::Slower => "slower",
+            Verdict::NoDifference => "no_difference",
+        };
+        format!(
+            r#"{{"mean_a_ns":{},"mean_b_ns":{},"difference":{},"lower":{},"upper":{},"confidence":{},"resamples":{},"verdict":"{}","a":{},"b":{}}}"#,
+            crate::timing::duration_to_nanos(self.mean_a),
+            crate::timing::duration_to_nanos(self.mean_b),
+            self.difference,
+            self.lower,
+            self.upper,
+            CONFIDENCE,
+            RESAMPLES,
+            verdict,
+            crate::manifest::json_string(&self.a),
+            crate::manifest::json_string(&self.b)
+        )
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` ({:.2?}) vs `{}` ({:.2?}): {:+.2}% [{:+.2}%, {:+.2}%] at {:.0}% confidence, ",
+            self.b,
+            self.mean_b,
+            self.a,
+            self.mean_a,
+            self.difference * 100.0,
+            self.lower * 100.0,
+            self.upper * 100.0,
+            CONFIDENCE * 100.0
+        )?;
+        match self.verdict {
+            Verdict::Faster => write!(f, "`{}` is faster", self.b),
+            Verdict::Slower => write!(f, "`{}` is slower", self.b),
+            Verdict::NoDifference => write!(f, "no significant difference"),
+        }
+    }
+}
+
+/// The mean of the given times, or ``None`` if there are none.
+fn mean(times: &[f64]) -> Option<f64> {
+    (!times.is_empty()).then(|| times.iter().sum::<f64>() / times.len() as f64)
+}
+
+/// The next number of a xorshift generator, which is good enough to draw resamples.
+fn next(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Compare benchmark ``id_b`` to benchmark ``id_a``, with the samples criterion saved last for
+/// them in the given output directory of criterion, e.g. ``target/criterion``.
+pub fn compare(directory: &Path, id_a: &str, id_b: &str) -> Result<Comparison, String> {
+    let (times_a, times_b) = (times(directory, id_a)?, times(directory, id_b)?);
+    Comparison::of(id_a, &times_a, id_b, &times_b).ok_or_else(|| {
+        format!(
+            "the samples of `{}` and `{}` cannot be compared",
+            id_a, id_b
+        )
+    })
+}
+
+/// The times per iteration of the samples criterion saved last for the benchmark with the given
+/// id, in nanoseconds.
+fn times(directory: &Path, id: &str) -> Result<Vec<f64>, String> {
+    let saved = crate::report::find_files(directory, "benchmark.json")
+        .into_iter()
+        .filter(|benchmark| {
+            benchmark
+                .parent()
+                .and_then(Path::file_name)
+                .is_some_and(|name| name == "new")
+        })
+        .find(|benchmark| {
+            std::fs::read_to_string(benchmark)
+                .ok()
+                .and_then(|json| crate::report::string_field(&json, "full_id"))
+                .is_some_and(|full_id| full_id == id)
+        })
+        .ok_or_else(|| format!("no samples of `{}` in {}", id, directory.display()))?;
+    let path = saved.with_file_name("sample.json");
+    let json = std::fs::read_to_string(&path)
+        .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+    let samples = crate::report::parse_samples(&json)
+        .ok_or_else(|| format!("could not parse {}", path.display()))?;
+    Ok(samples
+        .into_iter()
+        .filter(|&(iterations, _)| iterations > 0)
+        .map(|(iterations, nanos)| nanos as f64 / iterations as f64)
+        .collect())
+}
+
+/// The file the comparison of benchmark ``b`` to benchmark ``a`` is written to in the output
+/// directory of criterion.
+///
+/// # Example
+/// ```rust
+/// use mpirion::analysis::comparison_file;
+/// use std::path::Path;
+///
+/// assert_eq!(comparison_file(Path::new("target/criterion"), "scan/ring/p=16", "scan/tree/p=16"), Path::new("target/criterion/compare-scan_ring_p_16-vs-scan_tree_p_16.json"));
+/// ```
+pub fn comparison_file(directory: &Path, a: &str, b: &str) -> PathBuf {
+    let name = |id: &str| -> String {
+        id.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+    directory.join(format!("compare-{}-vs-{}.json", name(a), name(b)))
+}
+
+/// Write the comparison into its [`comparison_file`] in the given output directory of criterion.
+pub fn save(directory: &Path, comparison: &Comparison) -> std::io::Result<PathBuf> {
+    let path = comparison_file(directory, &comparison.a, &comparison.b);
+    std::fs::write(&path, comparison.to_json())?;
+    Ok(path)
+}
+
+/// The pairs of benchmarks to compare for a ``compare = [..]`` set. Each entry of the set is the
+/// id of a benchmark without the parameter, e.g. ``scan/ring`` for ``scan/ring/p=16``, and the
+/// other entries are compared to the first one with the same parameter.
+///
+/// # Example
+/// ```rust
+/// use mpirion::analysis::pairs;
+///
+/// let ids = ["scan/ring/p=8", "scan/ring/p=16", "scan/tree/p=16", "scan/tree/p=32", "scan/hypercube/p=16"];
+/// assert_eq!(
+///     pairs(&ids, &["scan/ring", "scan/tree", "scan/hypercube"]),
+///     [("scan/ring/p=16", "scan/tree/p=16"), ("scan/ring/p=16", "scan/hypercube/p=16")]
+/// );
+/// ```
+pub fn pairs<'a>(ids: &[&'a str], set: &[&str]) -> Vec<(&'a str, &'a str)> {
+    let parameter = |id: &'a str, entry: &str| -> Option<&'a str> {
+        let rest = id.strip_prefix(entry)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    };
+    let Some((reference, others)) = set.split_first() else {
+        return Vec::new();
+    };
+    let mut pairs = Vec::new();
+    for other in others {
+        for &a in ids {
+            let Some(parameter_a) = parameter(a, reference) else {
+                continue;
+            };
+            if let Some(&b) = ids
+                .iter()
+                .find(|&&b| parameter(b, other) == Some(parameter_a))
+            {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+/// Compare the benchmarks of a group that are listed in its ``compare = [..]`` set, see [`pairs`],
+/// and print and save the comparisons. Errors are printed, but don't fail the run. Called by
+/// ``mpirion_group!`` after the reporters.
+pub fn compare_set(records: &[BenchRecord], set: &[&str]) {
+    if set.len() < 2 || records.is_empty() {
+        return;
+    }
+    let directory = crate::report::criterion_home();
+    let ids: Vec<&str> = records
+        .iter()
+        .map(|record| record.metrics.id.as_str())
+        .collect();
+    for (a, b) in pairs(&ids, set) {
+        let comparison = match compare(&directory, a, b) {
+            Ok(comparison) => comparison,
+            Err(error) => {
+                eprintln!("mpirion: could not compare `{}` to `{}`: {}", b, a, error);
+                continue;
+            }
+        };
+        println!("mpirion: {}", comparison);
+        if let Err(error) = save(&directory, &comparison) {
+            eprintln!(
+                "mpirion: could not write the comparison of `{}` to `{}`: {}",
+                b, a, error
+            );
+        }
+    }
+}
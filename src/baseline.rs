@@ -0,0 +1,215 @@
+//! Baselines of criterion for comparing MPI runs, e.g. with ``critcmp``, across world sizes and
+//! machines.
+//!
+//! Benchmarks that sweep over world sizes name them with [`world_size_id`], so the same kernel at
+//! the same world size has the same id, ``kernel/p=8``, in every benchmark and on every machine.
+//! ``--mpirion-save-baseline <label>`` saves the results as the criterion baseline
+//! ``<label>-<machine>``, where the machine is the host name of the parent, see [`machine_tag`].
+//! It is passed to criterion as ``--save-baseline``, so the baselines are ordinary criterion
+//! baselines, which ``critcmp`` compares like any other: ``critcmp nightly-node01 nightly-node02``.
+//!
+//! Groups whose results go into a database with its own naming scheme set an [`IdFormatter`], and
+//! their benchmark functions name benchmarks with
+//! [`MpirionContext::benchmark_id`](crate::MpirionContext::benchmark_id). The [`default_id`]
+//! formatter names them like [`world_size_id`].
+//!
+//! [`load`] reads a saved baseline like ``critcmp`` does, for scripts and tests.
+
+use std::fmt::Display;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::criterion::BenchmarkId;
+
+/// The prefix of the world size in the ids of [`world_size_id`].
+pub const WORLD_SIZE_PREFIX: &str = "p=";
+
+/// The parameter of a benchmark id that encodes a world size.
+///
+/// # Example
+/// ```rust
+/// use mpirion::baseline::world_size_parameter;
+///
+/// assert_eq!(world_size_parameter(8), "p=8");
+/// ```
+pub fn world_size_parameter(world_size: impl Display) -> String {
+    format!("{}{}", WORLD_SIZE_PREFIX, world_size)
+}
+
+/// The id of a benchmark of the given kernel at the given world size, ``kernel/p=8``. Sweeps over
+/// world sizes should name their benchmarks with it, so the ids match across benchmarks and
+/// baselines.
+///
+/// # Example
+/// ```rust
+/// use mpirion::baseline::world_size_id;
+/// use mpirion::criterion::Criterion;
+///
+/// fn sweep(c: &mut Criterion) {
+///     let mut group = c.benchmark_group("gossiping");
+///     for world_size in [2, 4, 8] {
+///         // benchmarked as gossiping/all_to_all/p=2 and so on
+///         group.bench_with_input(world_size_id("all_to_all", world_size), &world_size, |b, _| b.iter(|| ()));
+///     }
+///     group.finish();
+/// }
+/// ```
+pub fn world_size_id(kernel: &str, world_size: impl Display) -> BenchmarkId {
+    BenchmarkId::new(kernel, world_size_parameter(world_size))
+}
+
+/// A function that names the benchmark of a kernel at a world size, with the argument of the
+/// kernel if it has one. Benchmark functions derive the ids of their benchmarks from it with
+/// [`MpirionContext::benchmark_id`](crate::MpirionContext::benchmark_id), and groups set it with
+/// ``id_format = formatter`` in ``mpirion_group!``, e.g. to match the naming scheme of a result
+/// database. The id is turned into a criterion ``BenchmarkId`` by [`benchmark_id`].
+pub type IdFormatter = fn(kernel: &str, world_size: usize, arg: Option<&dyn Display>) -> String;
+
+/// The [`IdFormatter`] of groups that don't set one: ``kernel/p=8`` like [`world_size_id`], so
+/// existing baselines stay comparable, and ``kernel/p=8/4096`` with an argument.
+///
+/// # Example
+/// ```rust
+/// use mpirion::baseline::default_id;
+///
+/// assert_eq!(default_id("bcast", 8, None), "bcast/p=8");
+/// assert_eq!(default_id("bcast", 8, Some(&4096)), "bcast/p=8/4096");
+/// ```
+pub fn default_id(kernel: &str, world_size: usize, arg: Option<&dyn Display>) -> String {
+    match arg {
+        Some(arg) => format!("{}/{}/{}", kernel, world_size_parameter(world_size), arg),
+        None => format!("{}/{}", kernel, world_size_parameter(world_size)),
+    }
+}
+
+/// The criterion ``BenchmarkId`` of an id formatted by an [`IdFormatter`]. The part before the
+/// first ``/`` is the function and the rest is the parameter, so ``kernel/p=8`` is the same id as
+/// ``world_size_id("kernel", 8)``, and ids without ``/``, like ``bcast.p8.m4096``, are only a
+/// parameter.
+pub fn benchmark_id(id: &str) -> BenchmarkId {
+    match id.split_once('/') {
+        Some((function, parameter)) => BenchmarkId::new(function, parameter),
+        None => BenchmarkId::from_parameter(id),
+    }
+}
+
+/// A label for the machine the benchmarks run on: the host name, reduced to the characters that
+/// are safe in the name of a baseline directory.
+pub fn machine_tag() -> String {
+    let host = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_default();
+    match sanitize(host.trim()) {
+        tag if tag.is_empty() => "unknown".to_string(),
+        tag => tag,
+    }
+}
+
+/// The name of the baseline for a label on a machine.
+///
+/// # Example
+/// ```rust
+/// use mpirion::baseline::label;
+///
+/// assert_eq!(label("nightly", "node01"), "nightly-node01");
+/// assert_eq!(label("before fix", "node01.cluster"), "before_fix-node01.cluster");
+/// ```
+pub fn label(label: &str, machine: &str) -> String {
+    format!("{}-{}", sanitize(label), sanitize(machine))
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A benchmark in a saved baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineEntry {
+    /// the id of the benchmark in criterion, like ``gossiping/all_to_all/p=8``
+    pub id: String,
+    /// the estimated mean time per iteration
+    pub mean: Duration,
+}
+
+/// Read the benchmarks saved in the given baseline in the output directory of criterion, ordered
+/// by id. Like ``critcmp``, it reads the ``benchmark.json`` and ``estimates.json`` in the baseline
+/// directory of each benchmark.
+pub fn load(home: &Path, baseline: &str) -> Vec<BaselineEntry> {
+    let mut entries: Vec<BaselineEntry> = crate::report::find_files(home, "estimates.json")
+        .into_iter()
+        .filter(|estimates| {
+            estimates
+                .parent()
+                .and_then(Path::file_name)
+                .is_some_and(|name| name == baseline)
+        })
+        .filter_map(|estimates| {
+            let benchmark =
+                std::fs::read_to_string(estimates.with_file_name("benchmark.json")).ok()?;
+            let estimates = std::fs::read_to_string(&estimates).ok()?;
+            Some(BaselineEntry {
+                id: crate::report::string_field(&benchmark, "full_id")?,
+                mean: mean_estimate(&estimates)?,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    entries
+}
+
+/// The point estimate of the mean in an ``estimates.json`` of criterion, in nanoseconds.
+///
+/// # Example
+/// ```rust
+/// use mpirion::baseline::mean_estimate;
+/// use std::time::Duration;
+///
+/// let json = r#"{"mean":{"confidence_interval":{"confidence_level":0.95,"lower_bound":98.0,"upper_bound":103.0},"point_estimate":100.4,"standard_error":1.2},"median":{"point_estimate":99.0}}"#;
+/// assert_eq!(mean_estimate(json), Some(Duration::from_nanos(100)));
+/// assert_eq!(mean_estimate("{}"), None);
+/// ```
+pub fn mean_estimate(estimates: &str) -> Option<Duration> {
+    let mean = &estimates[estimates.find("\"mean\":{")?..];
+    let nanos = crate::report::number_field(mean, "point_estimate")??;
+    (nanos.is_finite() && nanos >= 0.0).then(|| Duration::from_nanos(nanos.round() as u64))
+}
+
+/// The ratio of the mean time in ``new`` to the mean time in ``old`` of each benchmark in both
+/// baselines, ordered by id, like the comparison of ``critcmp``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::baseline::{compare, BaselineEntry};
+/// use std::time::Duration;
+///
+/// let entry = |id: &str, micros| BaselineEntry { id: id.to_string(), mean: Duration::from_micros(micros) };
+/// let old = [entry("scan/p=4", 100), entry("scan/p=8", 200)];
+/// let new = [entry("scan/p=8", 100), entry("scan/p=16", 50)];
+/// assert_eq!(compare(&old, &new), vec![("scan/p=8".to_string(), 0.5)]);
+/// ```
+pub fn compare(old: &[BaselineEntry], new: &[BaselineEntry]) -> Vec<(String, f64)> {
+    let mut ratios: Vec<(String, f64)> = new
+        .iter()
+        .filter_map(|new| {
+            let old = old.iter().find(|old| old.id == new.id)?;
+            (!old.mean.is_zero()).then(|| {
+                (
+                    new.id.clone(),
+                    new.mean.as_secs_f64() / old.mean.as_secs_f64(),
+                )
+            })
+        })
+        .collect();
+    ratios.sort_by(|a, b| a.0.cmp(&b.0));
+    ratios
+}
@@ -0,0 +1,430 @@
+//! The report of a benchmarking campaign. With ``--mpirion-report md``, ``mpirion_main!`` writes
+//! ``REPORT.md`` into the output directory of criterion at the end of the run, with a table of the
+//! benchmarks of each group, the scaling of each kernel as an ASCII chart, the environment the
+//! benchmarks ran in, and the warnings about the benchmarks: ranks that disagree about the time of
+//! a sample, oversubscribed hosts and truncated samples.
+//!
+//! The report is generated from the files in the output directory alone: the ``mpirion.json`` of
+//! each benchmark, see [`Metrics`], the ``estimates.json`` of criterion, the
+//! [scaling summaries](crate::scaling) and ``mpirion-meta.json``, see [`meta`](crate::meta). It
+//! therefore also lists the benchmarks of earlier runs that are still in the output directory.
+//!
+//! The report is meant to be pasted into merge requests, so its formatting is stable: the
+//! benchmarks are ordered by id, the numbers are printed with a fixed precision, and the report
+//! contains no timestamps, so the reports of two runs can be diffed.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cli::ReportFormat;
+use crate::report::{Metrics, METRICS_JSON};
+use crate::scaling::ScalingRow;
+
+/// The file name of the report in the output directory of criterion.
+pub const REPORT_MD: &str = "REPORT.md";
+
+/// The width of the bars of the scaling charts, in characters.
+const CHART_WIDTH: usize = 40;
+
+/// The estimate of criterion of the mean time per iteration of a benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    /// the point estimate of the mean
+    pub mean: Duration,
+    /// the lower bound of the confidence interval of the mean
+    pub lower: Duration,
+    /// the upper bound of the confidence interval of the mean
+    pub upper: Duration,
+}
+
+/// The estimate of the mean in an ``estimates.json`` of criterion.
+///
+/// # Example
+/// ```rust
+/// use mpirion::campaign::{parse_estimate, Estimate};
+/// use std::time::Duration;
+///
+/// let json = r#"{"mean":{"confidence_interval":{"confidence_level":0.95,"lower_bound":98.2,"upper_bound":103.0},"point_estimate":100.4,"standard_error":1.2}}"#;
+/// let nanos = Duration::from_nanos;
+/// assert_eq!(parse_estimate(json), Some(Estimate { mean: nanos(100), lower: nanos(98), upper: nanos(103) }));
+/// ```
+pub fn parse_estimate(estimates: &str) -> Option<Estimate> {
+    let mean = &estimates[estimates.find("\"mean\":{")?..];
+    let bound = |field: &str| {
+        let nanos = crate::report::number_field(mean, field)??;
+        (nanos.is_finite() && nanos >= 0.0).then(|| Duration::from_nanos(nanos.round() as u64))
+    };
+    Some(Estimate {
+        mean: crate::baseline::mean_estimate(estimates)?,
+        lower: bound("lower_bound")?,
+        upper: bound("upper_bound")?,
+    })
+}
+
+/// A benchmark in the report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CampaignBenchmark {
+    /// the metrics mpirion saved for the benchmark
+    pub metrics: Metrics,
+    /// the estimate of criterion, if it saved one
+    pub estimate: Option<Estimate>,
+    /// the directory of the benchmark relative to the output directory of criterion, separated
+    /// with ``/``
+    pub path: String,
+}
+
+/// Everything the report is generated from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Campaign {
+    /// the contents of ``mpirion-meta.json``, if it exists
+    pub environment: Option<String>,
+    /// the benchmarks, ordered by id
+    pub benchmarks: Vec<CampaignBenchmark>,
+    /// the scaling summary of each kernel, ordered by kernel
+    pub scaling: Vec<(String, Vec<ScalingRow>)>,
+}
+
+impl Campaign {
+    /// Read the files of all benchmarks in the given output directory of criterion. Benchmarks
+    /// whose ``mpirion.json`` cannot be read are left out with a message.
+    pub fn load(home: &Path) -> Self {
+        let mut benchmarks: Vec<CampaignBenchmark> = crate::report::find_files(home, METRICS_JSON)
+            .into_iter()
+            .filter_map(|file| {
+                let directory = file.parent()?;
+                let metrics = match crate::report::load(directory) {
+                    Ok(metrics) => metrics,
+                    Err(error) => {
+                        eprintln!(
+                            "mpirion: leaving {} out of the report: {}",
+                            directory.display(),
+                            error
+                        );
+                        return None;
+                    }
+                };
+                let estimate =
+                    std::fs::read_to_string(directory.join("new").join("estimates.json"))
+                        .ok()
+                        .and_then(|estimates| parse_estimate(&estimates));
+                let path = directory.strip_prefix(home).unwrap_or(directory);
+                let path: Vec<String> = path
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                Some(CampaignBenchmark {
+                    metrics,
+                    estimate,
+                    path: path.join("/"),
+                })
+            })
+            .collect();
+        benchmarks.sort_by(|a, b| a.metrics.id.cmp(&b.metrics.id));
+
+        let mut scaling: Vec<(String, Vec<ScalingRow>)> = std::fs::read_dir(home)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let kernel = name
+                    .strip_prefix("scaling-")?
+                    .strip_suffix(".csv")?
+                    .to_string();
+                let csv = std::fs::read_to_string(home.join(&name)).ok()?;
+                Some((kernel, crate::scaling::parse_csv(&csv)))
+            })
+            .filter(|(_, rows)| !rows.is_empty())
+            .collect();
+        scaling.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self {
+            environment: std::fs::read_to_string(home.join(crate::meta::META_JSON)).ok(),
+            benchmarks,
+            scaling,
+        }
+    }
+
+    /// The report as Markdown. Benchmarks whose ranks vary by more than ``variance_threshold``
+    /// percent are listed in the warnings, see [`variance`](crate::variance).
+    pub fn to_markdown(&self, variance_threshold: Option<f64>) -> String {
+        let mut groups: Vec<&str> = self
+            .benchmarks
+            .iter()
+            .map(|benchmark| group_of(&benchmark.metrics.id))
+            .collect();
+        groups.sort_unstable();
+        groups.dedup();
+
+        let mut markdown = String::from("# Benchmark report\n\n");
+        let _ = writeln!(
+            markdown,
+            "{} benchmark{} in {} group{}.\n",
+            self.benchmarks.len(),
+            if self.benchmarks.len() == 1 { "" } else { "s" },
+            groups.len(),
+            if groups.len() == 1 { "" } else { "s" }
+        );
+
+        markdown.push_str("## Environment\n\n");
+        match &self.environment {
+            Some(environment) => {
+                for (label, field) in [
+                    ("MPI library", "mpi_library"),
+                    ("MPI version", "mpi_version"),
+                    ("mpirion", "mpirion_version"),
+                    ("rustc", "rustc_version"),
+                    ("profile", "profile"),
+                ] {
+                    let value = crate::report::string_field(environment, field)
+                        .unwrap_or_else(|| "unknown".to_string());
+                    // multi-line library versions, like the one of MPICH, are shown on one line
+                    let value: Vec<&str> = value.split_whitespace().collect();
+                    let _ = writeln!(markdown, "- {}: {}", label, value.join(" "));
+                }
+                for host in crate::fingerprint::parse_meta(environment) {
+                    let _ = writeln!(markdown, "- host {}", host);
+                }
+                markdown.push('\n');
+            }
+            None => {
+                let _ = writeln!(markdown, "`{}` was not found.\n", crate::meta::META_JSON);
+            }
+        }
+
+        for group in &groups {
+            let _ = writeln!(markdown, "## Group `{}`\n", group);
+            markdown.push_str("| benchmark | ranks | argument | mean | confidence interval | throughput | imbalance | spawn |\n");
+            markdown.push_str("|---|---:|---:|---:|---:|---:|---:|---:|\n");
+            for benchmark in self
+                .benchmarks
+                .iter()
+                .filter(|benchmark| group_of(&benchmark.metrics.id) == *group)
+            {
+                markdown.push_str(&table_row(benchmark));
+            }
+            markdown.push('\n');
+        }
+
+        if !self.scaling.is_empty() {
+            markdown.push_str("## Scaling\n\n");
+            for (kernel, rows) in &self.scaling {
+                let _ = writeln!(
+                    markdown,
+                    "### `{}`\n\n```text\n{}```\n\nSee [scaling-{}.md](scaling-{}.md) for the times.\n",
+                    kernel,
+                    ascii_chart(rows),
+                    kernel,
+                    kernel
+                );
+            }
+        }
+
+        markdown.push_str("## Warnings\n\n");
+        let warnings: Vec<String> = self
+            .benchmarks
+            .iter()
+            .flat_map(|benchmark| {
+                warnings(&benchmark.metrics, variance_threshold)
+                    .into_iter()
+                    .map(|warning| format!("- `{}`: {}\n", benchmark.metrics.id, warning))
+            })
+            .collect();
+        if warnings.is_empty() {
+            markdown.push_str("None.\n");
+        } else {
+            markdown.push_str(&warnings.concat());
+        }
+        markdown
+    }
+}
+
+/// The criterion group of a benchmark id, the part before the first ``/``.
+fn group_of(id: &str) -> &str {
+    id.split('/').next().unwrap_or(id)
+}
+
+/// The row of a benchmark in the table of its group. Its id links to the chart of the times of
+/// its ranks.
+fn table_row(benchmark: &CampaignBenchmark) -> String {
+    let metrics = &benchmark.metrics;
+    let mean = benchmark
+        .estimate
+        .map_or(metrics.mean, |estimate| estimate.mean);
+    let cells = [
+        format!(
+            "[{}]({}/{})",
+            escape(&metrics.id),
+            benchmark.path,
+            crate::report::DISTRIBUTION_SVG
+        ),
+        metrics.world_size.to_string(),
+        metrics
+            .argument
+            .as_ref()
+            .and_then(|argument| argument.display.as_deref())
+            .map_or_else(|| "-".to_string(), escape),
+        format!("{:.2?}", mean),
+        benchmark.estimate.map_or_else(
+            || "-".to_string(),
+            |estimate| format!("{:.2?} – {:.2?}", estimate.lower, estimate.upper),
+        ),
+        metrics.throughput.as_ref().map_or_else(
+            || "-".to_string(),
+            |throughput| crate::summary::format_throughput(throughput, mean),
+        ),
+        crate::summary::imbalance(metrics)
+            .map_or_else(|| "-".to_string(), |imbalance| format!("{:.2}", imbalance)),
+        metrics
+            .spawn_share
+            .map_or_else(|| "-".to_string(), |share| format!("{:.1}%", share * 100.0)),
+    ];
+    format!("| {} |\n", cells.join(" | "))
+}
+
+/// Escape the characters that end a cell of a Markdown table or start a link.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+/// The speedup of each world size relative to the smallest one as a bar chart.
+///
+/// # Example
+/// ```rust
+/// use mpirion::campaign::ascii_chart;
+/// use mpirion::scaling::ScalingRow;
+/// use std::time::Duration;
+///
+/// let row = |world_size, millis| ScalingRow { world_size, mean: Duration::from_millis(millis), stddev: Duration::ZERO };
+/// assert_eq!(
+///     ascii_chart(&[row(2, 100), row(4, 50), row(8, 25)]),
+///     "ranks  speedup                                            efficiency\n\
+///      \x20   2  ##########                                  1.00x        100%\n\
+///      \x20   4  ####################                        2.00x        100%\n\
+///      \x20   8  ########################################    4.00x        100%\n"
+/// );
+/// ```
+pub fn ascii_chart(rows: &[ScalingRow]) -> String {
+    let speedups: Vec<Option<(f64, f64)>> = rows
+        .iter()
+        .map(|row| {
+            rows.first()
+                .and_then(|smallest| crate::scaling::speedup(smallest, row))
+        })
+        .collect();
+    let largest = speedups
+        .iter()
+        .flatten()
+        .map(|(speedup, _)| *speedup)
+        .fold(0.0, f64::max);
+
+    let mut chart = format!(
+        "ranks  {:<width$}  {:>10}\n",
+        "speedup",
+        "efficiency",
+        width = CHART_WIDTH + 9
+    );
+    for (row, speedup) in rows.iter().zip(speedups) {
+        let (bar, speedup, efficiency) = match speedup {
+            Some((speedup, efficiency)) if largest > 0.0 => (
+                "#".repeat((speedup / largest * CHART_WIDTH as f64).round() as usize),
+                format!("{:.2}x", speedup),
+                format!("{:.0}%", efficiency * 100.0),
+            ),
+            _ => (String::new(), "-".to_string(), "-".to_string()),
+        };
+        let _ = writeln!(
+            chart,
+            "{:>5}  {:<width$}  {:>7}  {:>10}",
+            row.world_size,
+            bar,
+            speedup,
+            efficiency,
+            width = CHART_WIDTH
+        );
+    }
+    chart
+}
+
+/// The warnings about a benchmark: ranks that disagree about the time of a sample by more than
+/// ``variance_threshold`` percent, hosts that ran more children than they have CPUs, and samples
+/// that were truncated by their ``max_sample_time``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::campaign::warnings;
+/// use mpirion::report::Metrics;
+/// use std::time::Duration;
+///
+/// let metrics = Metrics {
+///     id: "scan/p=8".to_string(),
+///     kernel: "scan_kernel".to_string(),
+///     world_size: 8,
+///     samples: 100,
+///     mean: Duration::from_micros(10),
+///     max_variation: Some(0.31),
+///     truncated_samples: 3,
+///     oversubscribed_hosts: vec!["node01".to_string()],
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     warnings(&metrics, Some(25.0)),
+///     vec![
+///         "the times of the ranks of a sample vary by up to 31.0% (imbalance)".to_string(),
+///         "node01 ran more ranks than it has CPUs (oversubscribed)".to_string(),
+///         "3 of 100 samples hit their max_sample_time and were extrapolated (truncated)".to_string(),
+///     ]
+/// );
+/// assert_eq!(warnings(&metrics, None).len(), 2);
+/// ```
+pub fn warnings(metrics: &Metrics, variance_threshold: Option<f64>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some(variation) = metrics
+        .max_variation
+        .filter(|variation| crate::variance::exceeds(*variation, variance_threshold))
+    {
+        warnings.push(format!(
+            "the times of the ranks of a sample vary by up to {:.1}% (imbalance)",
+            variation * 100.0
+        ));
+    }
+    if !metrics.oversubscribed_hosts.is_empty() {
+        warnings.push(format!(
+            "{} ran more ranks than {} CPUs (oversubscribed)",
+            metrics.oversubscribed_hosts.join(", "),
+            if metrics.oversubscribed_hosts.len() == 1 {
+                "it has"
+            } else {
+                "they have"
+            }
+        ));
+    }
+    if metrics.truncated_samples > 0 {
+        warnings.push(format!(
+            "{} of {} samples hit their max_sample_time and were extrapolated (truncated)",
+            metrics.truncated_samples, metrics.samples
+        ));
+    }
+    warnings
+}
+
+/// Write ``REPORT.md`` into the output directory of criterion if ``--mpirion-report md`` is set.
+/// Called by ``mpirion_main!`` after the [reports](crate::report::write) of the run are written.
+pub fn write() {
+    let options = crate::cli::options();
+    if options.quick || options.report != Some(ReportFormat::Markdown) {
+        return;
+    }
+
+    let home = crate::report::criterion_home();
+    let path = home.join(REPORT_MD);
+    match std::fs::write(
+        &path,
+        Campaign::load(&home).to_markdown(crate::variance::threshold()),
+    ) {
+        Ok(()) => println!("mpirion: wrote the report of the run to {}", path.display()),
+        Err(error) => eprintln!("mpirion: could not write {}: {}", path.display(), error),
+    }
+}
@@ -0,0 +1,724 @@
+//! Command line handling of ``mpirion_main!``. The binary is either started by the user to run the
+//! benchmarks, or spawned by a benchmark as a child process.
+//!
+//! Benchmark runs accept ``--mpirion-*`` flags next to the arguments for criterion. Criterion
+//! rejects arguments it doesn't know and only reads them from the process arguments, so if any
+//! mpirion flags are passed, the process restarts itself without them before MPI is initialized,
+//! and hands them to the restarted process through the ``MPIRION_FLAGS`` environment variable.
+//!
+//! Children are spawned with ``--mpirion-child <kernel>``. The marker is recognized anywhere in the
+//! arguments, so launchers may prepend their own. The older ``--child <kernel>`` is accepted as an
+//! alias, but only if it is followed by the name of a known kernel, so a criterion filter that
+//! happens to be ``--child`` still runs the benchmarks.
+//!
+//! ``--mpirion-save-baseline <label>`` is passed on to criterion as ``--save-baseline``, with the
+//! [machine tag](crate::baseline::machine_tag) appended to the label, see the
+//! [`baseline`](crate::baseline) module.
+//!
+//! ``cargo criterion`` runs the binary with ``--bench``, the arguments after ``--``, and the port
+//! of its IPC connection in ``CARGO_CRITERION_PORT``. The arguments for criterion pass through
+//! unchanged, and the restart keeps the environment, so criterion reports to ``cargo criterion``.
+//! The children are spawned with their own arguments, and remove the variable when they start, see
+//! [`scrub_child_environment`].
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::debug::RunOptions;
+use crate::mpi::Rank;
+
+/// The marker with which the parent spawns the children, followed by the name of the kernel.
+pub const CHILD_FLAG: &str = "--mpirion-child";
+
+/// The alias of [`CHILD_FLAG`], which only marks a child if it is followed by a known kernel.
+const CHILD_ALIAS: &str = "--child";
+
+/// The names of the kernels listed in ``mpirion_main!``, see [`set_kernel_names`].
+static KERNEL_NAMES: OnceLock<&'static [&'static str]> = OnceLock::new();
+
+/// The environment variables through which ``cargo criterion`` talks to the benchmark binary.
+pub const CARGO_CRITERION_VARS: &[&str] = &["CARGO_CRITERION_PORT"];
+
+/// The environment variable through which the mpirion flags are handed to the restarted process.
+const FLAGS_VAR: &str = "MPIRION_FLAGS";
+
+/// Separates the flags in [`FLAGS_VAR`]. Arguments cannot contain it in practice.
+const FLAGS_SEPARATOR: char = '\u{1f}';
+
+/// The mpirion flags of a benchmark run that take a value.
+const VALUE_FLAGS: &[&str] = &[
+    "--mpirion-group",
+    "--mpirion-only-kernel",
+    "--mpirion-watchdog-grace",
+    "--mpirion-max-world-size",
+    "--mpirion-save-baseline",
+    "--mpirion-keep-logs",
+    "--mpirion-report",
+    "--mpirion-sample-export",
+    "--mpirion-profile",
+    "--mpirion-profile-rank",
+];
+
+/// The mpirion flags of a benchmark run that don't take a value.
+const SWITCH_FLAGS: &[&str] = &[
+    "--mpirion-verbose",
+    "--mpirion-quick",
+    "--mpirion-dry-run",
+    "--mpirion-check-regressions",
+];
+
+/// Flags of the test harness of ``cargo test``. A benchmark binary that receives one of them
+/// without ``--bench``, which ``cargo bench`` always passes, was started by ``cargo test``.
+const TEST_HARNESS_FLAGS: &[&str] = &[
+    "--test",
+    "--list",
+    "--exact",
+    "--test-threads",
+    "--nocapture",
+    "--show-output",
+    "--format",
+    "--include-ignored",
+    "-Z",
+];
+
+/// The options of the current benchmark run, set by ``mpirion_main!``.
+static OPTIONS: OnceLock<BenchOptions> = OnceLock::new();
+
+/// What the process should do, decided from its command line arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dispatch {
+    /// run the given kernel as a child process of a benchmark
+    Child(String),
+    /// run a single iteration of a kernel without criterion, see ``--mpirion-run``
+    Run(RunOptions),
+    /// run the benchmarks with criterion, which parses the remaining arguments itself
+    Bench(BenchOptions),
+    /// print the kernels and groups of the binary as JSON, see ``--mpirion-manifest``
+    Manifest,
+    /// check that MPI can spawn children, see ``--mpirion-selfcheck`` and the
+    /// [`selfcheck`](crate::selfcheck) module
+    SelfCheck,
+    /// the binary was started by ``cargo test`` instead of ``cargo bench``, so nothing runs
+    CargoTest,
+}
+
+/// The mpirion flags of a benchmark run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BenchOptions {
+    /// the groups selected with ``--mpirion-group``. All groups run if it is empty.
+    pub groups: Vec<String>,
+    /// the only kernel that is benchmarked, selected with ``--mpirion-only-kernel``
+    pub only_kernel: Option<String>,
+    /// print a line per sample on the parent, enabled with ``--mpirion-verbose``
+    pub verbose: bool,
+    /// spawn the children once per benchmark instead of once per sample, enabled with
+    /// ``--mpirion-quick``, see [`runner::quick_config`](crate::runner::quick_config)
+    pub quick: bool,
+    /// list the benchmarks that would run instead of running them, enabled with
+    /// ``--mpirion-dry-run``. Combined with ``--mpirion-only-kernel``, the benchmarks of other
+    /// kernels are listed as skipped.
+    pub dry_run: bool,
+    /// how long the children wait for the parent before they exit, set in seconds with
+    /// ``--mpirion-watchdog-grace``, see the [`watchdog`](crate::watchdog) module
+    pub watchdog_grace: Option<Duration>,
+    /// the world size above which a benchmark warns, set with ``--mpirion-max-world-size``, see
+    /// [`exceeds_world_size_warning`](crate::runner::exceeds_world_size_warning)
+    pub max_world_size: Option<Rank>,
+    /// the label of the baseline the results are saved as, set with ``--mpirion-save-baseline``,
+    /// see the [`baseline`](crate::baseline) module
+    pub save_baseline: Option<String>,
+    /// the number of runs whose logs of the children are kept, set with ``--mpirion-keep-logs``,
+    /// see the [`logs`](crate::logs) module
+    pub keep_logs: Option<usize>,
+    /// the campaign report written at the end of the run, selected with ``--mpirion-report``, see
+    /// the [`campaign`](crate::campaign) module
+    pub report: Option<ReportFormat>,
+    /// what ``mpirion-samples.json`` contains about the ranks of each sample, selected with
+    /// ``--mpirion-sample-export``, see [`report::samples_to_json`](crate::report::samples_to_json)
+    pub sample_export: SampleExport,
+    /// compare the scaling curve of each kernel with the previous runs and fail the run if it
+    /// regressed, enabled with ``--mpirion-check-regressions``, see the
+    /// [`regression`](crate::regression) module
+    pub check_regressions: bool,
+    /// the kernel whose benchmarks are profiled, selected with ``--mpirion-profile``, see the
+    /// [`profile`](crate::profile) module
+    pub profile: Option<String>,
+    /// the child that profiles itself, set with ``--mpirion-profile-rank``, rank 0 if unset
+    pub profile_rank: Option<Rank>,
+}
+
+/// The formats of the campaign report of ``--mpirion-report``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// ``REPORT.md``, selected with ``--mpirion-report md``
+    Markdown,
+}
+
+/// What ``mpirion-samples.json`` contains about the ranks of each sample, selected with
+/// ``--mpirion-sample-export``.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleExport {
+    /// the time of every rank, the default, or ``--mpirion-sample-export raw``
+    #[default]
+    Raw,
+    /// the fastest, mean and slowest time of the ranks, selected with
+    /// ``--mpirion-sample-export summary`` for large world sizes
+    Summary,
+}
+
+impl BenchOptions {
+    fn parse(flags: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            if flag == "--mpirion-verbose" {
+                options.verbose = true;
+                continue;
+            }
+            if flag == "--mpirion-quick" {
+                options.quick = true;
+                continue;
+            }
+            if flag == "--mpirion-dry-run" {
+                options.dry_run = true;
+                continue;
+            }
+            if flag == "--mpirion-check-regressions" {
+                options.check_regressions = true;
+                continue;
+            }
+
+            let value = flags
+                .next()
+                .ok_or_else(|| format!("expected a value after {}", flag))?;
+            match flag.as_str() {
+                "--mpirion-group" => options.groups.extend(
+                    value
+                        .split(',')
+                        .filter(|group| !group.is_empty())
+                        .map(String::from),
+                ),
+                "--mpirion-only-kernel" => options.only_kernel = Some(value.clone()),
+                "--mpirion-watchdog-grace" => {
+                    let seconds: u64 = value.parse().map_err(|_| {
+                        format!(
+                            "expected a number of seconds after {}, got `{}`",
+                            flag, value
+                        )
+                    })?;
+                    options.watchdog_grace = Some(Duration::from_secs(seconds));
+                }
+                "--mpirion-max-world-size" => {
+                    let max_world_size = value.parse().map_err(|_| {
+                        format!("expected a world size after {}, got `{}`", flag, value)
+                    })?;
+                    options.max_world_size = Some(max_world_size);
+                }
+                "--mpirion-save-baseline" => options.save_baseline = Some(value.clone()),
+                "--mpirion-keep-logs" => {
+                    let keep_logs = value.parse().map_err(|_| {
+                        format!("expected a number of runs after {}, got `{}`", flag, value)
+                    })?;
+                    options.keep_logs = Some(keep_logs);
+                }
+                "--mpirion-report" => match value.as_str() {
+                    "md" => options.report = Some(ReportFormat::Markdown),
+                    _ => {
+                        return Err(format!(
+                            "unknown report format `{}` after {}, expected `md`",
+                            value, flag
+                        ))
+                    }
+                },
+                "--mpirion-sample-export" => match value.as_str() {
+                    "raw" => options.sample_export = SampleExport::Raw,
+                    "summary" => options.sample_export = SampleExport::Summary,
+                    _ => {
+                        return Err(format!(
+                            "unknown sample export `{}` after {}, expected `raw` or `summary`",
+                            value, flag
+                        ))
+                    }
+                },
+                "--mpirion-profile" => {
+                    if !cfg!(feature = "profile") {
+                        return Err(format!("{} needs the `profile` feature of mpirion", flag));
+                    }
+                    options.profile = Some(value.clone());
+                }
+                "--mpirion-profile-rank" => {
+                    let rank = value
+                        .parse()
+                        .map_err(|_| format!("expected a rank after {}, got `{}`", flag, value))?;
+                    options.profile_rank = Some(rank);
+                }
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        if options.quick && options.save_baseline.is_some() {
+            return Err(
+                "--mpirion-quick cannot be combined with --mpirion-save-baseline, its results are never saved"
+                    .to_string(),
+            );
+        }
+        Ok(options)
+    }
+
+    /// Whether the kernel with the given name should be benchmarked.
+    pub fn runs_kernel(&self, kernel: &str) -> bool {
+        !matches!(self.only_kernel.as_deref(), Some(only) if only != kernel)
+    }
+
+    /// Whether the group with the given name should run.
+    pub fn runs_group(&self, group: &str) -> bool {
+        self.groups.is_empty() || self.groups.iter().any(|selected| selected == group)
+    }
+
+    /// Check that all selected groups exist.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::cli::{dispatch, Dispatch};
+    ///
+    /// let args = ["--mpirion-group", "reduce_bench"].map(String::from);
+    /// let Ok(Dispatch::Bench(options)) = dispatch(&args) else { unreachable!() };
+    /// assert!(options.runs_group("reduce_bench"));
+    /// assert!(!options.runs_group("broadcast_bench"));
+    /// assert!(options.check_groups(&["broadcast_bench", "reduce_bench"]).is_ok());
+    /// assert!(options.check_groups(&["broadcast_bench"]).is_err());
+    /// ```
+    pub fn check_groups(&self, known: &[&str]) -> Result<(), String> {
+        match self
+            .groups
+            .iter()
+            .find(|group| !known.contains(&group.as_str()))
+        {
+            Some(group) => Err(format!(
+                "unknown group `{}` passed to --mpirion-group, the benchmark has the groups {}",
+                group,
+                known.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whether the arguments look like an invocation by the test harness of ``cargo test``, see
+/// [`TEST_HARNESS_FLAGS`].
+fn is_cargo_test(args: &[String]) -> bool {
+    !args.iter().any(|arg| arg == "--bench")
+        && args.iter().any(|arg| {
+            TEST_HARNESS_FLAGS.iter().any(|flag| {
+                arg == flag
+                    || arg
+                        .strip_prefix(flag)
+                        .is_some_and(|rest| rest.starts_with('='))
+            })
+        })
+}
+
+/// Split the arguments of a benchmark run into the mpirion flags and the arguments for criterion,
+/// which keep their order.
+pub fn split_flags(args: &[String]) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut flags = Vec::new();
+    let mut criterion_args = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("expected a value after {}", arg))?;
+            flags.push(arg.clone());
+            flags.push(value.clone());
+        } else if SWITCH_FLAGS.contains(&arg.as_str()) {
+            flags.push(arg.clone());
+        } else if arg.starts_with("--mpirion-") {
+            return Err(format!("unknown option {}", arg));
+        } else {
+            criterion_args.push(arg.clone());
+        }
+    }
+
+    Ok((flags, criterion_args))
+}
+
+/// Set the names of the kernels listed in ``mpirion_main!``, which [`dispatch`] accepts after the
+/// ``--child`` alias, next to the kernels of the [`registry`](crate::registry). Called by
+/// ``mpirion_main!`` before it dispatches. Later calls are ignored.
+pub fn set_kernel_names(names: &'static [&'static str]) {
+    let _ = KERNEL_NAMES.set(names);
+}
+
+/// Whether a kernel with the given name is listed in ``mpirion_main!`` or registered otherwise.
+fn is_known_kernel(name: &str) -> bool {
+    KERNEL_NAMES
+        .get()
+        .is_some_and(|names| names.contains(&name))
+        || crate::registry::find(name).is_some()
+        || crate::registry::registered_names()
+            .iter()
+            .any(|registered| registered == name)
+}
+
+/// Find the kernel a child process should run: the argument after [`CHILD_FLAG`], or after the
+/// ``--child`` alias if ``is_kernel`` accepts it. Returns ``None`` if the process is not a child.
+///
+/// # Example
+/// ```rust
+/// use mpirion::cli::child_kernel;
+///
+/// let is_kernel = |name: &str| name == "scan";
+/// let table: &[(&[&str], Result<Option<&str>, ()>)] = &[
+///     (&["--mpirion-child", "scan"], Ok(Some("scan"))),
+///     (&["--mpirion-child", "unknown"], Ok(Some("unknown"))),
+///     (&["--mpirion-child"], Err(())),
+///     (&["--child", "scan"], Ok(Some("scan"))),
+///     // a launcher prepended its own arguments
+///     (&["--launcher-flag", "value", "--mpirion-child", "scan"], Ok(Some("scan"))),
+///     (&["--bind-to", "core", "--child", "scan"], Ok(Some("scan"))),
+///     // criterion filters that look like the marker
+///     (&["--child"], Ok(None)),
+///     (&["--bench", "--child"], Ok(None)),
+///     (&["--child", "prefix-sum"], Ok(None)),
+///     (&["--bench", "--child", "--mpirion-verbose"], Ok(None)),
+///     (&["--bench", "scan"], Ok(None)),
+///     (&[], Ok(None)),
+/// ];
+///
+/// for (args, expected) in table {
+///     let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+///     let result = child_kernel(&args, &is_kernel);
+///     let kernel = match &result {
+///         Ok(kernel) => Ok(kernel.as_ref().map(|(_, name)| name.as_str())),
+///         Err(_) => Err(()),
+///     };
+///     assert_eq!(kernel, *expected, "{:?}", args);
+/// }
+/// ```
+pub fn child_kernel(
+    args: &[String],
+    is_kernel: &dyn Fn(&str) -> bool,
+) -> Result<Option<(usize, String)>, String> {
+    if let Some(index) = args.iter().position(|arg| arg == CHILD_FLAG) {
+        return match args.get(index + 1) {
+            Some(kernel) => Ok(Some((index + 1, kernel.clone()))),
+            None => Err(format!(
+                "called process with {}, but without specifying the kernel",
+                CHILD_FLAG
+            )),
+        };
+    }
+
+    let alias = args
+        .windows(2)
+        .position(|pair| pair[0] == CHILD_ALIAS && is_kernel(&pair[1]))
+        .map(|index| (index + 1, args[index + 1].clone()));
+    Ok(alias)
+}
+
+/// Decide what the process should do from its command line arguments, excluding the program name.
+/// Anything that is not an mpirion mode, including no arguments at all, runs the benchmarks, so
+/// ``cargo bench`` and plain invocations of the binary work. Arguments of the ``cargo test``
+/// harness without ``--bench`` are recognized as [`Dispatch::CargoTest`]. Children are recognized
+/// by [`child_kernel`], with the kernels listed in ``mpirion_main!`` or registered otherwise.
+///
+/// # Example
+/// ```rust
+/// use mpirion::cli::{dispatch, set_kernel_names, BenchOptions, Dispatch, SampleExport};
+/// use std::time::Duration;
+///
+/// fn args(args: &[&str]) -> Vec<String> {
+///     args.iter().map(|arg| arg.to_string()).collect()
+/// }
+///
+/// set_kernel_names(&["my_kernel"]);
+///
+/// let all_groups = Dispatch::Bench(BenchOptions::default());
+///
+/// // benchmarks, with or without arguments for criterion
+/// assert_eq!(dispatch(&args(&[])), Ok(all_groups.clone()));
+/// assert_eq!(dispatch(&args(&["--bench"])), Ok(all_groups.clone()));
+/// assert_eq!(dispatch(&args(&["--bench", "prefix-sum"])), Ok(all_groups.clone()));
+/// assert_eq!(dispatch(&args(&["prefix-sum"])), Ok(all_groups.clone()));
+///
+/// // selected groups, between the arguments for criterion
+/// let selected = Dispatch::Bench(BenchOptions { groups: args(&["a", "b", "c"]), ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-group", "a,b", "--mpirion-group", "c"])), Ok(selected));
+/// assert!(dispatch(&args(&["--bench", "--mpirion-group"])).is_err());
+/// assert!(dispatch(&args(&["--bench", "--mpirion-unknown"])).is_err());
+///
+/// // a single kernel
+/// let only = Dispatch::Bench(BenchOptions { only_kernel: Some("my_kernel".to_string()), ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--mpirion-only-kernel", "my_kernel"])), Ok(only));
+///
+/// // the benchmarks of a single kernel that would run, without running them
+/// let dry_run = Dispatch::Bench(BenchOptions { only_kernel: Some("my_kernel".to_string()), dry_run: true, ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--mpirion-dry-run", "--mpirion-only-kernel", "my_kernel"])), Ok(dry_run));
+///
+/// // a line per sample
+/// let verbose = Dispatch::Bench(BenchOptions { verbose: true, ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-verbose"])), Ok(verbose));
+///
+/// // a single spawn per benchmark
+/// let quick = Dispatch::Bench(BenchOptions { quick: true, ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-quick"])), Ok(quick));
+/// assert!(dispatch(&args(&["--bench", "--mpirion-quick", "--mpirion-save-baseline", "nightly"])).is_err());
+///
+/// // child processes, which refuse other kernels than the one selected on the parent
+/// assert_eq!(dispatch(&args(&["--mpirion-child", "my_kernel"])), Ok(Dispatch::Child("my_kernel".to_string())));
+/// assert_eq!(dispatch(&args(&["--child", "my_kernel"])), Ok(Dispatch::Child("my_kernel".to_string())));
+/// assert!(dispatch(&args(&["--mpirion-child"])).is_err());
+/// let selected_child = args(&["--mpirion-child", "my_kernel", "--mpirion-only-kernel", "my_kernel"]);
+/// assert_eq!(dispatch(&selected_child), Ok(Dispatch::Child("my_kernel".to_string())));
+/// assert!(dispatch(&args(&["--mpirion-child", "my_kernel", "--mpirion-only-kernel", "other"])).is_err());
+///
+/// // a single kernel iteration
+/// assert!(matches!(dispatch(&args(&["--mpirion-run", "my_kernel"])), Ok(Dispatch::Run(_))));
+/// assert!(dispatch(&args(&["--mpirion-run"])).is_err());
+///
+/// // the grace period of the watchdogs of the children
+/// let grace = Dispatch::Bench(BenchOptions { watchdog_grace: Some(Duration::from_secs(30)), ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-watchdog-grace", "30"])), Ok(grace));
+/// assert!(dispatch(&args(&["--bench", "--mpirion-watchdog-grace", "soon"])).is_err());
+///
+/// // a baseline to save the results as
+/// let baseline = Dispatch::Bench(BenchOptions { save_baseline: Some("nightly".to_string()), ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-save-baseline", "nightly"])), Ok(baseline));
+///
+/// // summaries of the ranks instead of their times in mpirion-samples.json
+/// let summary = Dispatch::Bench(BenchOptions { sample_export: SampleExport::Summary, ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-sample-export", "summary"])), Ok(summary));
+/// assert!(dispatch(&args(&["--bench", "--mpirion-sample-export", "all"])).is_err());
+///
+/// // the child that profiles itself with --mpirion-profile
+/// let profile_rank = Dispatch::Bench(BenchOptions { profile_rank: Some(3), ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-profile-rank", "3"])), Ok(profile_rank));
+/// assert!(dispatch(&args(&["--bench", "--mpirion-profile-rank", "first"])).is_err());
+///
+/// // a check of the scaling curves against the previous runs
+/// let check = Dispatch::Bench(BenchOptions { check_regressions: true, ..Default::default() });
+/// assert_eq!(dispatch(&args(&["--bench", "--mpirion-check-regressions"])), Ok(check));
+///
+/// // the manifest of the binary
+/// assert_eq!(dispatch(&args(&["--mpirion-manifest"])), Ok(Dispatch::Manifest));
+///
+/// // the check of the MPI setup
+/// assert_eq!(dispatch(&args(&["--mpirion-selfcheck"])), Ok(Dispatch::SelfCheck));
+///
+/// // cargo test instead of cargo bench
+/// assert_eq!(dispatch(&args(&["--test-threads", "4"])), Ok(Dispatch::CargoTest));
+/// assert_eq!(dispatch(&args(&["--test-threads=4", "--nocapture"])), Ok(Dispatch::CargoTest));
+/// assert_eq!(dispatch(&args(&["--format", "terse", "--list"])), Ok(Dispatch::CargoTest));
+/// assert_eq!(dispatch(&args(&["--test"])), Ok(Dispatch::CargoTest));
+/// assert_eq!(dispatch(&args(&["prefix-sum", "--exact"])), Ok(Dispatch::CargoTest));
+///
+/// // --child is only a marker if a known kernel follows, otherwise it is passed to criterion
+/// assert_eq!(dispatch(&args(&["--bench", "--child", "my_kernel"])), Ok(Dispatch::Child("my_kernel".to_string())));
+/// assert_eq!(dispatch(&args(&["--bench", "--child", "other"])), Ok(all_groups.clone()));
+/// assert_eq!(dispatch(&args(&["--child"])), Ok(all_groups));
+/// ```
+pub fn dispatch(args: &[String]) -> Result<Dispatch, String> {
+    if let Some((index, kernel)) = child_kernel(args, &is_known_kernel)? {
+        let only = args[index + 1..]
+            .windows(2)
+            .find(|pair| pair[0] == "--mpirion-only-kernel")
+            .map(|pair| &pair[1]);
+        return match only {
+            Some(only) if *only != kernel => Err(format!(
+                "refusing to run kernel `{}`, only `{}` was selected with --mpirion-only-kernel",
+                kernel, only
+            )),
+            _ => Ok(Dispatch::Child(kernel)),
+        };
+    }
+
+    match args.first().map(String::as_str) {
+        Some("--mpirion-run") => RunOptions::parse(args[1..].iter().cloned()).map(Dispatch::Run),
+        Some("--mpirion-manifest") => Ok(Dispatch::Manifest),
+        Some("--mpirion-selfcheck") => Ok(Dispatch::SelfCheck),
+        _ if is_cargo_test(args) => Ok(Dispatch::CargoTest),
+        _ => {
+            let (flags, _) = split_flags(args)?;
+            BenchOptions::parse(&flags).map(Dispatch::Bench)
+        }
+    }
+}
+
+/// Print why a benchmark binary started by ``cargo test`` doesn't run. Called by ``mpirion_main!``
+/// before MPI is initialized, which would fail or hang inside the test runner.
+pub fn explain_cargo_test() {
+    eprintln!(
+        "this is an mpirion benchmark, which spawns MPI processes and cannot run as a test. \
+         Run it with `cargo bench` instead, or exclude it from `cargo test` with `test = false` in the \
+         [[bench]] section of Cargo.toml. Skipping the benchmarks."
+    );
+}
+
+/// [`dispatch`] on the arguments of the current process. Panics if they are malformed. If the
+/// arguments of a benchmark run contain mpirion flags, the process is restarted without them, see
+/// the module documentation.
+pub fn dispatch_from_env() -> Dispatch {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let dispatch = dispatch(&args).unwrap_or_else(|message| panic!("{}", message));
+    if matches!(dispatch, Dispatch::Child(_)) {
+        scrub_child_environment();
+        crate::tuning::apply_child_args(&args);
+    }
+    if !matches!(dispatch, Dispatch::Bench(_)) {
+        return dispatch;
+    }
+
+    let (flags, mut criterion_args) =
+        split_flags(&args).unwrap_or_else(|message| panic!("{}", message));
+    if !flags.is_empty() {
+        if let Dispatch::Bench(BenchOptions {
+            save_baseline: Some(label),
+            ..
+        }) = &dispatch
+        {
+            criterion_args = with_baseline(criterion_args, label, &crate::baseline::machine_tag())
+                .unwrap_or_else(|message| panic!("{}", message));
+        }
+        if let Dispatch::Bench(BenchOptions { quick: true, .. }) = &dispatch {
+            criterion_args =
+                without_baseline(criterion_args).unwrap_or_else(|message| panic!("{}", message));
+        }
+        restart_without_flags(&flags, &criterion_args);
+    }
+
+    match std::env::var(FLAGS_VAR) {
+        Ok(flags) if !flags.is_empty() => {
+            let flags: Vec<String> = flags.split(FLAGS_SEPARATOR).map(String::from).collect();
+            let options =
+                BenchOptions::parse(&flags).unwrap_or_else(|message| panic!("{}", message));
+            Dispatch::Bench(options)
+        }
+        _ => dispatch,
+    }
+}
+
+/// Add the baseline of ``--mpirion-save-baseline`` to the arguments for criterion. Fails if they
+/// already select a baseline to save.
+///
+/// # Example
+/// ```rust
+/// use mpirion::cli::with_baseline;
+///
+/// let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+/// assert_eq!(
+///     with_baseline(args(&["--bench", "scan"]), "nightly", "node01"),
+///     Ok(args(&["--bench", "scan", "--save-baseline", "nightly-node01"]))
+/// );
+/// assert!(with_baseline(args(&["--bench", "--save-baseline", "base"]), "nightly", "node01").is_err());
+/// assert!(with_baseline(args(&["--bench", "-s", "base"]), "nightly", "node01").is_err());
+/// ```
+pub fn with_baseline(
+    mut criterion_args: Vec<String>,
+    label: &str,
+    machine: &str,
+) -> Result<Vec<String>, String> {
+    if criterion_args
+        .iter()
+        .any(|arg| arg == "-s" || arg == "--save-baseline" || arg.starts_with("--save-baseline="))
+    {
+        return Err("--mpirion-save-baseline cannot be combined with --save-baseline".to_string());
+    }
+    criterion_args.push("--save-baseline".to_string());
+    criterion_args.push(crate::baseline::label(label, machine));
+    Ok(criterion_args)
+}
+
+/// Keep criterion from saving the results of ``--mpirion-quick`` as a baseline, since they are
+/// replayed from a single spawn. Fails if the arguments for criterion save or compare to a
+/// baseline.
+///
+/// # Example
+/// ```rust
+/// use mpirion::cli::without_baseline;
+///
+/// let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+/// assert_eq!(
+///     without_baseline(args(&["--bench", "scan"])),
+///     Ok(args(&["--bench", "scan", "--discard-baseline"]))
+/// );
+/// assert!(without_baseline(args(&["--bench", "--save-baseline", "base"])).is_err());
+/// assert!(without_baseline(args(&["--bench", "--baseline=base"])).is_err());
+/// assert!(without_baseline(args(&["--bench", "-b", "base"])).is_err());
+/// ```
+pub fn without_baseline(mut criterion_args: Vec<String>) -> Result<Vec<String>, String> {
+    let baseline_flags = [
+        ("-s", "--save-baseline"),
+        ("-b", "--baseline"),
+        ("--baseline-lenient", "--baseline-lenient"),
+    ];
+    for (short, long) in baseline_flags {
+        if criterion_args.iter().any(|arg| {
+            arg == short
+                || arg == long
+                || arg
+                    .strip_prefix(long)
+                    .is_some_and(|rest| rest.starts_with('='))
+        }) {
+            return Err(format!(
+                "--mpirion-quick cannot be combined with {}, its results are never saved or compared to a baseline",
+                long
+            ));
+        }
+    }
+    if !criterion_args.iter().any(|arg| arg == "--discard-baseline") {
+        criterion_args.push("--discard-baseline".to_string());
+    }
+    Ok(criterion_args)
+}
+
+/// Remove the variables of [`CARGO_CRITERION_VARS`] from the environment of a child. Depending on
+/// the launcher, spawned processes inherit the environment of the parent, and a child must never
+/// connect to ``cargo criterion`` or pass the connection on. Called on dispatch to a child, before
+/// MPI is initialized.
+pub fn scrub_child_environment() {
+    for var in CARGO_CRITERION_VARS {
+        std::env::remove_var(var);
+    }
+}
+
+/// Set the options of the current benchmark run. Called by ``mpirion_main!`` before the groups
+/// run. Later calls are ignored.
+pub fn set_options(options: BenchOptions) {
+    let _ = OPTIONS.set(options);
+}
+
+/// The options of the current benchmark run, or the defaults if the benchmarks were not started by
+/// ``mpirion_main!``.
+pub fn options() -> &'static BenchOptions {
+    OPTIONS.get_or_init(BenchOptions::default)
+}
+
+/// Restart the process with only the criterion arguments, and hand the mpirion flags to it through
+/// the environment. Must be called before MPI is initialized.
+fn restart_without_flags(flags: &[String], criterion_args: &[String]) -> ! {
+    let exe = std::env::current_exe().expect("failed to retrieve benchmark executable path");
+    let mut command = std::process::Command::new(exe);
+    command
+        .args(criterion_args)
+        .env(FLAGS_VAR, flags.join(&FLAGS_SEPARATOR.to_string()));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let error = command.exec();
+        panic!(
+            "failed to restart the benchmark without the mpirion flags: {}",
+            error
+        );
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = command
+            .status()
+            .expect("failed to restart the benchmark without the mpirion flags");
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
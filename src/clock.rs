@@ -0,0 +1,163 @@
+//! The clock the children measure the kernels with. ``Instant`` is used unless it fails a self
+//! test: inside some virtual machines and containers, it is backed by a clock source with a coarse
+//! resolution or that jumps backwards, which shows up as samples of zero or absurd durations. The
+//! children then fall back to ``MPI_Wtime``.
+//!
+//! The self test runs once per child, right after the handshake with the parent, see [`select`].
+//! It reads the clock a few thousand times back to back and checks the readings with
+//! [`check_readings`]. The parent learns the clock of the children in the handshake, warns if it
+//! is not ``Instant`` and prints it with ``--mpirion-verbose``.
+
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How many back-to-back readings the self test takes.
+const READINGS: usize = 4096;
+
+/// The coarsest granularity a clock may have to pass the self test.
+pub const MAX_GRANULARITY: Duration = Duration::from_micros(10);
+
+/// A clock the children can measure with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Clock {
+    /// ``std::time::Instant``, the default
+    Instant,
+    /// ``MPI_Wtime``, if ``Instant`` failed the self test
+    MpiWtime,
+}
+
+/// A point in time of a [`Clock`].
+#[derive(Debug, Clone, Copy)]
+pub enum Timestamp {
+    Instant(Instant),
+    MpiWtime(f64),
+}
+
+impl Clock {
+    /// The current time of the clock.
+    pub fn now(self) -> Timestamp {
+        match self {
+            Clock::Instant => Timestamp::Instant(Instant::now()),
+            Clock::MpiWtime => Timestamp::MpiWtime(crate::mpi::environment::time()),
+        }
+    }
+
+    /// The number that identifies the clock in the handshake.
+    pub(crate) fn id(self) -> u64 {
+        self as u64
+    }
+
+    /// The clock with the given number, falling back to ``Instant`` for unknown numbers.
+    pub(crate) fn from_id(id: u64) -> Self {
+        match id {
+            1 => Clock::MpiWtime,
+            _ => Clock::Instant,
+        }
+    }
+}
+
+impl Display for Clock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Clock::Instant => write!(f, "Instant"),
+            Clock::MpiWtime => write!(f, "MPI_Wtime"),
+        }
+    }
+}
+
+impl Timestamp {
+    /// The time that passed since the timestamp was taken.
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            Timestamp::Instant(start) => start.elapsed(),
+            Timestamp::MpiWtime(start) => {
+                Duration::from_secs_f64((crate::mpi::environment::time() - start).max(0.0))
+            }
+        }
+    }
+}
+
+/// Check back-to-back readings of a clock, in nanoseconds relative to an arbitrary origin. Fails
+/// if the clock went backwards, never advanced, or its smallest step is coarser than
+/// [`MAX_GRANULARITY`].
+///
+/// # Example
+/// ```rust
+/// use mpirion::clock::check_readings;
+///
+/// assert!(check_readings(&[0, 30, 30, 60, 90]).is_ok());
+/// assert!(check_readings(&[0, 30, 20, 60]).unwrap_err().contains("backwards"));
+/// assert!(check_readings(&[5, 5, 5, 5]).unwrap_err().contains("never advanced"));
+/// assert!(check_readings(&[0, 0, 4_000_000, 4_000_000]).unwrap_err().contains("granularity"));
+/// ```
+pub fn check_readings(readings: &[i128]) -> Result<(), String> {
+    let steps: Vec<i128> = readings.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    if let Some(step) = steps.iter().find(|step| **step < 0) {
+        return Err(format!("the clock went backwards by {} ns", -step));
+    }
+
+    let Some(granularity) = steps.iter().filter(|step| **step > 0).min() else {
+        return Err(format!(
+            "the clock never advanced during {} readings",
+            readings.len()
+        ));
+    };
+    if *granularity > MAX_GRANULARITY.as_nanos() as i128 {
+        return Err(format!(
+            "the granularity of the clock is {} ns, coarser than {:?}",
+            granularity, MAX_GRANULARITY
+        ));
+    }
+    Ok(())
+}
+
+/// Read the clock back to back and check the readings.
+fn self_test(clock: Clock) -> Result<(), String> {
+    let start = clock.now();
+    let readings: Vec<i128> = (0..READINGS)
+        .map(|_| match (start, clock.now()) {
+            (Timestamp::Instant(start), Timestamp::Instant(now)) => {
+                match now.checked_duration_since(start) {
+                    Some(elapsed) => elapsed.as_nanos() as i128,
+                    None => -(start.duration_since(now).as_nanos() as i128),
+                }
+            }
+            (Timestamp::MpiWtime(start), Timestamp::MpiWtime(now)) => ((now - start) * 1e9) as i128,
+            _ => unreachable!("the readings of a clock have the same kind"),
+        })
+        .collect();
+    check_readings(&readings)
+}
+
+static SELECTED: OnceLock<Clock> = OnceLock::new();
+
+/// Run the self test and select the clock the kernels are measured with. ``Instant`` is selected
+/// if it passes, ``MPI_Wtime`` otherwise, unless it fails as well. Must be called after MPI was
+/// initialized. Later calls return the clock of the first call.
+pub fn select() -> Clock {
+    *SELECTED.get_or_init(|| {
+        let Err(instant) = self_test(Clock::Instant) else {
+            return Clock::Instant;
+        };
+        match self_test(Clock::MpiWtime) {
+            Ok(()) => {
+                eprintln!("mpirion: Instant failed the clock self test ({}), measuring with MPI_Wtime", instant);
+                Clock::MpiWtime
+            }
+            Err(wtime) => {
+                eprintln!(
+                    "mpirion: Instant failed the clock self test ({}), and so did MPI_Wtime ({}). Measuring with Instant, the timings may be wrong.",
+                    instant, wtime
+                );
+                Clock::Instant
+            }
+        }
+    })
+}
+
+/// The clock selected by [`select`], or ``Instant`` if no clock was selected, e.g. when the kernel
+/// runs in the parent process with the ``local-fallback`` feature.
+pub fn selected() -> Clock {
+    SELECTED.get().copied().unwrap_or(Clock::Instant)
+}
@@ -0,0 +1,482 @@
+//! The context that ``mpirion_group!`` passes to the target functions of benchmark groups. It
+//! bundles everything a benchmark function needs from mpirion, so new capabilities can be added to
+//! it without changing the signature of the target functions.
+
+use std::fmt::Display;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use crate::mpi::topology::SimpleCommunicator;
+use crate::mpi::traits::Communicator;
+use criterion::{Bencher, BenchmarkGroup, BenchmarkId, Criterion};
+
+use crate::baseline::IdFormatter;
+use crate::cli::BenchOptions;
+use crate::hostfile::Hostfile;
+
+/// The context of a benchmark group, passed to its target function as
+/// ``fn(&mut Criterion, &MpirionContext)``. Pass it to ``mpirion_bench!`` in place of the world.
+///
+/// Benchmarks are declared through the context with [`bench_function`](Self::bench_function), or
+/// in a group created with [`benchmark_group`](Self::benchmark_group), together with the name of
+/// their kernel. This lets mpirion skip a benchmark before criterion starts it, e.g. if another
+/// kernel was selected with ``--mpirion-only-kernel``, or list it without running it in a run with
+/// ``--mpirion-dry-run``. Criterion cannot skip a benchmark that already started, so a benchmark
+/// declared directly with criterion always runs, and fails in a dry run.
+pub struct MpirionContext {
+    group: &'static str,
+    world: SimpleCommunicator,
+    budget: Option<Budget>,
+    id_formatter: IdFormatter,
+}
+
+impl MpirionContext {
+    /// Create the context of a group. Called by ``mpirion_group!``.
+    #[doc(hidden)]
+    pub fn new(group: &'static str, world: SimpleCommunicator) -> Self {
+        Self {
+            group,
+            world,
+            budget: None,
+            id_formatter: crate::baseline::default_id,
+        }
+    }
+
+    /// Name the benchmarks of [`benchmark_id`](Self::benchmark_id) with the given formatter
+    /// instead of [`default_id`](crate::baseline::default_id). Called by ``mpirion_group!`` with
+    /// ``id_format = formatter``.
+    pub fn with_id_formatter(mut self, formatter: IdFormatter) -> Self {
+        self.id_formatter = formatter;
+        self
+    }
+
+    /// Limit the wall-clock time of the group, starting now. Called by ``mpirion_group!``.
+    #[doc(hidden)]
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(Budget {
+            group: self.group,
+            budget,
+            deadline: Instant::now() + budget,
+        });
+        self
+    }
+
+    /// The wall-clock budget of the group, if it has one.
+    pub fn budget(&self) -> Option<Budget> {
+        self.budget
+    }
+
+    /// Whether the budget of the group is used up. The benchmarks declared through the context are
+    /// skipped then, but a benchmark function can check it to skip its own preparations as well.
+    pub fn budget_exhausted(&self) -> bool {
+        self.budget.is_some_and(|budget| budget.exhausted())
+    }
+
+    /// The communicator from which the children are spawned. It only contains the parent process.
+    pub fn world(&self) -> &dyn Communicator {
+        &self.world
+    }
+
+    /// The name of the benchmark group.
+    pub fn group(&self) -> &str {
+        self.group
+    }
+
+    /// The id of the benchmark of a kernel at a world size, with the argument of the kernel if it
+    /// has one, formatted by the [`IdFormatter`] of the group, see
+    /// [`baseline::benchmark_id`](crate::baseline::benchmark_id). Without a formatter, it is
+    /// ``kernel/p=8``, the same as [`world_size_id`](crate::baseline::world_size_id).
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::prelude::*;
+    /// use std::fmt::Display;
+    ///
+    /// // the naming scheme of a result database, e.g. bcast.p8.m4096.ompi5
+    /// fn database_id(kernel: &str, world_size: usize, arg: Option<&dyn Display>) -> String {
+    ///     let size = arg.map(|size| format!(".m{}", size)).unwrap_or_default();
+    ///     format!("{}.p{}{}.ompi5", kernel, world_size, size)
+    /// }
+    ///
+    /// fn benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ///     let mut group = ctx.benchmark_group(c, "collectives");
+    ///     for size in [1024usize, 4096] {
+    ///         let id = ctx.benchmark_name("bcast", 8, Some(&size));
+    ///         group.bench_with_input(id, "bcast", &size, |b, &size| {
+    ///             mpirion_bench!(bcast, b, ctx; world_size = 8, arg = size)
+    ///         });
+    ///     }
+    ///     group.finish();
+    /// }
+    ///
+    /// fn setup(_comm: &dyn Communicator, size: usize) -> Vec<u8> {
+    ///     vec![0; size]
+    /// }
+    ///
+    /// fn bcast(comm: &dyn Communicator, data: &mut Vec<u8>) {
+    ///     comm.process_at_rank(0).broadcast_into(&mut data[..]);
+    /// }
+    ///
+    /// mpirion_kernel!(bcast, setup, usize);
+    /// mpirion_group!{ name = benches; config = std::convert::identity; target = benchmark; id_format = database_id }
+    /// mpirion_main!(benches, bcast);
+    /// ```
+    pub fn benchmark_id(
+        &self,
+        kernel: &str,
+        world_size: usize,
+        arg: Option<&dyn Display>,
+    ) -> BenchmarkId {
+        crate::baseline::benchmark_id(&self.benchmark_name(kernel, world_size, arg))
+    }
+
+    /// The id of [`benchmark_id`](Self::benchmark_id) as text, for the benchmarks of a
+    /// [`MpirionGroup`], which splits it the same way.
+    pub fn benchmark_name(
+        &self,
+        kernel: &str,
+        world_size: usize,
+        arg: Option<&dyn Display>,
+    ) -> String {
+        (self.id_formatter)(kernel, world_size, arg)
+    }
+
+    /// Benchmark ``kernel`` like ``criterion.bench_function(id, routine)``, unless the benchmark is
+    /// skipped. The routine calls ``mpirion_bench!`` for the kernel.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::prelude::*;
+    ///
+    /// fn barrier_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ///     ctx.bench_function(c, "barrier", "barrier_kernel", |b| {
+    ///         mpirion_bench!(barrier_kernel, b, ctx)
+    ///     });
+    /// }
+    ///
+    /// fn barrier_kernel(comm: &dyn Communicator) {
+    ///     comm.barrier();
+    /// }
+    ///
+    /// mpirion_kernel!(barrier_kernel);
+    /// ```
+    pub fn bench_function<R>(&self, criterion: &mut Criterion, id: &str, kernel: &str, routine: R)
+    where
+        R: FnMut(&mut Bencher<'_>),
+    {
+        if self.runs(id, kernel) {
+            crate::runner::run_declared(id.to_string(), kernel, || {
+                criterion.bench_function(id, routine);
+            });
+        }
+    }
+
+    /// Create a group of benchmarks like ``criterion.benchmark_group(name)``, whose benchmarks are
+    /// declared through the context, see [`MpirionGroup`].
+    pub fn benchmark_group<'a>(
+        &'a self,
+        criterion: &'a mut Criterion,
+        name: impl Into<String>,
+    ) -> MpirionGroup<'a> {
+        let name = name.into();
+        MpirionGroup {
+            context: self,
+            group: criterion.benchmark_group(name.clone()),
+            name,
+        }
+    }
+
+    /// Whether the benchmark with the given id runs, or is skipped with a note. In a dry run, the
+    /// benchmarks that are not skipped are only listed.
+    fn runs(&self, id: &str, kernel: &str) -> bool {
+        match &crate::cli::options().only_kernel {
+            Some(only) if only != kernel => {
+                eprintln!("skipped `{}`, only kernel `{}` is selected", id, only);
+                return false;
+            }
+            _ => {}
+        }
+        match self.budget.filter(Budget::exhausted) {
+            Some(budget) => {
+                crate::runner::warn_once(
+                    id,
+                    format_args!(
+                        "skipped `{}`, the budget of {:?} of group `{}` is used up",
+                        id,
+                        budget.duration(),
+                        budget.group()
+                    ),
+                );
+                false
+            }
+            None if crate::cli::options().dry_run => {
+                eprintln!("would run `{}` with kernel `{}`", id, kernel);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// The threading level MPI was initialized with, see the [`threading`](crate::threading)
+    /// module.
+    pub fn threading(&self) -> crate::threading::Threading {
+        crate::threading::granted()
+    }
+
+    /// The hostfile of the job in ``MPIRION_HOSTFILE``, if it is set, see the
+    /// [`hostfile`](crate::hostfile) module.
+    pub fn hostfile(&self) -> Option<&'static Hostfile> {
+        crate::hostfile::hostfile()
+    }
+
+    /// The number of children that fit next to the parent: the slots of the hostfile the parent
+    /// doesn't occupy, or the CPUs of this host but those of the parent without a hostfile. It is
+    /// at least one, so it can be used as the upper bound of a sweep, e.g. with
+    /// ``world_size = ctx.max_available()`` in ``mpirion_bench!``.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::prelude::*;
+    ///
+    /// // all powers of two that fit into the allocation of the job
+    /// fn scaling_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ///     let mut group = ctx.benchmark_group(c, "allreduce");
+    ///     for world_size in ctx.available_world_sizes((0..).map(|exponent| 1 << exponent)) {
+    ///         let id = ctx.benchmark_name("allreduce", world_size, None);
+    ///         group.bench_function(id, "allreduce", |b| {
+    ///             mpirion_bench!(allreduce, b, ctx; world_size = world_size)
+    ///         });
+    ///     }
+    ///     group.finish();
+    /// }
+    ///
+    /// fn allreduce(comm: &dyn Communicator) {
+    ///     let mut sum = 0u64;
+    ///     comm.all_reduce_into(&1u64, &mut sum, SystemOperation::sum());
+    /// }
+    ///
+    /// mpirion_kernel!(allreduce);
+    /// ```
+    pub fn max_available(&self) -> usize {
+        let parents = self.world.size() as usize;
+        let available = match crate::hostfile::available(parents) {
+            Some(hostfile) => hostfile.slots(),
+            None => std::thread::available_parallelism()
+                .map_or(1, usize::from)
+                .saturating_sub(parents),
+        };
+        available.max(1)
+    }
+
+    /// The world sizes of an ascending sweep that fit next to the parent, see
+    /// [`max_available`](Self::max_available). The sweep may be endless, it stops at the first
+    /// world size that doesn't fit.
+    pub fn available_world_sizes(
+        &self,
+        world_sizes: impl IntoIterator<Item = usize>,
+    ) -> Vec<usize> {
+        let available = self.max_available();
+        world_sizes
+            .into_iter()
+            .take_while(|world_size| *world_size <= available)
+            .collect()
+    }
+
+    /// The options of the current benchmark run, see [`cli::options`](crate::cli::options).
+    pub fn options(&self) -> &'static BenchOptions {
+        crate::cli::options()
+    }
+}
+
+/// A group of benchmarks created with [`MpirionContext::benchmark_group`]. Its benchmarks are
+/// declared with the name of their kernel, so mpirion can skip them before criterion starts them.
+/// The id of a benchmark is split like the ids of [`MpirionContext::benchmark_id`], so
+/// ``"bcast/4096"`` is the same benchmark as ``BenchmarkId::new("bcast", 4096)``. The group
+/// dereferences to the group of criterion, e.g. to set its sample size or throughput.
+///
+/// # Example
+/// ```rust
+/// use mpirion::prelude::*;
+///
+/// fn bcast_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+///     let mut group = ctx.benchmark_group(c, "bcast");
+///     group.sample_size(10);
+///     for size in [1024usize, 4096] {
+///         group.bench_with_input(format!("size/{}", size), "bcast", &size, |b, &size| {
+///             mpirion_bench!(bcast, b, ctx; arg = size)
+///         });
+///     }
+///     group.finish();
+/// }
+///
+/// fn setup(_comm: &dyn Communicator, size: usize) -> Vec<u8> {
+///     vec![0; size]
+/// }
+///
+/// fn bcast(comm: &dyn Communicator, data: &mut Vec<u8>) {
+///     comm.process_at_rank(0).broadcast_into(&mut data[..]);
+/// }
+///
+/// mpirion_kernel!(bcast, setup, usize);
+/// ```
+pub struct MpirionGroup<'a> {
+    context: &'a MpirionContext,
+    name: String,
+    group: BenchmarkGroup<'a, criterion::measurement::WallTime>,
+}
+
+impl MpirionGroup<'_> {
+    /// Benchmark ``kernel`` like ``group.bench_function(id, routine)``, unless the benchmark is
+    /// skipped.
+    pub fn bench_function<R>(&mut self, id: impl Display, kernel: &str, routine: R) -> &mut Self
+    where
+        R: FnMut(&mut Bencher<'_>),
+    {
+        let id = id.to_string();
+        let full_id = format!("{}/{}", self.name, id);
+        if self.context.runs(&full_id, kernel) {
+            crate::runner::run_declared(full_id, kernel, || {
+                self.group
+                    .bench_function(crate::baseline::benchmark_id(&id), routine);
+            });
+        }
+        self
+    }
+
+    /// Benchmark ``kernel`` like ``group.bench_with_input(id, input, routine)``, unless the
+    /// benchmark is skipped.
+    pub fn bench_with_input<I, R>(
+        &mut self,
+        id: impl Display,
+        kernel: &str,
+        input: &I,
+        mut routine: R,
+    ) -> &mut Self
+    where
+        I: ?Sized,
+        R: FnMut(&mut Bencher<'_>, &I),
+    {
+        self.bench_function(id, kernel, |b| routine(b, input))
+    }
+
+    /// Finish the group, like ``group.finish()`` in criterion.
+    pub fn finish(self) {
+        self.group.finish();
+    }
+}
+
+impl<'a> Deref for MpirionGroup<'a> {
+    type Target = BenchmarkGroup<'a, criterion::measurement::WallTime>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.group
+    }
+}
+
+impl DerefMut for MpirionGroup<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.group
+    }
+}
+
+/// The wall-clock budget of a benchmark group, set with ``budget`` in ``mpirion_group!``. It is
+/// checked before each benchmark declared through the [`MpirionContext`], so a benchmark that
+/// started is always finished. Once the budget is used up, the remaining benchmarks of the group
+/// are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    group: &'static str,
+    budget: Duration,
+    deadline: Instant,
+}
+
+impl Budget {
+    /// The name of the group the budget belongs to.
+    pub fn group(&self) -> &'static str {
+        self.group
+    }
+
+    /// The time the group may take.
+    pub fn duration(&self) -> Duration {
+        self.budget
+    }
+
+    /// Whether the budget is used up.
+    pub fn exhausted(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+/// Something ``mpirion_bench!`` can spawn the children from: an [`MpirionContext`], or, for
+/// benchmark functions written before the context existed, a communicator.
+pub trait ParentWorld {
+    /// The communicator from which the children are spawned.
+    fn parent_world(&self) -> &dyn Communicator;
+
+    /// The budget of the group, only known through an [`MpirionContext`].
+    fn budget(&self) -> Option<Budget> {
+        None
+    }
+}
+
+impl ParentWorld for MpirionContext {
+    fn parent_world(&self) -> &dyn Communicator {
+        self.world()
+    }
+
+    fn budget(&self) -> Option<Budget> {
+        self.budget
+    }
+}
+
+impl ParentWorld for dyn Communicator + '_ {
+    fn parent_world(&self) -> &dyn Communicator {
+        self
+    }
+}
+
+impl<C: Communicator> ParentWorld for C {
+    fn parent_world(&self) -> &dyn Communicator {
+        self
+    }
+}
+
+/// The target function of a benchmark group, of the form
+/// ``fn(&mut Criterion, &MpirionContext)``. Called by ``mpirion_group!``.
+#[doc(hidden)]
+pub trait GroupTarget {
+    /// Call the target function.
+    fn run_group(self, criterion: &mut Criterion, context: &MpirionContext);
+}
+
+impl<F> GroupTarget for F
+where
+    F: FnOnce(&mut Criterion, &MpirionContext),
+{
+    fn run_group(self, criterion: &mut Criterion, context: &MpirionContext) {
+        self(criterion, context)
+    }
+}
+
+/// The target function of a benchmark group of the deprecated form
+/// ``fn(&mut Criterion, &dyn Communicator)``. It is a separate trait with a method of the same
+/// name, so ``mpirion_group!`` resolves the call to the deprecated method for these functions
+/// only, and the benchmark compiles with a warning.
+#[doc(hidden)]
+pub trait WorldGroupTarget {
+    /// Call the target function with the world of the context.
+    #[deprecated(
+        since = "0.1.0",
+        note = "the target functions of mpirion_group! take `ctx: &MpirionContext` instead of `world: &dyn Communicator`, and pass it to mpirion_bench!"
+    )]
+    fn run_group(self, criterion: &mut Criterion, context: &MpirionContext);
+}
+
+impl<F> WorldGroupTarget for F
+where
+    F: FnOnce(&mut Criterion, &dyn Communicator),
+{
+    fn run_group(self, criterion: &mut Criterion, context: &MpirionContext) {
+        self(criterion, context.world())
+    }
+}
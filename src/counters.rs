@@ -0,0 +1,178 @@
+//! Times a kernel measures inside its iterations, like the phases of a non-blocking collective.
+//! The time of an iteration is measured around the whole kernel, so a kernel that wants to know
+//! how long its parts took records them as named counters with [`record`], or with the
+//! [`PhaseTimer`] it receives as its context with ``context = mpirion::counters::PhaseTimer::new``.
+//!
+//! Each child sums its counters over the iterations of a sample and sends them to the parent after
+//! the outcome, which keeps the slowest rank for each counter and divides it by the iterations the
+//! children ran. The mean of each counter over the samples of a benchmark is written into its
+//! ``mpirion.json``, see [`Metrics::counters`](crate::report::Metrics::counters).
+//!
+//! All ranks must record the same counters, the names recorded by rank 0 of the children are the
+//! ones sent to the parent.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::traits::*;
+use crate::transfer::broadcast_vec;
+
+/// The counters of this child in the current sample, in the order they were first recorded.
+static COUNTERS: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Add ``time`` to the counter with the given name in this child.
+pub fn record(name: &'static str, time: Duration) {
+    let mut counters = COUNTERS.lock().unwrap();
+    match counters.iter_mut().find(|(counter, _)| *counter == name) {
+        Some((_, total)) => *total += time,
+        None => counters.push((name, time)),
+    }
+}
+
+/// Take the counters this child recorded since the last call, which starts the next sample with
+/// no counters.
+pub(crate) fn take() -> Vec<(&'static str, Duration)> {
+    std::mem::take(&mut *COUNTERS.lock().unwrap())
+}
+
+/// Times the phases of a kernel, created as its context with
+/// ``context = mpirion::counters::PhaseTimer::new``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::counters::PhaseTimer;
+/// use mpirion::prelude::*;
+///
+/// fn pack_and_send_kernel(comm: &dyn Communicator, timer: &PhaseTimer) {
+///     let packed = timer.time("pack", || vec![comm.rank() as u64; 1024]);
+///     timer.time("reduce", || {
+///         let mut sums = vec![0u64; packed.len()];
+///         comm.all_reduce_into(&packed[..], &mut sums[..], SystemOperation::sum());
+///     });
+/// }
+///
+/// mpirion_kernel!(kernel = pack_and_send_kernel, context = PhaseTimer::new);
+/// ```
+pub struct PhaseTimer {
+    _private: (),
+}
+
+impl PhaseTimer {
+    /// A timer for the kernel running on ``world``.
+    pub fn new(_world: &dyn Communicator) -> Self {
+        Self { _private: () }
+    }
+
+    /// Run ``phase`` and add its time to the counter with the given name.
+    pub fn time<T>(&self, name: &'static str, phase: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = phase();
+        record(name, start.elapsed());
+        result
+    }
+}
+
+/// Send the counters of this child to the parent, see the module documentation. Called by the
+/// children after their output, before their CPU time.
+pub fn send(comm: &dyn Communicator, counters: &[(&str, Duration)]) {
+    // rank 0 of the merged communicator is the parent, rank 1 is rank 0 of the children
+    let mut names = Vec::new();
+    if comm.rank() == 1 {
+        names = counters
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_bytes();
+    }
+    broadcast_vec(comm, 1, &mut names);
+    if names.is_empty() {
+        return;
+    }
+
+    let totals: Vec<u64> = String::from_utf8_lossy(&names)
+        .split('\n')
+        .map(|name| {
+            counters
+                .iter()
+                .find(|(counter, _)| *counter == name)
+                .map_or(0, |(_, total)| crate::timing::duration_to_nanos(*total))
+        })
+        .collect();
+    comm.process_at_rank(0)
+        .reduce_into(&totals[..], SystemOperation::max());
+}
+
+/// Receive the counters of the children on the parent: the total of each counter over the
+/// iterations of the sample, of the slowest rank.
+pub fn receive(comm: &dyn Communicator) -> Vec<(String, Duration)> {
+    let mut names = Vec::new();
+    broadcast_vec(comm, 1, &mut names);
+    if names.is_empty() {
+        return Vec::new();
+    }
+
+    let names: Vec<String> = String::from_utf8_lossy(&names)
+        .split('\n')
+        .map(str::to_string)
+        .collect();
+    let mut totals = vec![0u64; names.len()];
+    comm.process_at_rank(0).reduce_into_root(
+        &vec![0u64; names.len()][..],
+        &mut totals[..],
+        SystemOperation::max(),
+    );
+    names
+        .into_iter()
+        .zip(totals)
+        .map(|(name, nanos)| (name, Duration::from_nanos(nanos)))
+        .collect()
+}
+
+/// The mean time per iteration of each counter, from its total over ``iterations`` iterations.
+///
+/// # Example
+/// ```rust
+/// use mpirion::counters::per_iteration;
+/// use std::time::Duration;
+///
+/// let totals = vec![("wait".to_string(), Duration::from_micros(500))];
+/// assert_eq!(per_iteration(&totals, 100), vec![("wait".to_string(), Duration::from_micros(5))]);
+/// assert_eq!(per_iteration(&totals, 0), totals);
+/// ```
+pub fn per_iteration(totals: &[(String, Duration)], iterations: u64) -> Vec<(String, Duration)> {
+    let iterations = u32::try_from(iterations.max(1)).unwrap_or(u32::MAX);
+    totals
+        .iter()
+        .map(|(name, total)| (name.clone(), *total / iterations))
+        .collect()
+}
+
+/// The mean of each counter over the samples that recorded it, in the order the counters were
+/// first recorded.
+///
+/// # Example
+/// ```rust
+/// use mpirion::counters::mean;
+/// use std::time::Duration;
+///
+/// let counter = |name: &str, micros| (name.to_string(), Duration::from_micros(micros));
+/// let samples = [vec![counter("initiation", 2), counter("wait", 10)], vec![counter("wait", 20)]];
+/// assert_eq!(mean(samples.iter().map(Vec::as_slice)), vec![counter("initiation", 2), counter("wait", 15)]);
+/// ```
+pub fn mean<'a>(
+    samples: impl IntoIterator<Item = &'a [(String, Duration)]>,
+) -> Vec<(String, Duration)> {
+    let mut times: Vec<(String, Vec<Duration>)> = Vec::new();
+    for (name, time) in samples.into_iter().flatten() {
+        match times.iter_mut().find(|(counter, _)| counter == name) {
+            Some((_, counter_times)) => counter_times.push(*time),
+            None => times.push((name.clone(), vec![*time])),
+        }
+    }
+    times
+        .into_iter()
+        .map(|(name, times)| (name, crate::timing::mean(&times)))
+        .collect()
+}
@@ -0,0 +1,106 @@
+//! Running a single iteration of a kernel outside of criterion, for debugging. ``mpirion_main!``
+//! enters this mode with ``--mpirion-run <kernel> [--world-size N] [--arg V]``.
+
+use crate::mpi::traits::Communicator;
+use crate::mpi::Rank;
+use std::time::Instant;
+
+use crate::runner::{BenchRunner, DEFAULT_WORLD_SIZE};
+
+/// The options of ``--mpirion-run``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOptions {
+    /// the kernel to run
+    pub kernel: String,
+    /// the number of children to spawn
+    pub world_size: Rank,
+    /// the argument passed to the setup function, parsed by the children
+    pub arg: Option<String>,
+}
+
+impl RunOptions {
+    /// Parse the arguments following ``--mpirion-run``.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::debug::RunOptions;
+    ///
+    /// let args = ["my_kernel", "--world-size", "8", "--arg", "42"].map(String::from);
+    /// let options = RunOptions::parse(args).unwrap();
+    /// assert_eq!(options.kernel, "my_kernel");
+    /// assert_eq!(options.world_size, 8);
+    /// assert_eq!(options.arg.as_deref(), Some("42"));
+    ///
+    /// assert!(RunOptions::parse(["--world-size", "8"].map(String::from)).is_err());
+    /// ```
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let kernel = match args.next() {
+            Some(kernel) if !kernel.starts_with("--") => kernel,
+            _ => return Err("expected a kernel name after --mpirion-run".to_string()),
+        };
+
+        let mut options = Self {
+            kernel,
+            world_size: DEFAULT_WORLD_SIZE,
+            arg: None,
+        };
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("expected a value after {}", flag))?;
+            match flag.as_str() {
+                "--world-size" => {
+                    options.world_size = value
+                        .parse()
+                        .ok()
+                        .filter(|&world_size: &Rank| world_size > 0)
+                        .ok_or_else(|| format!("invalid world size `{}`", value))?;
+                }
+                "--arg" => options.arg = Some(value),
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Spawn the children once, run a single iteration of the kernel and print the time each rank
+/// spent in it. Returns the exit code of the process.
+pub fn run_once(world: &dyn Communicator, options: &RunOptions) -> i32 {
+    eprintln!(
+        "mpirion: running a single iteration of `{}` on {} children",
+        options.kernel, options.world_size
+    );
+
+    let mut runner = BenchRunner::new(options.kernel.clone(), world).world_size(options.world_size);
+    if let Some(arg) = &options.arg {
+        eprintln!("mpirion: passing the argument `{}`", arg);
+        runner = runner.text_arg(arg.clone());
+    }
+
+    let start = Instant::now();
+    let result = runner.try_run_per_rank(1);
+    eprintln!(
+        "mpirion: spawn, run and collection took {:?}",
+        start.elapsed()
+    );
+
+    match result {
+        Ok(durations) => {
+            for (rank, duration) in durations.iter().enumerate() {
+                eprintln!("mpirion: rank {}: {:?}", rank, duration);
+            }
+            let min = durations.iter().min().copied().unwrap_or_default();
+            let max = durations.iter().max().copied().unwrap_or_default();
+            eprintln!("mpirion: min {:?}, max {:?}", min, max);
+            0
+        }
+        Err(message) => {
+            eprintln!("mpirion: kernel `{}` failed: {}", options.kernel, message);
+            1
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Detection of iteration times that trend over a sample. The children only report the total time
+//! of a sample, so a kernel that gets slower with every iteration, e.g. because of memory
+//! fragmentation or thermal throttling, or faster, because lazy initialization leaks into the
+//! measured region, has a misleading mean.
+//!
+//! Each child compares the mean time of the first quartile of the iterations of a sample with the
+//! mean of the last quartile, see [`DriftTracker`], without storing the individual times. The
+//! parent receives the drift of the rank that drifted most, and warns once per kernel if it
+//! exceeds the threshold set with ``MPIRION_DRIFT_THRESHOLD``, see [`threshold`].
+//! ``--mpirion-verbose`` prints the drift of every sample.
+
+use std::time::Duration;
+
+/// The environment variable that sets the drift threshold in percent, or ``off`` to disable the
+/// warning.
+pub const DRIFT_THRESHOLD_VAR: &str = "MPIRION_DRIFT_THRESHOLD";
+
+/// The drift threshold in percent if ``MPIRION_DRIFT_THRESHOLD`` is not set.
+pub const DEFAULT_THRESHOLD: f64 = 10.0;
+
+/// The fewest iterations a sample needs for its drift to be measured. With fewer iterations per
+/// quartile, the noise of single iterations dominates the drift.
+pub const MIN_ITERATIONS: u64 = 16;
+
+/// Tracks the time of the first and the last quartile of the iterations of a sample.
+///
+/// # Example
+/// A synthetic kernel whose iterations get 1 µs slower every time drifts by the difference of the
+/// quartile means:
+/// ```rust
+/// use mpirion::drift::DriftTracker;
+/// use std::time::Duration;
+///
+/// let iterations = 100;
+/// let mut drift = DriftTracker::new(iterations);
+/// for iteration in 0..iterations {
+///     drift.record(iteration, Duration::from_micros(100 + iteration));
+/// }
+/// // the first quartile averages 112 µs, the last one 187 µs
+/// let relative = drift.relative().unwrap();
+/// assert!((relative - 75.0 / 112.0).abs() < 1e-9);
+///
+/// let mut steady = DriftTracker::new(iterations);
+/// for iteration in 0..iterations {
+///     steady.record(iteration, Duration::from_micros(100));
+/// }
+/// assert_eq!(steady.relative(), Some(0.0));
+///
+/// // too few iterations to tell a trend from noise
+/// let mut short = DriftTracker::new(8);
+/// (0..8).for_each(|iteration| short.record(iteration, Duration::from_micros(100 + 50 * iteration)));
+/// assert_eq!(short.relative(), None);
+///
+/// // a truncated sample has no last quartile
+/// let mut truncated = DriftTracker::new(iterations);
+/// (0..60).for_each(|iteration| truncated.record(iteration, Duration::from_micros(100)));
+/// assert_eq!(truncated.relative(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DriftTracker {
+    iterations: u64,
+    recorded: u64,
+    quartile: u64,
+    first: Duration,
+    last: Duration,
+}
+
+impl DriftTracker {
+    /// Create a tracker for a sample of ``iterations`` iterations.
+    pub fn new(iterations: u64) -> Self {
+        Self {
+            iterations,
+            recorded: 0,
+            quartile: iterations / 4,
+            first: Duration::ZERO,
+            last: Duration::ZERO,
+        }
+    }
+
+    /// Record the time of an iteration, counted from 0.
+    pub fn record(&mut self, iteration: u64, elapsed: Duration) {
+        self.recorded += 1;
+        if iteration < self.quartile {
+            self.first += elapsed;
+        }
+        if iteration >= self.iterations - self.quartile {
+            self.last += elapsed;
+        }
+    }
+
+    /// The change of the mean time of the last quartile relative to the first quartile, e.g. 0.2
+    /// if the last iterations took 20% longer. ``None`` if the sample has fewer than
+    /// [`MIN_ITERATIONS`] iterations, not all of them were recorded, e.g. because the sample hit
+    /// its time cap, or the first quartile took no measurable time.
+    pub fn relative(&self) -> Option<f64> {
+        if self.iterations < MIN_ITERATIONS
+            || self.recorded < self.iterations
+            || self.first.is_zero()
+        {
+            return None;
+        }
+        // both quartiles have the same number of iterations, so their sums compare like their means
+        Some((self.last.as_secs_f64() - self.first.as_secs_f64()) / self.first.as_secs_f64())
+    }
+}
+
+/// Parse a drift threshold in percent, as used in ``MPIRION_DRIFT_THRESHOLD``. ``off`` disables
+/// the warning.
+///
+/// # Example
+/// ```rust
+/// use mpirion::drift::parse_threshold;
+///
+/// assert_eq!(parse_threshold("5"), Ok(Some(5.0)));
+/// assert_eq!(parse_threshold("12.5"), Ok(Some(12.5)));
+/// assert_eq!(parse_threshold("off"), Ok(None));
+/// assert!(parse_threshold("-3").is_err());
+/// assert!(parse_threshold("ten").is_err());
+/// ```
+pub fn parse_threshold(value: &str) -> Result<Option<f64>, String> {
+    parse_percent(DRIFT_THRESHOLD_VAR, value)
+}
+
+/// Parse a threshold in percent from the environment variable ``var``, or ``off``.
+pub(crate) fn parse_percent(var: &str, value: &str) -> Result<Option<f64>, String> {
+    if value.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    match value.parse::<f64>() {
+        Ok(percent) if percent.is_finite() && percent > 0.0 => Ok(Some(percent)),
+        _ => Err(format!(
+            "{} must be a positive number of percent or `off`, not `{}`",
+            var, value
+        )),
+    }
+}
+
+/// The drift threshold in percent, from ``MPIRION_DRIFT_THRESHOLD`` or [`DEFAULT_THRESHOLD`], or
+/// ``None`` if the warning is disabled. Panics if the variable is malformed.
+pub fn threshold() -> Option<f64> {
+    match std::env::var(DRIFT_THRESHOLD_VAR) {
+        Ok(value) => parse_threshold(&value).unwrap_or_else(|message| panic!("{}", message)),
+        Err(_) => Some(DEFAULT_THRESHOLD),
+    }
+}
+
+/// Whether a relative drift exceeds a threshold in percent, in either direction.
+///
+/// # Example
+/// ```rust
+/// use mpirion::drift::exceeds;
+///
+/// assert!(exceeds(0.25, Some(10.0)));
+/// assert!(exceeds(-0.25, Some(10.0)));
+/// assert!(!exceeds(0.05, Some(10.0)));
+/// assert!(!exceeds(0.25, None));
+/// ```
+pub fn exceeds(relative: f64, threshold: Option<f64>) -> bool {
+    threshold.is_some_and(|percent| relative.abs() * 100.0 > percent)
+}
@@ -0,0 +1,268 @@
+//! Reporting of MPI errors on the communicators mpirion creates. By default, MPI aborts the job on
+//! any error with a message of the implementation, which doesn't say which benchmark failed or what
+//! mpirion was doing at the time, and skips the report of the failures recorded so far.
+//!
+//! Instead, mpirion switches the parent world, the communicators to the children and the worlds of
+//! the children to ``MPI_ERRORS_RETURN``, and checks the return codes of the calls that frame each
+//! sample: the broadcast of the iterations and the arguments, and the barrier and the reductions of
+//! the outcome. The mpi crate ignores the return codes of its calls, so these go through the raw
+//! bindings. A failure becomes an [`Error::Mpi`](crate::Error::Mpi) that names the kernel, the
+//! phase of the protocol and the MPI error class. The parent fails the benchmark group with it. A
+//! child cannot report it to the parent anymore, so it prints it with the paths of its logs and
+//! aborts the job. Errors while spawning are returned by the mpi crate and reported as
+//! [`Error::SpawnUnsupported`](crate::Error::SpawnUnsupported) like before.
+//!
+//! MPI calls of the kernels on the world of the children return their errors as well, which the mpi
+//! crate ignores. Kernels that need to notice them check the return codes of the raw bindings with
+//! [`check`].
+
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::traits::Communicator;
+use crate::mpi::Rank;
+use crate::Error;
+
+/// The phase in which the parent spawns the children.
+pub const SPAWN: &str = "spawn";
+
+/// The phase in which the parent and the children merge their worlds.
+pub const MERGE: &str = "merge";
+
+/// The phase in which the parent and the children exchange the handshake and the kernel name.
+pub const HANDSHAKE: &str = "handshake";
+
+/// The phase in which the parent sends the iterations and the arguments.
+pub const ARGUMENTS: &str = "arguments";
+
+/// The phase in which the children run the samples.
+pub const KERNEL: &str = "kernel";
+
+/// The phase in which the children report the outcome and the outputs of a sample.
+pub const OUTCOME: &str = "outcome";
+
+/// The kernel and the phase the process is in, for the report of an MPI error.
+static PHASE: Mutex<Option<(String, &'static str)>> = Mutex::new(None);
+
+/// Record that the process enters a phase of the protocol for the given kernel.
+pub fn enter(kernel: &str, phase: &'static str) {
+    let mut current = PHASE.lock().unwrap();
+    match current.as_mut() {
+        Some((current_kernel, current_phase)) if current_kernel == kernel => *current_phase = phase,
+        _ => *current = Some((kernel.to_string(), phase)),
+    }
+}
+
+/// Switch a communicator to ``MPI_ERRORS_RETURN``, so that MPI returns the errors on it instead of
+/// aborting the job.
+pub fn install<C: AsRaw<Raw = ffi::MPI_Comm>>(comm: &C) -> Result<(), Error> {
+    // SAFETY: the communicator is valid while it is borrowed
+    check(unsafe { ffi::MPI_Comm_set_errhandler(comm.as_raw(), ffi::RSMPI_ERRORS_RETURN) })
+}
+
+/// Check the return code of an MPI call on a communicator with ``MPI_ERRORS_RETURN``. A failure is
+/// returned as [`Error::Mpi`] with the kernel and the phase the process is in, see [`enter`].
+pub fn check(code: c_int) -> Result<(), Error> {
+    if code == ffi::MPI_SUCCESS as c_int {
+        return Ok(());
+    }
+
+    let mut class: c_int = 0;
+    // SAFETY: both calls only read the error code
+    let message = unsafe {
+        ffi::MPI_Error_class(code, &mut class);
+        error_string(code)
+    };
+    Err(Error::Mpi {
+        phase: PHASE.lock().unwrap().clone(),
+        class,
+        message,
+    })
+}
+
+/// Report an error that a child cannot send to its parent anymore, and abort the job like MPI
+/// would have without ``MPI_ERRORS_RETURN``. Returns the value of the call otherwise.
+pub fn or_abort<T>(comm: &dyn Communicator, result: Result<T, Error>) -> T {
+    let error = match result {
+        Ok(value) => return value,
+        Err(error) => error,
+    };
+
+    eprintln!("mpirion: {}", error);
+    crate::logs::write(format_args!("mpirion: {}", error));
+    if let Error::Mpi {
+        phase: Some((kernel, _)),
+        ..
+    } = &error
+    {
+        crate::logs::print_paths(kernel);
+    }
+    crate::failure::print_recorded();
+    comm.abort(1)
+}
+
+/// Broadcast a value from ``root`` like ``broadcast_into``, but return the error of MPI.
+pub(crate) fn broadcast<T: Equivalence>(
+    comm: &dyn Communicator,
+    root: Rank,
+    value: &mut T,
+) -> Result<(), Error> {
+    // SAFETY: the buffer holds one value of the datatype of T
+    check(unsafe {
+        ffi::MPI_Bcast(
+            value as *mut T as *mut c_void,
+            1,
+            T::equivalent_datatype().as_raw(),
+            root,
+            comm.as_raw(),
+        )
+    })
+}
+
+/// Receive a value broadcast from ``root`` into uninitialized memory, for types that have no valid
+/// placeholder value. The value is only read once MPI reported that it wrote it.
+pub(crate) fn receive_broadcast<T: Equivalence>(
+    comm: &dyn Communicator,
+    root: Rank,
+) -> Result<T, Error> {
+    let mut value = MaybeUninit::<T>::uninit();
+    // SAFETY: the buffer holds one value of the datatype of T, which the root sends, and it is only
+    // assumed to be initialized if the broadcast succeeded
+    unsafe {
+        check(ffi::MPI_Bcast(
+            value.as_mut_ptr() as *mut c_void,
+            1,
+            T::equivalent_datatype().as_raw(),
+            root,
+            comm.as_raw(),
+        ))?;
+        Ok(value.assume_init())
+    }
+}
+
+/// Reduce the values of all ranks to their maximum on rank 0 like ``reduce_into_root``, but return
+/// the error of MPI. Only rank 0 passes a ``result``.
+pub(crate) fn reduce_max<T: Equivalence>(
+    comm: &dyn Communicator,
+    value: &T,
+    result: Option<&mut T>,
+) -> Result<(), Error> {
+    reduce(comm, value, result, Operation::Max)
+}
+
+/// Reduce the values of all ranks to their minimum on rank 0, see [`reduce_max`].
+pub(crate) fn reduce_min<T: Equivalence>(
+    comm: &dyn Communicator,
+    value: &T,
+    result: Option<&mut T>,
+) -> Result<(), Error> {
+    reduce(comm, value, result, Operation::Min)
+}
+
+/// The predefined operations of [`reduce`].
+enum Operation {
+    Max,
+    Min,
+}
+
+fn reduce<T: Equivalence>(
+    comm: &dyn Communicator,
+    value: &T,
+    result: Option<&mut T>,
+    operation: Operation,
+) -> Result<(), Error> {
+    let result = result.map_or(std::ptr::null_mut(), |result| {
+        result as *mut T as *mut c_void
+    });
+    // SAFETY: both buffers hold one value of the datatype of T, and MPI only writes the one of
+    // rank 0, which is the only one that passes it
+    check(unsafe {
+        let op = match operation {
+            Operation::Max => ffi::RSMPI_MAX,
+            Operation::Min => ffi::RSMPI_MIN,
+        };
+        ffi::MPI_Reduce(
+            value as *const T as *const c_void,
+            result,
+            1,
+            T::equivalent_datatype().as_raw(),
+            op,
+            0,
+            comm.as_raw(),
+        )
+    })
+}
+
+/// Wait in a nonblocking barrier like ``immediate_barrier``, but return the error of MPI. Returns
+/// false if the timeout expired before all ranks entered the barrier. The request cannot be
+/// cancelled then, so the caller has to give up on the communicator.
+pub(crate) fn barrier(comm: &dyn Communicator, timeout: Option<Duration>) -> Result<bool, Error> {
+    let mut request = MaybeUninit::<ffi::MPI_Request>::uninit();
+    // SAFETY: MPI initializes the request if the call succeeds
+    let mut request = unsafe {
+        check(ffi::MPI_Ibarrier(comm.as_raw(), request.as_mut_ptr()))?;
+        request.assume_init()
+    };
+
+    let Some(timeout) = timeout else {
+        // SAFETY: the request is active, and the status is ignored
+        check(unsafe { ffi::MPI_Wait(&mut request, ffi::RSMPI_STATUS_IGNORE) })?;
+        return Ok(true);
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let mut done: c_int = 0;
+        // SAFETY: the request is active, and the status is ignored
+        check(unsafe { ffi::MPI_Test(&mut request, &mut done, ffi::RSMPI_STATUS_IGNORE) })?;
+        if done != 0 {
+            return Ok(true);
+        }
+
+        if Instant::now() >= deadline {
+            return Ok(false);
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Describe an MPI error for the user.
+///
+/// # Example
+/// ```rust
+/// use mpirion::errhandler::{describe, HANDSHAKE};
+///
+/// let message = describe(Some(("scan_kernel", HANDSHAKE)), 15, "MPI_ERR_TRUNCATE: message truncated");
+/// assert_eq!(
+///     message,
+///     "MPI error in the handshake phase of kernel `scan_kernel`: MPI error class 15 (MPI_ERR_TRUNCATE: message truncated)"
+/// );
+/// assert!(describe(None, 15, "").contains("outside of a benchmark"));
+/// ```
+pub fn describe(phase: Option<(&str, &str)>, class: i32, message: &str) -> String {
+    let location = match phase {
+        Some((kernel, phase)) => format!("in the {} phase of kernel `{}`", phase, kernel),
+        None => "outside of a benchmark".to_string(),
+    };
+    format!(
+        "MPI error {}: MPI error class {} ({})",
+        location, class, message
+    )
+}
+
+/// The message of an MPI error code or class.
+unsafe fn error_string(code: c_int) -> String {
+    let mut buffer = [0 as c_char; 1024];
+    let mut length: c_int = 0;
+    ffi::MPI_Error_string(code, buffer.as_mut_ptr(), &mut length);
+    let bytes: Vec<u8> = buffer[..length.clamp(0, 1024) as usize]
+        .iter()
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
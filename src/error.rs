@@ -0,0 +1,80 @@
+//! Errors that prevent a benchmark group from running. Unlike a failed benchmark, which is
+//! recorded and reported at the end of the run, these are returned by the functions generated by
+//! ``mpirion_group!``, so an application that embeds the benchmarks can skip them.
+
+use crate::mpi::Rank;
+use std::fmt::{Display, Formatter};
+
+/// The reason why a benchmark group could not run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// the group was called on another rank than rank 0 of the parent world
+    WrongRank {
+        /// the rank the group was called on
+        rank: Rank,
+    },
+    /// the parent world has more than one rank, and the other ranks don't idle in
+    /// ``mpirion_main!`` while rank 0 spawns the children
+    WorldSize {
+        /// the size of the parent world
+        size: Rank,
+    },
+    /// the MPI implementation could not spawn the child processes
+    SpawnUnsupported {
+        /// the error reported by MPI
+        message: String,
+    },
+    /// an MPI call of mpirion failed on one of its communicators, see the
+    /// [`errhandler`](crate::errhandler) module
+    Mpi {
+        /// the kernel and the phase of the protocol, or ``None`` outside of a benchmark
+        phase: Option<(String, &'static str)>,
+        /// the MPI error class
+        class: i32,
+        /// the message of the MPI library for the error
+        message: String,
+    },
+}
+
+impl Error {
+    /// The exit code of ``mpirion_main!`` if a group returned this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::WrongRank { .. } | Error::WorldSize { .. } => 2,
+            Error::SpawnUnsupported { .. } => 3,
+            Error::Mpi { .. } => 4,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::WrongRank { rank } => write!(
+                f,
+                "the benchmark root process must run on rank 0, but it runs on rank {}",
+                rank
+            ),
+            Error::WorldSize { size } => write!(
+                f,
+                "the benchmark root process expected to have world size 1, but it has world size {}",
+                size
+            ),
+            Error::SpawnUnsupported { message } => {
+                write!(f, "the child processes could not be spawned: {}", message)
+            }
+            Error::Mpi {
+                phase,
+                class,
+                message,
+            } => f.write_str(&crate::errhandler::describe(
+                phase.as_ref().map(|(kernel, phase)| (kernel.as_str(), *phase)),
+                *class,
+                message,
+            )),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
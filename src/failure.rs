@@ -0,0 +1,132 @@
+//! Failed benchmarks. A benchmark fails if its children report that the kernel produced wrong
+//! results or returned an error. Criterion has no notion of a failed benchmark, so the failure
+//! unwinds out of the benchmark function and is caught by the group, which records it and
+//! continues with the next group. The main function reports all failures at the end of the run.
+//!
+//! Errors that concern the whole group, like a failed spawn, unwind the same way, but are returned
+//! by the group as an [`Error`] instead of being recorded.
+
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::Mutex;
+
+use crate::Error;
+
+static FAILURES: Mutex<Vec<BenchmarkFailure>> = Mutex::new(Vec::new());
+
+static GROUP_ERRORS: Mutex<Vec<(String, Error)>> = Mutex::new(Vec::new());
+
+/// The reason why a benchmark was aborted.
+#[derive(Debug, Clone)]
+pub struct BenchmarkFailure {
+    /// name of the kernel that failed
+    pub kernel: String,
+    /// human-readable description of the failure
+    pub message: String,
+}
+
+impl Display for BenchmarkFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "kernel `{}` failed: {}", self.kernel, self.message)
+    }
+}
+
+/// Abort the current benchmark. The failure is printed immediately, with the directory of the
+/// [logs](crate::logs) of the children, and then unwinds to the enclosing ``mpirion_group!``. Unwinding is started without invoking the panic hook, so no
+/// confusing panic message is printed.
+pub fn fail(kernel: &str, message: String) -> ! {
+    let failure = BenchmarkFailure {
+        kernel: kernel.to_string(),
+        message,
+    };
+    eprintln!("{}", failure);
+    crate::logs::print_paths(kernel);
+    resume_unwind(Box::new(failure))
+}
+
+/// Abort the current benchmark group with an error that prevents all of its benchmarks from
+/// running. It unwinds to the enclosing ``mpirion_group!``, which returns it.
+pub fn fail_group(error: Error) -> ! {
+    resume_unwind(Box::new(error))
+}
+
+/// Run the target of a benchmark group and record a [`BenchmarkFailure`] if one unwinds out of it.
+/// An [`Error`] raised with [`fail_group`] is returned. Any other panic is propagated unchanged.
+pub fn catch(group: &str, target: impl FnOnce()) -> Result<(), Error> {
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(target)) {
+        let payload = match payload.downcast::<Error>() {
+            Ok(error) => return Err(*error),
+            Err(payload) => payload,
+        };
+        match payload.downcast::<BenchmarkFailure>() {
+            Ok(failure) => {
+                eprintln!("skipping the remaining benchmarks of group `{}`", group);
+                FAILURES.lock().unwrap().push(*failure);
+            }
+            Err(payload) => resume_unwind(payload),
+        }
+    }
+
+    Ok(())
+}
+
+/// Record the error returned by a benchmark group. Called by ``mpirion_main!``.
+pub fn record_group_error(group: &str, error: Error) {
+    eprintln!("group `{}` could not run: {}", group, error);
+    GROUP_ERRORS
+        .lock()
+        .unwrap()
+        .push((group.to_string(), error));
+}
+
+/// The benchmark failures recorded so far.
+pub fn recorded() -> Vec<BenchmarkFailure> {
+    FAILURES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Print all failures and group errors recorded so far. Used by [`exit_on_failures`] and before
+/// a child aborts the job on an MPI error, see [`errhandler`](crate::errhandler).
+pub fn print_recorded() {
+    let errors = GROUP_ERRORS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let failures = FAILURES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    print_recorded_from(&errors, &failures);
+}
+
+fn print_recorded_from(errors: &[(String, Error)], failures: &[BenchmarkFailure]) {
+    if !errors.is_empty() {
+        eprintln!("{} group(s) could not run:", errors.len());
+        for (group, error) in errors.iter() {
+            eprintln!("  group `{}`: {}", group, error);
+        }
+    }
+
+    if !failures.is_empty() {
+        eprintln!("{} benchmark(s) failed:", failures.len());
+        for failure in failures.iter() {
+            eprintln!("  {}", failure);
+        }
+    }
+}
+
+/// Print all failures and group errors recorded during the run and exit the process with a
+/// non-zero exit code if there were any. Group errors take precedence, and the exit code is the
+/// one of the first error, see [`Error::exit_code`]. Otherwise, the exit code is 1.
+pub fn exit_on_failures() {
+    let errors = GROUP_ERRORS.lock().unwrap();
+    let failures = FAILURES.lock().unwrap();
+    print_recorded_from(&errors, &failures);
+
+    if let Some((_, error)) = errors.first() {
+        std::process::exit(error.exit_code());
+    }
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+}
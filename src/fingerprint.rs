@@ -0,0 +1,421 @@
+//! The fingerprint of the machines the children run on. A kernel that got faster after the cluster
+//! was upgraded, or because ``OMP_NUM_THREADS`` was set in one shell but not in the other, is not
+//! a faster kernel, so each child reports its host in the handshake: the model and the number of
+//! its CPUs, its memory, the version of its kernel, the environment variables that configure
+//! OpenMP, UCX and libfabric (``OMP_*``, ``UCX_*`` and ``FI_*``) and the InfiniBand, RoCE and
+//! Omni-Path devices in ``/sys/class/infiniband``, which hint at the interconnect.
+//!
+//! The fingerprint is gathered once per spawned world, outside of the measured iterations. The
+//! parent writes the distinct fingerprints of the run into ``mpirion-meta.json``, see
+//! [`meta`](crate::meta), and at the end of the run prints how they differ from those of the last
+//! run in the same output directory, see [`differences`].
+//!
+//! The fingerprint is read from ``/proc`` and ``/sys``, so it is only known on Linux.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::manifest::json_string;
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::traits::*;
+
+/// The prefixes of the environment variables that are part of the fingerprint.
+pub const VARIABLE_PREFIXES: &[&str] = &["OMP_", "UCX_", "FI_"];
+
+/// The distinct fingerprints of the children of this run, in the order they were first reported.
+static RUN: Mutex<Vec<HostFingerprint>> = Mutex::new(Vec::new());
+
+/// The fingerprints in ``mpirion-meta.json`` of the last run, read before it was replaced.
+static PREVIOUS: Mutex<Vec<HostFingerprint>> = Mutex::new(Vec::new());
+
+/// The fingerprint of the host of a child.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostFingerprint {
+    /// the processor name reported by MPI, usually the host name
+    pub host: String,
+    /// the model of the CPUs, from ``/proc/cpuinfo``
+    pub cpu_model: Option<String>,
+    /// the number of logical CPUs of the host, from ``/proc/cpuinfo``
+    pub cpus: Option<usize>,
+    /// the total memory of the host in bytes, from ``/proc/meminfo``
+    pub memory: Option<u64>,
+    /// the release of the kernel, from ``/proc/sys/kernel/osrelease``
+    pub kernel: Option<String>,
+    /// the environment variables with one of the [`VARIABLE_PREFIXES`], sorted by name
+    pub variables: Vec<(String, String)>,
+    /// the devices in ``/sys/class/infiniband``, sorted by name
+    pub interconnect: Vec<String>,
+}
+
+impl HostFingerprint {
+    /// The fingerprint of the host of this process.
+    pub fn collect() -> Self {
+        let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let cpu_model = cpuinfo
+            .lines()
+            .find(|line| line.starts_with("model name"))
+            .and_then(|line| line.split_once(':'))
+            .map(|(_, model)| model.trim().to_string());
+        let processors = cpuinfo
+            .lines()
+            .filter(|line| line.starts_with("processor"))
+            .count();
+        let memory = std::fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| {
+                let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+                let kilobytes: u64 = line["MemTotal:".len()..]
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse()
+                    .ok()?;
+                Some(kilobytes * 1024)
+            });
+        let mut variables: Vec<(String, String)> = std::env::vars()
+            .filter(|(name, _)| {
+                VARIABLE_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            })
+            .collect();
+        variables.sort();
+        let mut interconnect: Vec<String> = std::fs::read_dir("/sys/class/infiniband")
+            .map(|devices| {
+                devices
+                    .filter_map(|device| device.ok()?.file_name().into_string().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        interconnect.sort();
+
+        Self {
+            host: crate::mpi::environment::processor_name()
+                .unwrap_or_else(|_| "unknown".to_string()),
+            cpu_model,
+            cpus: (processors > 0).then_some(processors),
+            memory,
+            kernel: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .map(|release| release.trim().to_string()),
+            variables,
+            interconnect,
+        }
+    }
+
+    /// The fingerprint as a JSON object, as written into ``mpirion-meta.json``.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::fingerprint::HostFingerprint;
+    ///
+    /// let fingerprint = HostFingerprint {
+    ///     host: "node01".to_string(),
+    ///     cpu_model: Some("AMD EPYC 7763 64-Core Processor".to_string()),
+    ///     cpus: Some(128),
+    ///     memory: Some(270_000_000_000),
+    ///     kernel: Some("5.14.0".to_string()),
+    ///     variables: vec![("OMP_NUM_THREADS".to_string(), "4".to_string())],
+    ///     interconnect: vec!["mlx5_0".to_string()],
+    /// };
+    /// let json = fingerprint.to_json();
+    /// assert_eq!(
+    ///     json,
+    ///     r#"{"cpus":128,"memory_bytes":270000000000,"host":"node01","cpu_model":"AMD EPYC 7763 64-Core Processor","kernel":"5.14.0","variables":["OMP_NUM_THREADS=4"],"interconnect":["mlx5_0"]}"#
+    /// );
+    /// assert_eq!(HostFingerprint::parse(&json), Some(fingerprint));
+    /// ```
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<&str>| value.map_or_else(|| "null".to_string(), json_string);
+        let number = |value: Option<u64>| {
+            value.map_or_else(|| "null".to_string(), |value| value.to_string())
+        };
+        let variables: Vec<String> = self
+            .variables
+            .iter()
+            .map(|(name, value)| json_string(&format!("{}={}", name, value)))
+            .collect();
+        let interconnect: Vec<String> = self
+            .interconnect
+            .iter()
+            .map(|device| json_string(device))
+            .collect();
+        format!(
+            r#"{{"cpus":{},"memory_bytes":{},"host":{},"cpu_model":{},"kernel":{},"variables":[{}],"interconnect":[{}]}}"#,
+            number(self.cpus.map(|cpus| cpus as u64)),
+            number(self.memory),
+            json_string(&self.host),
+            optional(self.cpu_model.as_deref()),
+            optional(self.kernel.as_deref()),
+            variables.join(","),
+            interconnect.join(",")
+        )
+    }
+
+    /// Parse a fingerprint written by [`to_json`](Self::to_json).
+    pub fn parse(json: &str) -> Option<Self> {
+        let number = |field: &str| {
+            crate::report::number_field(json, field).map(|value| value.map(|value| value as u64))
+        };
+        let variables = crate::report::string_array(json, "variables")?
+            .into_iter()
+            .map(|variable| {
+                variable
+                    .split_once('=')
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            host: crate::report::string_field(json, "host")?,
+            cpu_model: crate::report::string_field(json, "cpu_model"),
+            cpus: number("cpus")?.map(|cpus| cpus as usize),
+            memory: number("memory_bytes")?,
+            kernel: crate::report::string_field(json, "kernel"),
+            variables,
+            interconnect: crate::report::string_array(json, "interconnect")?,
+        })
+    }
+}
+
+impl fmt::Display for HostFingerprint {
+    /// The fingerprint on one line, as in the [campaign report](crate::campaign).
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::fingerprint::HostFingerprint;
+    ///
+    /// let fingerprint = HostFingerprint {
+    ///     host: "node01".to_string(),
+    ///     cpu_model: Some("AMD EPYC 7763 64-Core Processor".to_string()),
+    ///     cpus: Some(128),
+    ///     memory: Some(256 << 30),
+    ///     kernel: Some("5.14.0".to_string()),
+    ///     variables: vec![("OMP_NUM_THREADS".to_string(), "4".to_string())],
+    ///     interconnect: vec!["mlx5_0".to_string()],
+    /// };
+    /// assert_eq!(
+    ///     fingerprint.to_string(),
+    ///     "`node01`: 128 CPUs, AMD EPYC 7763 64-Core Processor, 256.0 GiB, kernel 5.14.0, interconnect mlx5_0, OMP_NUM_THREADS=4"
+    /// );
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: ", self.host)?;
+        match self.cpus {
+            Some(cpus) => write!(f, "{} CPUs", cpus)?,
+            None => write!(f, "unknown CPUs")?,
+        }
+        if let Some(model) = &self.cpu_model {
+            write!(f, ", {}", model)?;
+        }
+        if let Some(memory) = self.memory {
+            write!(f, ", {}", format_memory(memory))?;
+        }
+        if let Some(kernel) = &self.kernel {
+            write!(f, ", kernel {}", kernel)?;
+        }
+        if !self.interconnect.is_empty() {
+            write!(f, ", interconnect {}", self.interconnect.join(" "))?;
+        }
+        for (name, value) in &self.variables {
+            write!(f, ", {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A number of bytes in GiB.
+fn format_memory(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1u64 << 30) as f64)
+}
+
+/// The fingerprints in the ``fingerprint`` array of a ``mpirion-meta.json``, empty if it has none.
+pub fn parse_meta(json: &str) -> Vec<HostFingerprint> {
+    let Some(start) = json.find("\"fingerprint\":[") else {
+        return Vec::new();
+    };
+    // the objects of the array, split at the braces outside of strings
+    let mut fingerprints = Vec::new();
+    let (mut depth, mut in_string, mut escaped, mut object_start) = (0usize, false, false, 0);
+    for (index, c) in json[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => {
+                if depth == 0 {
+                    object_start = start + index;
+                }
+                depth += 1;
+            }
+            '}' if !in_string && depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    fingerprints
+                        .extend(HostFingerprint::parse(&json[object_start..=start + index]));
+                }
+            }
+            ']' if !in_string && depth == 0 => break,
+            _ => {}
+        }
+    }
+    fingerprints
+}
+
+/// A property of the hosts by its label, and how it is shown for a host.
+type Property<'a> = (&'a str, &'a dyn Fn(&HostFingerprint) -> String);
+
+/// How the fingerprints of a run differ from those of an earlier run, one line per property. The
+/// hosts are not compared, only the values the hosts of each run have, so two allocations on
+/// identical nodes of a cluster have the same fingerprint.
+///
+/// # Example
+/// ```rust
+/// use mpirion::fingerprint::{differences, HostFingerprint};
+///
+/// let node = |host: &str, kernel: &str, threads: Option<&str>| HostFingerprint {
+///     host: host.to_string(),
+///     cpus: Some(64),
+///     kernel: Some(kernel.to_string()),
+///     variables: threads.map(|threads| ("OMP_NUM_THREADS".to_string(), threads.to_string())).into_iter().collect(),
+///     ..HostFingerprint::default()
+/// };
+/// let last = [node("node01", "5.14.0", None), node("node02", "5.14.0", None)];
+/// assert!(differences(&last, &[node("node03", "5.14.0", None)]).is_empty());
+/// assert_eq!(
+///     differences(&last, &[node("node01", "6.1.0", Some("4"))]),
+///     ["kernel: 6.1.0 instead of 5.14.0", "OMP_NUM_THREADS: 4 instead of unset"]
+/// );
+/// ```
+pub fn differences(last: &[HostFingerprint], now: &[HostFingerprint]) -> Vec<String> {
+    let values =
+        |fingerprints: &[HostFingerprint], value: &dyn Fn(&HostFingerprint) -> String| -> String {
+            let values: BTreeSet<String> = fingerprints.iter().map(value).collect();
+            values.into_iter().collect::<Vec<_>>().join(", ")
+        };
+    let unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+    let properties: [Property<'_>; 5] = [
+        ("CPU model", &|host| unknown(host.cpu_model.clone())),
+        ("CPUs", &|host| {
+            unknown(host.cpus.map(|cpus| cpus.to_string()))
+        }),
+        ("memory", &|host| unknown(host.memory.map(format_memory))),
+        ("kernel", &|host| unknown(host.kernel.clone())),
+        ("interconnect", &|host| {
+            if host.interconnect.is_empty() {
+                "none".to_string()
+            } else {
+                host.interconnect.join(" ")
+            }
+        }),
+    ];
+
+    let mut differences = Vec::new();
+    let mut compare = |label: &str, value: &dyn Fn(&HostFingerprint) -> String| {
+        let (before, after) = (values(last, value), values(now, value));
+        if before != after {
+            differences.push(format!("{}: {} instead of {}", label, after, before));
+        }
+    };
+    for (label, value) in properties {
+        compare(label, value);
+    }
+    let names: BTreeSet<&str> = last
+        .iter()
+        .chain(now)
+        .flat_map(|host| host.variables.iter().map(|(name, _)| name.as_str()))
+        .collect();
+    for name in names {
+        compare(name, &|host| {
+            host.variables
+                .iter()
+                .find(|(variable, _)| variable == name)
+                .map_or_else(|| "unset".to_string(), |(_, value)| value.clone())
+        });
+    }
+    differences
+}
+
+/// Send the fingerprint of this child to the parent. Called by the children in the handshake.
+pub fn send(comm: &dyn Communicator) {
+    let json = HostFingerprint::collect().to_json().into_bytes();
+    let len = exchange_len(comm, json.len());
+    let mut record = vec![0u8; len];
+    record[..json.len()].copy_from_slice(&json);
+    comm.process_at_rank(0).gather_into(&record[..]);
+}
+
+/// Receive the fingerprints of the children, in the order of their ranks. Called by the parent in
+/// the handshake.
+pub fn receive(comm: &dyn Communicator) -> Vec<HostFingerprint> {
+    let len = exchange_len(comm, 0);
+    let ranks = usize::try_from(comm.size()).expect("the size of a communicator is positive");
+    let mut records = vec![0u8; ranks * len];
+    comm.process_at_rank(0)
+        .gather_into_root(&vec![0u8; len][..], &mut records[..]);
+
+    // the first record is the dummy record of the parent
+    records
+        .chunks(len.max(1))
+        .skip(1)
+        .filter_map(|record| {
+            let end = record
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(record.len());
+            HostFingerprint::parse(&String::from_utf8_lossy(&record[..end]))
+        })
+        .collect()
+}
+
+/// The length of the longest fingerprint, so every child sends a record of the same length.
+fn exchange_len(comm: &dyn Communicator, len: usize) -> usize {
+    let mut longest = 0u64;
+    comm.all_reduce_into(&(len as u64), &mut longest, SystemOperation::max());
+    usize::try_from(longest).expect("a fingerprint fits into memory")
+}
+
+/// Remember the fingerprints of the last run from its ``mpirion-meta.json``, before it is replaced.
+/// Called by [`meta::write`](crate::meta::write).
+pub(crate) fn remember_last_run(meta: &str) {
+    *PREVIOUS.lock().unwrap() = parse_meta(meta);
+}
+
+/// Add the fingerprints of the children of a spawned world to those of the run, and write them into
+/// ``mpirion-meta.json`` if there are new ones. Called by the parent after the handshake.
+pub fn record(fingerprints: &[HostFingerprint]) {
+    let mut run = RUN.lock().unwrap();
+    let before = run.len();
+    for fingerprint in fingerprints {
+        if !run.contains(fingerprint) {
+            run.push(fingerprint.clone());
+        }
+    }
+    if run.len() > before {
+        crate::meta::save(&run);
+    }
+}
+
+/// The fingerprints of the children of this run, see [`record`].
+pub fn recorded() -> Vec<HostFingerprint> {
+    RUN.lock().unwrap().clone()
+}
+
+/// Print how the fingerprints of this run differ from those of the last run. Does nothing if either
+/// run has no fingerprints. Called by the [`JsonFileReporter`](crate::reporter::JsonFileReporter) at
+/// the end of the run.
+pub(crate) fn compare_with_last_run() {
+    let (last, now) = (PREVIOUS.lock().unwrap().clone(), recorded());
+    if last.is_empty() || now.is_empty() {
+        return;
+    }
+    let differences = differences(&last, &now);
+    if !differences.is_empty() {
+        eprintln!(
+            "mpirion: the environment differs from the last run, so the changes reported by criterion may not be caused by the benchmarks:"
+        );
+        for difference in differences {
+            eprintln!("mpirion:   {}", difference);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Device buffers for benchmarks of CUDA-aware MPI, enabled by the ``gpu`` feature. The buffers are
+//! allocated with ``cudarc``, and the kernel passes their device pointers to MPI, which moves the
+//! data between the GPUs itself.
+//!
+//! A kernel selects the GPU of each child with ``context = mpirion::gpu::Device::select`` in the
+//! named parameters syntax of ``mpirion_kernel!``. The context is created once per spawned world,
+//! before the setup, so the setup can allocate [`DeviceBuffer`]s on the selected GPU. The children
+//! on a node take turns on its GPUs by their [local rank](crate::placement::local_rank), so four
+//! children on a node with two GPUs use each GPU twice. After the last iteration, the device is
+//! synchronized and released when the context is dropped.
+//!
+//! CUDA is loaded when it is first used, so a benchmark built with the ``gpu`` feature still
+//! starts on machines without a GPU. Benchmark functions can check [`available`] in the parent
+//! and skip the GPU benchmarks instead of failing in the children.
+
+use std::cell::RefCell;
+use std::os::raw::c_void;
+use std::sync::Arc;
+
+use cudarc::driver::{CudaDevice, CudaSlice, DevicePtr, DevicePtrMut, DeviceRepr, ValidAsZeroBits};
+
+use crate::mpi::traits::*;
+
+thread_local! {
+    /// The GPU selected by the context of the kernel running in this child.
+    static CURRENT: RefCell<Option<Arc<CudaDevice>>> = const { RefCell::new(None) };
+}
+
+/// The number of GPUs of this machine, or ``None`` if CUDA is not available, e.g. because there is
+/// no driver.
+pub fn device_count() -> Option<usize> {
+    // cudarc panics if it cannot load the driver library
+    let count = std::panic::catch_unwind(|| {
+        cudarc::driver::result::init()?;
+        cudarc::driver::result::device::get_count()
+    });
+    match count {
+        Ok(Ok(count)) => usize::try_from(count).ok(),
+        _ => None,
+    }
+}
+
+/// Whether CUDA is available and this machine has at least one GPU.
+pub fn available() -> bool {
+    device_count().is_some_and(|count| count > 0)
+}
+
+/// The GPU of a child, created as the context of a kernel with
+/// ``context = mpirion::gpu::Device::select``. While it lives, [`DeviceBuffer`]s are allocated on
+/// it.
+pub struct Device {
+    device: Arc<CudaDevice>,
+}
+
+impl Device {
+    /// Select the GPU of this child: the children on a node are spread round-robin over its GPUs
+    /// by their local rank. Collective over ``world``. Panics if CUDA is not available.
+    pub fn select(world: &dyn Communicator) -> Self {
+        let (local_rank, _) = crate::placement::local_rank(world);
+        let count = match device_count() {
+            Some(count) if count > 0 => count,
+            _ => panic!(
+                "CUDA not available on rank {}: no driver or no GPU found",
+                world.rank()
+            ),
+        };
+        let ordinal = usize::try_from(local_rank).expect("ranks are not negative") % count;
+        let device = CudaDevice::new(ordinal).unwrap_or_else(|error| {
+            panic!(
+                "rank {} could not select GPU {}: {}",
+                world.rank(),
+                ordinal,
+                error
+            )
+        });
+        CURRENT.with(|current| *current.borrow_mut() = Some(device.clone()));
+        Self { device }
+    }
+
+    /// The ordinal of the selected GPU on its node.
+    pub fn ordinal(&self) -> usize {
+        self.device.ordinal()
+    }
+
+    /// Wait until all work on the GPU is done.
+    pub fn synchronize(&self) {
+        self.device
+            .synchronize()
+            .expect("could not synchronize the GPU");
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        // outstanding copies must be done before the buffers and the device are freed
+        let _ = self.device.synchronize();
+        CURRENT.with(|current| current.borrow_mut().take());
+    }
+}
+
+/// The GPU selected by the context of the running kernel.
+fn current() -> Arc<CudaDevice> {
+    CURRENT.with(|current| current.borrow().clone()).expect(
+        "no GPU was selected, add `context = mpirion::gpu::Device::select` to the mpirion_kernel! of the kernel",
+    )
+}
+
+/// A buffer in the memory of the GPU selected by [`Device::select`], usable as the data returned by
+/// a setup function. It is freed when it is dropped.
+pub struct DeviceBuffer<T> {
+    slice: CudaSlice<T>,
+}
+
+impl<T: DeviceRepr + ValidAsZeroBits> DeviceBuffer<T> {
+    /// A buffer of ``len`` zeros.
+    pub fn zeros(len: usize) -> Self {
+        let slice = current()
+            .alloc_zeros(len)
+            .expect("could not allocate the device buffer");
+        Self { slice }
+    }
+}
+
+impl<T: DeviceRepr + Unpin> DeviceBuffer<T> {
+    /// A buffer with a copy of ``values``.
+    pub fn from_host(values: &[T]) -> Self {
+        let slice = current()
+            .htod_sync_copy(values)
+            .expect("could not copy to the device buffer");
+        Self { slice }
+    }
+
+    /// A copy of the buffer in host memory, e.g. to validate the result of the kernel.
+    pub fn to_host(&self) -> Vec<T> {
+        current()
+            .dtoh_sync_copy(&self.slice)
+            .expect("could not copy from the device buffer")
+    }
+}
+
+impl<T> DeviceBuffer<T> {
+    /// The number of elements of the buffer.
+    pub fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// Whether the buffer has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.slice.len() == 0
+    }
+
+    /// The device pointer of the buffer, to pass as a send buffer to CUDA-aware MPI.
+    pub fn as_ptr(&self) -> *const c_void {
+        *self.slice.device_ptr() as *const c_void
+    }
+
+    /// The device pointer of the buffer, to pass as a receive buffer to CUDA-aware MPI.
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        *self.slice.device_ptr_mut() as *mut c_void
+    }
+}
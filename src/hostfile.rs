@@ -0,0 +1,395 @@
+//! The hosts a benchmark may spawn its children on, read from the hostfile of the job. Job scripts
+//! that write a hostfile pass its path in [`HOSTFILE_VAR`]. The file is either an Open MPI
+//! hostfile, with a host and its ``slots=N`` on each line, or a Slurm nodelist like
+//! ``node[01-04],gpu07``, whose slots are taken from ``SLURM_TASKS_PER_NODE`` if it is set, and
+//! are one per host otherwise.
+//!
+//! The slots the parent doesn't occupy are the capacity of the benchmark, which benchmark functions
+//! read with [`MpirionContext::max_available`](crate::MpirionContext::max_available) to derive the
+//! upper bound of their sweeps. When the children are spawned, the parent assigns them to the
+//! hosts by the [placement](crate::placement::PlacementMode) of the benchmark, by default filling
+//! each host before the next one. It passes the assignment to ``MPI_Comm_spawn`` as the ``host``
+//! info key, and warns if the children report other hosts.
+
+use std::sync::OnceLock;
+
+use crate::placement::{Placement, PlacementMode};
+
+/// The environment variable with the path of the hostfile.
+pub const HOSTFILE_VAR: &str = "MPIRION_HOSTFILE";
+
+/// The environment variable with the slots of the hosts of a Slurm nodelist, like ``4(x3),2``.
+pub const TASKS_PER_NODE_VAR: &str = "SLURM_TASKS_PER_NODE";
+
+/// The info key of ``MPI_Comm_spawn`` with the hosts of the children.
+pub(crate) const HOST_KEY: &str = "host";
+
+/// A host of the hostfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub name: String,
+    /// the number of processes the host may run
+    pub slots: usize,
+}
+
+/// The hosts of a hostfile, in the order of the file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Hostfile {
+    pub hosts: Vec<Host>,
+}
+
+impl Hostfile {
+    /// Parse a hostfile, which is a Slurm nodelist if it is a single line with a list or a range
+    /// of hosts, and an Open MPI hostfile otherwise. ``tasks_per_node`` are the slots of the hosts
+    /// of a nodelist, see [`parse_tasks_per_node`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::hostfile::Hostfile;
+    ///
+    /// let open_mpi = Hostfile::parse("# the job\nnode01 slots=4\nnode02 slots=4 max_slots=8\n", None).unwrap();
+    /// assert_eq!(open_mpi.slots(), 8);
+    ///
+    /// let slurm = Hostfile::parse("node[01-03]\n", Some("4(x2),2")).unwrap();
+    /// let slots: Vec<(&str, usize)> = slurm.hosts.iter().map(|host| (host.name.as_str(), host.slots)).collect();
+    /// assert_eq!(slots, [("node01", 4), ("node02", 4), ("node03", 2)]);
+    /// assert_eq!(Hostfile::parse("node[01-03]", None).unwrap().slots(), 3);
+    /// ```
+    pub fn parse(text: &str, tasks_per_node: Option<&str>) -> Result<Self, String> {
+        let lines: Vec<&str> = content_lines(text).collect();
+        match lines.as_slice() {
+            [list]
+                if !list.contains(char::is_whitespace)
+                    && (list.contains('[') || list.contains(',')) =>
+            {
+                Self::parse_nodelist(list, tasks_per_node)
+            }
+            _ => Self::parse_open_mpi(text),
+        }
+    }
+
+    /// Parse an Open MPI hostfile. Each line has a host, optionally followed by its ``slots=N``,
+    /// which defaults to one, and further ``key=value`` options, which are ignored. Hosts on several
+    /// lines have the slots of all of them, and ``#`` starts a comment.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::hostfile::Hostfile;
+    ///
+    /// let hostfile = Hostfile::parse_open_mpi("node01 slots=2\nnode02\nnode01 slots=2 # again\n").unwrap();
+    /// assert_eq!(hostfile.hosts.len(), 2);
+    /// assert_eq!((hostfile.hosts[0].slots, hostfile.hosts[1].slots), (4, 1));
+    /// assert!(Hostfile::parse_open_mpi("node01 slots=four").is_err());
+    /// assert!(Hostfile::parse_open_mpi("node01 fast").is_err());
+    /// ```
+    pub fn parse_open_mpi(text: &str) -> Result<Self, String> {
+        let mut hostfile = Hostfile::default();
+        for line in content_lines(text) {
+            let mut fields = line.split_whitespace();
+            let name = fields.next().expect("content lines are not empty");
+            let mut slots = 1;
+            for field in fields {
+                match field.split_once('=') {
+                    Some(("slots", value)) => {
+                        slots = value.parse().map_err(|_| {
+                            format!("malformed slots of host `{}`: `{}`", name, value)
+                        })?;
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Err(format!("malformed option of host `{}`: `{}`", name, field))
+                    }
+                }
+            }
+            match hostfile.hosts.iter_mut().find(|host| host.name == name) {
+                Some(host) => host.slots += slots,
+                None => hostfile.hosts.push(Host {
+                    name: name.to_string(),
+                    slots,
+                }),
+            }
+        }
+        Ok(hostfile)
+    }
+
+    /// Parse a Slurm nodelist, see [`expand_nodelist`], with the slots in ``tasks_per_node``, or
+    /// one per host without them. Fails if the number of slots doesn't match the hosts.
+    pub fn parse_nodelist(list: &str, tasks_per_node: Option<&str>) -> Result<Self, String> {
+        let names = expand_nodelist(list)?;
+        let slots = match tasks_per_node {
+            Some(tasks) => parse_tasks_per_node(tasks)?,
+            None => vec![1; names.len()],
+        };
+        if slots.len() != names.len() {
+            return Err(format!(
+                "the nodelist `{}` has {} hosts, but {} slots were given for them",
+                list,
+                names.len(),
+                slots.len()
+            ));
+        }
+        Ok(Hostfile {
+            hosts: names
+                .into_iter()
+                .zip(slots)
+                .map(|(name, slots)| Host { name, slots })
+                .collect(),
+        })
+    }
+
+    /// The slots of all hosts.
+    pub fn slots(&self) -> usize {
+        self.hosts.iter().map(|host| host.slots).sum()
+    }
+
+    /// The hostfile without ``processes`` slots of ``host``, e.g. those of the parent. Hosts
+    /// are compared by their name up to the domain, see [`same_host`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::hostfile::Hostfile;
+    ///
+    /// let hostfile = Hostfile::parse_open_mpi("node01 slots=4\nnode02 slots=4").unwrap();
+    /// assert_eq!(hostfile.without("node01.cluster", 1).slots(), 7);
+    /// assert_eq!(hostfile.without("login01", 1).slots(), 8);
+    /// ```
+    pub fn without(&self, host: &str, processes: usize) -> Hostfile {
+        let mut hostfile = self.clone();
+        if let Some(host) = hostfile
+            .hosts
+            .iter_mut()
+            .find(|candidate| same_host(&candidate.name, host))
+        {
+            host.slots = host.slots.saturating_sub(processes);
+        }
+        hostfile
+    }
+
+    /// The host of each of ``world_size`` children placed as ``mode`` says, ordered by rank.
+    /// ``PerCore`` fills the slots of each host before the next one, ``PerNode`` places one child on
+    /// each host, and ``Spread`` the given number of children on each host with enough slots.
+    /// Fails if the children don't fit.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::hostfile::Hostfile;
+    /// use mpirion::placement::PlacementMode;
+    ///
+    /// let hostfile = Hostfile::parse_open_mpi("node01 slots=3\nnode02 slots=4\nnode03 slots=4").unwrap();
+    /// assert_eq!(hostfile.assign(PlacementMode::PerCore, 5).unwrap(), ["node01", "node01", "node01", "node02", "node02"]);
+    /// assert_eq!(hostfile.assign(PlacementMode::PerNode, 3).unwrap(), ["node01", "node02", "node03"]);
+    /// assert_eq!(hostfile.assign(PlacementMode::Spread(4), 6).unwrap(), ["node02", "node02", "node02", "node02", "node03", "node03"]);
+    /// assert!(hostfile.assign(PlacementMode::PerNode, 4).is_err());
+    /// assert!(hostfile.assign(PlacementMode::PerCore, 12).is_err());
+    /// ```
+    pub fn assign(&self, mode: PlacementMode, world_size: usize) -> Result<Vec<String>, String> {
+        let mut hosts = Vec::with_capacity(world_size);
+        for host in &self.hosts {
+            let remaining = world_size - hosts.len();
+            let children = match mode {
+                PlacementMode::PerCore => host.slots.min(remaining),
+                PlacementMode::PerNode => host.slots.min(remaining).min(1),
+                PlacementMode::Spread(ranks) => {
+                    let children = ranks.min(remaining);
+                    if host.slots < children {
+                        continue;
+                    }
+                    children
+                }
+            };
+            hosts.extend(std::iter::repeat_n(host.name.clone(), children));
+        }
+        if hosts.len() < world_size {
+            return Err(format!(
+                "the hostfile has {} free slots on {} hosts, which cannot place {} children {}",
+                self.slots(),
+                self.hosts.len(),
+                world_size,
+                mode
+            ));
+        }
+        Ok(hosts)
+    }
+}
+
+/// The lines of a hostfile without comments and blank lines.
+fn content_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+}
+
+/// The hosts of a Slurm nodelist like ``node[01-03,07],login``, with the zeros the ranges are
+/// padded with. Each host has at most one range.
+///
+/// # Example
+/// ```rust
+/// use mpirion::hostfile::expand_nodelist;
+///
+/// assert_eq!(expand_nodelist("node[08-10,12],login").unwrap(), ["node08", "node09", "node10", "node12", "login"]);
+/// assert_eq!(expand_nodelist("rack[1-2]-gpu").unwrap(), ["rack1-gpu", "rack2-gpu"]);
+/// assert!(expand_nodelist("node[03-01]").is_err());
+/// assert!(expand_nodelist("node[01").is_err());
+/// ```
+pub fn expand_nodelist(list: &str) -> Result<Vec<String>, String> {
+    let malformed = || format!("malformed nodelist `{}`", list);
+    let mut hosts = Vec::new();
+    let mut rest = list.trim();
+    while !rest.is_empty() {
+        // the commas inside of brackets separate ranges, not hosts
+        let end = match (rest.find('['), rest.find(',')) {
+            (Some(open), Some(comma)) if open < comma => {
+                let close = rest[open..].find(']').ok_or_else(malformed)? + open;
+                close + rest[close..].find(',').unwrap_or(rest.len() - close)
+            }
+            (_, Some(comma)) => comma,
+            (_, None) => rest.len(),
+        };
+        let entry = &rest[..end];
+        rest = rest[end..].strip_prefix(',').unwrap_or(&rest[end..]);
+        let Some((prefix, ranges)) = entry.split_once('[') else {
+            if entry.contains(']') || entry.is_empty() {
+                return Err(malformed());
+            }
+            hosts.push(entry.to_string());
+            continue;
+        };
+        let (ranges, suffix) = ranges.split_once(']').ok_or_else(malformed)?;
+        if suffix.contains(['[', ']']) {
+            return Err(malformed());
+        }
+        for range in ranges.split(',') {
+            let (first, last) = range.split_once('-').unwrap_or((range, range));
+            let (start, end): (u64, u64) = (
+                first.parse().map_err(|_| malformed())?,
+                last.parse().map_err(|_| malformed())?,
+            );
+            if start > end {
+                return Err(malformed());
+            }
+            for number in start..=end {
+                hosts.push(format!(
+                    "{}{:0width$}{}",
+                    prefix,
+                    number,
+                    suffix,
+                    width = first.len()
+                ));
+            }
+        }
+    }
+    Ok(hosts)
+}
+
+/// The slots of each host from the format of ``SLURM_TASKS_PER_NODE``, like ``4(x3),2`` for
+/// three hosts with four slots and one with two.
+///
+/// # Example
+/// ```rust
+/// use mpirion::hostfile::parse_tasks_per_node;
+///
+/// assert_eq!(parse_tasks_per_node("4(x3),2").unwrap(), [4, 4, 4, 2]);
+/// assert_eq!(parse_tasks_per_node("16").unwrap(), [16]);
+/// assert!(parse_tasks_per_node("4(3)").is_err());
+/// ```
+pub fn parse_tasks_per_node(tasks: &str) -> Result<Vec<usize>, String> {
+    let malformed = || format!("malformed {} `{}`", TASKS_PER_NODE_VAR, tasks);
+    let mut slots = Vec::new();
+    for entry in tasks.trim().split(',') {
+        let (count, repeat) = match entry.split_once('(') {
+            Some((count, repeat)) => {
+                let repeat = repeat
+                    .strip_prefix('x')
+                    .and_then(|repeat| repeat.strip_suffix(')'))
+                    .ok_or_else(malformed)?;
+                (count, repeat.parse::<usize>().map_err(|_| malformed())?)
+            }
+            None => (entry, 1),
+        };
+        let count: usize = count.parse().map_err(|_| malformed())?;
+        slots.extend(std::iter::repeat_n(count, repeat));
+    }
+    Ok(slots)
+}
+
+/// Whether two host names are the same host, compared up to the first ``.``, since MPI reports
+/// the name of the processor with or without the domain.
+///
+/// # Example
+/// ```rust
+/// use mpirion::hostfile::same_host;
+///
+/// assert!(same_host("node01", "node01.cluster.example"));
+/// assert!(!same_host("node01", "node02"));
+/// ```
+pub fn same_host(first: &str, second: &str) -> bool {
+    let short = |name: &str| name.split('.').next().unwrap_or_default().to_string();
+    short(first) == short(second)
+}
+
+/// The value of the ``host`` info key for children on the given hosts, ordered by rank, with the
+/// number of children of consecutive ranks on each host, like ``node01:4,node02:2``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::hostfile::host_value;
+///
+/// let hosts = ["node01", "node01", "node02"].map(String::from);
+/// assert_eq!(host_value(&hosts), "node01:2,node02:1");
+/// ```
+pub fn host_value(hosts: &[String]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for host in hosts {
+        match counts.last_mut() {
+            Some((last, count)) if *last == host.as_str() => *count += 1,
+            _ => counts.push((host.as_str(), 1)),
+        }
+    }
+    counts
+        .iter()
+        .map(|(host, count)| format!("{}:{}", host, count))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Whether the children ran on the hosts they were assigned to, ordered by rank.
+pub fn matches(assigned: &[String], placement: &Placement) -> bool {
+    assigned.len() == placement.ranks.len()
+        && assigned
+            .iter()
+            .zip(&placement.ranks)
+            .all(|(host, rank)| same_host(host, &rank.host))
+}
+
+/// The hostfile in [`HOSTFILE_VAR`], read once. Panics if it cannot be read or is malformed.
+pub fn hostfile() -> Option<&'static Hostfile> {
+    static HOSTFILE: OnceLock<Option<Hostfile>> = OnceLock::new();
+
+    HOSTFILE
+        .get_or_init(|| {
+            let path = std::env::var_os(HOSTFILE_VAR)?;
+            let text = std::fs::read_to_string(&path).unwrap_or_else(|error| {
+                panic!(
+                    "{}: could not read {}: {}",
+                    HOSTFILE_VAR,
+                    path.to_string_lossy(),
+                    error
+                )
+            });
+            let tasks_per_node = std::env::var(TASKS_PER_NODE_VAR).ok();
+            let hostfile =
+                Hostfile::parse(&text, tasks_per_node.as_deref()).unwrap_or_else(|message| {
+                    panic!("{}: {}: {}", HOSTFILE_VAR, path.to_string_lossy(), message)
+                });
+            Some(hostfile)
+        })
+        .as_ref()
+}
+
+/// The hosts of the hostfile with the slots the ``parents`` processes of the parent world don't
+/// occupy on the host of this process, or ``None`` without a hostfile.
+pub fn available(parents: usize) -> Option<Hostfile> {
+    let hostfile = hostfile()?;
+    let host = crate::mpi::environment::processor_name().unwrap_or_default();
+    Some(hostfile.without(&host, parents))
+}
@@ -0,0 +1,326 @@
+//! Files written collectively with MPI-IO, for benchmarks of parallel I/O like
+//! ``MPI_File_write_all``. Opening and deleting the file is part of the setup, so it fits the
+//! ``init``, ``reset`` and ``teardown`` functions of ``mpirion_kernel!``, like the requests of the
+//! [`persistent`](crate::persistent) module: the init function [creates](SharedFile::create) the
+//! file, the reset function [truncates](SharedFile::truncate) it between the iterations, outside
+//! the measured time, the kernel writes it, and the teardown function [deletes](SharedFile::delete)
+//! it.
+//!
+//! All ranks must open the same path. The parent chooses a path for each spawned world and sends it
+//! to the children in the handshake, see [`scratch_path`], so the iterations of a benchmark never
+//! see the files of other benchmarks or of earlier runs. The files are written below
+//! ``target/mpirion-scratch``, or below the directory in ``MPIRION_SCRATCH``, which must be on a
+//! filesystem all nodes of the job share, e.g. the parallel filesystem of a cluster. Only rank 0
+//! creates the directory of the file and deletes the file, the other ranks wait for it.
+//!
+//! Unlike communicators, files return their errors by default instead of aborting the job, so each
+//! method panics with the error code of MPI if an operation fails.
+//!
+//! # Example
+//! ```rust
+//! use mpirion::io::SharedFile;
+//! use mpirion::prelude::*;
+//!
+//! const BLOCK: usize = 1 << 20;
+//!
+//! fn init(comm: &dyn Communicator) -> SharedFile<'_> {
+//!     SharedFile::create(comm)
+//! }
+//!
+//! fn reset<'s, 'w>(_comm: &dyn Communicator, file: &'s SharedFile<'w>) -> &'s SharedFile<'w> {
+//!     file.truncate();
+//!     file
+//! }
+//!
+//! fn teardown(_comm: &dyn Communicator, file: SharedFile<'_>) {
+//!     file.delete();
+//! }
+//!
+//! // every rank writes its block next to the blocks of the other ranks
+//! fn write_kernel(comm: &dyn Communicator, file: &&SharedFile) {
+//!     file.write_at_all((comm.rank() as usize * BLOCK) as u64, &vec![comm.rank() as u8; BLOCK]);
+//! }
+//!
+//! mpirion_kernel!(kernel = write_kernel, init = init, reset = reset, teardown = teardown, data = ref);
+//! ```
+
+use std::ffi::CString;
+use std::os::raw::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::traits::*;
+
+/// The environment variable with the directory the files of the children are written below,
+/// instead of ``target/mpirion-scratch``.
+pub const SCRATCH_VAR: &str = "MPIRION_SCRATCH";
+
+/// The path the parent sent to this child in the handshake, or, with the ``local-fallback``
+/// feature, the path of the kernel that runs in the parent.
+static SCRATCH_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The directory the files of all runs are written below, ``MPIRION_SCRATCH`` if it is set, and
+/// ``mpirion-scratch`` in the target directory otherwise.
+pub fn root() -> PathBuf {
+    std::env::var_os(SCRATCH_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::report::target_dir().join("mpirion-scratch"))
+}
+
+/// The path of the file of the given kernel in the spawned world with the given id, in a directory
+/// of the current run, e.g. ``target/mpirion-scratch/20261015-123005-4242/write_kernel-3.dat``.
+/// The directory is named after the start of the run and the process id of the parent, so
+/// concurrent runs don't share it. Called by the parent before it spawns the children, which
+/// create the directory only if they open the file.
+pub(crate) fn file_path(kernel: &str, world: u64) -> PathBuf {
+    static RUN: OnceLock<String> = OnceLock::new();
+    let run = RUN.get_or_init(|| {
+        format!(
+            "{}-{}",
+            crate::logs::run_name(std::time::SystemTime::now()),
+            std::process::id()
+        )
+    });
+    root().join(run).join(format!("{}-{}.dat", kernel, world))
+}
+
+/// Remember the path of the file of the world this process runs a kernel in. Called by the
+/// children with the path the parent sent in the handshake, and by the parent before it runs a
+/// kernel itself.
+pub(crate) fn set_scratch_path(path: PathBuf) {
+    *SCRATCH_PATH
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(path);
+}
+
+/// The path the parent chose for the file of this world, which [`SharedFile::create`] opens, or
+/// ``None`` if this process runs no kernel.
+pub fn scratch_path() -> Option<PathBuf> {
+    SCRATCH_PATH
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// A file opened by all ranks of a communicator, see the module documentation. It is closed when it
+/// is dropped.
+pub struct SharedFile<'w> {
+    raw: ffi::MPI_File,
+    path: PathBuf,
+    comm: &'w dyn Communicator,
+}
+
+impl<'w> SharedFile<'w> {
+    /// Create the file at the [`scratch_path`] of this world, or empty it if it exists, and open it
+    /// for reading and writing. Collective over ``comm``. Panics if this process runs no kernel,
+    /// use [`create_at`](Self::create_at) there.
+    pub fn create(comm: &'w dyn Communicator) -> Self {
+        let path = scratch_path()
+            .expect("SharedFile::create is only available in the kernels of a benchmark");
+        Self::create_at(comm, path)
+    }
+
+    /// Create the file at ``path``, or empty it if it exists, and open it for reading and writing.
+    /// Rank 0 creates the directory of the file. Collective over ``comm``, and all ranks must pass
+    /// the same path. Panics if the directory cannot be created or MPI cannot open the file.
+    pub fn create_at(comm: &'w dyn Communicator, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if comm.rank() == 0 {
+            if let Some(directory) = path
+                .parent()
+                .filter(|directory| !directory.as_os_str().is_empty())
+            {
+                if let Err(error) = std::fs::create_dir_all(directory) {
+                    panic!(
+                        "could not create the directory {} of a shared file: {}",
+                        directory.display(),
+                        error
+                    );
+                }
+            }
+        }
+        comm.barrier();
+        let name = c_path(&path);
+        let mut raw = std::mem::MaybeUninit::uninit();
+        // SAFETY: MPI copies the name and writes the handle of the file into raw
+        let raw = unsafe {
+            let amode = (ffi::MPI_MODE_CREATE | ffi::MPI_MODE_RDWR) as c_int;
+            check(
+                ffi::MPI_File_open(
+                    comm.as_raw(),
+                    name.as_ptr(),
+                    amode,
+                    ffi::RSMPI_INFO_NULL,
+                    raw.as_mut_ptr(),
+                ),
+                "MPI_File_open",
+                &path,
+            );
+            raw.assume_init()
+        };
+        let file = SharedFile { raw, path, comm };
+        file.truncate();
+        file
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size of the file in bytes, like ``MPI_File_get_size``.
+    pub fn size(&self) -> u64 {
+        let mut size = 0;
+        // SAFETY: the file is open
+        unsafe {
+            check(
+                ffi::MPI_File_get_size(self.raw, &mut size),
+                "MPI_File_get_size",
+                &self.path,
+            );
+        }
+        size as u64
+    }
+
+    /// Empty the file and move the file pointer of this rank to its start, like
+    /// ``MPI_File_set_size`` with zero and ``MPI_File_seek``. Collective, meant for the reset
+    /// function of a kernel, so every iteration writes into an empty file.
+    pub fn truncate(&self) {
+        // SAFETY: the file is open
+        unsafe {
+            check(
+                ffi::MPI_File_set_size(self.raw, 0),
+                "MPI_File_set_size",
+                &self.path,
+            );
+        }
+        self.seek(0);
+    }
+
+    /// Move the file pointer of this rank to ``offset`` bytes from the start, like
+    /// ``MPI_File_seek`` with ``MPI_SEEK_SET``.
+    pub fn seek(&self, offset: u64) {
+        // SAFETY: the file is open
+        unsafe {
+            check(
+                ffi::MPI_File_seek(self.raw, offset_of(offset), ffi::MPI_SEEK_SET as c_int),
+                "MPI_File_seek",
+                &self.path,
+            );
+        }
+    }
+
+    /// Write ``buffer`` at the file pointer of this rank and move it past the written bytes, like
+    /// ``MPI_File_write_all``. Collective. Panics if the buffer is longer than ``i32::MAX``.
+    pub fn write_all<T: Equivalence>(&self, buffer: &[T]) {
+        let count = c_int::try_from(buffer.len())
+            .expect("the buffer of a collective write is longer than i32::MAX");
+        // SAFETY: MPI only reads the buffer, which outlives the blocking call
+        unsafe {
+            check(
+                ffi::MPI_File_write_all(
+                    self.raw,
+                    buffer.as_ptr() as *const c_void,
+                    count,
+                    T::equivalent_datatype().as_raw(),
+                    ffi::RSMPI_STATUS_IGNORE,
+                ),
+                "MPI_File_write_all",
+                &self.path,
+            );
+        }
+    }
+
+    /// Write ``buffer`` at ``offset`` bytes from the start, like ``MPI_File_write_at_all``. The
+    /// file pointer of this rank doesn't move. Collective. Panics if the buffer is longer than
+    /// ``i32::MAX``.
+    pub fn write_at_all<T: Equivalence>(&self, offset: u64, buffer: &[T]) {
+        let count = c_int::try_from(buffer.len())
+            .expect("the buffer of a collective write is longer than i32::MAX");
+        // SAFETY: MPI only reads the buffer, which outlives the blocking call
+        unsafe {
+            check(
+                ffi::MPI_File_write_at_all(
+                    self.raw,
+                    offset_of(offset),
+                    buffer.as_ptr() as *const c_void,
+                    count,
+                    T::equivalent_datatype().as_raw(),
+                    ffi::RSMPI_STATUS_IGNORE,
+                ),
+                "MPI_File_write_at_all",
+                &self.path,
+            );
+        }
+    }
+
+    /// Transfer the writes of all ranks to the storage device, like ``MPI_File_sync``.
+    /// Collective. Without it, a write may only have reached the caches of the filesystem.
+    pub fn sync(&self) {
+        // SAFETY: the file is open
+        unsafe {
+            check(ffi::MPI_File_sync(self.raw), "MPI_File_sync", &self.path);
+        }
+    }
+
+    /// Close the file and delete it from rank 0, like ``MPI_File_delete``, once all ranks closed
+    /// it. Rank 0 also removes the directory of the file if it is empty then. Collective, meant for
+    /// the teardown function of a kernel.
+    pub fn delete(mut self) {
+        let path = std::mem::take(&mut self.path);
+        let comm = self.comm;
+        drop(self);
+        comm.barrier();
+        if comm.rank() == 0 {
+            let name = c_path(&path);
+            // SAFETY: the file is closed on all ranks
+            unsafe {
+                check(
+                    ffi::MPI_File_delete(name.as_ptr(), ffi::RSMPI_INFO_NULL),
+                    "MPI_File_delete",
+                    &path,
+                );
+            }
+            // the directory of a run is shared by its worlds, so it is only removed by the last one
+            if let Some(directory) = path.parent() {
+                let _ = std::fs::remove_dir(directory);
+            }
+        }
+    }
+}
+
+impl Drop for SharedFile<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the file is open, and no write is in progress, since they block
+        unsafe {
+            ffi::MPI_File_close(&mut self.raw);
+        }
+    }
+}
+
+/// The path as a string for MPI. Panics if it contains a null byte.
+fn c_path(path: &Path) -> CString {
+    CString::new(path.to_string_lossy().into_owned())
+        .expect("the path of a shared file contains a null byte")
+}
+
+/// An offset in bytes as an ``MPI_Offset``. Panics if it is too large.
+fn offset_of(offset: u64) -> ffi::MPI_Offset {
+    ffi::MPI_Offset::try_from(offset)
+        .expect("the offset into a shared file is larger than MPI can address")
+}
+
+/// Panic if an operation on the file at ``path`` failed.
+fn check(code: c_int, operation: &str, path: &Path) {
+    if code != 0 {
+        panic!(
+            "{} failed on {} with MPI error code {}",
+            operation,
+            path.display(),
+            code
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! The process launches of a run. Every sample spawns a fresh world of children, so a benchmark of
+//! 100 samples on 32 children starts 3200 processes, which matters to the admins of a cluster. The
+//! parent counts the worlds it spawns and the children it starts, and each child reports the CPU
+//! time it used right before it exits.
+//!
+//! The launches of the samples of a benchmark are written into its ``mpirion.json``, see
+//! [`Metrics::launches`](crate::report::Metrics::launches). At the end of the run, the
+//! [`TableReporter`](crate::reporter::TableReporter) prints them for each benchmark, together with
+//! the totals of the run, which also count the warm-up and priming samples and the worlds abandoned
+//! after a timeout.
+//!
+//! The CPU time is read from ``/proc/self/stat``, so it is only known for children on Linux.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::traits::*;
+
+/// The clock ticks per second of the CPU times in ``/proc/self/stat``. Linux reports them in
+/// ``USER_HZ``, which is 100 on all architectures it supports.
+const TICKS_PER_SECOND: u64 = 100;
+
+/// The launches of all samples of this run.
+static RUN: Mutex<Launches> = Mutex::new(Launches::NONE);
+
+/// The processes started for a number of samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Launches {
+    /// the number of worlds of children that were spawned
+    pub spawn_rounds: u64,
+    /// the number of children that were started, over all worlds
+    pub children: u64,
+    /// the CPU time the children reported, user and system time summed over all children
+    pub child_cpu: Duration,
+}
+
+impl Launches {
+    /// No launches at all.
+    pub const NONE: Launches = Launches {
+        spawn_rounds: 0,
+        children: 0,
+        child_cpu: Duration::ZERO,
+    };
+
+    /// The launches of a single world of ``children`` children, which reported ``child_cpu``.
+    pub fn world(children: u64, child_cpu: Duration) -> Self {
+        Self {
+            spawn_rounds: 1,
+            children,
+            child_cpu,
+        }
+    }
+
+    /// The launches of all given launches together.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::launches::Launches;
+    /// use std::time::Duration;
+    ///
+    /// let samples = [
+    ///     Launches::world(32, Duration::from_millis(800)),
+    ///     Launches::world(32, Duration::from_millis(700)),
+    ///     Launches::NONE,
+    /// ];
+    /// let total = Launches::sum(&samples);
+    /// assert_eq!(total.spawn_rounds, 2);
+    /// assert_eq!(total.children, 64);
+    /// assert_eq!(total.child_cpu, Duration::from_millis(1500));
+    /// assert_eq!(total.to_string(), "2 spawn rounds, 64 children, 1.5 CPU-seconds");
+    /// ```
+    pub fn sum<'a>(launches: impl IntoIterator<Item = &'a Launches>) -> Self {
+        launches
+            .into_iter()
+            .fold(Self::NONE, |total, launches| Self {
+                spawn_rounds: total.spawn_rounds + launches.spawn_rounds,
+                children: total.children + launches.children,
+                child_cpu: total.child_cpu + launches.child_cpu,
+            })
+    }
+}
+
+impl fmt::Display for Launches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} spawn rounds, {} children, {:.1} CPU-seconds",
+            self.spawn_rounds,
+            self.children,
+            self.child_cpu.as_secs_f64()
+        )
+    }
+}
+
+/// Add the launches of a sample to the totals of the run.
+pub(crate) fn note(launches: Launches) {
+    let mut run = RUN.lock().unwrap();
+    *run = Launches::sum([&*run, &launches]);
+}
+
+/// The launches of all samples of this run so far.
+pub fn run_total() -> Launches {
+    *RUN.lock().unwrap()
+}
+
+/// The user and system CPU time this process used so far, or ``None`` if it is unknown because
+/// ``/proc/self/stat`` could not be read.
+pub fn cpu_time() -> Option<Duration> {
+    parse_stat(&std::fs::read_to_string("/proc/self/stat").ok()?)
+}
+
+/// The user and system CPU time in the contents of ``/proc/<pid>/stat``, the 14th and 15th field.
+///
+/// # Example
+/// ```rust
+/// use mpirion::launches::parse_stat;
+/// use std::time::Duration;
+///
+/// // the name of the command may contain spaces and parentheses
+/// let stat = "4711 (bench (mpi)) R 1 4711 4711 0 -1 4194304 900 0 0 0 250 37 0 0 20 0 1 0 100";
+/// assert_eq!(parse_stat(stat), Some(Duration::from_millis(2870)));
+/// assert_eq!(parse_stat("4711 (bench) R"), None);
+/// ```
+pub fn parse_stat(stat: &str) -> Option<Duration> {
+    // the fields after the command, which starts with the 3rd field
+    let (_, fields) = stat.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace().skip(14 - 3);
+    let user: u64 = fields.next()?.parse().ok()?;
+    let system: u64 = fields.next()?.parse().ok()?;
+    Some(Duration::from_nanos(
+        (user + system) * (1_000_000_000 / TICKS_PER_SECOND),
+    ))
+}
+
+/// Send the CPU time this child used to the parent, which receives the sum over all children. A
+/// child that does not know its CPU time sends zero. Called by the children after their outcome and
+/// output, right before they exit.
+pub fn send_cpu_time(comm: &dyn Communicator) {
+    let nanos = cpu_time().map_or(0, crate::timing::duration_to_nanos);
+    comm.process_at_rank(0)
+        .reduce_into(&nanos, SystemOperation::sum());
+}
+
+/// Receive the CPU time the children used, summed over all children.
+pub fn receive_cpu_time(comm: &dyn Communicator) -> Duration {
+    let mut nanos = 0u64;
+    comm.process_at_rank(0)
+        .reduce_into_root(&0u64, &mut nanos, SystemOperation::sum());
+    Duration::from_nanos(nanos)
+}
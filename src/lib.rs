@@ -1,13 +1,103 @@
 pub use paste::*;
 
+pub mod agreement;
+pub mod analysis;
+pub mod baseline;
+pub mod campaign;
+pub mod cli;
+pub mod clock;
+pub mod context;
+pub mod counters;
+pub mod debug;
+pub mod drift;
+pub mod errhandler;
+pub mod error;
+pub mod failure;
+pub mod fingerprint;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod hostfile;
+pub mod io;
+pub mod launches;
+pub mod logs;
+pub mod manifest;
+pub mod meta;
+pub mod nb;
+pub mod op;
+pub mod pair;
+pub mod partition;
+pub mod persistent;
+pub mod placement;
+pub mod prelude;
+pub mod profile;
+pub mod registry;
+pub mod regression;
+pub mod report;
+pub mod reporter;
+pub mod runner;
+pub mod scaling;
+pub mod selfcheck;
+pub mod shared;
+pub mod split;
+pub mod summary;
+pub mod testing;
+pub mod threading;
+pub mod timing;
+pub mod topology;
+pub mod trace;
+pub mod transfer;
+pub mod tuning;
+pub mod variance;
+pub mod watchdog;
+
+pub use agreement::assert_agreement;
+pub use context::{MpirionContext, MpirionGroup};
+pub use error::Error;
+#[cfg(feature = "attributes")]
+pub use mpirion_macros::kernel;
+pub use registry::{register_kernel, Kernel};
+pub use reporter::{register_reporter, MpirionReporter};
+pub use runner::bench_dyn;
+
+#[doc(hidden)]
+pub mod protocol;
+
+/// The criterion crate mpirion is built against. Benchmarks should use it instead of depending on
+/// criterion themselves, since a different version has different types, which are rejected by the
+/// macros with confusing errors.
+pub use criterion;
+/// The mpi crate mpirion is built against. Like [`criterion`], benchmarks should use it instead of
+/// depending on mpi themselves. The ``derive`` and ``user-operations`` features of mpirion enable
+/// the features of the same name of mpi.
+#[cfg(not(feature = "stub"))]
+pub use mpi;
+
+/// A stand-in for the mpi crate with the ``stub`` feature, which builds without an MPI
+/// installation, but cannot run anything.
+#[cfg(feature = "stub")]
+#[path = "stub.rs"]
+pub mod mpi;
+
+/// The rayon crate mpirion builds the pool of the children with, see
+/// [`threading`](crate::threading). Kernels must use it instead of depending on rayon themselves,
+/// since another version has its own global pool.
+#[cfg(feature = "rayon")]
+pub use rayon;
+
+#[cfg(not(any(feature = "mpi", feature = "stub")))]
+compile_error!(
+    "mpirion needs either the `mpi` feature, which is enabled by default, or the `stub` feature"
+);
+
 /// Generate a main method that configures criterion and initializes MPI, or runs a kernel
 /// function if it is called for an MPI spawned process. Multiple group targets can be specified,
 /// but the user needs to use named parameters syntax to avoid ambiguity with multiple kernel
 /// functions.
 ///
-/// The main method will panic if it is called without any arguments. If the first argument is
-/// ``--child``, it expects a second argument with the name of the kernel function to execute.
-/// Otherwise, the benchmark group will be executed.
+/// If the arguments contain ``--mpirion-child``, the main method expects the next argument to be
+/// the name of the kernel function to execute. ``--child`` is accepted as well if a known kernel
+/// follows it, see [`cli::child_kernel`]. Otherwise, including when it is called without any
+/// arguments, the benchmark groups will be executed. See [`cli::dispatch`] for all modes.
 ///
 /// If the benchmark parent is called, it accepts all CLI parameters that Criterion usually accepts.
 ///
@@ -23,9 +113,9 @@ pub use paste::*;
 /// use mpi::traits::Communicator;
 /// use mpirion::*;
 ///
-/// fn bench_func(c: &mut Criterion, world: &dyn Communicator) {
-///     c.bench_function("my-bench", |b| {
-///        mpirion_bench!(kernel_func, b, world)
+/// fn bench_func(c: &mut Criterion, ctx: &MpirionContext) {
+///     ctx.bench_function(c, "my-bench", "kernel_func", |b| {
+///        mpirion_bench!(kernel_func, b, ctx)
 ///     });
 /// }
 ///
@@ -46,14 +136,14 @@ pub use paste::*;
 /// ```rust
 /// use criterion::Criterion;
 /// use mpi::traits::Communicator;
-/// use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main};
+/// use mpirion::{mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main, MpirionContext};
 ///
-/// fn broadcast_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-///     c.bench_function("kernel1", |b| mpirion_bench!(kernel1, b, world));
+/// fn broadcast_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+///     ctx.bench_function(c, "kernel1", "kernel1", |b| mpirion_bench!(kernel1, b, ctx));
 /// }
 ///
-/// fn reduce_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-///     c.bench_function("kernel2", |b| mpirion_bench!(kernel2, b, world));
+/// fn reduce_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+///     ctx.bench_function(c, "kernel2", "kernel2", |b| mpirion_bench!(kernel2, b, ctx));
 /// }
 ///
 /// fn setup(comm: &dyn Communicator) -> u64 { 42 }
@@ -61,95 +151,288 @@ pub use paste::*;
 /// fn kernel2(comm: &dyn Communicator, data: &u64) { /* do something with data */ }
 ///
 /// mpirion_kernel!(kernel1, setup);
-/// mpirion_kernel!(kernel2, setup);
+/// mpirion_kernel!(kernel = kernel2, setup = setup, data = ref);
 /// mpirion_group!(kernel1_bench, broadcast_benchmark);
 /// mpirion_group!(kernel2_bench, reduce_benchmark);
 /// // named parameters syntax is required to avoid ambiguity when multiple groups are used
 /// mpirion_main!(groups = kernel1_bench, kernel2_bench; kernels = kernel1, kernel2);
 /// ```
+///
+/// To debug a kernel without criterion, run the benchmark with
+/// ``--mpirion-run <kernel> [--world-size N] [--arg V]``. This spawns the children once, runs a
+/// single iteration of the kernel and prints the time each rank spent in it. The argument is
+/// parsed by the children, so only primitive argument types are supported.
+///
+/// ``--mpirion-selfcheck`` checks the MPI setup instead of running the benchmarks: it spawns a
+/// built-in kernel on two children and prints PASS or FAIL with a diagnosis, see the
+/// [`selfcheck`] module.
+///
+/// Groups can be selected with ``--mpirion-group <group>``, which can be repeated or take a comma
+/// separated list. The other groups are skipped entirely, so their target functions are never
+/// called. All other arguments are passed to criterion.
+///
+/// During development, ``--mpirion-only-kernel <kernel>`` benchmarks a single kernel. The groups
+/// still run, but the benchmarks of other kernels are skipped before criterion starts them, so
+/// they leave no trace in the report or the baselines. This requires the benchmarks to be declared
+/// with the name of their kernel through the [`MpirionContext`], see
+/// [`MpirionContext::bench_function`]. The children refuse to run any other kernel. Together with
+/// ``--mpirion-quick``, which spawns the children only once per benchmark, see
+/// [`runner::quick_config`], a single kernel is measured in seconds. ``--mpirion-dry-run`` lists
+/// the benchmarks that would run without spawning any children, and composes with
+/// ``--mpirion-only-kernel`` to check which benchmarks the selection keeps.
+///
+/// With ``--mpirion-verbose``, the parent prints a line for every sample of ``mpirion_bench!``
+/// with the requested iterations, the number of children, the spawn time, the times of the
+/// children and their imbalance. This shows whether a suite that seems stuck is still ramping up
+/// the iterations or hangs in a sample.
+///
+/// The ``Criterion`` configuration of all groups can be given with ``config = expr;`` before the
+/// groups in named parameters syntax, e.g.
+/// ``mpirion_main!(config = Criterion::default().output_directory(dir); groups = benches)``.
+/// It is evaluated once, and all groups run on the resulting ``Criterion``, which also prints the
+/// final summary. Settings that affect the whole report, like the output directory or plotting,
+/// therefore belong here rather than in the ``config`` of a ``mpirion_group!``.
+///
+/// A function that runs first in the parent and in the children can be given with
+/// ``init = path;`` before the groups, after ``config`` if there is one. It is the place to
+/// register kernels that are only known at runtime with [`register_kernel`], which are then
+/// benchmarked with [`bench_dyn`].
+///
+/// Kernels registered with the ``#[mpirion::kernel]`` attribute don't need to be listed, so the
+/// kernel list can be omitted entirely: ``mpirion_main!(benches)`` or
+/// ``mpirion_main!(groups = kernel1_bench, kernel2_bench)``.
+///
+/// The children look up kernels by name, so every kernel needs a distinct name. Kernels with the
+/// same function name in different modules are given one with ``kernel = my_kernel as other_name``
+/// in ``mpirion_kernel!`` or ``name = other_name`` in the attribute. Listing two kernels with the
+/// same name fails to compile:
+/// ```compile_fail
+/// use mpirion::*;
+///
+/// fn kernel(_comm: &dyn mpi::traits::Communicator) {}
+/// fn benchmark(_c: &mut criterion::Criterion, _ctx: &MpirionContext) {}
+///
+/// mpirion_kernel!(kernel);
+/// mpirion_group!(benches, benchmark);
+/// mpirion_main!(benches, kernel, kernel);
+/// ```
+/// Duplicates among the attribute kernels are only found when the benchmark starts, which then
+/// exits with an error.
 #[macro_export]
 macro_rules! mpirion_main {
-    (groups = $($group:path),+; kernels = $($kernel:path),+) => {
+    ($(init = $init:path;)? groups = $($group:path),+ $(; kernels = $($kernel:path),*)? $(;)?) => {
+        $crate::mpirion_main!{
+            config = $crate::criterion::Criterion::default();
+            $(init = $init;)?
+            groups = $($group),+;
+            kernels = $($($kernel),*)?
+        }
+    };
+    (config = $config:expr; $(init = $init:path;)? groups = $($group:path),+ $(; kernels = $($kernel:path),*)? $(;)?) => {
+        // the children look up kernels by name, so the names must be unique
+        const _: () = $crate::registry::assert_unique_names(&[$($(stringify!($kernel)),*)?]);
+
         fn main() {
-            let mut args = std::env::args();
-
-            if let Some(p) = args.nth(1) {
-                if p == "--child" {
-                    if let Some(kernel_arg) = args.next() {
-                        match kernel_arg.as_str() {
-                            $(
-                            stringify!($kernel) => $crate::paste! {[<execute_kernel_ $kernel>]} (),
-                            )*
-                            _ => panic!("unknown child kernel \"{}\"", kernel_arg),
-                        };
-                    } else {
-                        panic!("called process with --child, but without specifying the kernel");
+            $crate::trace::init_from_env();
+            $crate::meta::set_unoptimized(cfg!(debug_assertions));
+            $( $init(); )?
+            $crate::cli::set_kernel_names(&[$($(stringify!($kernel)),*)?]);
+            match $crate::cli::dispatch_from_env() {
+                $crate::cli::Dispatch::Child(kernel_arg) => {
+                    let mut registry = $crate::registry::KernelRegistry::new();
+                    $($(
+                    registry.register(stringify!($kernel), $crate::paste! {[<execute_kernel_ $kernel>]});
+                    )*)?
+                    $crate::runner::ChildRunner::new(registry).run(&kernel_arg);
+                }
+                $crate::cli::Dispatch::Manifest => {
+                    let kernels = ::std::vec![$($($crate::paste! {[<kernel_info_ $kernel>]}()),*)?];
+                    $crate::manifest::print(&[$(stringify!($group)),+], kernels);
+                }
+                $crate::cli::Dispatch::CargoTest => {
+                    // exit before MPI is initialized, so the test suite neither fails nor hangs
+                    $crate::cli::explain_cargo_test();
+                    std::process::exit(0);
+                }
+                $crate::cli::Dispatch::SelfCheck => {
+                    let universe = $crate::threading::initialize();
+                    if $crate::runner::idle_non_root(&universe.world()) {
+                        drop(universe);
+                        std::process::exit(0);
                     }
-                } else {
+
+                    let exit_code = $crate::selfcheck::run(&$crate::runner::spawn_world(universe.world()));
+                    $crate::runner::release_idle_ranks(&universe.world());
+
+                    // finalize MPI before exiting
+                    drop(universe);
+                    std::process::exit(exit_code);
+                }
+                $crate::cli::Dispatch::Run(options) => {
+                    if let Err(message) = $crate::registry::check_unique_names(&[$($(stringify!($kernel)),*)?]) {
+                        eprintln!("{}", message);
+                        std::process::exit(2);
+                    }
+
+                    let universe = $crate::threading::initialize();
+                    if $crate::runner::idle_non_root(&universe.world()) {
+                        drop(universe);
+                        std::process::exit(0);
+                    }
+
+                    let exit_code = $crate::debug::run_once(&$crate::runner::spawn_world(universe.world()), &options);
+                    $crate::runner::release_idle_ranks(&universe.world());
+
+                    // finalize MPI before exiting
+                    drop(universe);
+                    std::process::exit(exit_code);
+                }
+                $crate::cli::Dispatch::Bench(options) => {
+                    if let Err(message) = options.check_groups(&[$(stringify!($group)),+]) {
+                        eprintln!("{}", message);
+                        std::process::exit(2);
+                    }
+                    if let Err(message) = $crate::registry::check_unique_names(&[$($(stringify!($kernel)),*)?]) {
+                        eprintln!("{}", message);
+                        std::process::exit(2);
+                    }
+
+                    let groups_selected = !options.groups.is_empty();
+                    $crate::cli::set_options(options);
+
                     // create universe in main function so MPI is only initialized once
-                    let universe = mpi::initialize().unwrap();
+                    let universe = $crate::threading::initialize();
 
+                    // if the parent was launched on more than one rank, only rank 0 runs the groups
+                    if $crate::runner::idle_non_root(&universe.world()) {
+                        drop(universe);
+                        std::process::exit(0);
+                    }
+                    $crate::meta::write();
+
+                    // all groups run on the same criterion, so the final summary covers them all
+                    let mut criterion: $crate::criterion::Criterion = $config.configure_from_args();
                     $(
-                    $group(&universe);
+                    if $crate::cli::options().runs_group(stringify!($group)) {
+                        if let Err(error) = $group(&mut criterion, &universe) {
+                            $crate::failure::record_group_error(stringify!($group), error);
+                        }
+                    } else if groups_selected {
+                        eprintln!("skipping group `{}`, it was not selected with --mpirion-group", stringify!($group));
+                    }
                     )*
 
-                    criterion::Criterion::default()
-                        .configure_from_args()
-                        .final_summary();
+                    criterion.final_summary();
+                    $crate::report::write();
+                    $crate::reporter::run_complete();
+                    $crate::campaign::write();
+
+                    $crate::runner::release_idle_ranks(&universe.world());
+                    $crate::failure::exit_on_failures();
+                    $crate::regression::exit_on_regressions();
                 }
-            } else {
-                panic!("Expected cli arguments for criterion or for MPI child process.")
             }
         }
     };
-    ( $group:path, $($kernel:path),+) => {
+    ( $group:path $(, $kernel:path)* $(,)?) => {
         $crate::mpirion_main!{
             groups = $group;
-            kernels = $($kernel),+
+            kernels = $($kernel),*
         }
     };
 }
 
 /// Generate a criterion benchmark group that initializes MPI for the root process and
-/// then calls the target function. This function panics if it isn't run on a single node at rank
-/// 0. This macro works the same as criterion's ``criterion_group!``.
+/// then calls the target function. If the parent world has more ranks, ``mpirion_main!`` lets them
+/// idle until rank 0 is done, and the target function receives a communicator with only rank 0.
+/// This macro works similar to criterion's ``criterion_group!``.
 /// The child processes are spawned by the benchmark function (assuming it calls ``mpirion_bench!``).
 ///
+/// The target function has the signature ``fn(&mut Criterion, &MpirionContext)``, see
+/// [`MpirionContext`]. Target functions that take a ``&dyn Communicator`` instead of the context
+/// are still accepted, but deprecated: the context is where new capabilities are added, and they
+/// compile with a deprecation warning.
+///
+/// The group receives the ``Criterion`` configured by ``mpirion_main!``, which all groups share.
+/// In the named parameters syntax, ``config`` is a function that takes this ``Criterion`` and
+/// returns it with the overrides of the group applied, e.g.
+/// ``config = |c: criterion::Criterion| c.sample_size(10)``. Command line arguments take
+/// precedence over the overrides. Since the ``Criterion`` is shared, the overrides stay in effect
+/// for the groups that run after this one.
+///
+/// The generated function returns an [`Error`] if the group cannot run: if it is not called on
+/// rank 0, if the other ranks of the parent world don't idle, or if the children cannot be
+/// spawned. ``mpirion_main!`` reports the errors at the end of the run and exits with
+/// [`Error::exit_code`]. With ``panic = true`` after the target in the named parameters syntax,
+/// the function panics instead of returning the error.
+///
+/// Before it returns, the function passes the benchmarks of the group to the
+/// [reporters](reporter), which write their ``mpirion.json`` and print a table of them, see the
+/// [`summary`] module.
+///
+/// ``budget = duration`` after the target limits the wall-clock time of the group, e.g. to fit
+/// the group into a cluster allocation. The budget is checked before each benchmark that is
+/// declared through the [`MpirionContext`]. Once it is used up, the remaining benchmarks are
+/// skipped with a note before criterion starts them, and the results collected so far are
+/// reported normally. The target function can check
+/// [`MpirionContext::budget_exhausted`] to skip its own work.
+///
+/// ``compare = ["scan/ring", "scan/tree"]`` after the target compares the benchmarks of the group
+/// with these ids to the first one with the same parameter, e.g. ``scan/tree/p=16`` to
+/// ``scan/ring/p=16``, when the group ends, see [`analysis::compare_set`].
+///
+/// ``id_format = formatter`` after the target sets the [`baseline::IdFormatter`] with which the
+/// target function names its benchmarks, see [`MpirionContext::benchmark_id`].
+///
 /// # Example
 /// See ``mpirion_main!``.
 #[macro_export]
 macro_rules! mpirion_group {
-    (name = $name:ident; config = $config:expr; target = $target:path) => {
-        pub fn $name(universe: &mpi::environment::Universe) {
-            let mut criterion: criterion::Criterion<_> = $config
-                .configure_from_args();
+    (name = $name:ident; config = $config:expr; target = $target:path $(; budget = $budget:expr)? $(; panic = $panic:literal)? $(; compare = [$($compare:expr),* $(,)?])? $(; id_format = $id_format:expr)? $(;)?) => {
+        pub fn $name(criterion: &mut $crate::criterion::Criterion, universe: &$crate::mpi::environment::Universe) -> ::std::result::Result<(), $crate::Error> {
+            // the overrides of the group are applied on top of the criterion shared by all groups
+            // of mpirion_main!, and the command line arguments take precedence over both
+            let overrides = $config;
+            *criterion = overrides(std::mem::take(criterion)).configure_from_args();
+            if $crate::cli::options().quick {
+                *criterion = $crate::runner::quick_config(std::mem::take(criterion));
+            }
 
             let world = universe.world();
-            let rank = world.rank() as usize;
-            let world_size = world.size() as usize;
-
-            if rank != 0 {
-                panic!("The benchmark root process was run on another node than root. Was run on rank {}.", rank);
-            }
+            let first_sample = $crate::report::samples_recorded();
+            let result = $crate::runner::check_parent_world(&world).and_then(|_| {
+                // the other ranks of a larger parent world idle in mpirion_main!, so rank 0
+                // spawns the children on its own
+                let context = $crate::MpirionContext::new(stringify!($name), $crate::runner::spawn_world(world))
+                    $(.with_budget($budget))?
+                    $(.with_id_formatter($id_format))?;
+                $crate::failure::catch(stringify!($name), || {
+                    #[allow(unused_imports)]
+                    use $crate::context::{GroupTarget as _, WorldGroupTarget as _};
+                    $target.run_group(criterion, &context)
+                })
+            });
+            let records = $crate::reporter::group_complete(stringify!($name), first_sample);
+            $crate::analysis::compare_set(&records, &[$($($compare),*)?]);
 
-            if world_size != 1 {
-                eprintln!("The benchmark root process expected to have world size 1, but it has world size {}.", world_size);
+            let panic = false $(|| $panic)?;
+            match result {
+                Err(error) if panic => panic!("group `{}` could not run: {}", stringify!($name), error),
+                result => result,
             }
-
-            $target(&mut criterion, &world);
         }
     };
     ($name:ident, $target:path $(,)?) => {
         $crate::mpirion_group!{
             name = $name;
-            config = criterion::Criterion::default();
+            config = std::convert::identity;
             target = $target
         }
     }
 }
 
 /// Generate a bootstrap function for MPI child processes. This function will be called by the main
-/// function if the first argument is ``--child``. The function will initialize MPI and then
+/// function if the process is started with ``--mpirion-child``. The function will initialize MPI and then
 /// repeatedly call the setup function and the kernel function. The results of the kernel function
 /// will be sent back to the root process via MPI reduce. This overhead is not included in the
 /// benchmark measurements.
@@ -157,56 +440,555 @@ macro_rules! mpirion_group {
 /// The kernel function must take a ``&dyn Communicator`` as its first argument, and a mutable
 /// reference to the data type that is returned by the setup function as its second argument.
 /// The communicator is the intra-communicator of the spawned child processes.
+/// Kernels that only read their data can take a shared reference instead, which has to be
+/// declared with ``data = ref`` in named parameters syntax, so the generated code does not need a
+/// mutable binding. ``data = mut`` is the default. Either reference may also be to what the data
+/// can be borrowed as, like ``&[T]`` or ``&mut [T]`` for a ``Vec<T>``.
+///
+/// Generic kernels can be registered once per instantiation. Each instantiation needs a name,
+/// which is used instead of the kernel function in ``mpirion_bench!`` and ``mpirion_main!``:
+/// ``mpirion_kernel!(kernel = my_kernel::<u32> as my_kernel_u32, setup = my_setup::<u32>)``.
+/// If the kernel and the setup function are generic over the same single type parameter, all
+/// instantiations can be registered at once with
+/// ``mpirion_kernel!(kernel = my_kernel, setup = my_setup, types = [u32, u64])``, which registers
+/// the kernels ``my_kernel_u32`` and ``my_kernel_u64``.
+///
+/// Kernels that need resources which outlive all iterations, like a duplicated communicator or a
+/// pre-registered buffer, can declare a context with ``context = make_context`` in named parameters
+/// syntax. The context function takes a ``&dyn Communicator`` and is called once when the child
+/// process starts. A shared reference to its result is passed to the kernel as a third argument.
+/// The context is dropped after the last iteration and after the state of ``init``, but before MPI
+/// is finalized, so it can hold a user-defined reduction of [`op`](crate::op).
 ///
-/// The setup function must take a ``&dyn Communicator`` as its only argument and return the data
+/// Kernels whose ranks work in groups can split the world with ``split = by_rows(4)`` in named
+/// parameters syntax, or with their own function ``split = my_split`` that takes a
+/// ``&dyn Communicator`` and returns a ``SplitSpec``. The sub-communicators are created once per
+/// spawned world and passed to the kernel as its context, a shared reference to a ``Split``, so a
+/// kernel cannot declare both a split and a context. See [`split`](crate::split) for the helpers.
+///
+/// The parent can inspect what the kernel computed with ``output = project`` in named parameters
+/// syntax. The projection takes a shared reference to the data and returns a summary of it, which
+/// must implement ``Equivalence`` and ``Default`` and may be at most 64 KiB large. After all
+/// iterations, the summary of the data of the last iteration is gathered to the parent and passed
+/// to the ``output`` callback of ``mpirion_bench!``. This happens outside the measured time.
+///
+/// The kernel function may either return nothing, or a ``Result<(), E>`` where ``E`` implements
+/// ``Display``. If the kernel returns an error on any rank, all ranks stop, and the error message
+/// of the lowest failing rank is reported to the parent, which fails the benchmark the same way as
+/// a failed validation.
+///
+/// The setup function must take a ``&dyn Communicator`` as its first argument and return the data
 /// type that is passed to the kernel function. The setup function is called before each iteration
 /// of the kernel function, but it is not included in the benchmark measurements.
 ///
+/// Kernels that need no input can omit the setup function: ``mpirion_kernel!(my_kernel)``. The
+/// kernel function then only takes a ``&dyn Communicator`` (and the context, if declared).
+///
+/// If the setup can fail, for example because an input file is missing, it can be declared with
+/// ``try_setup = my_setup`` in named parameters syntax instead. The setup function then returns
+/// a ``Result<T, E>`` where ``E`` implements ``Display``. If it fails on any rank, all ranks stop,
+/// and the benchmark fails the same way as if the kernel returned an error.
+///
+/// If the data must be identical on all ranks, like a ``#[derive(Equivalence)]`` struct of
+/// parameters drawn at random, it can be declared with ``replicated_setup = my_setup`` in named
+/// parameters syntax instead. The setup function then only runs on rank 0 of the children, and its
+/// data is broadcast to the other ranks before each iteration, outside the measurements. The data
+/// must implement ``Equivalence`` and ``Default``, which the other ranks start from.
+///
+/// If the data is expensive to create but cheap to reset, the setup function can be replaced by
+/// ``init = my_init, reset = my_reset`` in named parameters syntax. The init function has the same
+/// signature as a setup function, but is only called once per child process. Its result is the
+/// state of the child process. The reset function takes a ``&dyn Communicator`` and a shared
+/// reference to the state, and returns the data for the next iteration. It is called before each
+/// iteration and not included in the measurements. The data may borrow from the state, which
+/// lives until all iterations are done.
+///
+/// The state may borrow from the communicator, so the init function can create resources that
+/// must outlive all iterations but not MPI, like an RMA window. The state is dropped after the last
+/// iteration, before the outcome is reported to the parent. If freeing it needs the communicator,
+/// like the collective free of a window, a teardown function can be added with
+/// ``init = my_init, reset = my_reset, teardown = my_teardown``. It takes a ``&dyn Communicator``
+/// and the state by value, and is called on all ranks instead of dropping the state. Persistent
+/// requests, which are created by the init function and started by the kernel in each iteration,
+/// are held in the state with ``mpirion::persistent::Persistent``. Files for collective I/O,
+/// which the reset function empties and the teardown function deletes, are held with
+/// ``mpirion::io::SharedFile``.
+///
+/// An optional validation function can be given with ``validate = my_check`` in named parameters
+/// syntax. It must take a ``&dyn Communicator`` and a shared reference to the data and return
+/// whether the kernel computed the correct result. It is called after every iteration, outside the
+/// measured time. If it fails on any rank, the children stop and the benchmark is marked as
+/// failed instead of recording timings. Since criterion cannot skip a single benchmark, the
+/// remaining benchmarks of the same group are skipped as well, and the process exits with an
+/// error after all other groups have finished.
+///
+/// The setup function may take an argument after the communicator, which is passed to
+/// ``mpirion_bench!``. Its type is inferred from the signature of the setup function.
+/// The parent sends a type tag before the argument, and the children abort with an error naming
+/// both types if the argument passed to ``mpirion_bench!`` has a different type.
+///
+/// A setup function can take a run-constant argument (e.g. a dataset path or an algorithm variant)
+/// before the swept argument. The constant argument is only sent once per spawned child world,
+/// not for every benchmark point.
+///
+/// Kernels that need a process grid, like a halo exchange, can declare it with
+/// ``topology = cart(dims = [0, 0], periodic = [true, true])`` in named parameters syntax. The
+/// Cartesian communicator is created once per spawned world, before the setup, and passed to the
+/// setup, context, kernel and validation functions instead of the plain world. Dimensions that are
+/// zero are inferred from the world size, see [`topology`](crate::topology) for how a kernel finds
+/// its neighbours. ``reorder = true`` after the periodicities lets MPI reorder the ranks, and
+/// ``reorder = compare`` creates both communicators and runs a sample on each in the same world,
+/// so a benchmark can compare them with ``mpirion::topology::compare_reorder``. Kernels of neighborhood collectives declare a graph with
+/// ``topology = graph(my_neighbours)`` instead, with a function that takes a rank and the world
+/// size and returns the ranks the rank sends to. A distributed graph communicator is created from
+/// it once per spawned world, in the same way.
+///
+/// Kernels that call MPI from several threads declare the threading level they need with
+/// ``threading = multiple`` (or ``serialized`` or ``funneled``) in named parameters syntax. Their
+/// children initialize MPI with it, and the benchmark fails with the level MPI provides if it is
+/// lower. The threads must be joined before the kernel returns, and share the communicator through
+/// ``mpirion::threading::ThreadSafe``, see [`threading`](crate::threading).
+///
+/// The argument types can optionally be stated as the third macro parameter, or with
+/// ``const_arg = C`` and ``arg = A`` in named parameters syntax. In that case, the macro asserts
+/// that they match the signature of the setup function.
+///
 /// # Example
-/// See ``mpirion_main!``.
+/// See ``mpirion_main!`` for a complete benchmark. Kernels with different reference kinds and
+/// return types can be mixed freely:
+/// ```rust
+/// use mpi::traits::Communicator;
+/// use mpirion::mpirion_kernel;
+///
+/// fn setup(comm: &dyn Communicator) -> Vec<u64> {
+///     vec![comm.rank() as u64; 16]
+/// }
+///
+/// fn mutating_kernel(comm: &dyn Communicator, data: &mut Vec<u64>) {
+///     data.push(comm.size() as u64);
+/// }
+///
+/// fn reading_kernel(comm: &dyn Communicator, data: &Vec<u64>) {
+///     assert!(data.len() > comm.rank() as usize);
+/// }
+///
+/// fn fallible_kernel(comm: &dyn Communicator, data: &mut Vec<u64>) -> Result<(), String> {
+///     data.pop().map(|_| ()).ok_or_else(|| format!("no data on rank {}", comm.rank()))
+/// }
+///
+/// fn load(_comm: &dyn Communicator) -> Result<Vec<u64>, std::io::Error> {
+///     std::fs::read("input.bin").map(|bytes| bytes.into_iter().map(u64::from).collect())
+/// }
+///
+/// fn sum_kernel(_comm: &dyn Communicator, data: &Vec<u64>) {
+///     let _sum: u64 = data.iter().sum();
+/// }
+///
+/// mpirion_kernel!(mutating_kernel, setup);
+/// mpirion_kernel!(kernel = reading_kernel, setup = setup, data = ref);
+/// mpirion_kernel!(fallible_kernel, setup);
+/// mpirion_kernel!(kernel = sum_kernel, try_setup = load, data = ref);
+/// ```
 #[macro_export]
 macro_rules! mpirion_kernel {
+    (@let [ref] $data:ident = $value:expr) => {
+        let $data = $value;
+    };
+    (@let [$($kind:tt)?] $data:ident = $value:expr) => {
+        let mut $data = $value;
+    };
+    (@args_of [setup = $setup:path]) => {
+        $setup
+    };
+    (@args_of [try_setup = $setup:path]) => {
+        $setup
+    };
+    (@args_of [replicated_setup = $setup:path]) => {
+        $setup
+    };
+    (@args_of [init = $init:path, reset = $reset:path $(, teardown = $teardown:path)?]) => {
+        $init
+    };
+    (@args_of [none]) => {
+        $crate::protocol::no_setup
+    };
+    (@init [setup = $setup:path] $world:ident, $const_input:ident, $input:ident) => {
+        ()
+    };
+    (@init [try_setup = $setup:path] $world:ident, $const_input:ident, $input:ident) => {
+        ()
+    };
+    (@init [replicated_setup = $setup:path] $world:ident, $const_input:ident, $input:ident) => {
+        ()
+    };
+    (@init [init = $init:path, reset = $reset:path $(, teardown = $teardown:path)?] $world:ident, $const_input:ident, $input:ident) => {
+        $crate::protocol::Setup::call(&$init, $world, &$const_input, &$input)
+    };
+    (@init [none] $world:ident, $const_input:ident, $input:ident) => {
+        ()
+    };
+    (@reset [setup = $setup:path] $world:ident, $children:ident, $state:ident, $const_input:ident, $input:ident, $error:ident) => {{
+        let _ = &$state;
+        $crate::protocol::Setup::call(&$setup, $world, &$const_input, &$input)
+    }};
+    (@reset [try_setup = $setup:path] $world:ident, $children:ident, $state:ident, $const_input:ident, $input:ident, $error:ident) => {{
+        let _ = &$state;
+        match $crate::protocol::check_setup_result($children, $crate::protocol::Setup::call(&$setup, $world, &$const_input, &$input)) {
+            Ok(data) => data,
+            Err(setup_error) => {
+                $error = Some(setup_error);
+                break;
+            }
+        }
+    }};
+    (@reset [replicated_setup = $setup:path] $world:ident, $children:ident, $state:ident, $const_input:ident, $input:ident, $error:ident) => {{
+        let _ = &$state;
+        $crate::protocol::replicate($world, || $crate::protocol::Setup::call(&$setup, $world, &$const_input, &$input))
+    }};
+    (@reset [init = $init:path, reset = $reset:path $(, teardown = $teardown:path)?] $world:ident, $children:ident, $state:ident, $const_input:ident, $input:ident, $error:ident) => {
+        $reset($world, &$state)
+    };
+    (@reset [none] $world:ident, $children:ident, $state:ident, $const_input:ident, $input:ident, $error:ident) => {
+        ()
+    };
+    (@teardown [init = $init:path, reset = $reset:path, teardown = $teardown:path] $world:ident, $state:ident) => {
+        $teardown($world, $state)
+    };
+    (@teardown [init = $init:path, reset = $reset:path] $world:ident, $state:ident) => {{
+        let _state = $state;
+    }};
+    (@teardown [$($lifecycle:tt)*] $world:ident, $state:ident) => {};
+    (@call [none] [$($kind:tt)?] $target:path, $world:ident, $data:ident) => {{
+        let _ = &$data;
+        $crate::protocol::call_kernel($target, $world)
+    }};
+    (@call [none] [$($kind:tt)?] $target:path, $world:ident, $data:ident, $context:expr) => {{
+        let _ = &$data;
+        $crate::protocol::call_kernel_with_context($target, $world, $context)
+    }};
+    (@call [$($lifecycle:tt)*] [ref] $target:path, $world:ident, $data:ident) => {
+        $crate::protocol::call_kernel_with_ref_data($target, $world, &$data)
+    };
+    (@call [$($lifecycle:tt)*] [ref] $target:path, $world:ident, $data:ident, $context:expr) => {
+        $crate::protocol::call_kernel_with_ref_data_and_context($target, $world, &$data, $context)
+    };
+    (@call [$($lifecycle:tt)*] [$($kind:tt)?] $target:path, $world:ident, $data:ident) => {
+        $crate::protocol::call_kernel_with_mut_data($target, $world, &mut $data)
+    };
+    (@call [$($lifecycle:tt)*] [$($kind:tt)?] $target:path, $world:ident, $data:ident, $context:expr) => {
+        $crate::protocol::call_kernel_with_mut_data_and_context($target, $world, &mut $data, $context)
+    };
     ($target:path, $setup:path $(, $t:ty)?) => {
+        $crate::mpirion_kernel!(kernel = $target, setup = $setup $(, arg = $t)?);
+    };
+    ($target:path $(,)?) => {
+        $crate::mpirion_kernel!(kernel = $target);
+    };
+    (kernel = $target:ident, setup = $setup:ident, types = [$($ty:ident),+ $(,)?] $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@types $target, $setup, [$($ty),+], [$(, $($options)*)?]);
+    };
+    (kernel = $target:path as $alias:ident $(, $($rest:tt)*)?) => {
+        $crate::mpirion_kernel!(@named $target, $alias $(, $($rest)*)?);
+    };
+    (kernel = $target:path $(, $($rest:tt)*)?) => {
+        $crate::mpirion_kernel!(@named $target, $target $(, $($rest)*)?);
+    };
+    (@types $target:ident, $setup:ident, [$ty:ident $(, $rest:ident)*], [$($options:tt)*]) => {
         $crate::paste! {
-            fn [<execute_kernel_ $target>] () {
-                let universe = mpi::initialize().unwrap();
+            $crate::mpirion_kernel!(@named $target::<$ty>, [<$target _ $ty>], setup = $setup::<$ty> $($options)*);
+        }
+        $crate::mpirion_kernel!(@types $target, $setup, [$($rest),*], [$($options)*]);
+    };
+    (@types $target:ident, $setup:ident, [], [$($options:tt)*]) => {};
+    (@named $target:path, $name:tt, setup = $setup:path $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@kernel $target, $name, [setup = $setup] $(, $($options)*)?);
+    };
+    (@named $target:path, $name:tt, try_setup = $setup:path $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@kernel $target, $name, [try_setup = $setup] $(, $($options)*)?);
+    };
+    (@named $target:path, $name:tt, replicated_setup = $setup:path $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@kernel $target, $name, [replicated_setup = $setup] $(, $($options)*)?);
+    };
+    (@named $target:path, $name:tt, init = $init:path, reset = $reset:path, teardown = $teardown:path $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@kernel $target, $name, [init = $init, reset = $reset, teardown = $teardown] $(, $($options)*)?);
+    };
+    (@named $target:path, $name:tt, init = $init:path, reset = $reset:path $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@kernel $target, $name, [init = $init, reset = $reset] $(, $($options)*)?);
+    };
+    (@named $target:path, $name:tt $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@kernel $target, $name, [none] $(, $($options)*)?);
+    };
+    (@threading) => {
+        $crate::threading::MINIMUM
+    };
+    (@threading funneled) => {
+        $crate::threading::Threading::Funneled
+    };
+    (@threading serialized) => {
+        $crate::threading::Threading::Serialized
+    };
+    (@threading multiple) => {
+        $crate::threading::Threading::Multiple
+    };
+    (@reorder) => {
+        $crate::topology::Reorder::Never
+    };
+    (@reorder false) => {
+        $crate::topology::Reorder::Never
+    };
+    (@reorder true) => {
+        $crate::topology::Reorder::Always
+    };
+    (@reorder compare) => {
+        $crate::topology::Reorder::Compare
+    };
+    (@declared_reorder) => {
+        $crate::topology::Reorder::Never
+    };
+    (@declared_reorder [$($reorder:tt)?]) => {
+        $crate::mpirion_kernel!(@reorder $($reorder)?)
+    };
+    (@context $context_fn:path, $context:ident) => {
+        &$context.0
+    };
+    (@split $split:expr, $context:ident) => {
+        &$context.0
+    };
+    (@kernel $target:path, $name:tt, [$($lifecycle:tt)*] $(, const_arg = $c:ty)? $(, arg = $t:ty)? $(, data = $kind:tt)? $(, threading = $threading:ident)? $(, topology = cart(dims = [$($dim:expr),+ $(,)?], periodic = [$($periodic:expr),+ $(,)?] $(, reorder = $reorder:tt)?))? $(, topology = graph($graph:expr))? $(, split = $split:expr)? $(, context = $context_fn:path)? $(, output = $project:path)? $(, validate = $validate:path)?) => {
+        $crate::paste! {
+            fn [<execute_kernel_ $name>] () {
+                let universe = $crate::threading::initialize_kernel($crate::mpirion_kernel!(@threading $($threading)?));
                 let world = universe.world();
 
-                let inter_comm = world.parent().expect("child could not retrieve parent comm");
-                let merged_comm = inter_comm.merge(mpi::topology::MergeOrder::High);
+                $crate::errhandler::enter(stringify!($name), $crate::errhandler::MERGE);
+                let merged_comm = $crate::protocol::merge_with_parent(&world);
+                $crate::errhandler::enter(stringify!($name), $crate::errhandler::HANDSHAKE);
+                $crate::protocol::receive_handshake(&merged_comm, cfg!(debug_assertions));
+                let reorder = $crate::mpirion_kernel!(@declared_reorder $([$($reorder)?])?);
+                if !$crate::protocol::acknowledge_kernel(&merged_comm, stringify!($name))
+                    || !$crate::threading::confirm_level(&merged_comm)
+                    || !$crate::topology::confirm_reorder(&merged_comm, reorder)
+                {
+                    return;
+                }
+
+                // a kernel with reorder = compare runs a sample on each of its communicators
+                for reordered in $crate::topology::rounds(reorder == $crate::topology::Reorder::Compare) {
+                    $crate::topology::select(reordered);
+                    [<run_kernel_ $name>](&world, $crate::protocol::Parent::Remote(&merged_comm));
+                }
+            }
+
+            /// The static information about the kernel, for ``--mpirion-manifest``.
+            #[allow(dead_code)]
+            fn [<kernel_info_ $name>] () -> $crate::registry::KernelInfo {
+                $crate::registry::KernelInfo::of(stringify!($name), &$crate::mpirion_kernel!(@args_of [$($lifecycle)*]))
+            }
+
+            /// Run the samples of the kernel on ``world`` and report the outcome to ``parent``.
+            // kernels without arguments, context or validation leave some of the bindings unused
+            #[allow(unused_variables, unused_mut)]
+            fn [<run_kernel_ $name>] (world: &dyn $crate::mpi::traits::Communicator, mut parent: $crate::protocol::Parent<'_>) {
+                let _span = $crate::trace::child_span(stringify!($name), world);
+                $crate::threading::enter_kernel(stringify!($name), $crate::mpirion_kernel!(@threading $($threading)?));
+                // the parent may shut the children down instead of requesting a sample
+                $crate::errhandler::enter(stringify!($name), $crate::errhandler::ARGUMENTS);
+                let Some(iterations) = parent.receive_iterations() else {
+                    return;
+                };
+                let time_cap = parent.receive_time_cap();
+                let threads = parent.receive_threads();
+
+                let (const_input, input) = parent.receive_arguments(&$crate::mpirion_kernel!(@args_of [$($lifecycle)*]));
+                // explicitly declared argument types must match the signature of the setup function
+                $( $crate::protocol::assert_const_argument_type::<$c>(&const_input); )?
+                $( $crate::protocol::assert_argument_type::<$t>(&input); )?
 
-                let mut iterations = 0u64;
-                mpi::collective::Root::broadcast_into(&merged_comm.process_at_rank(0), &mut iterations);
+                let output_tag = $crate::protocol::TypeTag::none();
+                $( let output_tag = $crate::protocol::output_tag($project); )?
+                parent.verify_output_tag(output_tag);
+                let profile = parent.receive_profile(world);
+                $( let mut output = $crate::protocol::no_output($project); )?
 
+                // the setup, the kernel and the validation run on the communicator of the topology instead
                 $(
-                    let mut input: $t;
-                    unsafe {
-                        input = std::mem::zeroed();
-                        mpi::collective::Root::broadcast_into(&merged_comm.process_at_rank(0), &mut input);
-                    }
+                    let topology = $crate::topology::cartesian(
+                        stringify!($name),
+                        world,
+                        &[$($dim),+],
+                        &[$($periodic),+],
+                        $crate::mpirion_kernel!(@reorder $($reorder)?),
+                    );
+                    let world: &dyn $crate::mpi::traits::Communicator = &topology;
+                )?
+                $(
+                    let topology = $crate::topology::distributed_graph(stringify!($name), world, $graph);
+                    let world: &dyn $crate::mpi::traits::Communicator = &topology;
                 )?
+                $crate::threading::build_pool(stringify!($name), threads);
+                // the kernels of a pair run on the inter-communicator between their groups, while
+                // the barrier, the checks and the time cap span all children
+                let children = world;
+                let pair = $crate::pair::join(stringify!($name), world);
+                let world: &dyn $crate::mpi::traits::Communicator = match &pair {
+                    Some(inter) => inter,
+                    None => world,
+                };
+                // the context is declared before the state, so it is dropped after it, but before
+                // the communicators and the universe
+                let context = ($($context_fn(world),)? $($crate::split::Split::new(stringify!($name), world, $split),)?);
+                let state = $crate::mpirion_kernel!(@init [$($lifecycle)*] world, const_input, input);
 
+                $crate::errhandler::enter(stringify!($name), $crate::errhandler::KERNEL);
+                let clock = $crate::clock::selected();
+                let deadline = $crate::timing::Deadline::start(time_cap);
+                let mut iterations_run = 0u64;
+                let mut drift = $crate::drift::DriftTracker::new(iterations);
                 let mut total_duration = std::time::Duration::from_secs(0);
-                for _ in 0..iterations {
-                    let mut data = $setup(&world,
-                        $(
-                            input as $t
-                        )?
-                    );
-                    mpi::collective::CommunicatorCollectives::barrier(&world);
-                    let start = std::time::Instant::now();
-                    $target(&world, &mut data);
-                    total_duration += start.elapsed();
+                let mut failed_ranks = 0u64;
+                let mut error = None;
+                let profiler = $crate::profile::Profiler::start(profile);
+                for iteration in 0..iterations {
+                    $crate::mpirion_kernel!(@let [$($kind)?] data = $crate::mpirion_kernel!(@reset [$($lifecycle)*] world, children, state, const_input, input, error));
+                    $crate::mpi::collective::CommunicatorCollectives::barrier(children);
+                    $crate::trace::iteration(iteration);
+                    let start = clock.now();
+                    let result = $crate::mpirion_kernel!(@call [$($lifecycle)*] [$($kind)?] $target, world, data $(, $crate::mpirion_kernel!(@context $context_fn, context))? $(, $crate::mpirion_kernel!(@split $split, context))?);
+                    let elapsed = start.elapsed();
+                    total_duration += elapsed;
+                    drift.record(iteration, elapsed);
+
+                    error = $crate::protocol::check_kernel_result(children, result);
+                    if error.is_some() {
+                        break;
+                    }
+
+                    $(
+                        failed_ranks = $crate::protocol::count_failed_ranks(children, $validate(world, &data));
+                        if failed_ranks > 0 {
+                            break;
+                        }
+                    )?
+
+                    // all ranks stop at the same iteration once the time cap of the sample is up
+                    let truncated = iteration + 1 < iterations && deadline.reached(children);
+                    $(
+                        if iteration + 1 == iterations || truncated {
+                            output = Some($project(&data));
+                        }
+                    )?
+                    iterations_run += 1;
+                    if truncated {
+                        break;
+                    }
+                }
+                profiler.finish();
+                // resources of the state, like RMA windows, are freed on all ranks before the outcome
+                $crate::mpirion_kernel!(@teardown [$($lifecycle)*] world, state);
+                $crate::errhandler::enter(stringify!($name), $crate::errhandler::OUTCOME);
+                parent.send_outcome(failed_ranks, error, total_duration);
+                parent.send_drift(drift.relative());
+                parent.send_iterations_run(iterations_run);
+                parent.send_ranks_permuted();
+                $( parent.send_output($project, output); )?
+                parent.send_counters();
+                parent.send_cpu_time();
+            }
+        }
+    };
+}
+
+/// Export kernels declared with ``mpirion_kernel!`` from a library crate, so benchmark binaries
+/// depending on it can run them. The functions generated by ``mpirion_kernel!`` are private to the
+/// module that declares the kernel, so this macro generates a public module ``mpirion_kernels``
+/// next to them, which forwards to them under the same names. Call it once, in the module where the
+/// kernels are declared.
+///
+/// The binaries bring the kernels into scope with ``import_kernels!``, after which the kernels are
+/// used by name in ``mpirion_bench!`` and ``mpirion_main!`` as if they were declared in the
+/// binary. The kernel names passed to ``--child`` are the plain kernel names, so they match
+/// across crates.
+///
+/// # Example
+/// ```rust
+/// // in the library crate
+/// use mpirion::prelude::*;
+///
+/// fn barrier_kernel(comm: &dyn Communicator) {
+///     comm.barrier();
+/// }
+///
+/// mpirion_kernel!(barrier_kernel);
+/// mpirion::export_kernels!(barrier_kernel);
+///
+/// // in the benchmark binary, with the path of the library module
+/// mod bench {
+///     use mpirion::prelude::*;
+///     mpirion::import_kernels!(super);
+///
+///     fn barrier_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+///         ctx.bench_function(c, "barrier", "barrier_kernel", |b| {
+///             mpirion_bench!(barrier_kernel, b, ctx)
+///         });
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! export_kernels {
+    ($($kernel:ident),+ $(,)?) => {
+        /// The kernels of this module, exported with ``mpirion::export_kernels!``.
+        pub mod mpirion_kernels {
+            $crate::paste! {
+                $(
+                #[doc(hidden)]
+                pub fn [<execute_kernel_ $kernel>] () {
+                    super::[<execute_kernel_ $kernel>]()
+                }
+
+                #[doc(hidden)]
+                pub fn [<run_kernel_ $kernel>] (world: &dyn $crate::mpi::traits::Communicator, parent: $crate::protocol::Parent<'_>) {
+                    super::[<run_kernel_ $kernel>](world, parent)
+                }
+
+                #[doc(hidden)]
+                pub fn [<kernel_info_ $kernel>] () -> $crate::registry::KernelInfo {
+                    super::[<kernel_info_ $kernel>]()
                 }
-                let nanos = total_duration.as_nanos() as u64;
-                mpi::collective::Root::reduce_into(&merged_comm.process_at_rank(0), &nanos, mpi::collective::SystemOperation::sum());
+                )+
+
+                /// The exported kernels, under the names the children are spawned with.
+                pub const KERNELS: &[$crate::registry::KernelEntry] = &[
+                    $($crate::registry::KernelEntry {
+                        name: stringify!($kernel),
+                        run: [<execute_kernel_ $kernel>],
+                        info: [<kernel_info_ $kernel>],
+                    }),+
+                ];
             }
         }
     };
 }
 
+/// Import the kernels that a library crate exported with ``export_kernels!``, given the path of
+/// the module that called it. The kernels can then be used by name in ``mpirion_bench!`` and
+/// listed in ``mpirion_main!``. Instead of listing them, they can also be registered with
+/// [`register_kernel`] from the ``init`` function of ``mpirion_main!``, by iterating
+/// ``<module>::mpirion_kernels::KERNELS``.
+///
+/// # Example
+/// See ``export_kernels!``.
+#[macro_export]
+macro_rules! import_kernels {
+    ($($module:ident)::+) => {
+        #[allow(unused_imports)]
+        use $($module)::+::mpirion_kernels::*;
+    };
+}
+
 /// Generate the communication and spawning code for a benchmark. This macro must be called inside
-/// the ``criterion::Criterion::bench_function`` closure (or one of its variants).
+/// the closure of [`MpirionContext::bench_function`] or ``criterion::Criterion::bench_function``
+/// (or one of their variants).
 /// The macro will spawn child processes and then supply the child processes with the number of
 /// iterations that should be executed.
 ///
@@ -216,69 +998,216 @@ macro_rules! mpirion_kernel {
 /// # Parameters
 /// - `kernel` the kernel function that clients run
 /// - `bencher` the criterion bencher for collecting results
-/// - `world` the current communicator in which the child processes are spawned
-/// - `world_size` how many children to spawn. This parameter is optional and defaults to 4.
+/// - `world` the [`MpirionContext`] passed to the benchmark function, from which the child
+///   processes are spawned. A communicator is accepted as well, for benchmark functions that take
+///   the world instead of the context.
+/// - `world_size` how many children to spawn. This parameter is optional and defaults to 4. It can be
+///   of any integer type, and the benchmark panics if it is not positive or doesn't fit into an MPI
+///   rank.
 /// - `argument` optional. An argument to pass to all child processes. This is passed via collective
-/// communication. See `examples/benchmark_with_input` for usage: the `mpirion_group!` macro needs
-/// to know the argument type, and the kernel setup function needs a parameter for it.
+///   communication. See `examples/benchmark_with_input` for usage: the kernel setup function needs
+///   a parameter for it.
+/// - `const_arg` optional, named syntax only. A second argument that stays constant over the whole
+///   run, like a configuration value. It is passed to the setup function before `arg`.
+///   See `examples/benchmark_with_const_input` for usage.
+/// - `output` optional, named syntax only. A callback that takes a slice with the outputs of all
+///   children, ordered by rank. The kernel must declare an output projection, see
+///   ``mpirion_kernel!``.
+/// - `timeout` optional, named syntax only. A ``std::time::Duration`` after which a sample is
+///   considered hung. Since a hung child world cannot be recovered, the whole run is aborted.
+/// - `retry_on_timeout` optional, named syntax only. How often a hung sample is retried on a fresh
+///   child world before the run is aborted, see [`runner::BenchRunner::retry_on_timeout`]. Only has
+///   an effect together with `timeout`.
+/// - `max_sample_time` optional, named syntax only. A ``std::time::Duration`` after which the
+///   children stop a sample early. Its times are then extrapolated to the requested iterations, see
+///   [`runner::BenchRunner::max_sample_time`].
+/// - `reject_outliers` optional, named syntax only. A number of median absolute deviations beyond
+///   which the time of a rank is discarded, see [`runner::BenchRunner::reject_outliers`]. The
+///   reported times are then the mean of the remaining ranks.
+/// - `discard_first_sample` optional, named syntax only. If `true`, a throwaway sample of a single
+///   iteration is run before the first sample of the benchmark, so the slow first spawn of a run
+///   doesn't skew it, see [`runner::BenchRunner::discard_first_sample`]. Defaults to `false`.
+/// - `threads` optional, named syntax only. The size of the Rayon pool each child builds before the
+///   setup, for hybrid kernels, see [`runner::BenchRunner::threads`]. Needs the ``rayon`` feature.
+/// - `tuning` optional, named syntax only. Pairs of environment variables and values that each
+///   child sets before it initializes MPI, e.g. ``[("OMPI_MCA_coll_tuned_bcast_algorithm", "6")]``
+///   to select the algorithm of a collective, see [`tuning`].
+/// - `placement` optional, named syntax only. A [`placement::PlacementMode`] like
+///   ``PlacementMode::PerNode`` that asks MPI to spread the children over the nodes, see
+///   [`runner::BenchRunner::placement`].
+/// - `consumer` and `consumers` optional, named syntax only, together. A second kernel and the
+///   number of children that run it next to the children of `kernel`, connected to them by an
+///   inter-communicator, see [`pair`]. They are followed by an optional `report`, a
+///   [`pair::Report`] that selects whose time is reported, by default the slower group.
+/// - `reorder` optional, named syntax only. Whether a kernel with
+///   ``topology = cart(.., reorder = compare)`` is measured on the communicator with reordered
+///   ranks, see [`runner::BenchRunner::reorder`]. Usually passed by [`topology::compare_reorder`].
+///   The benchmarks of such kernels fail without it, and the ones of all other kernels with it.
+///
+/// The named-only parameters can also follow the unnamed ``kernel, bencher, world`` after a
+/// semicolon, in the same order as in the named syntax. ``world_size`` can be omitted there.
+///
+/// The macro is a wrapper around [`runner::BenchRunner`], which can be used directly if the
+/// benchmarks are assembled at runtime.
 ///
 /// # Example
 /// ```rust
 /// use criterion::Criterion;
 /// use mpi::traits::Communicator;
-/// use mpirion::mpirion_bench;
+/// use mpirion::{mpirion_bench, mpirion_kernel, MpirionContext};
 ///
-/// // a simple benchmark call without arguments and default world size
-/// fn simple_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-///     c.bench_function("prefix-sum", |b| mpirion_bench!(simple_kernel, b, world));
+/// fn setup(_comm: &dyn Communicator, len: usize) -> Vec<u64> {
+///     vec![0; len]
 /// }
 ///
-/// // if a world_size is required, it can be specified as a named parameter.
-/// // Unnamed syntax is not allowed if world_size is required.
-/// fn custom_world_benchmark(c: &mut Criterion, world: &dyn Communicator) {
-///    c.bench_function("prefix-sum", |b| mpirion_bench!(
+/// fn simple_kernel(_comm: &dyn Communicator, _data: &mut Vec<u64>) {}
+///
+/// mpirion_kernel!(simple_kernel, setup, usize);
+///
+/// fn shapes(c: &mut Criterion, ctx: &MpirionContext) {
+///     // unnamed, with and without argument
+///     c.bench_function("default", |b| mpirion_bench!(simple_kernel, b, ctx));
+///     c.bench_function("argument", |b| mpirion_bench!(simple_kernel, b, ctx, 42usize));
+///
+///     // unnamed, followed by named parameters
+///     c.bench_function("sized", |b| mpirion_bench!(simple_kernel, b, ctx; world_size = 8));
+///     c.bench_function("sized-argument", |b| {
+///         mpirion_bench!(simple_kernel, b, ctx; world_size = 8, arg = 42usize)
+///     });
+///     c.bench_function("argument-only", |b| mpirion_bench!(simple_kernel, b, ctx; arg = 42usize));
+///
+///     // fully named
+///     c.bench_function("named", |b| mpirion_bench!(
 ///         kernel = simple_kernel,
 ///         bencher = b,
-///         world = world,
+///         world = ctx,
 ///         world_size = 8,
-///         arg = 42));
+///         arg = 42usize));
 /// }
 /// ```
 ///
 /// For a full benchmark example see ``mpirion_main!`` or the ``examples`` directory.
 #[macro_export]
 macro_rules! mpirion_bench {
+    ($kernel:path, $bencher:expr, $world:expr; world_size = $world_size:expr $(, $($options:tt)+)?) => {
+        $crate::mpirion_bench!(kernel = $kernel, bencher = $bencher, world = $world, world_size = $world_size $(, $($options)+)?)
+    };
+    ($kernel:path, $bencher:expr, $world:expr; $($options:tt)+) => {
+        $crate::mpirion_bench!(kernel = $kernel, bencher = $bencher, world = $world, world_size = $crate::runner::DEFAULT_WORLD_SIZE, $($options)+)
+    };
     ($kernel:path, $bencher:expr, $world:expr $(, $argument:expr)?) => {
-        mpirion_bench!(kernel = $kernel, bencher = $bencher, world = $world, world_size = 4 $(, arg = $argument)?)
-    };
-    (kernel = $kernel:path, bencher = $bencher:expr, world = $world:expr, world_size = $world_size:expr $(, arg = $argument:expr)?) => {
-        $bencher.iter_custom(|mut iterations| {
-            // create child processes
-            let mut child_exe = std::process::Command::new(std::env::current_exe().expect("failed to retrieve benchmark executable path"));
-            child_exe.arg("--child");
-            child_exe.arg(stringify!($kernel));
-
-            let child_inter_comm = mpi::collective::Root::spawn(
-                &$world.process_at_rank(0),
-                &child_exe,
-                $world_size,
-            ).expect("failed to spawn child processes");
-            let child_world_size = child_inter_comm.remote_size();
-            assert_eq!(child_world_size, $world_size);
-
-            // create intracomm for parent and the children
-            let merged_comm = child_inter_comm.merge(mpi::topology::MergeOrder::Low);
-
-            mpi::collective::Root::broadcast_into(&merged_comm.this_process(), &mut iterations);
-            $(
-                let mut input = $argument.clone();
-                mpi::collective::Root::broadcast_into(&merged_comm.this_process(), &mut input);
-            )?
-
-            let mut total_nanos: u64 = 0;
-            mpi::collective::Root::reduce_into_root(&merged_comm.this_process(), &[0u64], &mut total_nanos, mpi::collective::SystemOperation::sum());
-            total_nanos = (total_nanos as f64 / child_world_size as f64) as u64;
-            std::time::Duration::from_nanos(total_nanos)
+        $crate::mpirion_bench!(kernel = $kernel, bencher = $bencher, world = $world, world_size = $crate::runner::DEFAULT_WORLD_SIZE $(, arg = $argument)?)
+    };
+    (kernel = $kernel:path, bencher = $bencher:expr, world = $world:expr, world_size = $world_size:expr $(, const_arg = $const_argument:expr)? $(, arg = $argument:expr)? $(, output = $output_callback:expr)? $(, timeout = $timeout:expr)? $(, retry_on_timeout = $retries:expr)? $(, max_sample_time = $max_sample_time:expr)? $(, reject_outliers = $mads:expr)? $(, discard_first_sample = $discard:expr)? $(, threads = $threads:expr)? $(, tuning = $tuning:expr)? $(, placement = $placement:expr)? $(, consumer = $consumer:path, consumers = $consumers:expr $(, report = $report:expr)?)? $(, reorder = $reorder:expr)?) => {
+        $bencher.iter_custom(|iterations| {
+            $crate::runner::BenchRunner::new(stringify!($kernel), $crate::context::ParentWorld::parent_world($world))
+                .local_kernel($crate::paste! {[<run_kernel_ $kernel>]})
+                .world_size($world_size)
+                .budget($crate::context::ParentWorld::budget($world))
+                $(.const_arg($const_argument.clone()))?
+                $(.arg($argument.clone()))?
+                $(.output($output_callback))?
+                $(.timeout($timeout))?
+                $(.retry_on_timeout($retries))?
+                $(.max_sample_time($max_sample_time))?
+                $(.reject_outliers($mads))?
+                $(.discard_first_sample($discard))?
+                $(.threads($threads))?
+                $(.tuning($tuning.clone()))?
+                $(.placement($placement))?
+                $(.consumer(stringify!($consumer), $consumers) $(.report($report))?)?
+                $(.reorder($reorder))?
+                .run(iterations)
         })
     }
 }
+
+/// Declare a test that runs a kernel once on ``world_size`` spawned children and checks the result
+/// on every rank. The check function has the signature of a ``validate`` function of
+/// ``mpirion_kernel!``: it takes a ``&dyn Communicator`` and a shared reference to the data after
+/// the kernel ran, and returns whether the result is correct. The test fails if the check fails on
+/// any rank, or if the kernel returns an error. ``world_size`` is optional and defaults to 4.
+/// Further options are passed to ``mpirion_kernel!``, e.g. ``data = ref``.
+///
+/// The test registers its own kernel under the name of the test, so the kernel does not need to be
+/// registered with ``mpirion_kernel!``. Tests are run by ``mpirion_test_main!``, which generates
+/// the main function of a test binary. The binary is launched with ``mpiexec -n 1``, just like a
+/// benchmark.
+///
+/// # Example
+/// ```rust
+/// use mpi::collective::{CommunicatorCollectives, SystemOperation};
+/// use mpi::traits::Communicator;
+/// use mpirion::{mpirion_test, mpirion_test_main};
+///
+/// fn setup(comm: &dyn Communicator) -> [u64; 2] {
+///     [comm.rank() as u64, 0]
+/// }
+///
+/// fn prefix_sum_kernel(comm: &dyn Communicator, data: &mut [u64; 2]) {
+///     let input = data[0];
+///     comm.scan_into(&input, &mut data[1], SystemOperation::sum());
+/// }
+///
+/// fn check_prefix_sum(comm: &dyn Communicator, data: &[u64; 2]) -> bool {
+///     let rank = comm.rank() as u64;
+///     data[1] == rank * (rank + 1) / 2
+/// }
+///
+/// mpirion_test!(prefix_sum_is_correct, prefix_sum_kernel, setup, world_size = 8, check = check_prefix_sum);
+/// mpirion_test_main!(prefix_sum_is_correct);
+/// ```
+#[macro_export]
+macro_rules! mpirion_test {
+    ($name:ident, $kernel:path, $setup:path $(, world_size = $world_size:expr)?, check = $check:path $(, $($options:tt)*)?) => {
+        $crate::mpirion_kernel!(@named $kernel, $name, setup = $setup $(, $($options)*)?, validate = $check);
+
+        fn $name(world: &dyn $crate::mpi::traits::Communicator) -> Result<(), String> {
+            let world_size = $crate::runner::DEFAULT_WORLD_SIZE;
+            $( let world_size = $world_size; )?
+            $crate::runner::BenchRunner::new(stringify!($name), world)
+                .local_kernel($crate::paste! {[<run_kernel_ $name>]})
+                .world_size(world_size)
+                .try_run(1)
+                .map(|_| ())
+        }
+    };
+}
+
+/// Generate the main function of a test binary that runs the given ``mpirion_test!`` tests. It
+/// prints the results like the built-in test harness and exits with a non-zero exit code if any
+/// test failed. Like ``mpirion_main!``, the binary also serves as the child process of the tests.
+///
+/// # Example
+/// See ``mpirion_test!``.
+#[macro_export]
+macro_rules! mpirion_test_main {
+    ($($test:ident),+ $(,)?) => {
+        fn main() {
+            $crate::trace::init_from_env();
+            $crate::cli::set_kernel_names(&[$(stringify!($test)),+]);
+            if let Some(kernel_arg) = $crate::runner::ChildRunner::kernel_from_args() {
+                let mut registry = $crate::registry::KernelRegistry::new();
+                $(
+                registry.register(stringify!($test), $crate::paste! {[<execute_kernel_ $test>]});
+                )+
+                $crate::runner::ChildRunner::new(registry).run(&kernel_arg);
+                return;
+            }
+
+            let universe = $crate::threading::initialize();
+            if $crate::runner::idle_non_root(&universe.world()) {
+                drop(universe);
+                std::process::exit(0);
+            }
+
+            let tests: &[(&str, $crate::testing::KernelTest)] = &[$((stringify!($test), $test)),+];
+            let exit_code = $crate::testing::run_tests(&$crate::runner::spawn_world(universe.world()), tests);
+            $crate::runner::release_idle_ranks(&universe.world());
+
+            // finalize MPI before exiting
+            drop(universe);
+            std::process::exit(exit_code);
+        }
+    };
+}
@@ -0,0 +1,246 @@
+//! Log files of the children. Each child of a spawned sample appends to
+//! ``target/mpirion/<timestamp>/<kernel>/rank-N.log``, where the timestamp is the start of the run
+//! of the parent and ``N`` is the rank of the child in the child world. The file starts each
+//! sample with what the child reported in the handshake, followed by the panic messages of the
+//! child and, with the ``tracing`` feature and ``MPIRION_LOG`` set, its tracing output.
+//!
+//! The parent creates the directory of a kernel before it spawns the children and sends its path
+//! to them in the handshake, so the children don't need to agree on the output directory. When a
+//! benchmark fails, the parent prints the directory with the logs of its children.
+//!
+//! Each run creates a new directory, and the directories of older runs are deleted beyond the
+//! [`DEFAULT_KEEP`] most recent ones, so long benchmarking campaigns don't fill the disk. The
+//! number of runs that are kept is set with ``--mpirion-keep-logs``, and ``--mpirion-keep-logs 0``
+//! disables the logs.
+
+use std::fmt::Arguments;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mpi::Rank;
+
+/// The number of runs whose logs are kept, unless it is set with ``--mpirion-keep-logs``.
+pub const DEFAULT_KEEP: usize = 10;
+
+/// The directory of the logs of the current run on the parent, or ``None`` if the logs are
+/// disabled or the directory could not be created.
+static RUN_DIRECTORY: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// The log file of this child, once it received its path in the handshake.
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// The directory the logs of all runs are written to, ``mpirion`` in the target directory.
+pub fn root() -> PathBuf {
+    crate::report::target_dir().join("mpirion")
+}
+
+/// The name of the directory of a run that started at the given time, in UTC, which sorts in the
+/// order the runs started.
+///
+/// # Example
+/// ```rust
+/// use mpirion::logs::run_name;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// assert_eq!(run_name(UNIX_EPOCH + Duration::from_secs(1_792_067_405)), "20261015-123005");
+/// assert_eq!(run_name(UNIX_EPOCH), "19700101-000000");
+/// ```
+pub fn run_name(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_date(seconds / 86_400);
+    let time_of_day = seconds % 86_400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+/// The year, month and day of a number of days since 1970-01-01, see
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Whether a directory name is one of [`run_name`], so [`prune`] never deletes anything else.
+fn is_run_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() == 15
+        && bytes[..8].iter().all(u8::is_ascii_digit)
+        && bytes[8] == b'-'
+        && bytes[9..15].iter().all(u8::is_ascii_digit)
+}
+
+/// Delete the oldest run directories in ``root`` until at most ``keep`` are left, and return the
+/// deleted ones. Only directories named like [`run_name`] count as runs.
+pub fn prune(root: &Path, keep: usize) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut runs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| entry.file_name().to_str().is_some_and(is_run_name))
+        .map(|entry| entry.path())
+        .collect();
+    runs.sort();
+    let excess = runs.len().saturating_sub(keep);
+    runs.into_iter()
+        .take(excess)
+        .filter(|run| std::fs::remove_dir_all(run).is_ok())
+        .collect()
+}
+
+/// The directory of the logs of this run, created and with older runs pruned on first use.
+fn run_directory() -> Option<&'static Path> {
+    RUN_DIRECTORY
+        .get_or_init(|| {
+            let keep = crate::cli::options().keep_logs.unwrap_or(DEFAULT_KEEP);
+            if keep == 0 {
+                return None;
+            }
+            let root = root();
+            let run = root.join(run_name(SystemTime::now()));
+            if let Err(error) = std::fs::create_dir_all(&run) {
+                eprintln!(
+                    "mpirion: could not create the log directory {}: {}",
+                    run.display(),
+                    error
+                );
+                return None;
+            }
+            prune(&root, keep);
+            Some(run)
+        })
+        .as_deref()
+}
+
+/// The directory the children of the given kernel write their logs to, created if needed, or
+/// ``None`` if the logs are disabled. Called by the parent before it spawns the children.
+pub fn kernel_directory(kernel: &str) -> Option<PathBuf> {
+    let directory = run_directory()?.join(kernel);
+    match std::fs::create_dir_all(&directory) {
+        Ok(()) => Some(directory),
+        Err(error) => {
+            eprintln!(
+                "mpirion: could not create the log directory {}: {}",
+                directory.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Print where the logs of the children of a failed kernel are. Does nothing in the children, or
+/// if the logs are disabled.
+pub fn print_paths(kernel: &str) {
+    let Some(Some(run)) = RUN_DIRECTORY.get() else {
+        return;
+    };
+    let directory = run.join(kernel);
+    if directory.is_dir() {
+        eprintln!(
+            "mpirion: the logs of the children of `{}` are in {}",
+            kernel,
+            directory.display()
+        );
+    }
+}
+
+/// The log file of a child in the directory sent by the parent.
+///
+/// # Example
+/// ```rust
+/// use mpirion::logs::rank_file;
+/// use std::path::Path;
+///
+/// assert_eq!(rank_file(Path::new("target/mpirion/20261015-123005/scan"), 3), Path::new("target/mpirion/20261015-123005/scan/rank-3.log"));
+/// ```
+pub fn rank_file(directory: &Path, rank: Rank) -> PathBuf {
+    directory.join(format!("rank-{}.log", rank))
+}
+
+/// Open the log file of this child for appending, and log its panics into it from now on. Called
+/// by the children in the handshake. If the file cannot be opened, the child runs without a log.
+pub fn open(directory: &Path, rank: Rank) {
+    let path = rank_file(directory, rank);
+    let file = match std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!(
+                "mpirion: child rank {} could not open its log file {}: {}",
+                rank,
+                path.display(),
+                error
+            );
+            return;
+        }
+    };
+    if LOG_FILE.set(Mutex::new(file)).is_err() {
+        return;
+    }
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        write(format_args!("{}", info));
+        previous(info);
+    }));
+}
+
+/// Append a line to the log file of this child, if it has one.
+pub fn write(line: Arguments) {
+    if let Some(file) = LOG_FILE.get() {
+        // a poisoned lock only means another thread panicked while logging
+        let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// A writer for ``tracing_subscriber`` that writes to stderr and to the log file of the child, if
+/// it has one.
+#[cfg(feature = "tracing")]
+pub(crate) struct TeeWriter;
+
+#[cfg(feature = "tracing")]
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(file) = LOG_FILE.get() {
+            let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let _ = file.write_all(buf);
+        }
+        std::io::stderr().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
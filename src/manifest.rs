@@ -0,0 +1,92 @@
+//! The manifest of a benchmark binary, printed with ``--mpirion-manifest``. It lists the groups and
+//! kernels of the binary as JSON, so scripts can plan jobs before launching anything. The manifest
+//! is assembled from the static information that ``mpirion_kernel!`` records about each kernel,
+//! so printing it doesn't initialize MPI. The manifest also contains the
+//! [environment](crate::meta::Environment) of the binary.
+//!
+//! Which group benchmarks a kernel is only known when the group runs, so the groups and kernels
+//! are listed separately. Kernels registered at runtime with
+//! [`register_kernel`](crate::register_kernel) aren't listed, since their setup functions are not
+//! known.
+
+use crate::meta::Environment;
+use crate::registry::{self, KernelInfo};
+
+/// The manifest of the given groups and kernels in the given environment as JSON.
+///
+/// # Example
+/// ```rust
+/// use mpirion::manifest::to_json;
+/// use mpirion::meta::Environment;
+/// use mpirion::registry::KernelInfo;
+///
+/// let kernel = KernelInfo { name: "scan_kernel", const_arg: None, arg: Some("u32"), default_world_size: 4 };
+/// let environment = Environment {
+///     mpi_library: "MPICH 4.1".to_string(),
+///     mpi_version: (4, 0),
+///     mpirion_version: "0.1.0",
+///     rustc_version: "rustc 1.80.0",
+///     profile: "release",
+/// };
+/// assert_eq!(
+///     to_json(&["benches"], &[kernel], &environment),
+///     concat!(
+///         r#"{"groups":["benches"],"kernels":[{"name":"scan_kernel","const_arg":null,"arg":"u32","default_world_size":4}],"#,
+///         r#""environment":{"mpi_library":"MPICH 4.1","mpi_version":"4.0","mpirion_version":"0.1.0","rustc_version":"rustc 1.80.0","profile":"release"}}"#
+///     )
+/// );
+/// ```
+pub fn to_json(groups: &[&str], kernels: &[KernelInfo], environment: &Environment) -> String {
+    let groups: Vec<String> = groups.iter().map(|group| json_string(group)).collect();
+    let kernels: Vec<String> = kernels
+        .iter()
+        .map(|kernel| {
+            format!(
+                r#"{{"name":{},"const_arg":{},"arg":{},"default_world_size":{}}}"#,
+                json_string(kernel.name),
+                json_optional(kernel.const_arg),
+                json_optional(kernel.arg),
+                kernel.default_world_size
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"groups":[{}],"kernels":[{}],"environment":{}}}"#,
+        groups.join(","),
+        kernels.join(","),
+        environment.to_json()
+    )
+}
+
+/// Print the manifest of the given groups and kernels, and of the kernels registered with the
+/// ``#[mpirion::kernel]`` attribute. Called by ``mpirion_main!`` for ``--mpirion-manifest``.
+pub fn print(groups: &[&str], mut kernels: Vec<KernelInfo>) {
+    for kernel in registry::attribute_kernels() {
+        if !kernels.iter().any(|listed| listed.name == kernel.name) {
+            kernels.push(kernel);
+        }
+    }
+
+    println!("{}", to_json(groups, &kernels, &Environment::collect()));
+}
+
+fn json_optional(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), json_string)
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,163 @@
+//! The environment a benchmark binary runs in. Times measured with Open MPI 4 and Open MPI 5, or
+//! with MPICH and Open MPI, are not comparable, so the MPI library, the version of the MPI standard
+//! it implements, the versions of mpirion and of the compiler, and the build profile are recorded
+//! with the results. At the start of a benchmark run, ``mpirion_main!`` writes them into
+//! ``mpirion-meta.json`` in the output directory of criterion, see [`write`], next to the
+//! [fingerprints](crate::fingerprint) of the hosts the children run on.
+//! ``--mpirion-selfcheck`` prints them, and ``--mpirion-manifest`` includes them in its JSON.
+//!
+//! MPI allows querying its versions before it is initialized, so the manifest still doesn't
+//! initialize MPI.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::fingerprint::HostFingerprint;
+use crate::manifest::json_string;
+
+/// The file name of the metadata in the output directory of criterion.
+pub const META_JSON: &str = "mpirion-meta.json";
+
+/// Whether the benchmark crate was built without optimizations, see [`set_unoptimized`].
+static UNOPTIMIZED: AtomicBool = AtomicBool::new(false);
+
+/// Record whether the benchmark crate was built without optimizations. The build profile of mpirion
+/// itself may differ, so ``mpirion_main!`` sets it from ``debug_assertions`` in the crate of the
+/// benchmark.
+pub fn set_unoptimized(unoptimized: bool) {
+    UNOPTIMIZED.store(unoptimized, Ordering::Relaxed);
+}
+
+/// The environment of the benchmark binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    /// the version string of the MPI library, from ``MPI_Get_library_version``
+    pub mpi_library: String,
+    /// the version of the MPI standard the library implements, from ``MPI_Get_version``
+    pub mpi_version: (i32, i32),
+    /// the version of mpirion
+    pub mpirion_version: &'static str,
+    /// the version of the compiler that built mpirion
+    pub rustc_version: &'static str,
+    /// ``debug`` if the benchmark was built without optimizations, else ``release``
+    pub profile: &'static str,
+}
+
+impl Environment {
+    /// Query the environment of this process.
+    pub fn collect() -> Self {
+        Self {
+            mpi_library: crate::mpi::environment::library_version().map_or_else(
+                |_| "unknown".to_string(),
+                |library| library.trim().to_string(),
+            ),
+            mpi_version: crate::mpi::environment::version(),
+            mpirion_version: env!("CARGO_PKG_VERSION"),
+            rustc_version: env!("MPIRION_RUSTC_VERSION"),
+            profile: if UNOPTIMIZED.load(Ordering::Relaxed) {
+                "debug"
+            } else {
+                "release"
+            },
+        }
+    }
+
+    /// The environment as a JSON object.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::meta::Environment;
+    ///
+    /// let environment = Environment {
+    ///     mpi_library: "Open MPI v4.1.5".to_string(),
+    ///     mpi_version: (3, 1),
+    ///     mpirion_version: "0.1.0",
+    ///     rustc_version: "rustc 1.80.0",
+    ///     profile: "release",
+    /// };
+    /// assert_eq!(
+    ///     environment.to_json(),
+    ///     r#"{"mpi_library":"Open MPI v4.1.5","mpi_version":"3.1","mpirion_version":"0.1.0","rustc_version":"rustc 1.80.0","profile":"release"}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"mpi_library":{},"mpi_version":"{}.{}","mpirion_version":{},"rustc_version":{},"profile":{}}}"#,
+            json_string(&self.mpi_library),
+            self.mpi_version.0,
+            self.mpi_version.1,
+            json_string(self.mpirion_version),
+            json_string(self.rustc_version),
+            json_string(self.profile)
+        )
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // multi-line library versions, like the one of MPICH, are shown on one line
+        let library: Vec<&str> = self.mpi_library.split_whitespace().collect();
+        write!(
+            f,
+            "MPI {}.{}, {}; mpirion {}, {}, {} build",
+            self.mpi_version.0,
+            self.mpi_version.1,
+            library.join(" "),
+            self.mpirion_version,
+            self.rustc_version,
+            self.profile
+        )
+    }
+}
+
+/// The contents of ``mpirion-meta.json``: the fields of the environment, followed by the
+/// fingerprints of the hosts of the children in ``fingerprint``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::fingerprint::{parse_meta, HostFingerprint};
+/// use mpirion::meta::{to_json, Environment};
+///
+/// let environment = Environment {
+///     mpi_library: "Open MPI v4.1.5".to_string(),
+///     mpi_version: (3, 1),
+///     mpirion_version: "0.1.0",
+///     rustc_version: "rustc 1.80.0",
+///     profile: "release",
+/// };
+/// let node = HostFingerprint { host: "node01".to_string(), ..HostFingerprint::default() };
+/// let json = to_json(&environment, &[node.clone()]);
+/// assert!(json.starts_with(r#"{"mpi_library":"Open MPI v4.1.5","#));
+/// assert_eq!(parse_meta(&json), [node]);
+/// ```
+pub fn to_json(environment: &Environment, fingerprints: &[HostFingerprint]) -> String {
+    let environment = environment.to_json();
+    let fingerprints: Vec<String> = fingerprints.iter().map(HostFingerprint::to_json).collect();
+    format!(
+        r#"{},"fingerprint":[{}]}}"#,
+        &environment[..environment.len() - 1],
+        fingerprints.join(",")
+    )
+}
+
+/// Write the environment into ``mpirion-meta.json`` in the output directory of criterion, after
+/// remembering the fingerprints of the last run, see [`fingerprint`](crate::fingerprint). Called
+/// by ``mpirion_main!`` before the groups run.
+pub fn write() {
+    let path = crate::report::criterion_home().join(META_JSON);
+    if let Ok(last) = std::fs::read_to_string(&path) {
+        crate::fingerprint::remember_last_run(&last);
+    }
+    save(&[]);
+}
+
+/// Write the environment and the given fingerprints into ``mpirion-meta.json``.
+pub(crate) fn save(fingerprints: &[HostFingerprint]) {
+    let home = crate::report::criterion_home();
+    let path = home.join(META_JSON);
+    let json = to_json(&Environment::collect(), fingerprints);
+    let result = std::fs::create_dir_all(&home).and_then(|_| std::fs::write(&path, json));
+    if let Err(error) = result {
+        eprintln!("mpirion: could not write {}: {}", path.display(), error);
+    }
+}
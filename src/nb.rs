@@ -0,0 +1,95 @@
+//! Benchmarks of non-blocking collectives, whose cost is split between starting the operation and
+//! waiting for it, with computation in between that the communication may overlap with. The time
+//! of an iteration covers all of it, so [`measure_overlap`] also records the time of each phase as
+//! a [counter](crate::counters): [`INITIATION`], [`COMPUTE`], [`WAIT`] and [`TOTAL`].
+//!
+//! How much of the communication the computation hides follows from a second benchmark of the
+//! same collective without computation, see [`overlap`].
+//!
+//! # Example
+//! ```rust
+//! use mpirion::prelude::*;
+//!
+//! fn barrier_kernel(comm: &dyn Communicator) {
+//!     mpirion::nb::measure_overlap(
+//!         comm,
+//!         |comm| {
+//!             let request = comm.immediate_barrier();
+//!             // waiting is part of the completion, so it happens in the returned closure
+//!             move || {
+//!                 request.wait();
+//!             }
+//!         },
+//!         |_| std::hint::black_box((0..1000u64).sum::<u64>()),
+//!     );
+//! }
+//!
+//! mpirion_kernel!(barrier_kernel);
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::counters::record;
+use crate::mpi::traits::*;
+
+/// The counter of the time it took to start the operation.
+pub const INITIATION: &str = "initiation";
+
+/// The counter of the time of the computation while the operation was in flight.
+pub const COMPUTE: &str = "compute";
+
+/// The counter of the time it took to complete the operation after the computation.
+pub const WAIT: &str = "wait";
+
+/// The counter of the time of all three phases.
+pub const TOTAL: &str = "total";
+
+/// Start a non-blocking operation with ``initiate``, run ``compute`` while it is in flight, and
+/// complete it with the closure ``initiate`` returned, e.g. one that waits for the request. Each
+/// phase is timed and recorded as a counter, see the module documentation. Returns the result of
+/// the computation.
+pub fn measure_overlap<I, W, C, T>(comm: &dyn Communicator, initiate: I, compute: C) -> T
+where
+    I: FnOnce(&dyn Communicator) -> W,
+    W: FnOnce(),
+    C: FnOnce(&dyn Communicator) -> T,
+{
+    let start = Instant::now();
+    let wait = initiate(comm);
+    let initiated = Instant::now();
+    let result = compute(comm);
+    let computed = Instant::now();
+    wait();
+    let completed = Instant::now();
+
+    record(INITIATION, initiated - start);
+    record(COMPUTE, computed - initiated);
+    record(WAIT, completed - computed);
+    record(TOTAL, completed - start);
+    result
+}
+
+/// The share of the communication that was hidden behind the computation, between 0 and 1.
+/// ``communication`` is the time of the operation alone, e.g. the [`TOTAL`] of a benchmark with no
+/// computation, and ``compute`` and ``total`` are the counters of the benchmark with computation.
+/// Without any overlap, the total is the sum of both, with perfect overlap, it is the larger one.
+///
+/// # Example
+/// ```rust
+/// use mpirion::nb::overlap;
+/// use std::time::Duration;
+///
+/// let micros = Duration::from_micros;
+/// assert_eq!(overlap(micros(40), micros(100), micros(140)), 0.0);
+/// assert_eq!(overlap(micros(40), micros(100), micros(120)), 0.5);
+/// assert_eq!(overlap(micros(40), micros(100), micros(100)), 1.0);
+/// assert_eq!(overlap(Duration::ZERO, micros(100), micros(100)), 0.0);
+/// ```
+pub fn overlap(communication: Duration, compute: Duration, total: Duration) -> f64 {
+    let hidden = (communication + compute).saturating_sub(total);
+    let hideable = communication.min(compute);
+    if hideable.is_zero() {
+        return 0.0;
+    }
+    (hidden.as_secs_f64() / hideable.as_secs_f64()).min(1.0)
+}
@@ -0,0 +1,210 @@
+//! User-defined reduction operations, created with ``MPI_Op_create`` from a Rust function, for
+//! benchmarks of reductions MPI doesn't provide, like an argmax that carries a payload. Creating the
+//! operation is part of the setup, so it is created once per child, either as the context of the
+//! kernel with ``context = my_operation`` in ``mpirion_kernel!``, or by the ``init`` function, next
+//! to other resources of the state. The operation is freed when it is dropped, or explicitly with
+//! [`free`](UserOperation::free) in the ``teardown`` function.
+//!
+//! The reduction is a type that implements [`Reduction`], whose function MPI calls through a
+//! trampoline that is generic over the type, so the function needs no state and no closure has to
+//! outlive the operation. The operation is only used on buffers of the values of its reduction,
+//! with [`all_reduce_into`](UserOperation::all_reduce_into) and
+//! [`reduce_into_root`](UserOperation::reduce_into_root), so MPI never passes it other values.
+//! A panic in the reduction cannot unwind through MPI and aborts the child.
+//!
+//! # Example
+//! ```rust
+//! use mpirion::op::{Reduction, UserOperation};
+//! use mpirion::prelude::*;
+//!
+//! // the largest value and the index of the rank that holds it, stored as a float next to it
+//! struct ArgMax;
+//!
+//! impl Reduction for ArgMax {
+//!     type Value = [f64; 2];
+//!
+//!     fn reduce(input: &[[f64; 2]], inout: &mut [[f64; 2]]) {
+//!         for (input, inout) in input.iter().zip(inout.iter_mut()) {
+//!             if input[0] > inout[0] || (input[0] == inout[0] && input[1] < inout[1]) {
+//!                 *inout = *input;
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! fn argmax(_comm: &dyn Communicator) -> UserOperation<ArgMax> {
+//!     UserOperation::new()
+//! }
+//!
+//! fn setup(comm: &dyn Communicator) -> (Vec<[f64; 2]>, Vec<[f64; 2]>) {
+//!     let rank = comm.rank() as f64;
+//!     ((0..1024).map(|index| [(rank * 7.0 + index as f64) % 13.0, rank]).collect(), vec![[0.0; 2]; 1024])
+//! }
+//!
+//! fn argmax_kernel(
+//!     comm: &dyn Communicator,
+//!     (values, maxima): &mut (Vec<[f64; 2]>, Vec<[f64; 2]>),
+//!     argmax: &UserOperation<ArgMax>,
+//! ) {
+//!     argmax.all_reduce_into(comm, values, maxima);
+//! }
+//!
+//! mpirion_kernel!(kernel = argmax_kernel, setup = setup, context = argmax);
+//! ```
+
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+
+/// A reduction for a [`UserOperation`], see the module documentation.
+pub trait Reduction {
+    /// The values the reduction combines.
+    type Value: Equivalence;
+
+    /// Whether the reduction is commutative, which lets MPI combine the values of the ranks in any
+    /// order. Otherwise, they are combined in the order of the ranks. It must be associative either
+    /// way.
+    const COMMUTATIVE: bool = true;
+
+    /// Combine the values of ``input`` into those of ``inout`` elementwise, so that each value of
+    /// ``inout`` becomes ``input[i] ∘ inout[i]``. Both slices have the same length.
+    fn reduce(input: &[Self::Value], inout: &mut [Self::Value]);
+}
+
+/// A reduction operation created from a [`Reduction`], see the module documentation. It is freed
+/// when it is dropped.
+pub struct UserOperation<R: Reduction> {
+    raw: ffi::MPI_Op,
+    _reduction: PhantomData<R>,
+}
+
+impl<R: Reduction> UserOperation<R> {
+    /// Create the operation, like ``MPI_Op_create``. It is local, so each rank creates its own.
+    pub fn new() -> Self {
+        let mut raw = std::mem::MaybeUninit::uninit();
+        // SAFETY: the trampoline only reads and writes values of the reduction, see reduce_raw
+        let raw = unsafe {
+            ffi::MPI_Op_create(
+                Some(reduce_raw::<R>),
+                c_int::from(R::COMMUTATIVE),
+                raw.as_mut_ptr(),
+            );
+            raw.assume_init()
+        };
+        Self {
+            raw,
+            _reduction: PhantomData,
+        }
+    }
+
+    /// Reduce ``send`` of all ranks of ``comm`` into ``receive`` of all ranks, like
+    /// ``MPI_Allreduce``. Panics if the buffers have different lengths, or if they are longer than
+    /// ``i32::MAX``.
+    pub fn all_reduce_into(
+        &self,
+        comm: &dyn Communicator,
+        send: &[R::Value],
+        receive: &mut [R::Value],
+    ) {
+        let count = count_of(send.len(), receive.len());
+        // SAFETY: both buffers hold count values of the datatype of the reduction
+        unsafe {
+            ffi::MPI_Allreduce(
+                send.as_ptr() as *const c_void,
+                receive.as_mut_ptr() as *mut c_void,
+                count,
+                R::Value::equivalent_datatype().as_raw(),
+                self.raw,
+                comm.as_raw(),
+            );
+        }
+    }
+
+    /// Reduce ``send`` of all ranks of ``comm`` into ``receive`` of ``root``, like ``MPI_Reduce``.
+    /// The receive buffer of the other ranks is not used and may be empty. Panics if the buffers
+    /// of the root have different lengths, or if they are longer than ``i32::MAX``.
+    pub fn reduce_into_root(
+        &self,
+        comm: &dyn Communicator,
+        root: Rank,
+        send: &[R::Value],
+        receive: &mut [R::Value],
+    ) {
+        let count = if comm.rank() == root {
+            count_of(send.len(), receive.len())
+        } else {
+            count_of(send.len(), send.len())
+        };
+        // SAFETY: the send buffer holds count values of the datatype of the reduction, and so does
+        // the receive buffer of the root, the only one MPI writes
+        unsafe {
+            ffi::MPI_Reduce(
+                send.as_ptr() as *const c_void,
+                receive.as_mut_ptr() as *mut c_void,
+                count,
+                R::Value::equivalent_datatype().as_raw(),
+                self.raw,
+                root,
+                comm.as_raw(),
+            );
+        }
+    }
+
+    /// The handle of the operation, for the collectives that are not wrapped here, e.g.
+    /// ``MPI_Reduce_scatter_block``. It may only be used on buffers of the values of the
+    /// reduction, and only until the operation is freed.
+    pub fn as_raw(&self) -> ffi::MPI_Op {
+        self.raw
+    }
+
+    /// Free the operation, the same as dropping it. Meant for the teardown function of a kernel,
+    /// so the operation is visibly freed before MPI is finalized.
+    pub fn free(self) {}
+}
+
+impl<R: Reduction> Default for UserOperation<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Reduction> Drop for UserOperation<R> {
+    fn drop(&mut self) {
+        // SAFETY: the operation is not used by any pending collective, since they all block
+        unsafe {
+            ffi::MPI_Op_free(&mut self.raw);
+        }
+    }
+}
+
+/// The number of values of a collective on a send and a receive buffer. Panics if they differ, or
+/// if they don't fit into a count of MPI.
+fn count_of(send: usize, receive: usize) -> c_int {
+    assert_eq!(
+        send, receive,
+        "the send and the receive buffer of a reduction have different lengths"
+    );
+    c_int::try_from(send).expect("the buffers of a reduction are longer than i32::MAX")
+}
+
+/// The function MPI calls to combine the values of the reduction ``R``.
+///
+/// # Safety
+/// MPI passes buffers of ``len`` values of the datatype the operation is used with, which is the
+/// datatype of ``R::Value`` for all collectives of [`UserOperation`].
+unsafe extern "C" fn reduce_raw<R: Reduction>(
+    input: *mut c_void,
+    inout: *mut c_void,
+    len: *mut c_int,
+    _datatype: *mut ffi::MPI_Datatype,
+) {
+    let len = usize::try_from(*len).unwrap_or(0);
+    let input = std::slice::from_raw_parts(input as *const R::Value, len);
+    let inout = std::slice::from_raw_parts_mut(inout as *mut R::Value, len);
+    R::reduce(input, inout);
+}
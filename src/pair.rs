@@ -0,0 +1,231 @@
+//! Benchmarks of two groups of ranks, like producers that stream data to consumers over an
+//! inter-communicator. The producers run the kernel of the benchmark, the consumers another kernel
+//! declared with ``mpirion_kernel!``, added with
+//! ``consumer = consumer_kernel, consumers = 2`` in ``mpirion_bench!``.
+//!
+//! Both groups are spawned together with ``MPI_Comm_spawn_multiple``, so they form one world of
+//! children, the producers first. The parent runs the protocol with all of them, so the iterations
+//! and the arguments reach both groups in the same broadcast, and the setup function of each
+//! kernel receives the same arguments. Each child joins its group and the inter-communicator
+//! between the groups is created once per spawned world. The setup, kernel and validation functions
+//! receive the inter-communicator, on which the ranks of the other group are addressed by their
+//! rank in it, see [`remote_size`]. The barrier before each iteration, the checks of the results and
+//! the reduction of the times still span both groups, and ``replicated_setup`` is not supported, as
+//! it broadcasts on the communicator of the kernel.
+//!
+//! The parent reduces the times of each group separately and reports the time of the group
+//! selected with ``report = Report::Consumers`` in ``mpirion_bench!``, or the slower one by
+//! default, see [`Report`]. The times of both groups are recorded as the counters [`PRODUCERS`]
+//! and [`CONSUMERS`] in the ``mpirion.json`` of the benchmark.
+//!
+//! # Example
+//! ```rust
+//! use mpirion::pair::{remote_size, Report};
+//! use mpirion::prelude::*;
+//!
+//! fn stream_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+//!     ctx.bench_function(c, "stream", "produce", |b| {
+//!         mpirion_bench!(produce, b, ctx; world_size = 4, consumer = consume, consumers = 2, report = Report::Consumers)
+//!     });
+//! }
+//!
+//! // each producer sends to one consumer, rank by rank
+//! fn produce(comm: &dyn Communicator) {
+//!     let chunk = vec![comm.rank() as u64; 1024];
+//!     comm.process_at_rank(comm.rank() % remote_size()).send(&chunk[..]);
+//! }
+//!
+//! fn consume(comm: &dyn Communicator) {
+//!     let mut chunk = vec![0u64; 1024];
+//!     for producer in (comm.rank()..remote_size()).step_by(comm.size() as usize) {
+//!         comm.process_at_rank(producer).receive_into(&mut chunk[..]);
+//!     }
+//! }
+//!
+//! mpirion_kernel!(produce);
+//! mpirion_kernel!(consume);
+//! ```
+
+use std::os::raw::c_int;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::topology::{Color, SimpleCommunicator};
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+
+/// The counter of the mean time per iteration of the producers.
+pub const PRODUCERS: &str = "producers";
+
+/// The counter of the mean time per iteration of the consumers.
+pub const CONSUMERS: &str = "consumers";
+
+/// The flag that tells a child which group of a pair it is in.
+const SIDE_FLAG: &str = "--mpirion-side";
+
+/// The tag of the messages that create the inter-communicator.
+const INTERCOMM_TAG: c_int = 0x6d70;
+
+/// The group of this child and the size of the other group, once the inter-communicator was
+/// created.
+static SIDE: Mutex<Option<(Side, Rank)>> = Mutex::new(None);
+
+/// One of the two groups of a pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// the group that runs the kernel of the benchmark, the first ranks of the world
+    Producer,
+    /// the group that runs the consumer kernel, the last ranks of the world
+    Consumer,
+}
+
+/// Which time a benchmark of a pair reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Report {
+    /// the mean time of the producers
+    Producers,
+    /// the mean time of the consumers
+    Consumers,
+    /// the larger of the two means
+    #[default]
+    Max,
+}
+
+impl Side {
+    fn name(self) -> &'static str {
+        match self {
+            Side::Producer => "producer",
+            Side::Consumer => "consumer",
+        }
+    }
+}
+
+/// The arguments that tell a child which group it is in.
+pub fn child_args(side: Side) -> [String; 2] {
+    [SIDE_FLAG.to_string(), side.name().to_string()]
+}
+
+/// The group in the arguments of a child, or ``None`` if it doesn't run in a pair. Fails if the
+/// group is unknown.
+///
+/// # Example
+/// ```rust
+/// use mpirion::pair::{side_from_args, Side};
+///
+/// let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+/// assert_eq!(side_from_args(&args(&["--mpirion-child", "sink", "--mpirion-side", "consumer"])), Ok(Some(Side::Consumer)));
+/// assert_eq!(side_from_args(&args(&["--mpirion-child", "sink"])), Ok(None));
+/// assert!(side_from_args(&args(&["--mpirion-side", "server"])).is_err());
+/// assert!(side_from_args(&args(&["--mpirion-side"])).is_err());
+/// ```
+pub fn side_from_args(args: &[String]) -> Result<Option<Side>, String> {
+    let Some(position) = args.iter().position(|arg| arg == SIDE_FLAG) else {
+        return Ok(None);
+    };
+    match args.get(position + 1).map(String::as_str) {
+        Some("producer") => Ok(Some(Side::Producer)),
+        Some("consumer") => Ok(Some(Side::Consumer)),
+        Some(side) => Err(format!(
+            "{} must be `producer` or `consumer`, not `{}`",
+            SIDE_FLAG, side
+        )),
+        None => Err(format!("{} needs `producer` or `consumer`", SIDE_FLAG)),
+    }
+}
+
+/// The group this child runs in. Panics if its kernel doesn't run in a pair.
+pub fn side() -> Side {
+    SIDE.lock()
+        .unwrap()
+        .expect("the kernel does not run in a pair, add `consumer = ..` to mpirion_bench!")
+        .0
+}
+
+/// The number of ranks in the other group, the remote group of the inter-communicator. Panics if
+/// the kernel doesn't run in a pair.
+pub fn remote_size() -> Rank {
+    SIDE.lock()
+        .unwrap()
+        .expect("the kernel does not run in a pair, add `consumer = ..` to mpirion_bench!")
+        .1
+}
+
+/// Create the inter-communicator between the groups of a pair, if this child was spawned as one of
+/// them. Called by the code ``mpirion_kernel!`` generates, once per spawned world, on the world of
+/// the children. Panics with the name of the kernel if the arguments of the child are malformed.
+pub fn join(kernel: &str, world: &dyn Communicator) -> Option<SimpleCommunicator> {
+    let args: Vec<String> = std::env::args().collect();
+    let side = side_from_args(&args)
+        .unwrap_or_else(|message| panic!("kernel `{}`: {}", kernel, message))?;
+    let local = world
+        .split_by_color(Color::with_value(side as i32))
+        .unwrap_or_else(|| {
+            panic!(
+                "kernel `{}`: rank {} is in no group of the pair",
+                kernel,
+                world.rank()
+            )
+        });
+    // the producers are the first ranks of the world, so the leader of the consumers follows them
+    let remote_leader = match side {
+        Side::Producer => local.size(),
+        Side::Consumer => 0,
+    };
+    let mut raw = std::mem::MaybeUninit::uninit();
+    // SAFETY: both groups create the inter-communicator with the leader of the other one
+    let inter = unsafe {
+        ffi::MPI_Intercomm_create(
+            local.as_raw(),
+            0,
+            world.as_raw(),
+            remote_leader,
+            INTERCOMM_TAG,
+            raw.as_mut_ptr(),
+        );
+        SimpleCommunicator::from_raw(raw.assume_init())
+    };
+    *SIDE.lock().unwrap() = Some((side, world.size() - local.size()));
+    Some(inter)
+}
+
+/// The time a benchmark of a pair reports, from the mean time of each group and the number of
+/// outliers rejected in it.
+///
+/// # Example
+/// ```rust
+/// use mpirion::pair::{reported, Report};
+/// use std::time::Duration;
+///
+/// let (producers, consumers) = ((Duration::from_micros(40), 0), (Duration::from_micros(55), 1));
+/// assert_eq!(reported(Report::Producers, producers, consumers), producers);
+/// assert_eq!(reported(Report::Consumers, producers, consumers), consumers);
+/// assert_eq!(reported(Report::Max, producers, consumers), (Duration::from_micros(55), 1));
+/// ```
+pub fn reported(
+    report: Report,
+    producers: (Duration, usize),
+    consumers: (Duration, usize),
+) -> (Duration, usize) {
+    match report {
+        Report::Producers => producers,
+        Report::Consumers => consumers,
+        Report::Max => (producers.0.max(consumers.0), producers.1 + consumers.1),
+    }
+}
+
+/// The counters with the mean time per iteration of each group, from the times of all ranks over
+/// ``iterations`` iterations, the ``producers`` first.
+pub fn side_counters(
+    per_rank: &[Duration],
+    producers: usize,
+    iterations: u64,
+) -> Vec<(String, Duration)> {
+    let (producer_times, consumer_times) = per_rank.split_at(producers.min(per_rank.len()));
+    let totals = [
+        (PRODUCERS.to_string(), crate::timing::mean(producer_times)),
+        (CONSUMERS.to_string(), crate::timing::mean(consumer_times)),
+    ];
+    crate::counters::per_iteration(&totals, iterations)
+}
@@ -0,0 +1,90 @@
+//! Helpers to distribute a global index space over the ranks of a communicator. They are meant to
+//! be used in setup functions to generate the local part of a distributed input.
+
+use crate::mpi::traits::*;
+use std::iter::StepBy;
+use std::ops::Range;
+
+/// The global indices of the block of ``rank`` if ``total`` elements are distributed over ``size``
+/// ranks in contiguous blocks. If ``total`` is not divisible by ``size``, the first
+/// ``total % size`` ranks receive one element more than the others.
+///
+/// # Example
+/// ```rust
+/// use mpirion::partition::block_range;
+///
+/// // uneven division: the first ranks receive the remainder
+/// assert_eq!(block_range(10, 0, 4), 0..3);
+/// assert_eq!(block_range(10, 1, 4), 3..6);
+/// assert_eq!(block_range(10, 2, 4), 6..8);
+/// assert_eq!(block_range(10, 3, 4), 8..10);
+///
+/// // fewer elements than ranks
+/// assert_eq!(block_range(2, 1, 4), 1..2);
+/// assert_eq!(block_range(2, 3, 4), 2..2);
+///
+/// // zero elements and world size 1
+/// assert_eq!(block_range(0, 2, 4), 0..0);
+/// assert_eq!(block_range(7, 0, 1), 0..7);
+/// ```
+pub fn block_range(total: u64, rank: u64, size: u64) -> Range<u64> {
+    assert!(
+        rank < size,
+        "rank {} is out of bounds for size {}",
+        rank,
+        size
+    );
+    let base = total / size;
+    let remainder = total % size;
+    let start = rank * base + rank.min(remainder);
+    let len = base + (rank < remainder) as u64;
+    start..start + len
+}
+
+/// The global indices of the block of the calling rank if ``total`` elements are distributed over
+/// all ranks of ``comm``. See [`block_range`].
+pub fn block_partition(total: u64, comm: &dyn Communicator) -> Range<u64> {
+    block_range(total, comm.rank() as u64, comm.size() as u64)
+}
+
+/// The global indices of ``rank`` if ``total`` elements are distributed over ``size`` ranks
+/// round-robin, i.e. element ``i`` belongs to rank ``i % size``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::partition::cyclic_indices;
+///
+/// assert_eq!(cyclic_indices(10, 1, 4).collect::<Vec<_>>(), vec![1, 5, 9]);
+/// assert_eq!(cyclic_indices(10, 3, 4).collect::<Vec<_>>(), vec![3, 7]);
+/// assert_eq!(cyclic_indices(2, 3, 4).count(), 0);
+/// assert_eq!(cyclic_indices(0, 0, 4).count(), 0);
+/// assert_eq!(cyclic_indices(3, 0, 1).collect::<Vec<_>>(), vec![0, 1, 2]);
+/// ```
+pub fn cyclic_indices(total: u64, rank: u64, size: u64) -> StepBy<Range<u64>> {
+    assert!(
+        rank < size,
+        "rank {} is out of bounds for size {}",
+        rank,
+        size
+    );
+    (rank.min(total)..total).step_by(size as usize)
+}
+
+/// The global indices of the calling rank if ``total`` elements are distributed over all ranks of
+/// ``comm`` round-robin. See [`cyclic_indices`].
+pub fn cyclic_partition(total: u64, comm: &dyn Communicator) -> StepBy<Range<u64>> {
+    cyclic_indices(total, comm.rank() as u64, comm.size() as u64)
+}
+
+/// Allocate the local block of a distributed array of ``total`` elements and fill it by calling
+/// ``value`` with the global index of each element. See [`block_partition`].
+pub fn block_shard<T>(total: u64, comm: &dyn Communicator, value: impl FnMut(u64) -> T) -> Vec<T> {
+    block_partition(total, comm).map(value).collect()
+}
+
+/// Allocate the local elements of a distributed array of ``total`` elements distributed
+/// round-robin, and fill them by calling ``value`` with the global index of each element.
+/// See [`cyclic_partition`].
+pub fn cyclic_shard<T>(total: u64, comm: &dyn Communicator, value: impl FnMut(u64) -> T) -> Vec<T> {
+    cyclic_partition(total, comm).map(value).collect()
+}
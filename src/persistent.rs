@@ -0,0 +1,216 @@
+//! Persistent requests, which are created once and started in every iteration, like the requests
+//! of ``MPI_Send_init`` and ``MPI_Recv_init``, or the persistent collectives of MPI 4. Creating
+//! them is part of the setup, not of the measured kernel, so they fit the ``init``, ``reset`` and
+//! ``teardown`` functions of ``mpirion_kernel!``: the init function creates the requests, the
+//! kernel [starts](Persistent::start) and [completes](Persistent::wait) them in each iteration, and
+//! the teardown function [frees](Persistent::free) them.
+//!
+//! A [`Persistent`] request owns its buffer, so the state returned by the init function can hold
+//! both without borrowing from itself. The buffer is on the heap, so MPI keeps its address when the
+//! state is moved. The kernel only gets a shared reference to the state, so the request is started
+//! and completed through a shared reference, and it checks like a ``RefCell`` that its buffer is not
+//! borrowed while MPI uses it.
+//!
+//! The request borrows the communicator it was created on for ``'w``. The code generated by
+//! ``mpirion_kernel!`` creates the communicators of the kernel, i.e. the world of the children and
+//! the communicator of its ``topology``, before the init function. It tears the state down after the
+//! last iteration, before the communicators are freed and MPI is finalized, so the state can hold
+//! requests on the communicator the init function receives.
+//!
+//! # Example
+//! ```rust
+//! use mpirion::persistent::Persistent;
+//! use mpirion::prelude::*;
+//!
+//! // a ring: every rank sends to the next one and receives from the previous one
+//! struct Ring<'w> {
+//!     send: Persistent<'w, f64>,
+//!     receive: Persistent<'w, f64>,
+//! }
+//!
+//! fn init(comm: &dyn Communicator) -> Ring<'_> {
+//!     let (rank, size) = (comm.rank(), comm.size());
+//!     Ring {
+//!         send: Persistent::send_init(comm, vec![rank as f64; 1024], (rank + 1) % size, 0),
+//!         receive: Persistent::receive_init(comm, vec![0.0; 1024], (rank + size - 1) % size, 0),
+//!     }
+//! }
+//!
+//! fn reset<'s, 'w>(_comm: &dyn Communicator, ring: &'s Ring<'w>) -> &'s Ring<'w> {
+//!     ring
+//! }
+//!
+//! fn teardown(_comm: &dyn Communicator, ring: Ring<'_>) {
+//!     ring.send.free();
+//!     ring.receive.free();
+//! }
+//!
+//! fn ring_kernel(_comm: &dyn Communicator, ring: &&Ring) {
+//!     ring.receive.start();
+//!     ring.send.start();
+//!     ring.receive.wait();
+//!     ring.send.wait();
+//! }
+//!
+//! mpirion_kernel!(kernel = ring_kernel, init = init, reset = reset, teardown = teardown, data = ref);
+//! ```
+
+use std::cell::{Cell, Ref, RefCell};
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+
+/// A persistent request on a buffer it owns, see the module documentation. It is freed when it is
+/// dropped, after it was completed if it is active.
+pub struct Persistent<'w, T> {
+    raw: Cell<ffi::MPI_Request>,
+    buffer: RefCell<Vec<T>>,
+    active: Cell<bool>,
+    _comm: PhantomData<&'w dyn Communicator>,
+}
+
+impl<'w, T: Equivalence> Persistent<'w, T> {
+    /// A request that sends ``buffer`` to ``destination`` with ``tag`` when it is started, like
+    /// ``MPI_Send_init``.
+    pub fn send_init(
+        comm: &'w dyn Communicator,
+        buffer: Vec<T>,
+        destination: Rank,
+        tag: i32,
+    ) -> Self {
+        // SAFETY: MPI_Send_init creates a persistent request on the buffer it receives
+        unsafe {
+            Self::with_raw(comm, buffer, |buffer, count, datatype, request| {
+                ffi::MPI_Send_init(
+                    buffer,
+                    count,
+                    datatype,
+                    destination,
+                    tag,
+                    comm.as_raw(),
+                    request,
+                );
+            })
+        }
+    }
+
+    /// A request that receives into ``buffer`` from ``source`` with ``tag`` when it is started,
+    /// like ``MPI_Recv_init``.
+    pub fn receive_init(
+        comm: &'w dyn Communicator,
+        buffer: Vec<T>,
+        source: Rank,
+        tag: i32,
+    ) -> Self {
+        // SAFETY: MPI_Recv_init creates a persistent request on the buffer it receives
+        unsafe {
+            Self::with_raw(comm, buffer, |buffer, count, datatype, request| {
+                ffi::MPI_Recv_init(buffer, count, datatype, source, tag, comm.as_raw(), request);
+            })
+        }
+    }
+
+    /// A request created by ``create`` on ``buffer``, for the persistent requests that are not
+    /// wrapped here, e.g. ``MPI_Allreduce_init`` of MPI 4 with ``MPI_IN_PLACE``. ``create``
+    /// receives the address of the buffer, its length, the datatype of ``T`` and the request to
+    /// initialize. Panics if the buffer is longer than ``i32::MAX``.
+    ///
+    /// # Safety
+    /// ``create`` must initialize the request as a persistent request on ``comm``, which accesses
+    /// no memory but the buffer it receives.
+    pub unsafe fn with_raw<F>(comm: &'w dyn Communicator, mut buffer: Vec<T>, create: F) -> Self
+    where
+        F: FnOnce(*mut c_void, c_int, ffi::MPI_Datatype, *mut ffi::MPI_Request),
+    {
+        // the request borrows the communicator for 'w
+        let _ = comm;
+        let count = c_int::try_from(buffer.len())
+            .expect("the buffer of a persistent request is longer than i32::MAX");
+        let mut raw = std::mem::MaybeUninit::uninit();
+        create(
+            buffer.as_mut_ptr() as *mut c_void,
+            count,
+            T::equivalent_datatype().as_raw(),
+            raw.as_mut_ptr(),
+        );
+        Self {
+            raw: Cell::new(raw.assume_init()),
+            buffer: RefCell::new(buffer),
+            active: Cell::new(false),
+            _comm: PhantomData,
+        }
+    }
+}
+
+impl<T> Persistent<'_, T> {
+    /// Start the request, like ``MPI_Start``. Panics if it is active, or if its buffer is borrowed.
+    pub fn start(&self) {
+        assert!(
+            !self.active.get(),
+            "the persistent request was started twice without waiting for it"
+        );
+        assert!(
+            self.buffer.try_borrow_mut().is_ok(),
+            "the buffer of a persistent request is borrowed while it is started"
+        );
+        // SAFETY: the request is inactive, and its buffer is not borrowed until it is complete
+        unsafe {
+            ffi::MPI_Start(self.raw.as_ptr());
+        }
+        self.active.set(true);
+    }
+
+    /// Wait until the request is complete, like ``MPI_Wait``. Does nothing if it is not active.
+    pub fn wait(&self) {
+        if self.active.get() {
+            // SAFETY: the request is active
+            unsafe {
+                ffi::MPI_Wait(self.raw.as_ptr(), ffi::RSMPI_STATUS_IGNORE);
+            }
+            self.active.set(false);
+        }
+    }
+
+    /// Whether the request was started and not waited for.
+    pub fn is_active(&self) -> bool {
+        self.active.get()
+    }
+
+    /// The buffer of the request, e.g. the values it received. Panics if the request is active.
+    pub fn buffer(&self) -> Ref<'_, [T]> {
+        assert!(
+            !self.active.get(),
+            "the buffer of an active persistent request is in use by MPI"
+        );
+        Ref::map(self.buffer.borrow(), Vec::as_slice)
+    }
+
+    /// The buffer of the request, e.g. to fill in the values to send. Panics if the request is
+    /// active.
+    pub fn buffer_mut(&mut self) -> &mut [T] {
+        assert!(
+            !self.active.get(),
+            "the buffer of an active persistent request is in use by MPI"
+        );
+        self.buffer.get_mut()
+    }
+
+    /// Complete the request if it is active and free it, the same as dropping it. Meant for the
+    /// teardown function of a kernel, so the requests are visibly freed before the communicator.
+    pub fn free(self) {}
+}
+
+impl<T> Drop for Persistent<'_, T> {
+    fn drop(&mut self) {
+        self.wait();
+        // SAFETY: the request is inactive, so MPI does not use the buffer anymore
+        unsafe {
+            ffi::MPI_Request_free(self.raw.as_ptr());
+        }
+    }
+}
@@ -0,0 +1,529 @@
+//! The placement of the children on the nodes of the cluster. When results differ between runs, the
+//! first question is whether the ranks ran on the same nodes. Each child reports the name of its
+//! processor, i.e. its host, and the CPUs it may run on in the handshake. The parent prints a
+//! summary the first time it sees a placement, and the [report](crate::report) writes the
+//! placements of each benchmark into its ``placement.json``.
+//!
+//! The CPUs are read from ``/proc/self/status``, so they are only known on Linux.
+//!
+//! Benchmarks can also ask for a placement with ``placement = PlacementMode::PerNode`` in
+//! ``mpirion_bench!``, to separate the effects within a node from those between nodes, see
+//! [`PlacementMode`]. The mode is passed to ``MPI_Comm_spawn`` as an info key, and the parent checks
+//! the placement the children report against it and warns if MPI placed them differently. The
+//! requested and the achieved mode are written into the ``mpirion.json`` of the benchmark.
+
+use std::ffi::CString;
+use std::fmt::{self, Display, Write as _};
+use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::mpi::ffi;
+use crate::mpi::topology::InterCommunicator;
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+
+/// The info key of ``MPI_Comm_spawn`` that selects the mapping of Open MPI.
+const MAP_BY: &str = "map_by";
+
+/// The bytes each child sends for its host and its CPUs. Longer names are truncated.
+const FIELD_LEN: usize = 256;
+
+/// Where a child ran.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RankPlacement {
+    /// the processor name reported by MPI, usually the host name
+    pub host: String,
+    /// the CPUs the child may run on, like ``0-3,8``, if known
+    pub cpus: Option<String>,
+}
+
+/// Where the children of a spawn ran, ordered by rank.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Placement {
+    pub ranks: Vec<RankPlacement>,
+}
+
+impl Placement {
+    /// A summary of the hosts the ranks ran on, in the order the hosts first appear.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::placement::{Placement, RankPlacement};
+    ///
+    /// let on = |host: &str| RankPlacement { host: host.to_string(), cpus: None };
+    /// let placement = Placement { ranks: vec![on("node01"), on("node02"), on("node01"), on("node02")] };
+    /// assert_eq!(placement.summary(), "4 ranks on 2 hosts (node01×2, node02×2)");
+    ///
+    /// let single = Placement { ranks: vec![on("node01")] };
+    /// assert_eq!(single.summary(), "1 rank on 1 host (node01×1)");
+    /// ```
+    pub fn summary(&self) -> String {
+        let mut hosts: Vec<(&str, usize)> = Vec::new();
+        for rank in &self.ranks {
+            match hosts.iter_mut().find(|(host, _)| *host == rank.host) {
+                Some((_, count)) => *count += 1,
+                None => hosts.push((rank.host.as_str(), 1)),
+            }
+        }
+        let counts: Vec<String> = hosts
+            .iter()
+            .map(|(host, count)| format!("{}×{}", host, count))
+            .collect();
+        format!(
+            "{} rank{} on {} host{} ({})",
+            self.ranks.len(),
+            if self.ranks.len() == 1 { "" } else { "s" },
+            hosts.len(),
+            if hosts.len() == 1 { "" } else { "s" },
+            counts.join(", ")
+        )
+    }
+
+    /// The hosts that run more ranks than the CPUs the ranks may run on together, so the ranks share
+    /// CPUs. Hosts whose CPUs are unknown are left out.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::placement::{Placement, RankPlacement};
+    ///
+    /// let on = |host: &str, cpus: &str| RankPlacement { host: host.to_string(), cpus: Some(cpus.to_string()) };
+    /// let placement = Placement { ranks: vec![on("node01", "0-1"), on("node01", "0-1"), on("node01", "0-1"), on("node02", "0"), on("node02", "4,6")] };
+    /// assert_eq!(placement.oversubscribed_hosts(), vec!["node01".to_string()]);
+    /// ```
+    pub fn oversubscribed_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<(&str, usize, Vec<u32>, bool)> = Vec::new();
+        for rank in &self.ranks {
+            let index = match hosts.iter().position(|(host, ..)| *host == rank.host) {
+                Some(index) => index,
+                None => {
+                    hosts.push((rank.host.as_str(), 0, Vec::new(), true));
+                    hosts.len() - 1
+                }
+            };
+            let (_, ranks, cpus, known) = &mut hosts[index];
+            *ranks += 1;
+            match rank.cpus.as_deref().and_then(parse_cpu_list) {
+                Some(allowed) => cpus.extend(allowed),
+                None => *known = false,
+            }
+        }
+        hosts
+            .into_iter()
+            .filter_map(|(host, ranks, mut cpus, known)| {
+                cpus.sort_unstable();
+                cpus.dedup();
+                (known && ranks > cpus.len()).then(|| host.to_string())
+            })
+            .collect()
+    }
+
+    /// The placement as a JSON array of the ranks.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::placement::{Placement, RankPlacement};
+    ///
+    /// let placement = Placement {
+    ///     ranks: vec![
+    ///         RankPlacement { host: "node01".to_string(), cpus: Some("0-3".to_string()) },
+    ///         RankPlacement { host: "node02".to_string(), cpus: None },
+    ///     ],
+    /// };
+    /// assert_eq!(
+    ///     placement.to_json(),
+    ///     r#"[{"rank":0,"host":"node01","cpus":"0-3"},{"rank":1,"host":"node02","cpus":null}]"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (rank, placement) in self.ranks.iter().enumerate() {
+            if rank > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                r#"{{"rank":{},"host":{},"cpus":{}}}"#,
+                rank,
+                crate::manifest::json_string(&placement.host),
+                placement
+                    .cpus
+                    .as_deref()
+                    .map_or_else(|| "null".to_string(), crate::manifest::json_string)
+            );
+        }
+        json.push(']');
+        json
+    }
+}
+
+/// How the children of a benchmark are distributed over the nodes, requested with ``placement`` in
+/// ``mpirion_bench!``. MPI receives the mode as the ``map_by`` info key of Open MPI when spawning the
+/// children. Other MPI libraries may ignore it, which the parent notices and warns about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlacementMode {
+    /// fill the cores of a node before the next one
+    PerCore,
+    /// one rank on each node
+    PerNode,
+    /// the given number of ranks on each node, the last one may have fewer
+    Spread(usize),
+}
+
+impl PlacementMode {
+    /// The value of the ``map_by`` info key that requests the mode from Open MPI.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::placement::PlacementMode;
+    ///
+    /// assert_eq!(PlacementMode::PerCore.map_by(), "core");
+    /// assert_eq!(PlacementMode::PerNode.map_by(), "ppr:1:node");
+    /// assert_eq!(PlacementMode::Spread(4).map_by(), "ppr:4:node");
+    /// ```
+    pub fn map_by(self) -> String {
+        match self {
+            PlacementMode::PerCore => "core".to_string(),
+            PlacementMode::PerNode => "ppr:1:node".to_string(),
+            PlacementMode::Spread(ranks) => format!("ppr:{}:node", ranks),
+        }
+    }
+
+    /// Whether the children were placed as the mode asks. The ranks of each host must be
+    /// consecutive, except for ``PerNode``, which only needs a single rank on every host. For
+    /// ``PerCore``, the hosts before the last one must have the same number of ranks, as MPI
+    /// fills them up to their cores, and the last host may have fewer.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::placement::{Placement, PlacementMode, RankPlacement};
+    ///
+    /// let on = |hosts: &[&str]| Placement {
+    ///     ranks: hosts.iter().map(|host| RankPlacement { host: host.to_string(), cpus: None }).collect(),
+    /// };
+    /// let filled = on(&["node01", "node01", "node01", "node02"]);
+    /// assert!(PlacementMode::PerCore.holds(&filled));
+    /// assert!(!PlacementMode::PerNode.holds(&filled));
+    /// assert!(!PlacementMode::Spread(2).holds(&filled));
+    ///
+    /// let spread = on(&["node01", "node01", "node02", "node02", "node03"]);
+    /// assert!(PlacementMode::Spread(2).holds(&spread));
+    /// assert!(PlacementMode::PerNode.holds(&on(&["node01", "node02", "node03"])));
+    /// assert!(!PlacementMode::PerCore.holds(&on(&["node01", "node02", "node01"])));
+    /// ```
+    pub fn holds(self, placement: &Placement) -> bool {
+        let Some(counts) = consecutive_hosts(placement) else {
+            return false;
+        };
+        let (last, before) = match counts.split_last() {
+            Some((last, before)) => (*last, before),
+            None => return true,
+        };
+        match self {
+            PlacementMode::PerCore => {
+                let full = before.first().copied().unwrap_or(last);
+                before.iter().all(|count| *count == full) && last <= full
+            }
+            PlacementMode::PerNode => counts.iter().all(|count| *count == 1),
+            PlacementMode::Spread(ranks) => {
+                before.iter().all(|count| *count == ranks) && last <= ranks
+            }
+        }
+    }
+
+    /// The mode a placement achieved, for the labels of graphs: ``PerNode`` if every host has a
+    /// single rank, ``PerCore`` if all ranks ran on one host, ``Spread`` with the ranks of the
+    /// first host if the others have as many, the last one up to as many, and ``None`` otherwise.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::placement::{Placement, PlacementMode, RankPlacement};
+    ///
+    /// let on = |hosts: &[&str]| Placement {
+    ///     ranks: hosts.iter().map(|host| RankPlacement { host: host.to_string(), cpus: None }).collect(),
+    /// };
+    /// assert_eq!(PlacementMode::achieved(&on(&["node01", "node02"])), Some(PlacementMode::PerNode));
+    /// assert_eq!(PlacementMode::achieved(&on(&["node01", "node01"])), Some(PlacementMode::PerCore));
+    /// assert_eq!(PlacementMode::achieved(&on(&["node01", "node01", "node02"])), Some(PlacementMode::Spread(2)));
+    /// assert_eq!(PlacementMode::achieved(&on(&["node01", "node02", "node02"])), None);
+    /// ```
+    pub fn achieved(placement: &Placement) -> Option<PlacementMode> {
+        let counts = consecutive_hosts(placement).filter(|counts| !counts.is_empty())?;
+        let mode = if counts.iter().all(|count| *count == 1) {
+            PlacementMode::PerNode
+        } else if counts.len() == 1 {
+            PlacementMode::PerCore
+        } else {
+            PlacementMode::Spread(counts[0])
+        };
+        mode.holds(placement).then_some(mode)
+    }
+}
+
+/// The modes are written like ``per-core``, ``per-node`` and ``spread(4)``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::placement::PlacementMode;
+///
+/// for mode in [PlacementMode::PerCore, PlacementMode::PerNode, PlacementMode::Spread(4)] {
+///     assert_eq!(mode.to_string().parse(), Ok(mode));
+/// }
+/// assert!("spread(0)".parse::<PlacementMode>().is_err());
+/// assert!("per-socket".parse::<PlacementMode>().is_err());
+/// ```
+impl Display for PlacementMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementMode::PerCore => write!(f, "per-core"),
+            PlacementMode::PerNode => write!(f, "per-node"),
+            PlacementMode::Spread(ranks) => write!(f, "spread({})", ranks),
+        }
+    }
+}
+
+impl FromStr for PlacementMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, String> {
+        match mode {
+            "per-core" => Ok(PlacementMode::PerCore),
+            "per-node" => Ok(PlacementMode::PerNode),
+            _ => mode
+                .strip_prefix("spread(")
+                .and_then(|ranks| ranks.strip_suffix(')'))
+                .and_then(|ranks| ranks.parse().ok())
+                .filter(|ranks| *ranks > 0)
+                .map(PlacementMode::Spread)
+                .ok_or_else(|| format!("unknown placement mode `{}`", mode)),
+        }
+    }
+}
+
+/// The number of ranks on each host, in the order of the ranks, or ``None`` if the ranks of a
+/// host are not consecutive.
+fn consecutive_hosts(placement: &Placement) -> Option<Vec<usize>> {
+    let mut hosts: Vec<(&str, usize)> = Vec::new();
+    for rank in &placement.ranks {
+        if let Some((host, count)) = hosts.last_mut() {
+            if *host == rank.host {
+                *count += 1;
+                continue;
+            }
+        }
+        if hosts.iter().any(|(host, _)| *host == rank.host) {
+            return None;
+        }
+        hosts.push((rank.host.as_str(), 1));
+    }
+    Some(hosts.into_iter().map(|(_, count)| count).collect())
+}
+
+/// The info keys of ``MPI_Comm_spawn`` for children placed as ``mode`` says on ``hosts``, ordered
+/// by rank, see [`hostfile`](crate::hostfile).
+pub(crate) fn spawn_info(
+    mode: Option<PlacementMode>,
+    hosts: Option<&[String]>,
+) -> Vec<(&'static str, String)> {
+    let mut info = Vec::new();
+    if let Some(mode) = mode {
+        info.push((MAP_BY, mode.map_by()));
+    }
+    if let Some(hosts) = hosts {
+        info.push((
+            crate::hostfile::HOST_KEY,
+            crate::hostfile::host_value(hosts),
+        ));
+    }
+    info
+}
+
+/// Spawn the children like ``MPI_Comm_spawn_multiple``, with the info keys of each command, see
+/// [`spawn_info`]. Collective over ``world``, with rank 0 as the root. Returns the error code of
+/// MPI if it refused to spawn the children.
+pub(crate) fn spawn(
+    world: &dyn Communicator,
+    commands: &[Command],
+    world_sizes: &[Rank],
+    infos: &[Vec<(&'static str, String)>],
+) -> Result<InterCommunicator, c_int> {
+    let c_string = |value: &std::ffi::OsStr| {
+        CString::new(value.as_bytes()).expect("the arguments of a child contain a NUL byte")
+    };
+    let programs: Vec<CString> = commands
+        .iter()
+        .map(|command| c_string(command.get_program()))
+        .collect();
+    let arguments: Vec<Vec<CString>> = commands
+        .iter()
+        .map(|command| command.get_args().map(c_string).collect())
+        .collect();
+    let mut program_pointers: Vec<*mut c_char> = programs
+        .iter()
+        .map(|program| program.as_ptr() as *mut c_char)
+        .collect();
+    // the arguments of each command are terminated by a null pointer
+    let mut argument_pointers: Vec<Vec<*mut c_char>> = arguments
+        .iter()
+        .map(|arguments| {
+            arguments
+                .iter()
+                .map(|argument| argument.as_ptr() as *mut c_char)
+                .chain([std::ptr::null_mut()])
+                .collect()
+        })
+        .collect();
+    let mut argvs: Vec<*mut *mut c_char> = argument_pointers
+        .iter_mut()
+        .map(|argv| argv.as_mut_ptr())
+        .collect();
+
+    let keys: Vec<Vec<(CString, CString)>> = infos
+        .iter()
+        .map(|info| {
+            info.iter()
+                .map(|(key, value)| {
+                    (
+                        CString::new(*key).unwrap(),
+                        CString::new(value.as_str()).expect("an info value contains a NUL byte"),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    let mut raw = std::mem::MaybeUninit::uninit();
+    let mut error_codes = vec![0; world_sizes.iter().map(|size| *size as usize).sum()];
+    // SAFETY: the strings and arrays outlive the call, and MPI copies the infos
+    unsafe {
+        let mut infos: Vec<ffi::MPI_Info> = keys
+            .iter()
+            .map(|keys| {
+                let mut info = std::mem::MaybeUninit::uninit();
+                ffi::MPI_Info_create(info.as_mut_ptr());
+                let info = info.assume_init();
+                for (key, value) in keys {
+                    ffi::MPI_Info_set(info, key.as_ptr(), value.as_ptr());
+                }
+                info
+            })
+            .collect();
+        let code = ffi::MPI_Comm_spawn_multiple(
+            commands.len() as c_int,
+            program_pointers.as_mut_ptr(),
+            argvs.as_mut_ptr(),
+            world_sizes.as_ptr(),
+            infos.as_ptr(),
+            0,
+            world.as_raw(),
+            raw.as_mut_ptr(),
+            error_codes.as_mut_ptr(),
+        );
+        for info in &mut infos {
+            ffi::MPI_Info_free(info);
+        }
+        match code {
+            0 => Ok(InterCommunicator::from_raw(raw.assume_init())),
+            code => Err(code),
+        }
+    }
+}
+
+/// The CPUs this process may run on, from the ``Cpus_allowed_list`` of ``/proc/self/status``.
+pub(crate) fn cpus() -> Option<String> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("Cpus_allowed_list:"))?;
+    Some(line["Cpus_allowed_list:".len()..].trim().to_string())
+}
+
+/// The CPUs of a list like ``0-3,8``, as in ``Cpus_allowed_list``, or ``None`` if it is malformed.
+fn parse_cpu_list(list: &str) -> Option<Vec<u32>> {
+    let mut cpus = Vec::new();
+    for range in list
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+    {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<u32>().ok()?..=last.parse().ok()?),
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// Write a string into a fixed-size field, truncated at a character boundary.
+fn write_field(field: &mut [u8], value: &str) {
+    let mut len = value.len().min(field.len());
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Read a string from a fixed-size field padded with zeros.
+fn read_field(field: &[u8]) -> String {
+    let len = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+/// Send the host and the CPUs of this child to the parent. Called by the children in the handshake.
+pub fn send(comm: &dyn Communicator) {
+    let host = crate::mpi::environment::processor_name().unwrap_or_else(|_| "unknown".to_string());
+    let mut record = [0u8; 2 * FIELD_LEN];
+    write_field(&mut record[..FIELD_LEN], &host);
+    write_field(&mut record[FIELD_LEN..], &cpus().unwrap_or_default());
+    comm.process_at_rank(0).gather_into(&record[..]);
+}
+
+/// Receive the hosts and the CPUs of the children. Called by the parent in the handshake.
+pub fn receive(comm: &dyn Communicator) -> Placement {
+    let ranks = usize::try_from(comm.size()).expect("the size of a communicator is positive");
+    let mut records = vec![0u8; ranks * 2 * FIELD_LEN];
+    comm.process_at_rank(0)
+        .gather_into_root(&[0u8; 2 * FIELD_LEN][..], &mut records[..]);
+
+    // the first record is the dummy record of the parent
+    let ranks = records
+        .chunks(2 * FIELD_LEN)
+        .skip(1)
+        .map(|record| {
+            let cpus = read_field(&record[FIELD_LEN..]);
+            RankPlacement {
+                host: read_field(&record[..FIELD_LEN]),
+                cpus: (!cpus.is_empty()).then_some(cpus),
+            }
+        })
+        .collect();
+    Placement { ranks }
+}
+
+/// The rank of this process among the processes of ``world`` on the same node, and how many of
+/// them there are, e.g. to pick one of the GPUs of the node. Collective over ``world``, since it
+/// splits it into shared-memory communicators.
+pub fn local_rank(world: &dyn Communicator) -> (Rank, Rank) {
+    let node = world.split_shared(world.rank());
+    (node.rank(), node.size())
+}
+
+/// Print the summary of a placement, unless the same hosts were seen before in this run.
+pub fn note(kernel: &str, placement: &Placement) {
+    static SEEN: Mutex<Vec<Vec<String>>> = Mutex::new(Vec::new());
+
+    let hosts: Vec<String> = placement
+        .ranks
+        .iter()
+        .map(|rank| rank.host.clone())
+        .collect();
+    let mut seen = SEEN.lock().unwrap();
+    if !seen.contains(&hosts) {
+        eprintln!("mpirion: kernel `{}`: {}", kernel, placement.summary());
+        seen.push(hosts);
+    }
+}
@@ -0,0 +1,37 @@
+//! Everything a benchmark needs in a single import: the macros, the types of criterion that
+//! benchmark functions use, the [`MpirionContext`](crate::MpirionContext), and the communicator
+//! and collective traits of mpi.
+//!
+//! # Example
+//! ```rust
+//! use mpirion::prelude::*;
+//!
+//! fn simple_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+//!     ctx.bench_function(c, "prefix-sum", "simple_kernel", |b| {
+//!         mpirion_bench!(simple_kernel, b, ctx)
+//!     });
+//! }
+//!
+//! fn setup(comm: &dyn Communicator) -> u64 {
+//!     comm.rank() as u64
+//! }
+//!
+//! fn simple_kernel(comm: &dyn Communicator, data: &u64) {
+//!     let mut recv_buffer = 0u64;
+//!     comm.scan_into(data, &mut recv_buffer, SystemOperation::sum());
+//! }
+//!
+//! mpirion_kernel!(kernel = simple_kernel, setup = setup, data = ref);
+//! mpirion_group!(benches, simple_benchmark);
+//! mpirion_main!(benches, simple_kernel);
+//! ```
+
+pub use crate::criterion::{BenchmarkId, Criterion, Throughput};
+pub use crate::mpi::collective::SystemOperation;
+pub use crate::mpi::traits::*;
+
+pub use crate::{assert_agreement, MpirionContext, MpirionGroup};
+pub use crate::{export_kernels, import_kernels};
+pub use crate::{
+    mpirion_bench, mpirion_group, mpirion_kernel, mpirion_main, mpirion_test, mpirion_test_main,
+};
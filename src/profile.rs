@@ -0,0 +1,221 @@
+//! Flamegraphs of a kernel, captured on one of its children with ``--mpirion-profile <kernel>``,
+//! enabled by the ``profile`` feature. Profiling a spawned child by hand means attaching ``perf``
+//! to a process that lives for a fraction of a second, so mpirion lets the child profile itself.
+//!
+//! Before the first sample of each benchmark of the kernel, the parent runs a profiling sample that
+//! lasts [`DURATION`], so there are enough stacks to sample. In it, the child with the rank set
+//! with ``--mpirion-profile-rank``, rank 0 by default, samples its own stacks with ``pprof`` while
+//! it runs the timed loop. The other children run the kernel as usual, so the communication pattern
+//! stays realistic. The sample is discarded like a priming sample.
+//!
+//! The child writes the flamegraph into ``mpirion-profiles`` in the output directory of criterion.
+//! When the group of the benchmark completes, the
+//! [`JsonFileReporter`](crate::reporter::JsonFileReporter) moves it into the directory of the
+//! benchmark as [`FLAMEGRAPH_SVG`].
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+use crate::report::{ArgumentValue, Metrics};
+use crate::transfer::broadcast_vec;
+
+/// How long the profiling sample runs.
+pub const DURATION: Duration = Duration::from_secs(10);
+
+/// The iterations requested for the profiling sample. The children stop at the end of
+/// [`DURATION`] long before they run them all.
+pub const ITERATIONS: u64 = 1 << 40;
+
+/// How often the profiled child samples its stacks per second. It is prime, so the samples don't
+/// line up with periodic work in the kernel.
+pub const FREQUENCY: i32 = 997;
+
+/// The directory in the output directory of criterion the children write the flamegraphs into.
+pub const DIRECTORY: &str = "mpirion-profiles";
+
+/// The file name of the flamegraph in the directory of a benchmark.
+pub const FLAMEGRAPH_SVG: &str = "flamegraph.svg";
+
+/// The benchmarks that were profiled in this run, and the flamegraphs that were not moved into
+/// the directory of their benchmark yet.
+static PROFILES: Mutex<Vec<Profile>> = Mutex::new(Vec::new());
+
+/// A benchmark that was profiled.
+struct Profile {
+    kernel: String,
+    world_size: usize,
+    argument: Option<ArgumentValue>,
+    /// the flamegraph, until it was moved into the directory of the benchmark
+    path: Option<PathBuf>,
+}
+
+/// The profile of a sample: the child that profiles itself, and where it writes the flamegraph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// the rank of the child in the child world
+    pub rank: Rank,
+    /// the file the child writes the flamegraph into
+    pub path: PathBuf,
+}
+
+/// The plan of the profiling sample of a benchmark, or ``None`` if the kernel was not selected
+/// with ``--mpirion-profile``, or the benchmark with the given world size and argument was already
+/// profiled in this run. Warns if the selected rank is not in the child world.
+pub(crate) fn plan(
+    kernel: &str,
+    world_size: Rank,
+    argument: Option<ArgumentValue>,
+) -> Option<Plan> {
+    let options = crate::cli::options();
+    if options.profile.as_deref() != Some(kernel) {
+        return None;
+    }
+    let world_size = usize::try_from(world_size).expect("the world size was validated");
+    let mut profiles = PROFILES.lock().unwrap();
+    if profiles.iter().any(|profile| {
+        profile.kernel == kernel && profile.world_size == world_size && profile.argument == argument
+    }) {
+        return None;
+    }
+
+    let rank = options.profile_rank.unwrap_or(0);
+    let path = crate::report::criterion_home()
+        .join(DIRECTORY)
+        .join(format!(
+            "{}-p{}-rank{}-{}.svg",
+            kernel,
+            world_size,
+            rank,
+            profiles.len()
+        ));
+    let in_world = usize::try_from(rank).is_ok_and(|rank| rank < world_size);
+    profiles.push(Profile {
+        kernel: kernel.to_string(),
+        world_size,
+        argument,
+        path: None,
+    });
+    if !in_world {
+        eprintln!(
+            "mpirion: kernel `{}` is not profiled on {} children, --mpirion-profile-rank {} is not one of them",
+            kernel, world_size, rank
+        );
+        return None;
+    }
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    profiles.last_mut().unwrap().path = Some(path.clone());
+    Some(Plan { rank, path })
+}
+
+/// Move the flamegraph of the benchmark with the given metrics into its directory, if it was
+/// profiled in this run.
+pub(crate) fn claim(metrics: &Metrics, directory: &Path) {
+    let mut profiles = PROFILES.lock().unwrap();
+    let Some(path) = profiles
+        .iter_mut()
+        .find(|profile| {
+            profile.kernel == metrics.kernel
+                && profile.world_size == metrics.world_size
+                && profile.argument == metrics.argument
+        })
+        .and_then(|profile| profile.path.take())
+    else {
+        return;
+    };
+
+    let target = directory.join(FLAMEGRAPH_SVG);
+    match std::fs::rename(&path, &target) {
+        Ok(()) => eprintln!(
+            "mpirion: flamegraph of `{}` written to {}",
+            metrics.id,
+            target.display()
+        ),
+        Err(error) => eprintln!(
+            "mpirion: could not move the flamegraph of `{}` from {} to {}: {}",
+            metrics.id,
+            path.display(),
+            target.display(),
+            error
+        ),
+    }
+}
+
+/// Send the plan of the sample to the children, or that no child profiles itself.
+pub fn send(comm: &dyn Communicator, plan: Option<&Plan>) {
+    let mut rank = plan.map_or(-1, |plan| plan.rank);
+    comm.this_process().broadcast_into(&mut rank);
+    let mut path = plan.map_or_else(Vec::new, |plan| {
+        plan.path.to_string_lossy().into_owned().into_bytes()
+    });
+    broadcast_vec(comm, 0, &mut path);
+}
+
+/// Receive the plan of the sample on a child with the given rank in the child world, and return
+/// the file to write the flamegraph into if this child profiles itself.
+pub fn receive(comm: &dyn Communicator, rank: Rank) -> Option<PathBuf> {
+    let mut profiled = -1;
+    comm.process_at_rank(0).broadcast_into(&mut profiled);
+    let mut path = Vec::new();
+    broadcast_vec(comm, 0, &mut path);
+    (profiled == rank).then(|| PathBuf::from(String::from_utf8_lossy(&path).into_owned()))
+}
+
+/// Samples the stacks of this process until it is [finished](Self::finish). Without the ``profile``
+/// feature, or if this child doesn't profile itself, it does nothing.
+#[must_use = "the flamegraph is only written by finish"]
+pub struct Profiler {
+    #[cfg(feature = "profile")]
+    running: Option<(pprof::ProfilerGuard<'static>, PathBuf)>,
+}
+
+impl Profiler {
+    /// Start sampling the stacks of this process, if ``path`` is set.
+    pub fn start(path: Option<PathBuf>) -> Self {
+        #[cfg(not(feature = "profile"))]
+        let _ = path;
+
+        Self {
+            #[cfg(feature = "profile")]
+            running: path.and_then(|path| {
+                let guard = pprof::ProfilerGuardBuilder::default()
+                    .frequency(FREQUENCY)
+                    .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+                    .build();
+                match guard {
+                    Ok(guard) => Some((guard, path)),
+                    Err(error) => {
+                        eprintln!("mpirion: could not start the profiler: {}", error);
+                        None
+                    }
+                }
+            }),
+        }
+    }
+
+    /// Stop sampling and write the flamegraph.
+    pub fn finish(self) {
+        #[cfg(feature = "profile")]
+        if let Some((guard, path)) = self.running {
+            let written = guard
+                .report()
+                .build()
+                .map_err(|error| error.to_string())
+                .and_then(|report| {
+                    let file = std::fs::File::create(&path).map_err(|error| error.to_string())?;
+                    report.flamegraph(file).map_err(|error| error.to_string())
+                });
+            if let Err(error) = written {
+                eprintln!(
+                    "mpirion: could not write the flamegraph to {}: {}",
+                    path.display(),
+                    error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,1324 @@
+//! Implementation details of the communication protocol between the benchmark parent process and
+//! the spawned child processes. The functions in this module are called by the code generated by
+//! the ``mpirion_*`` macros and are not meant to be called directly.
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::topology::{MergeOrder, SimpleCommunicator};
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+use std::any::Any;
+use std::borrow::{Borrow, BorrowMut};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::errhandler;
+use crate::fingerprint::HostFingerprint;
+use crate::placement::{self, Placement};
+use crate::report::ArgumentValue;
+use crate::timing;
+use crate::trace;
+use crate::transfer::broadcast_vec;
+use crate::Error;
+
+/// A runtime description of the argument type that is passed from the parent to the children.
+/// It is exchanged before the argument itself, so the children can detect if the parent sends a
+/// different type than the kernel was declared with, which would otherwise silently transfer the
+/// wrong number of bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeTag {
+    size: u64,
+    hash: u64,
+    name: &'static str,
+}
+
+impl TypeTag {
+    /// Create the type tag of the type ``T``.
+    pub fn of<T>() -> Self {
+        let name = std::any::type_name::<T>();
+        Self {
+            size: std::mem::size_of::<T>() as u64,
+            hash: fnv1a(name.as_bytes()),
+            name,
+        }
+    }
+
+    /// The name of the type, as given by ``std::any::type_name``.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The size of the type in bytes, which is the number of bytes broadcast for it.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The hash of the type name.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Create the type tag that is used if no argument is passed to the children.
+    pub fn none() -> Self {
+        Self {
+            size: 0,
+            hash: fnv1a(b""),
+            name: "no argument",
+        }
+    }
+
+    /// Create the type tag that is used if the argument is passed as text, which the children
+    /// parse into the argument type of the kernel. See [`Argument::from_text`].
+    pub fn text() -> Self {
+        Self {
+            size: 0,
+            hash: fnv1a(b"mpirion text argument"),
+            name: "text",
+        }
+    }
+}
+
+/// FNV-1a hash of the given bytes. Used instead of the standard library hasher, because its
+/// output is stable across binaries and compiler versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Broadcast the type tag from the parent (rank 0 of the merged communicator) to the children.
+fn send_type_tag(comm: &dyn Communicator, tag: TypeTag) {
+    let mut header = [tag.size, tag.hash];
+    comm.process_at_rank(0).broadcast_into(&mut header[..]);
+    let mut name = tag.name.as_bytes().to_vec();
+    broadcast_vec(comm, 0, &mut name);
+}
+
+/// Receive the type tag of the parent and abort the child world if it does not match the tag the
+/// kernel was compiled with. Returns true if the parent sends the argument as text instead, which
+/// is only accepted if the kernel expects an argument.
+fn verify_type_tag(comm: &dyn Communicator, expected: TypeTag) -> bool {
+    let mut header = [0u64; 2];
+    comm.process_at_rank(0).broadcast_into(&mut header[..]);
+    let mut name = Vec::new();
+    broadcast_vec(comm, 0, &mut name);
+
+    match check_type_tag(header, &String::from_utf8_lossy(&name), expected) {
+        Ok(text) => text,
+        Err(message) => {
+            eprintln!("{}", message);
+            comm.abort(1)
+        }
+    }
+}
+
+/// Compare the type tag header ``[size, hash]`` and the type name received from the parent with
+/// the tag the kernel expects. Returns whether the parent sends the argument as text, or an error
+/// if the types differ. If the type names match but the sizes don't, the type has a different
+/// layout in the parent and the child, e.g. because they were built with different versions or
+/// features of the crate that defines it, and the broadcast would transfer the wrong number of
+/// bytes.
+///
+/// # Example
+/// ```rust
+/// use mpirion::protocol::{check_type_tag, TypeTag};
+///
+/// let expected = TypeTag::of::<u64>();
+/// assert_eq!(check_type_tag([expected.size(), expected.hash()], expected.name(), expected), Ok(false));
+///
+/// // a forged handshake of the same type with another layout
+/// let error = check_type_tag([4, expected.hash()], expected.name(), expected).unwrap_err();
+/// assert!(error.contains("layout mismatch"), "{}", error);
+/// assert!(error.contains("4 bytes") && error.contains("8 bytes"), "{}", error);
+///
+/// // another type
+/// let other = TypeTag::of::<u32>();
+/// let error = check_type_tag([other.size(), other.hash()], other.name(), expected).unwrap_err();
+/// assert!(error.contains("argument type mismatch"), "{}", error);
+///
+/// // text is accepted by kernels that take an argument
+/// let text = TypeTag::text();
+/// assert_eq!(check_type_tag([text.size(), text.hash()], text.name(), expected), Ok(true));
+/// ```
+pub fn check_type_tag(header: [u64; 2], name: &str, expected: TypeTag) -> Result<bool, String> {
+    let [size, hash] = header;
+    let text = TypeTag::text();
+    if size == text.size && hash == text.hash && expected != TypeTag::none() {
+        return Ok(true);
+    }
+
+    if hash == expected.hash && size != expected.size {
+        return Err(format!(
+            "argument layout mismatch: `{}` is {} bytes in the benchmark, but {} bytes in the kernel. \
+             Were the parent and the children built with different versions or features?",
+            expected.name, size, expected.size
+        ));
+    }
+
+    if size != expected.size || hash != expected.hash {
+        return Err(type_mismatch(name, size, expected));
+    }
+    Ok(false)
+}
+
+fn type_mismatch(actual_name: &str, actual_size: u64, expected: TypeTag) -> String {
+    format!(
+        "argument type mismatch: the benchmark passes `{}` ({} bytes), but the kernel expects `{}` ({} bytes)",
+        actual_name, actual_size, expected.name, expected.size
+    )
+}
+
+/// The argument that is passed from the parent to all child processes. It is implemented for
+/// ``()`` if no argument is passed, and for a 1-tuple ``(T,)`` containing the argument otherwise.
+/// In both cases a type tag is exchanged first, so a missing or superfluous argument is detected
+/// as well as a wrong argument type.
+pub trait Argument: Sized {
+    /// The type tag that is exchanged before the argument.
+    fn tag() -> TypeTag;
+
+    /// Send the argument from the parent to the children.
+    fn send(&mut self, comm: &dyn Communicator);
+
+    /// Receive the argument from the parent.
+    fn receive(comm: &dyn Communicator) -> Self;
+
+    /// Parse the argument from text, which the parent sends instead of the argument if it does
+    /// not know the argument type, e.g. for ``--mpirion-run``.
+    fn from_text(text: &str) -> Result<Self, String>;
+
+    /// Describe the argument for the reports, or ``None`` if there is no argument.
+    fn describe(&self) -> Option<ArgumentValue>;
+}
+
+impl Argument for () {
+    fn tag() -> TypeTag {
+        TypeTag::none()
+    }
+
+    fn send(&mut self, comm: &dyn Communicator) {
+        send_type_tag(comm, <Self as Argument>::tag());
+    }
+
+    fn receive(comm: &dyn Communicator) -> Self {
+        verify_type_tag(comm, <Self as Argument>::tag());
+    }
+
+    fn from_text(text: &str) -> Result<Self, String> {
+        Err(format!(
+            "the kernel takes no argument, but `{}` was passed",
+            text
+        ))
+    }
+
+    fn describe(&self) -> Option<ArgumentValue> {
+        None
+    }
+}
+
+impl<T: Equivalence> Argument for (T,) {
+    fn tag() -> TypeTag {
+        TypeTag::of::<T>()
+    }
+
+    fn send(&mut self, comm: &dyn Communicator) {
+        send_type_tag(comm, Self::tag());
+        comm.process_at_rank(0).broadcast_into(&mut self.0);
+    }
+
+    fn receive(comm: &dyn Communicator) -> Self {
+        if verify_type_tag(comm, Self::tag()) {
+            let mut text = Vec::new();
+            broadcast_vec(comm, 0, &mut text);
+            return match Self::from_text(&String::from_utf8_lossy(&text)) {
+                Ok(input) => input,
+                Err(message) => {
+                    eprintln!("{}", message);
+                    comm.abort(1)
+                }
+            };
+        }
+
+        // not every type is valid when zeroed, so the value only exists once MPI wrote it
+        let input = errhandler::or_abort(comm, errhandler::receive_broadcast(comm, 0));
+        (input,)
+    }
+
+    fn from_text(text: &str) -> Result<Self, String> {
+        parse_primitive(text).map(|input| (input,))
+    }
+
+    fn describe(&self) -> Option<ArgumentValue> {
+        Some(ArgumentValue::of(&self.0))
+    }
+}
+
+/// Parse a primitive type from text. The type is identified by its name, because the argument
+/// types are only bound by ``Equivalence``, so there is no ``FromStr`` implementation to use.
+fn parse_primitive<T>(text: &str) -> Result<T, String> {
+    macro_rules! parse_as {
+        ($($primitive:ty),*) => {
+            $(
+            if std::any::type_name::<T>() == std::any::type_name::<$primitive>() {
+                let value: $primitive = text
+                    .trim()
+                    .parse()
+                    .map_err(|e| format!("cannot parse `{}` as {}: {}", text, stringify!($primitive), e))?;
+                // SAFETY: T and $primitive are the same type
+                return Ok(unsafe { std::mem::transmute_copy(&value) });
+            }
+            )*
+        };
+    }
+
+    parse_as!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool);
+    Err(format!(
+        "arguments of type `{}` cannot be passed as text, only primitive types can",
+        std::any::type_name::<T>()
+    ))
+}
+
+/// An argument that is passed as text, and parsed by the children. See [`Argument::from_text`].
+#[derive(Debug, Clone)]
+pub struct TextArgument(pub String);
+
+/// An [`Argument`] with its type erased, so the parent can store arguments of any type.
+pub trait DynArgument {
+    /// The type tag of the argument.
+    fn tag(&self) -> TypeTag;
+
+    /// Send a copy of the argument from the parent to the children.
+    fn send(&self, comm: &dyn Communicator);
+
+    /// A copy of the argument, for a kernel that runs in the parent process.
+    fn boxed(&self) -> Box<dyn Any>;
+
+    /// Describe the argument for the reports, or ``None`` if there is no argument.
+    fn describe(&self) -> Option<ArgumentValue>;
+}
+
+impl DynArgument for TextArgument {
+    fn tag(&self) -> TypeTag {
+        TypeTag::text()
+    }
+
+    fn send(&self, comm: &dyn Communicator) {
+        send_type_tag(comm, TypeTag::text());
+        let mut text = self.0.clone().into_bytes();
+        broadcast_vec(comm, 0, &mut text);
+    }
+
+    fn boxed(&self) -> Box<dyn Any> {
+        Box::new(self.clone())
+    }
+
+    fn describe(&self) -> Option<ArgumentValue> {
+        Some(ArgumentValue::text(&self.0))
+    }
+}
+
+impl<A: Argument + Clone + 'static> DynArgument for A {
+    fn tag(&self) -> TypeTag {
+        A::tag()
+    }
+
+    fn send(&self, comm: &dyn Communicator) {
+        Argument::send(&mut self.clone(), comm);
+    }
+
+    fn boxed(&self) -> Box<dyn Any> {
+        Box::new(self.clone())
+    }
+
+    fn describe(&self) -> Option<ArgumentValue> {
+        Argument::describe(self)
+    }
+}
+
+/// A setup function. It is implemented for all functions that take a ``&dyn Communicator`` and
+/// optionally an argument, or a constant argument and an argument. The type parameters ``C`` and
+/// ``A`` are the constant argument and the argument wrapped like in [`Argument`], so they can be
+/// inferred from the signature of the setup function. The data may borrow from the communicator
+/// for the lifetime ``'w``, like an RMA window created by an init function.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid setup function",
+    label = "expected `fn(&dyn Communicator) -> T`, `fn(&dyn Communicator, A) -> T` or `fn(&dyn Communicator, C, A) -> T`",
+    note = "the first parameter of a setup function must be `&dyn Communicator`, not a concrete communicator type"
+)]
+pub trait Setup<'w, C, A> {
+    /// The data returned by the setup function and passed to the kernel.
+    type Data;
+
+    /// Call the setup function with the received arguments.
+    fn call(&self, world: &'w dyn Communicator, const_arg: &C, arg: &A) -> Self::Data;
+}
+
+impl<'w, F, T> Setup<'w, (), ()> for F
+where
+    F: Fn(&'w dyn Communicator) -> T,
+{
+    type Data = T;
+
+    fn call(&self, world: &'w dyn Communicator, _: &(), _: &()) -> T {
+        self(world)
+    }
+}
+
+impl<'w, F, A: Clone, T> Setup<'w, (), (A,)> for F
+where
+    F: Fn(&'w dyn Communicator, A) -> T,
+{
+    type Data = T;
+
+    fn call(&self, world: &'w dyn Communicator, _: &(), arg: &(A,)) -> T {
+        self(world, arg.0.clone())
+    }
+}
+
+impl<'w, F, C: Clone, A: Clone, T> Setup<'w, (C,), (A,)> for F
+where
+    F: Fn(&'w dyn Communicator, C, A) -> T,
+{
+    type Data = T;
+
+    fn call(&self, world: &'w dyn Communicator, const_arg: &(C,), arg: &(A,)) -> T {
+        self(world, const_arg.0.clone(), arg.0.clone())
+    }
+}
+
+/// Merge the world of a spawned child with its parent into an intra-communicator in which the
+/// parent is rank 0. Starts the watchdog that exits the child if the parent is gone, see the
+/// [`watchdog`](crate::watchdog) module.
+pub fn merge_with_parent(world: &SimpleCommunicator) -> SimpleCommunicator {
+    trace::debug!(rank = world.rank(), "merging with parent");
+    let inter_comm = world
+        .parent()
+        .expect("child could not retrieve parent comm");
+    errhandler::or_abort(world, errhandler::install(&inter_comm));
+    let merged_comm = inter_comm.merge(MergeOrder::High);
+    errhandler::or_abort(world, errhandler::install(&merged_comm));
+    trace::debug!(rank = world.rank(), "merged with parent");
+    crate::watchdog::start();
+    merged_comm
+}
+
+/// The version of the protocol between the parent and the children. It is bumped whenever the
+/// messages exchanged after the handshake change.
+pub const PROTOCOL_VERSION: u64 = 17;
+
+/// An identifier of the build of the running binary, exchanged in the handshake, so children
+/// started from a different binary than the parent, e.g. a stale one on a shared filesystem, are
+/// detected. It combines the version of mpirion with the size and the modification time of the
+/// executable, so every rebuild changes it without reading the binary. Copies on other nodes are
+/// accepted if they keep the modification time, as ``rsync -a`` and ``cp -p`` do. It is computed
+/// once per process.
+pub fn build_id() -> u64 {
+    static BUILD_ID: OnceLock<u64> = OnceLock::new();
+    *BUILD_ID.get_or_init(|| {
+        let mut bytes = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+        if let Ok(metadata) = std::env::current_exe().and_then(std::fs::metadata) {
+            bytes.extend(metadata.len().to_le_bytes());
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok());
+            if let Some(modified) = modified {
+                bytes.extend(modified.as_nanos().to_le_bytes());
+            }
+        }
+        fnv1a(&bytes)
+    })
+}
+
+/// What the parent learns about the children in the handshake.
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    /// the number of children that were built without optimizations
+    pub unoptimized: u64,
+    /// the clock the children measure with, see [`clock::select`](crate::clock::select). If the
+    /// children selected different clocks, this is the fallback clock.
+    pub clock: Clock,
+    /// the hosts and CPUs the children run on
+    pub placement: Placement,
+    /// the fingerprints of the hosts of the children, in the order of their ranks
+    pub fingerprints: Vec<HostFingerprint>,
+}
+
+/// Send the protocol version and the build of the parent to the children right after the merge,
+/// and abort if any child runs a different one. Returns what the children reported about
+/// themselves. Finally, the children receive the directory they write their logs to, if any, see
+/// [`logs`](crate::logs), and the path of the file they may open, see [`io`](crate::io). Called by
+/// the parent.
+pub fn send_handshake(
+    comm: &dyn Communicator,
+    log_directory: Option<&Path>,
+    scratch_path: &Path,
+) -> Handshake {
+    let root = comm.process_at_rank(0);
+    let mut handshake = [PROTOCOL_VERSION, build_id()];
+    root.broadcast_into(&mut handshake[..]);
+
+    let mut mismatches = 0u64;
+    root.reduce_into_root(&0u64, &mut mismatches, SystemOperation::sum());
+    trace::debug!(mismatches, "exchanged handshake");
+    if mismatches > 0 {
+        eprintln!(
+            "{} of {} children run a different build of the benchmark than the parent (protocol version {}, build {:016x}), \
+             aborting. Is a stale binary on a shared filesystem, or was the binary rebuilt during the run?",
+            mismatches,
+            comm.size() - 1,
+            PROTOCOL_VERSION,
+            build_id()
+        );
+        comm.abort(1);
+    }
+
+    let mut unoptimized = 0u64;
+    root.reduce_into_root(&0u64, &mut unoptimized, SystemOperation::sum());
+    let mut clock = 0u64;
+    root.reduce_into_root(&0u64, &mut clock, SystemOperation::max());
+    let placement = placement::receive(comm);
+    let fingerprints = crate::fingerprint::receive(comm);
+
+    let mut directory = log_directory.map_or_else(Vec::new, |directory| {
+        directory.to_string_lossy().into_owned().into_bytes()
+    });
+    broadcast_vec(comm, 0, &mut directory);
+    let mut scratch_path = scratch_path.to_string_lossy().into_owned().into_bytes();
+    broadcast_vec(comm, 0, &mut scratch_path);
+    Handshake {
+        unoptimized,
+        clock: Clock::from_id(clock),
+        placement,
+        fingerprints,
+    }
+}
+
+/// Receive the handshake of the parent and compare it to the protocol version and the build of
+/// this child. If they differ, the child reports the mismatch to the parent and aborts. Otherwise
+/// it tells the parent whether it was built without optimizations, which the generated code
+/// decides from ``debug_assertions`` in the crate of the kernel, and which clock passed the self
+/// test of [`clock::select`](crate::clock::select), and where it runs, see
+/// [`placement`](crate::placement), and the fingerprint of its host, see
+/// [`fingerprint`](crate::fingerprint). Then it opens its log file in the directory sent by the
+/// parent and writes the handshake into it, see [`logs`](crate::logs), and remembers the path of
+/// its file, see [`io`](crate::io). Called by the children right after the merge.
+pub fn receive_handshake(comm: &dyn Communicator, unoptimized: bool) {
+    let root = comm.process_at_rank(0);
+    let mut handshake = [0u64; 2];
+    root.broadcast_into(&mut handshake[..]);
+
+    let expected = [PROTOCOL_VERSION, build_id()];
+    let mismatch = handshake != expected;
+    root.reduce_into(&(mismatch as u64), SystemOperation::sum());
+    trace::debug!(mismatch, "exchanged handshake");
+    if mismatch {
+        eprintln!(
+            "child rank {} runs protocol version {} and build {:016x}, but the parent runs protocol version {} and build {:016x}",
+            comm.rank() - 1,
+            expected[0],
+            expected[1],
+            handshake[0],
+            handshake[1]
+        );
+        comm.abort(1);
+    }
+
+    root.reduce_into(&(unoptimized as u64), SystemOperation::sum());
+    let clock = crate::clock::select();
+    root.reduce_into(&clock.id(), SystemOperation::max());
+    placement::send(comm);
+    crate::fingerprint::send(comm);
+
+    let mut directory = Vec::new();
+    broadcast_vec(comm, 0, &mut directory);
+    if !directory.is_empty() {
+        crate::logs::open(
+            Path::new(&*String::from_utf8_lossy(&directory)),
+            comm.rank() - 1,
+        );
+        crate::logs::write(format_args!(
+            "--- {}: rank {} of {}, pid {}, host {}, CPUs {}, protocol version {}, build {:016x}, clock {}{} ---",
+            crate::logs::run_name(std::time::SystemTime::now()),
+            comm.rank() - 1,
+            comm.size() - 1,
+            std::process::id(),
+            crate::mpi::environment::processor_name().unwrap_or_else(|_| "unknown".to_string()),
+            placement::cpus().as_deref().unwrap_or("unknown"),
+            PROTOCOL_VERSION,
+            build_id(),
+            clock,
+            if unoptimized { ", unoptimized" } else { "" }
+        ));
+    }
+
+    let mut scratch_path = Vec::new();
+    broadcast_vec(comm, 0, &mut scratch_path);
+    crate::io::set_scratch_path(PathBuf::from(&*String::from_utf8_lossy(&scratch_path)));
+}
+
+/// Send the name of the kernel to the children after the handshake, and wait for them to confirm
+/// that they run it. If they don't know the kernel, returns an error with the kernels the children
+/// know, and the children exit. Called by the parent.
+pub fn send_kernel_name(comm: &dyn Communicator, kernel: &str) -> Result<(), String> {
+    let mut name = kernel.as_bytes().to_vec();
+    broadcast_vec(comm, 0, &mut name);
+
+    if all_children_found(comm, true) {
+        trace::debug!("children acknowledged the kernel");
+        return Ok(());
+    }
+
+    let mut known = Vec::new();
+    broadcast_vec(comm, 1, &mut known);
+    let known = String::from_utf8_lossy(&known).into_owned();
+    Err(format!(
+        "the children don't know the kernel `{}`, they know {}",
+        kernel,
+        if known.is_empty() {
+            "no kernels"
+        } else {
+            &known
+        }
+    ))
+}
+
+/// Confirm to the parent that this child runs the kernel it asked for. Called by the children
+/// after the handshake with the name of the kernel they run. Returns false if the parent asked for
+/// another kernel, or another child doesn't know the kernel, in which case the parent doesn't run
+/// it and the child must exit.
+pub fn acknowledge_kernel(comm: &dyn Communicator, kernel: &str) -> bool {
+    let mut name: Vec<u8> = Vec::new();
+    broadcast_vec(comm, 0, &mut name);
+    let found = name == kernel.as_bytes();
+    if !found {
+        trace::debug!(kernel, requested = %String::from_utf8_lossy(&name), "rejected another kernel");
+    }
+    if all_children_found(comm, found) {
+        return true;
+    }
+
+    // the children of a spawn all run the same kernel, which is the one they know
+    let mut known = if found {
+        Vec::new()
+    } else {
+        kernel.as_bytes().to_vec()
+    };
+    broadcast_vec(comm, 1, &mut known);
+    false
+}
+
+/// Tell the parent that this child doesn't know the kernel it asked for, and send it the names of
+/// the kernels the child knows. The parent fails the benchmark, and the child should exit.
+/// Called by the children after the handshake.
+pub fn reject_kernel(comm: &dyn Communicator, known: &[&str]) {
+    let mut name: Vec<u8> = Vec::new();
+    broadcast_vec(comm, 0, &mut name);
+    all_children_found(comm, false);
+
+    let mut known = known.join(", ").into_bytes();
+    broadcast_vec(comm, 1, &mut known);
+    trace::debug!(kernel = %String::from_utf8_lossy(&name), "rejected unknown kernel");
+}
+
+/// Whether all children know the kernel. The parent contributes ``true``.
+fn all_children_found(comm: &dyn Communicator, found: bool) -> bool {
+    let mut all_found = 0u64;
+    comm.all_reduce_into(&(found as u64), &mut all_found, SystemOperation::min());
+    all_found == 1
+}
+
+/// The value of the iteration broadcast that tells the children to shut down instead of running a
+/// sample. Zero is a legitimate sample without iterations, which the children run and report as
+/// zero time, so it cannot double as the shutdown signal.
+pub const SHUTDOWN: u64 = u64::MAX;
+
+/// The value the parent broadcasts to request a sample of ``iterations`` iterations. Panics if
+/// ``iterations`` is the reserved [`SHUTDOWN`] value.
+///
+/// # Example
+/// ```rust
+/// use mpirion::protocol::{decode_iterations, encode_iterations, SHUTDOWN};
+///
+/// assert_eq!(decode_iterations(encode_iterations(0)), Some(0));
+/// assert_eq!(decode_iterations(encode_iterations(100)), Some(100));
+/// assert_eq!(decode_iterations(SHUTDOWN), None);
+/// assert!(std::panic::catch_unwind(|| encode_iterations(SHUTDOWN)).is_err());
+/// ```
+pub fn encode_iterations(iterations: u64) -> u64 {
+    assert_ne!(
+        iterations, SHUTDOWN,
+        "{} iterations are reserved to shut the children down",
+        SHUTDOWN
+    );
+    iterations
+}
+
+/// The number of iterations the children should run, or ``None`` if the parent sent
+/// [`SHUTDOWN`].
+pub fn decode_iterations(value: u64) -> Option<u64> {
+    (value != SHUTDOWN).then_some(value)
+}
+
+/// Broadcast the number of iterations of the next sample to the children.
+pub fn send_iterations(comm: &dyn Communicator, iterations: u64) -> Result<(), Error> {
+    let mut value = encode_iterations(iterations);
+    errhandler::broadcast(comm, comm.rank(), &mut value)?;
+    trace::debug!(iterations, "broadcast iterations");
+    Ok(())
+}
+
+/// Tell the children to shut down instead of running another sample. They exit without sending
+/// anything back.
+pub fn send_shutdown(comm: &dyn Communicator) {
+    let mut value = SHUTDOWN;
+    comm.this_process().broadcast_into(&mut value);
+    trace::debug!("broadcast shutdown");
+}
+
+/// The setup function of kernels that do not need any data.
+pub fn no_setup(_world: &dyn Communicator) {}
+
+/// Check that an argument type declared with ``arg = T`` matches the setup function. If it
+/// doesn't, the compiler reports the mismatch on this function.
+pub fn assert_argument_type<T>(_arg: &(T,)) {}
+
+/// Check that a constant argument type declared with ``const_arg = T`` matches the setup
+/// function. If it doesn't, the compiler reports the mismatch on this function.
+pub fn assert_const_argument_type<T>(_const_arg: &(T,)) {}
+
+// The kernel is called through the following functions, so that a kernel whose signature doesn't
+// match the communicator, the data returned by the setup function, or the context fails on a bound
+// of one of them, instead of somewhere in the expansion of ``mpirion_kernel!``.
+
+/// Call a kernel that takes no data.
+pub fn call_kernel<K, R>(kernel: K, world: &dyn Communicator) -> R
+where
+    K: FnOnce(&dyn Communicator) -> R,
+{
+    kernel(world)
+}
+
+/// Call a kernel that takes no data, but a context.
+pub fn call_kernel_with_context<K, X, R>(kernel: K, world: &dyn Communicator, context: &X) -> R
+where
+    K: FnOnce(&dyn Communicator, &X) -> R,
+{
+    kernel(world, context)
+}
+
+/// Call a kernel that takes the data returned by the setup function as ``&mut D``, or as a mutable
+/// reference to what ``D`` can be borrowed as, like a slice of a vector.
+pub fn call_kernel_with_mut_data<K, D, T, R>(kernel: K, world: &dyn Communicator, data: &mut D) -> R
+where
+    D: BorrowMut<T>,
+    T: ?Sized,
+    K: FnOnce(&dyn Communicator, &mut T) -> R,
+{
+    kernel(world, data.borrow_mut())
+}
+
+/// Call a kernel that takes the data returned by the setup function as ``&D``, or as a reference to
+/// what ``D`` can be borrowed as.
+pub fn call_kernel_with_ref_data<K, D, T, R>(kernel: K, world: &dyn Communicator, data: &D) -> R
+where
+    D: Borrow<T>,
+    T: ?Sized,
+    K: FnOnce(&dyn Communicator, &T) -> R,
+{
+    kernel(world, data.borrow())
+}
+
+/// Call a kernel that takes the data returned by the setup function as ``&mut D`` and a context.
+pub fn call_kernel_with_mut_data_and_context<K, D, T, X, R>(
+    kernel: K,
+    world: &dyn Communicator,
+    data: &mut D,
+    context: &X,
+) -> R
+where
+    D: BorrowMut<T>,
+    T: ?Sized,
+    K: FnOnce(&dyn Communicator, &mut T, &X) -> R,
+{
+    kernel(world, data.borrow_mut(), context)
+}
+
+/// Call a kernel that takes the data returned by the setup function as ``&D`` and a context.
+pub fn call_kernel_with_ref_data_and_context<K, D, T, X, R>(
+    kernel: K,
+    world: &dyn Communicator,
+    data: &D,
+    context: &X,
+) -> R
+where
+    D: Borrow<T>,
+    T: ?Sized,
+    K: FnOnce(&dyn Communicator, &T, &X) -> R,
+{
+    kernel(world, data.borrow(), context)
+}
+
+/// Receive the constant argument and the argument on a child. Their types are inferred from the
+/// signature of the setup function.
+pub fn receive_arguments<'w, C, A, S>(comm: &dyn Communicator, _setup: &S) -> (C, A)
+where
+    C: Argument,
+    A: Argument,
+    S: Setup<'w, C, A>,
+{
+    let const_arg = C::receive(comm);
+    let arg = A::receive(comm);
+    (const_arg, arg)
+}
+
+/// The connection of a kernel to its parent. Usually the parent is rank 0 of the merged
+/// communicator, but with the ``local-fallback`` feature the kernel runs in the parent process
+/// itself.
+pub enum Parent<'a> {
+    /// the merged communicator of the parent and the children
+    Remote(&'a dyn Communicator),
+    /// the kernel runs in the parent process
+    Local(&'a mut LocalRun),
+}
+
+impl Parent<'_> {
+    /// Receive the number of iterations to run, or ``None`` if the parent asks the children to
+    /// shut down, see [`SHUTDOWN`].
+    pub fn receive_iterations(&mut self) -> Option<u64> {
+        match self {
+            Parent::Remote(comm) => {
+                let mut value = 0u64;
+                errhandler::or_abort(*comm, errhandler::broadcast(*comm, 0, &mut value));
+                let iterations = decode_iterations(value);
+                trace::debug!(iterations, "received iterations");
+                iterations
+            }
+            Parent::Local(run) => Some(run.iterations),
+        }
+    }
+
+    /// Receive the cap on the wall time of the sample, see [`timing::Deadline`]. Must be called
+    /// right after [`receive_iterations`](Self::receive_iterations).
+    pub fn receive_time_cap(&mut self) -> Option<Duration> {
+        match self {
+            Parent::Remote(comm) => timing::receive_time_cap(*comm),
+            Parent::Local(run) => run.time_cap,
+        }
+    }
+
+    /// Receive the size of the thread pool of the children, see
+    /// [`threading::send_threads`](crate::threading::send_threads). Must be called right after
+    /// [`receive_time_cap`](Self::receive_time_cap). A local run builds no pool, since it runs in
+    /// the parent.
+    pub fn receive_threads(&mut self) -> Option<usize> {
+        match self {
+            Parent::Remote(comm) => crate::threading::receive_threads(*comm),
+            Parent::Local(_) => None,
+        }
+    }
+
+    /// Receive the constant argument and the argument. Their types are inferred from the signature
+    /// of the setup function.
+    pub fn receive_arguments<'w, C, A, S>(&mut self, setup: &S) -> (C, A)
+    where
+        C: Argument + 'static,
+        A: Argument + 'static,
+        S: Setup<'w, C, A>,
+    {
+        match self {
+            Parent::Remote(comm) => receive_arguments(*comm, setup),
+            Parent::Local(run) => (run.take_argument(0), run.take_argument(1)),
+        }
+    }
+
+    /// Verify that the output the parent expects matches the output projection of the kernel.
+    pub fn verify_output_tag(&mut self, tag: TypeTag) {
+        match self {
+            Parent::Remote(comm) => {
+                verify_type_tag(*comm, tag);
+            }
+            Parent::Local(run) => {
+                if run.output_tag != tag {
+                    panic!(
+                        "{}",
+                        type_mismatch(run.output_tag.name, run.output_tag.size, tag)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Receive which child profiles itself in the sample, and return the file it writes the
+    /// flamegraph into if it is this one, see [`profile::receive`](crate::profile::receive). Must
+    /// be called right after [`verify_output_tag`](Self::verify_output_tag). A local run is never
+    /// profiled.
+    pub fn receive_profile(&mut self, world: &dyn Communicator) -> Option<std::path::PathBuf> {
+        match self {
+            Parent::Remote(comm) => crate::profile::receive(*comm, world.rank()),
+            Parent::Local(_) => None,
+        }
+    }
+
+    /// Send the outcome of the samples to the parent, see [`send_outcome`].
+    pub fn send_outcome(&mut self, failed_ranks: u64, error: Option<RankError>, total: Duration) {
+        match self {
+            Parent::Remote(comm) => send_outcome(*comm, failed_ranks, error, total),
+            Parent::Local(run) => {
+                let error_message = error.map(|error| (error.rank, error.message));
+                run.outcome = Some(outcome(failed_ranks, run.ranks, error_message, vec![total]));
+            }
+        }
+    }
+
+    /// Send the drift of the iteration times of this rank to the parent, see
+    /// [`timing::send_drift`]. Must be called after [`send_outcome`](Self::send_outcome).
+    pub fn send_drift(&mut self, drift: Option<f64>) {
+        match self {
+            Parent::Remote(comm) => timing::send_drift(*comm, drift),
+            Parent::Local(run) => run.drift = drift,
+        }
+    }
+
+    /// Send the number of iterations the children ran to the parent, which is less than requested
+    /// if the sample hit its time cap, see [`timing::send_iterations_run`]. Must be called after
+    /// [`send_drift`](Self::send_drift).
+    pub fn send_iterations_run(&mut self, iterations: u64) {
+        match self {
+            Parent::Remote(comm) => timing::send_iterations_run(*comm, iterations),
+            Parent::Local(run) => run.iterations_run = Some(iterations),
+        }
+    }
+
+    /// Send whether MPI permuted the ranks of the communicator of the kernel to the parent, see
+    /// [`topology::send_ranks_permuted`](crate::topology::send_ranks_permuted). Must be called
+    /// after [`send_iterations_run`](Self::send_iterations_run). A local run creates its
+    /// communicator in the parent, so there is nothing to send.
+    pub fn send_ranks_permuted(&mut self) {
+        match self {
+            Parent::Remote(comm) => crate::topology::send_ranks_permuted(*comm),
+            Parent::Local(_) => {}
+        }
+    }
+
+    /// Send the output of the last iteration to the parent, see [`send_output`].
+    pub fn send_output<T, O>(&mut self, project: fn(&T) -> O, output: Option<O>)
+    where
+        O: Equivalence + Default + 'static,
+    {
+        match self {
+            Parent::Remote(comm) => send_output(*comm, project, output),
+            Parent::Local(run) => run.output = Some(Box::new(output.unwrap_or_default())),
+        }
+    }
+
+    /// Send the counters the kernel recorded in this sample to the parent, see
+    /// [`counters::send`](crate::counters::send). Must be called after
+    /// [`send_output`](Self::send_output).
+    pub fn send_counters(&mut self) {
+        let counters = crate::counters::take();
+        match self {
+            Parent::Remote(comm) => crate::counters::send(*comm, &counters),
+            Parent::Local(run) => {
+                run.counters = counters
+                    .into_iter()
+                    .map(|(name, total)| (name.to_string(), total))
+                    .collect();
+            }
+        }
+    }
+
+    /// Send the CPU time this child used to the parent, see
+    /// [`launches::send_cpu_time`](crate::launches::send_cpu_time). Must be called last, after
+    /// [`send_counters`](Self::send_counters). A local run starts no children, so there is nothing to
+    /// send.
+    pub fn send_cpu_time(&mut self) {
+        match self {
+            Parent::Remote(comm) => crate::launches::send_cpu_time(*comm),
+            Parent::Local(_) => {}
+        }
+    }
+}
+
+/// A run of a kernel in the parent process. It holds what would otherwise be sent between the
+/// parent and the children.
+pub struct LocalRun {
+    iterations: u64,
+    time_cap: Option<Duration>,
+    ranks: u64,
+    arguments: [Option<(TypeTag, Box<dyn Any>)>; 2],
+    output_tag: TypeTag,
+    outcome: Option<Result<Vec<Duration>, String>>,
+    drift: Option<f64>,
+    iterations_run: Option<u64>,
+    output: Option<Box<dyn Any>>,
+    counters: Vec<(String, Duration)>,
+}
+
+/// The outcome of a kernel that ran in the parent process, in the same form as
+/// [`receive_outcome`], and its output, if it has one.
+pub type LocalOutcome = (Result<Vec<Duration>, String>, Option<Box<dyn Any>>);
+
+impl LocalRun {
+    /// Create a run of ``iterations`` iterations on ``ranks`` ranks with the given time cap and
+    /// arguments.
+    pub fn new(
+        iterations: u64,
+        time_cap: Option<Duration>,
+        ranks: u64,
+        const_arg: &dyn DynArgument,
+        arg: &dyn DynArgument,
+        output_tag: TypeTag,
+    ) -> Self {
+        Self {
+            iterations,
+            time_cap,
+            ranks,
+            arguments: [
+                Some((const_arg.tag(), const_arg.boxed())),
+                Some((arg.tag(), arg.boxed())),
+            ],
+            output_tag,
+            outcome: None,
+            drift: None,
+            iterations_run: None,
+            output: None,
+            counters: Vec::new(),
+        }
+    }
+
+    fn take_argument<A: Argument + 'static>(&mut self, index: usize) -> A {
+        let (tag, argument) = self.arguments[index]
+            .take()
+            .expect("argument was already received");
+        if let Some(TextArgument(text)) = argument.downcast_ref::<TextArgument>() {
+            return A::from_text(text).unwrap_or_else(|message| panic!("{}", message));
+        }
+
+        match argument.downcast::<A>() {
+            Ok(argument) => *argument,
+            Err(_) => panic!("{}", type_mismatch(tag.name, tag.size, A::tag())),
+        }
+    }
+
+    /// The drift of the iteration times reported by the kernel, in the same form as
+    /// [`timing::receive_drift`].
+    pub fn drift(&self) -> Option<f64> {
+        self.drift
+    }
+
+    /// The number of iterations the kernel ran, in the same form as
+    /// [`timing::receive_iterations_run`].
+    pub fn iterations_run(&self) -> u64 {
+        self.iterations_run.unwrap_or(self.iterations)
+    }
+
+    /// The counters recorded by the kernel, in the same form as
+    /// [`counters::receive`](crate::counters::receive).
+    pub fn counters(&self) -> Vec<(String, Duration)> {
+        self.counters.clone()
+    }
+
+    /// The outcome reported by the kernel, in the same form as [`receive_outcome`], and its
+    /// output, if it has one.
+    pub fn finish(self) -> LocalOutcome {
+        let outcome = self.outcome.expect("the kernel did not report an outcome");
+        (outcome, self.output)
+    }
+}
+
+/// Count the ranks of the child world for which ``valid`` is false. All ranks receive the same
+/// count, so they can agree to stop running the kernel.
+pub fn count_failed_ranks(world: &dyn Communicator, valid: bool) -> u64 {
+    let failed = !valid as u64;
+    let mut total = 0u64;
+    world.all_reduce_into(&failed, &mut total, SystemOperation::sum());
+    total
+}
+
+/// The return type of a kernel function. Kernels either return nothing, or a ``Result`` whose
+/// error is reported to the parent.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid kernel return type",
+    label = "kernels must return `()` or `Result<(), E>` where `E: Display`"
+)]
+pub trait KernelResult {
+    /// Whether the kernel can fail. The children only need to agree on errors if this is true.
+    const FALLIBLE: bool;
+
+    /// Convert the result into an error message if the kernel failed.
+    fn into_error(self) -> Option<String>;
+}
+
+impl KernelResult for () {
+    const FALLIBLE: bool = false;
+
+    fn into_error(self) -> Option<String> {
+        None
+    }
+}
+
+impl<E: Display> KernelResult for Result<(), E> {
+    const FALLIBLE: bool = true;
+
+    fn into_error(self) -> Option<String> {
+        self.err().map(|e| e.to_string())
+    }
+}
+
+/// An error returned by the kernel on one rank of the child world.
+#[derive(Debug, Clone)]
+pub struct RankError {
+    rank: u64,
+    message: String,
+}
+
+/// Let all ranks of the child world agree on whether any rank failed. If one did, the lowest
+/// failing rank is returned, and the error message is only known to that rank.
+fn agree_on_error(world: &dyn Communicator, error: Option<String>) -> Option<RankError> {
+    let own_rank = if error.is_some() {
+        world.rank() as u64
+    } else {
+        u64::MAX
+    };
+    let mut failed_rank = u64::MAX;
+    world.all_reduce_into(&own_rank, &mut failed_rank, SystemOperation::min());
+
+    if failed_rank == u64::MAX {
+        None
+    } else {
+        Some(RankError {
+            rank: failed_rank,
+            message: error.unwrap_or_default(),
+        })
+    }
+}
+
+/// Let all ranks of the child world agree on whether the kernel failed on any rank.
+/// Infallible kernels skip the agreement, so they pay no communication overhead.
+pub fn check_kernel_result<R: KernelResult>(
+    world: &dyn Communicator,
+    result: R,
+) -> Option<RankError> {
+    if !R::FALLIBLE {
+        return None;
+    }
+
+    agree_on_error(world, result.into_error())
+}
+
+/// Let all ranks of the child world agree on whether the setup failed on any rank. Returns the
+/// data if the setup succeeded on all ranks.
+pub fn check_setup_result<T, E: Display>(
+    world: &dyn Communicator,
+    result: Result<T, E>,
+) -> Result<T, RankError> {
+    match result {
+        Ok(data) => match agree_on_error(world, None) {
+            None => Ok(data),
+            Some(error) => Err(error),
+        },
+        Err(e) => Err(agree_on_error(world, Some(format!("setup failed: {}", e)))
+            .expect("a failed rank must take part in the error agreement")),
+    }
+}
+
+/// Run a replicated setup: the setup only runs on rank 0 of the child world, and its data is
+/// broadcast to the other ranks, which start from the default value. Called by the code
+/// ``mpirion_kernel!`` generates for a kernel with ``replicated_setup``, before each iteration and
+/// outside the measured time.
+pub fn replicate<T, F>(world: &dyn Communicator, setup: F) -> T
+where
+    T: Equivalence + Default,
+    F: FnOnce() -> T,
+{
+    let mut data = if world.rank() == 0 {
+        setup()
+    } else {
+        T::default()
+    };
+    world.process_at_rank(0).broadcast_into(&mut data);
+    data
+}
+
+/// Send the outcome of a sample from a child process to the parent. ``failed_ranks`` is the
+/// number of ranks whose validation failed, ``error`` is the kernel error all ranks agreed on, and
+/// ``total`` is the time this rank spent in the kernel, see [`timing::send_time`].
+pub fn send_outcome(
+    comm: &dyn Communicator,
+    failed_ranks: u64,
+    error: Option<RankError>,
+    total: Duration,
+) {
+    // signal the parent that the samples are done, see `wait_for_children`
+    trace::debug!(failed_ranks, ?total, "samples done, sending outcome");
+    // the parent may poll this barrier, so it has to be the nonblocking kind on all ranks
+    errhandler::or_abort(comm, errhandler::barrier(comm, None));
+
+    errhandler::or_abort(comm, errhandler::reduce_max(comm, &failed_ranks, None));
+
+    let error_rank = error.as_ref().map_or(u64::MAX, |e| e.rank);
+    errhandler::or_abort(comm, errhandler::reduce_min(comm, &error_rank, None));
+    if let Some(error) = error {
+        // the merged communicator contains the parent at rank 0, so the children are shifted by one
+        let mut message = error.message.into_bytes();
+        broadcast_vec(comm, error.rank as Rank + 1, &mut message);
+    }
+
+    timing::send_time(comm, total);
+    trace::debug!("sent outcome");
+}
+
+/// Wait on the parent until the children finished their samples. Returns false if the timeout
+/// expired first, in which case the caller has to abort the child world. Without a timeout this
+/// blocks until the children are done.
+pub fn wait_for_children(
+    comm: &dyn Communicator,
+    timeout: Option<Duration>,
+) -> Result<bool, Error> {
+    errhandler::barrier(comm, timeout)
+}
+
+/// Receive the outcome of a sample on the parent. Must be called after [`wait_for_children`].
+/// Returns the kernel time of each child, ordered by rank, or an error message if the children
+/// reported a failure. Returns an error if MPI failed to receive the outcome.
+pub fn receive_outcome(comm: &dyn Communicator) -> Result<Result<Vec<Duration>, String>, Error> {
+    let mut failed_ranks = 0u64;
+    errhandler::reduce_max(comm, &0u64, Some(&mut failed_ranks))?;
+
+    let mut error_rank = u64::MAX;
+    errhandler::reduce_min(comm, &u64::MAX, Some(&mut error_rank))?;
+    let mut error = None;
+    if error_rank != u64::MAX {
+        let mut message = Vec::new();
+        broadcast_vec(comm, error_rank as Rank + 1, &mut message);
+        error = Some((error_rank, String::from_utf8_lossy(&message).into_owned()));
+    }
+
+    let per_rank = timing::receive_times(comm);
+    Ok(outcome(
+        failed_ranks,
+        comm.size() as u64 - 1,
+        error,
+        per_rank,
+    ))
+}
+
+fn outcome(
+    failed_ranks: u64,
+    ranks: u64,
+    error: Option<(u64, String)>,
+    per_rank: Vec<Duration>,
+) -> Result<Vec<Duration>, String> {
+    if let Some((rank, message)) = error {
+        Err(format!("rank {} returned an error: {}", rank, message))
+    } else if failed_ranks > 0 {
+        Err(format!(
+            "validation failed on {} of {} ranks",
+            failed_ranks, ranks
+        ))
+    } else {
+        Ok(per_rank)
+    }
+}
+
+/// The maximum size in bytes of the output a single child rank may send back to the parent.
+pub const MAX_OUTPUT_SIZE: usize = 64 * 1024;
+
+/// The type tag of the output type ``O``. Panics if the output is larger than
+/// [`MAX_OUTPUT_SIZE`].
+pub fn output_tag_of<O>() -> TypeTag {
+    assert!(
+        std::mem::size_of::<O>() <= MAX_OUTPUT_SIZE,
+        "kernel output `{}` is larger than {} bytes",
+        std::any::type_name::<O>(),
+        MAX_OUTPUT_SIZE
+    );
+    TypeTag::of::<O>()
+}
+
+/// A callback that receives the outputs of all children on the parent. It is implemented for
+/// ``()`` if the parent does not collect outputs, and for a 1-tuple containing a function that
+/// takes the slice of outputs, ordered by child rank.
+pub trait OutputCallback<O> {
+    /// The type tag of the expected output.
+    fn tag(&self) -> TypeTag;
+
+    /// Gather the outputs of the children and pass them to the callback.
+    fn receive(self, comm: &dyn Communicator);
+
+    /// Gather the outputs of the children without passing them to the callback.
+    fn discard(self, comm: &dyn Communicator);
+}
+
+impl OutputCallback<()> for () {
+    fn tag(&self) -> TypeTag {
+        TypeTag::none()
+    }
+
+    fn receive(self, _comm: &dyn Communicator) {}
+
+    fn discard(self, _comm: &dyn Communicator) {}
+}
+
+impl<F, O> OutputCallback<O> for (F,)
+where
+    F: FnOnce(&[O]),
+    O: Equivalence + Default + Clone,
+{
+    fn tag(&self) -> TypeTag {
+        output_tag_of::<O>()
+    }
+
+    fn receive(self, comm: &dyn Communicator) {
+        let outputs = gather_outputs::<O>(comm);
+        (self.0)(&outputs);
+    }
+
+    fn discard(self, comm: &dyn Communicator) {
+        gather_outputs::<O>(comm);
+    }
+}
+
+/// Gather the outputs of all children on the parent, ordered by child rank.
+fn gather_outputs<O: Equivalence + Default + Clone>(comm: &dyn Communicator) -> Vec<O> {
+    let mut outputs = vec![O::default(); comm.size() as usize];
+    comm.process_at_rank(0)
+        .gather_into_root(&O::default(), &mut outputs[..]);
+    // the first output is the dummy value of the parent
+    outputs.remove(0);
+    outputs
+}
+
+/// Send the type tag of the output the parent expects, or [`TypeTag::none`] if it does not collect
+/// outputs.
+pub fn send_output_tag(comm: &dyn Communicator, tag: TypeTag) {
+    send_type_tag(comm, tag);
+}
+
+/// Verify that the output the parent expects matches the output projection of the kernel.
+pub fn verify_output_tag(comm: &dyn Communicator, tag: TypeTag) {
+    verify_type_tag(comm, tag);
+}
+
+/// The type tag of the output of an output projection.
+pub fn output_tag<T, O>(_project: fn(&T) -> O) -> TypeTag {
+    output_tag_of::<O>()
+}
+
+/// The initial, empty output of an output projection. Exists to name the output type.
+pub fn no_output<T, O>(_project: fn(&T) -> O) -> Option<O> {
+    None
+}
+
+/// Send the output of the last iteration from a child to the parent. If no iteration ran, the
+/// default value is sent.
+pub fn send_output<T, O>(comm: &dyn Communicator, _project: fn(&T) -> O, output: Option<O>)
+where
+    O: Equivalence + Default,
+{
+    comm.process_at_rank(0)
+        .gather_into(&output.unwrap_or_default());
+}
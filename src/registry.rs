@@ -0,0 +1,303 @@
+//! Kernels registered with the ``#[mpirion::kernel]`` attribute. The attribute adds an entry to a
+//! distributed slice, so ``mpirion_main!`` can dispatch to the kernel without listing it.
+//!
+//! Kernels that are only known at runtime, e.g. because the suite is read from a configuration
+//! file, are registered with [`register_kernel`] instead.
+
+use std::sync::Mutex;
+
+use crate::mpi::Rank;
+use crate::protocol::{Argument, Setup, TypeTag};
+use crate::runner::DEFAULT_WORLD_SIZE;
+
+/// A registered kernel: the name the parent uses to spawn it, and its bootstrap function.
+#[derive(Debug, Clone, Copy)]
+pub struct KernelEntry {
+    /// the name of the kernel, as passed to ``--child``
+    pub name: &'static str,
+    /// the bootstrap function generated by ``mpirion_kernel!``
+    pub run: fn(),
+    /// the static information about the kernel generated by ``mpirion_kernel!``
+    pub info: fn() -> KernelInfo,
+}
+
+/// Static information about a kernel, which ``mpirion_kernel!`` records next to the bootstrap
+/// function. It is printed by ``--mpirion-manifest``, see the [`manifest`](crate::manifest)
+/// module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelInfo {
+    /// the name of the kernel, as passed to ``--child``
+    pub name: &'static str,
+    /// the type of the constant argument of the setup function, if it takes one
+    pub const_arg: Option<&'static str>,
+    /// the type of the argument of the setup function, if it takes one
+    pub arg: Option<&'static str>,
+    /// the number of children spawned for the kernel, unless the benchmark sets ``world_size``
+    pub default_world_size: Rank,
+}
+
+impl KernelInfo {
+    /// The information about the kernel with the given name and setup function.
+    #[doc(hidden)]
+    pub fn of<'w, C: Argument, A: Argument, S: Setup<'w, C, A>>(
+        name: &'static str,
+        _setup: &S,
+    ) -> Self {
+        let type_name = |tag: TypeTag| (tag != TypeTag::none()).then(|| tag.name());
+        Self {
+            name,
+            const_arg: type_name(C::tag()),
+            arg: type_name(A::tag()),
+            default_world_size: DEFAULT_WORLD_SIZE,
+        }
+    }
+}
+
+impl Kernel for KernelEntry {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn run_child(&self) {
+        (self.run)()
+    }
+}
+
+/// A kernel that a child process can run, looked up by its name.
+pub trait Kernel: Send {
+    /// The name of the kernel, as passed to ``--child``.
+    fn name(&self) -> &str;
+
+    /// Run the kernel in a child process, like the bootstrap function generated by
+    /// ``mpirion_kernel!``.
+    fn run_child(&self);
+}
+
+/// A [`Kernel`] with a name chosen at runtime, which runs a bootstrap function generated by
+/// ``mpirion_kernel!``.
+#[derive(Debug, Clone)]
+pub struct BootstrapKernel {
+    name: String,
+    run: fn(),
+}
+
+impl BootstrapKernel {
+    /// Create a kernel that runs the bootstrap function ``run`` under the given name.
+    pub fn new(name: impl Into<String>, run: fn()) -> Self {
+        Self {
+            name: name.into(),
+            run,
+        }
+    }
+}
+
+impl Kernel for BootstrapKernel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run_child(&self) {
+        (self.run)()
+    }
+}
+
+static DYNAMIC_KERNELS: Mutex<Vec<Box<dyn Kernel>>> = Mutex::new(Vec::new());
+
+/// Register a kernel at runtime. The children look up the kernel by name, so it must be
+/// registered in the parent and in the children, before ``mpirion_main!`` dispatches to them.
+/// Register it from the ``init`` function of ``mpirion_main!``, which runs in both. A later
+/// registration with the same name replaces the earlier one.
+pub fn register_kernel(kernel: Box<dyn Kernel>) {
+    let mut kernels = DYNAMIC_KERNELS.lock().unwrap();
+    kernels.retain(|registered| registered.name() != kernel.name());
+    kernels.push(kernel);
+}
+
+/// The names of all kernels registered with [`register_kernel`].
+pub fn registered_names() -> Vec<String> {
+    DYNAMIC_KERNELS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|kernel| kernel.name().to_string())
+        .collect()
+}
+
+/// Run the kernel with the given name if it was registered with [`register_kernel`]. Returns
+/// whether such a kernel exists.
+pub fn run_registered(name: &str) -> bool {
+    let kernels = DYNAMIC_KERNELS.lock().unwrap();
+    match kernels.iter().find(|kernel| kernel.name() == name) {
+        Some(kernel) => {
+            kernel.run_child();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(feature = "attributes")]
+#[doc(hidden)]
+pub use linkme;
+
+/// All kernels registered with the ``#[mpirion::kernel]`` attribute.
+#[cfg(feature = "attributes")]
+#[linkme::distributed_slice]
+pub static KERNELS: [KernelEntry];
+
+/// The information about all kernels registered with the ``#[mpirion::kernel]`` attribute.
+pub fn attribute_kernels() -> Vec<KernelInfo> {
+    #[cfg(feature = "attributes")]
+    let entries: &[KernelEntry] = &KERNELS;
+    #[cfg(not(feature = "attributes"))]
+    let entries: &[KernelEntry] = &[];
+
+    entries.iter().map(|entry| (entry.info)()).collect()
+}
+
+/// Fail to compile if ``mpirion_main!`` lists two kernels with the same name, since the children
+/// could not tell them apart. Called by ``mpirion_main!`` in a constant.
+#[doc(hidden)]
+pub const fn assert_unique_names(names: &[&str]) {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            if const_str_eq(names[i], names[j]) {
+                panic!(
+                    "mpirion_main! lists two kernels with the same name. Kernels with the same function name in different \
+                     modules need distinct names: `mpirion_kernel!(kernel = my_kernel as other_name)` or \
+                     `#[mpirion::kernel(name = other_name)]`"
+                );
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+const fn const_str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Check that no two kernels share a name: the kernels listed in ``mpirion_main!`` and the
+/// kernels registered with the ``#[mpirion::kernel]`` attribute, which can only be checked at
+/// runtime. Returns a message naming the duplicates otherwise.
+///
+/// # Example
+/// ```rust
+/// use mpirion::registry::check_unique_names;
+///
+/// assert!(check_unique_names(&["scan", "reduce"]).is_ok());
+/// assert!(check_unique_names(&["scan", "reduce", "scan"]).unwrap_err().contains("`scan`"));
+/// ```
+pub fn check_unique_names(listed: &[&str]) -> Result<(), String> {
+    let mut names = listed.to_vec();
+    #[cfg(feature = "attributes")]
+    names.extend(KERNELS.iter().map(|entry| entry.name));
+
+    let mut duplicates = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        if names[..index].contains(name) && !duplicates.contains(name) {
+            duplicates.push(*name);
+        }
+    }
+
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+    let duplicates: Vec<String> = duplicates
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect();
+    Err(format!(
+        "several kernels are registered as {}, so the children cannot tell them apart. Give them distinct names with \
+         `mpirion_kernel!(kernel = my_kernel as other_name)` or `#[mpirion::kernel(name = other_name)]`.",
+        duplicates.join(", ")
+    ))
+}
+
+/// Find the bootstrap function of a kernel registered with the ``#[mpirion::kernel]`` attribute,
+/// or of the built-in kernel of the [`selfcheck`](crate::selfcheck).
+pub fn find(name: &str) -> Option<fn()> {
+    if name == crate::selfcheck::KERNEL {
+        return Some(crate::selfcheck::entry().run);
+    }
+
+    #[cfg(feature = "attributes")]
+    if let Some(entry) = KERNELS.iter().find(|entry| entry.name == name) {
+        return Some(entry.run);
+    }
+
+    let _ = name;
+    None
+}
+
+/// A set of kernels that a child process can run, for programs that assemble their kernels at
+/// runtime instead of listing them in ``mpirion_main!``. Kernels registered with the
+/// ``#[mpirion::kernel]`` attribute are always found, even if they are not added to the registry,
+/// and [`ChildRunner`](crate::runner::ChildRunner) also runs kernels registered with
+/// [`register_kernel`].
+///
+/// # Example
+/// ```rust
+/// use mpirion::registry::KernelRegistry;
+///
+/// fn execute_my_kernel() { /* generated by mpirion_kernel! */ }
+///
+/// let mut registry = KernelRegistry::new();
+/// registry.register("my_kernel", execute_my_kernel);
+/// assert!(registry.find("my_kernel").is_some());
+/// assert!(registry.find("other_kernel").is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KernelRegistry {
+    kernels: Vec<(String, fn())>,
+}
+
+impl KernelRegistry {
+    /// Create a registry that only contains the kernels registered with the attribute.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the bootstrap function of a kernel under the given name. The name must match the
+    /// kernel name the parent passes to [`BenchRunner::new`](crate::runner::BenchRunner::new).
+    /// A later registration with the same name replaces the earlier one.
+    pub fn register(&mut self, name: impl Into<String>, run: fn()) -> &mut Self {
+        let name = name.into();
+        self.kernels.retain(|(registered, _)| *registered != name);
+        self.kernels.push((name, run));
+        self
+    }
+
+    /// Find the bootstrap function of a kernel, either in this registry or among the kernels
+    /// registered with the attribute.
+    pub fn find(&self, name: &str) -> Option<fn()> {
+        self.kernels
+            .iter()
+            .find(|(registered, _)| registered == name)
+            .map(|&(_, run)| run)
+            .or_else(|| find(name))
+    }
+
+    /// The names of all kernels in this registry, followed by the kernels registered with the
+    /// attribute.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.kernels.iter().map(|(name, _)| name.as_str()).collect();
+        #[cfg(feature = "attributes")]
+        names.extend(KERNELS.iter().map(|entry| entry.name));
+        names
+    }
+}
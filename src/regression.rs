@@ -0,0 +1,190 @@
+//! The check for regressions over the whole scaling curve of each kernel, enabled with
+//! ``--mpirion-check-regressions``. Comparing a benchmark against its last run misses regressions
+//! that only show at large world sizes, so before the [scaling summaries](crate::scaling) are
+//! replaced, the time per iteration of each kernel at each world size is compared with the one in
+//! the ``scaling-<kernel>.csv`` of the previous runs. Only the world sizes both runs measured are
+//! compared, so the runs may sweep slightly different world sizes.
+//!
+//! A world size regressed if its time grew by more than the tolerance in percent, set with
+//! ``MPIRION_REGRESSION_TOLERANCE`` or [`DEFAULT_TOLERANCE`]. ``mpirion_main!`` lists the
+//! regressions at the end of the run, like ``bcast at p=64: +18% vs baseline``, and exits with
+//! [`EXIT_CODE`] if there are any.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::scaling::ScalingRow;
+
+/// The environment variable that sets the tolerance in percent.
+pub const TOLERANCE_VAR: &str = "MPIRION_REGRESSION_TOLERANCE";
+
+/// The tolerance in percent if ``MPIRION_REGRESSION_TOLERANCE`` is not set.
+pub const DEFAULT_TOLERANCE: f64 = 10.0;
+
+/// The exit code of a run that found regressions.
+pub const EXIT_CODE: i32 = 4;
+
+/// The regressions found in this run.
+static FOUND: Mutex<Vec<Regression>> = Mutex::new(Vec::new());
+
+/// The number of world sizes of the kernels of this run that the previous runs measured too.
+static COMPARED: AtomicUsize = AtomicUsize::new(0);
+
+/// A world size at which a kernel got slower than in the previous runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regression {
+    /// the kernel
+    pub kernel: String,
+    /// the number of children
+    pub world_size: usize,
+    /// the mean time per iteration in the previous runs
+    pub baseline: Duration,
+    /// the mean time per iteration in this run
+    pub now: Duration,
+}
+
+impl Regression {
+    /// How much slower the kernel got, in percent of the baseline.
+    pub fn percent(&self) -> f64 {
+        (self.now.as_secs_f64() / self.baseline.as_secs_f64() - 1.0) * 100.0
+    }
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at p={}: {:+.0}% vs baseline ({:.2?} -> {:.2?})",
+            self.kernel,
+            self.world_size,
+            self.percent(),
+            self.baseline,
+            self.now
+        )
+    }
+}
+
+/// Parse a tolerance in percent, which may be zero to flag every slowdown.
+///
+/// # Example
+/// ```rust
+/// use mpirion::regression::parse_tolerance;
+///
+/// assert_eq!(parse_tolerance("5"), Ok(5.0));
+/// assert_eq!(parse_tolerance("0"), Ok(0.0));
+/// assert!(parse_tolerance("-3").is_err());
+/// assert!(parse_tolerance("off").is_err());
+/// ```
+pub fn parse_tolerance(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(percent) if percent.is_finite() && percent >= 0.0 => Ok(percent),
+        _ => Err(format!(
+            "{} must be a number of percent, not `{}`",
+            TOLERANCE_VAR, value
+        )),
+    }
+}
+
+/// The tolerance in percent, from ``MPIRION_REGRESSION_TOLERANCE`` or [`DEFAULT_TOLERANCE`].
+/// Panics if the variable is malformed.
+pub fn tolerance() -> f64 {
+    match std::env::var(TOLERANCE_VAR) {
+        Ok(value) => parse_tolerance(&value).unwrap_or_else(|message| panic!("{}", message)),
+        Err(_) => DEFAULT_TOLERANCE,
+    }
+}
+
+/// The world sizes at which the time per iteration of a kernel grew by more than ``tolerance``
+/// percent between the rows of the previous runs and those of this run. World sizes only one of
+/// them measured, and those without a measurable time in the previous runs, are skipped.
+///
+/// # Example
+/// ```rust
+/// use mpirion::regression::regressions;
+/// use mpirion::scaling::ScalingRow;
+/// use std::time::Duration;
+///
+/// let row = |world_size, micros| ScalingRow { world_size, mean: Duration::from_micros(micros), stddev: Duration::ZERO };
+/// let baseline = [row(8, 100), row(16, 60), row(64, 50), row(128, 45)];
+/// let now = [row(4, 190), row(16, 62), row(64, 59)];
+/// let found = regressions("bcast", &baseline, &now, 10.0);
+/// assert_eq!(found.len(), 1);
+/// assert_eq!(found[0].world_size, 64);
+/// assert_eq!(found[0].to_string(), "bcast at p=64: +18% vs baseline (50.00µs -> 59.00µs)");
+/// assert!(regressions("bcast", &baseline, &now, 20.0).is_empty());
+/// ```
+pub fn regressions(
+    kernel: &str,
+    baseline: &[ScalingRow],
+    now: &[ScalingRow],
+    tolerance: f64,
+) -> Vec<Regression> {
+    now.iter()
+        .filter_map(|row| {
+            let previous = baseline
+                .iter()
+                .find(|previous| previous.world_size == row.world_size)?;
+            Some(Regression {
+                kernel: kernel.to_string(),
+                world_size: row.world_size,
+                baseline: previous.mean,
+                now: row.mean,
+            })
+        })
+        .filter(|regression| !regression.baseline.is_zero() && regression.percent() > tolerance)
+        .collect()
+}
+
+/// Compare the rows of a kernel measured in this run with those of the previous runs, and keep
+/// the regressions for [`exit_on_regressions`]. Does nothing unless ``--mpirion-check-regressions``
+/// is set. Called by [`scaling::write`](crate::scaling::write) before it replaces the rows.
+pub(crate) fn check(kernel: &str, baseline: &[ScalingRow], now: &[ScalingRow]) {
+    if !crate::cli::options().check_regressions {
+        return;
+    }
+    let compared = now.iter().filter(|row| {
+        baseline
+            .iter()
+            .any(|previous| previous.world_size == row.world_size)
+    });
+    COMPARED.fetch_add(compared.count(), Ordering::Relaxed);
+    FOUND
+        .lock()
+        .unwrap()
+        .extend(regressions(kernel, baseline, now, tolerance()));
+}
+
+/// Print the regressions found in this run and exit the process with [`EXIT_CODE`] if there were
+/// any, or confirm that there were none. Does nothing unless ``--mpirion-check-regressions`` is
+/// set. Called by ``mpirion_main!`` at the end of the run.
+pub fn exit_on_regressions() {
+    if !crate::cli::options().check_regressions {
+        return;
+    }
+    let found = FOUND.lock().unwrap();
+    if found.is_empty() {
+        match COMPARED.load(Ordering::Relaxed) {
+            0 => {
+                eprintln!("mpirion: the previous runs measured none of the world sizes of this run, nothing to compare")
+            }
+            compared => eprintln!(
+                "mpirion: none of the {} world sizes measured in the previous runs regressed by more than {}%",
+                compared,
+                tolerance()
+            ),
+        }
+        return;
+    }
+
+    eprintln!(
+        "mpirion: {} regressions by more than {}% since the previous runs:",
+        found.len(),
+        tolerance()
+    );
+    for regression in found.iter() {
+        eprintln!("  {}", regression);
+    }
+    std::process::exit(EXIT_CODE);
+}
@@ -0,0 +1,1655 @@
+//! Reports of the times of the individual ranks, written next to the HTML report of criterion.
+//! Criterion only sees the mean over the ranks that [`BenchRunner::run`](crate::runner::BenchRunner::run)
+//! returns, so mpirion keeps the times of all ranks of every sample and writes them out at the end
+//! of the run, see [`write`].
+//!
+//! The benchmarks are named by criterion, which doesn't tell the benchmark function which
+//! benchmark it measures. The samples are therefore assigned to the benchmarks afterwards, by
+//! matching them with the samples criterion saved in the ``sample.json`` of each benchmark. For
+//! each benchmark of the run, ``rank-distribution.svg`` and ``rank-distribution.html`` are written
+//! into its directory, and ``rank-distributions.html`` in the output directory of criterion links
+//! to all of them. ``placement.json`` lists where the children of the samples ran, see
+//! [`placements_to_json`]. Benchmarks whose ranks disagreed about the time of a sample, see
+//! [`variance`](crate::variance), are marked in the index and listed at the end of the run.
+//!
+//! The [scaling summaries](crate::scaling) of the kernels are written into the output directory
+//! of criterion.
+//!
+//! ``mpirion.json`` holds the metrics mpirion collected for a benchmark in a versioned format for
+//! other tools, see [`Metrics`], including the world size and the [argument](ArgumentValue) of the
+//! benchmark. It is written when the group of the benchmark ends, by the
+//! [`JsonFileReporter`](crate::reporter::JsonFileReporter), and can be read back with [`load`].
+//!
+//! The same reporter writes ``mpirion-samples.json`` next to the ``sample.json`` of criterion the
+//! samples were matched with, with the times of the ranks of each sample of criterion, see
+//! [`samples_to_json`], so tools that read ``sample.json`` can join them by index.
+//!
+//! The output directory is ``CRITERION_HOME``, or ``criterion`` in the target directory, like in
+//! criterion. Benchmarks that change the output directory in their criterion configuration get no
+//! reports, and neither do runs with ``--mpirion-quick``, whose samples are replayed.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::cli::SampleExport;
+use crate::launches::Launches;
+use crate::placement::{Placement, PlacementMode};
+
+/// The file name of the distribution chart of a benchmark.
+pub const DISTRIBUTION_SVG: &str = "rank-distribution.svg";
+
+/// The file name of the distribution page of a benchmark.
+pub const DISTRIBUTION_HTML: &str = "rank-distribution.html";
+
+/// The file name of the index of the distribution pages, in the output directory of criterion.
+pub const INDEX_HTML: &str = "rank-distributions.html";
+
+/// The file name of the placements of the children of a benchmark.
+pub const PLACEMENT_JSON: &str = "placement.json";
+
+/// The file name of the metrics of a benchmark, see [`Metrics`].
+pub const METRICS_JSON: &str = "mpirion.json";
+
+/// The file name of the times of the ranks of each sample, next to the ``sample.json`` of
+/// criterion, see [`samples_to_json`].
+pub const SAMPLES_JSON: &str = "mpirion-samples.json";
+
+/// The version of the format of ``mpirion-samples.json``.
+pub const SAMPLES_VERSION: u64 = 1;
+
+/// The version of the format of ``mpirion.json``. It must be incremented whenever a field is added,
+/// removed or changes its meaning.
+pub const METRICS_VERSION: u64 = 11;
+
+/// A sample measured by spawning children, as criterion received it.
+#[derive(Debug, Clone)]
+pub struct SampleRecord {
+    /// the id of the benchmark in criterion, if it was declared through the
+    /// [`MpirionContext`](crate::MpirionContext)
+    pub benchmark: Option<String>,
+    /// the kernel of the sample
+    pub kernel: String,
+    /// the iterations of the sample
+    pub iterations: u64,
+    /// the time returned to criterion
+    pub reported: Duration,
+    /// the time each child spent in the kernel, ordered by rank
+    pub per_rank: Vec<Duration>,
+    /// where the children ran, unless the sample ran locally
+    pub placement: Option<Placement>,
+    /// how long the phases of the protocol took on the parent, unless the sample ran locally
+    pub overhead: Option<Overhead>,
+    /// the argument passed to the setup function of the children, if any
+    pub argument: Option<ArgumentValue>,
+    /// whether the sample hit its ``max_sample_time`` and was extrapolated
+    pub truncated: bool,
+    /// the worlds spawned for the sample, including those of a priming sample or of timed-out
+    /// attempts before it, see [`launches`](crate::launches)
+    pub launches: Launches,
+    /// the size of the Rayon pool of each child, if it built one
+    pub threads: Option<usize>,
+    /// the environment variables the children were started with, see [`tuning`](crate::tuning)
+    pub tuning: Vec<(String, String)>,
+    /// the placement of the children requested from MPI, if any
+    pub placement_mode: Option<PlacementMode>,
+    /// whether MPI permuted the ranks of the Cartesian communicator of the kernel, if it was
+    /// created with reordering, see [`topology`](crate::topology)
+    pub ranks_permuted: Option<bool>,
+    /// the time per iteration of each counter the kernel recorded, of the slowest rank, see
+    /// [`counters`](crate::counters)
+    pub counters: Vec<(String, Duration)>,
+}
+
+/// The time the parent spent in the phases of the protocol of a sample, outside of the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Overhead {
+    /// spawning the children
+    pub spawn: Duration,
+    /// merging the communicator of the children with the parent
+    pub merge: Duration,
+    /// the handshake and sending the kernel, the iterations and the arguments to the children
+    pub broadcast: Duration,
+    /// receiving the outcome, the times and the outputs of the children
+    pub reduce: Duration,
+}
+
+impl Overhead {
+    /// The names of the phases, as used in ``mpirion.json`` and in the comparison with the last
+    /// run.
+    pub const PHASES: [&'static str; 4] = ["spawn", "merge", "broadcast", "reduce"];
+
+    /// The time of each phase, in the order of [`PHASES`](Self::PHASES).
+    pub fn phases(&self) -> [Duration; 4] {
+        [self.spawn, self.merge, self.broadcast, self.reduce]
+    }
+
+    /// The time until the children were connected to the parent.
+    pub fn connect(&self) -> Duration {
+        self.spawn + self.merge
+    }
+
+    /// The mean time of each phase, or ``None`` if there are no overheads.
+    pub fn mean(overheads: &[Overhead]) -> Option<Overhead> {
+        if overheads.is_empty() {
+            return None;
+        }
+        let phase = |phase: fn(&Overhead) -> Duration| {
+            crate::timing::mean(&overheads.iter().map(phase).collect::<Vec<_>>())
+        };
+        Some(Overhead {
+            spawn: phase(|overhead| overhead.spawn),
+            merge: phase(|overhead| overhead.merge),
+            broadcast: phase(|overhead| overhead.broadcast),
+            reduce: phase(|overhead| overhead.reduce),
+        })
+    }
+}
+
+/// The environment variable that sets the change of the protocol overhead since the last run in
+/// percent above which it is reported, or ``off``.
+pub const OVERHEAD_CHANGE_VAR: &str = "MPIRION_OVERHEAD_CHANGE";
+
+/// The change of the protocol overhead in percent above which it is reported, if
+/// ``MPIRION_OVERHEAD_CHANGE`` is not set.
+pub const DEFAULT_OVERHEAD_CHANGE: f64 = 50.0;
+
+/// The change of the protocol overhead above which it is reported, from ``MPIRION_OVERHEAD_CHANGE``
+/// or [`DEFAULT_OVERHEAD_CHANGE`]. Panics if the variable is malformed.
+pub fn overhead_change_threshold() -> Option<f64> {
+    match std::env::var(OVERHEAD_CHANGE_VAR) {
+        Ok(value) => crate::drift::parse_percent(OVERHEAD_CHANGE_VAR, &value)
+            .unwrap_or_else(|message| panic!("{}", message)),
+        Err(_) => Some(DEFAULT_OVERHEAD_CHANGE),
+    }
+}
+
+/// The phases of the protocol whose mean time changed by more than ``threshold`` percent since
+/// the last run, in either direction.
+///
+/// # Example
+/// ```rust
+/// use mpirion::report::{overhead_changes, Overhead};
+/// use std::time::Duration;
+///
+/// let ms = Duration::from_millis;
+/// let last = Overhead { spawn: ms(100), merge: ms(10), broadcast: ms(1), reduce: ms(1) };
+/// let now = Overhead { spawn: ms(220), merge: ms(11), broadcast: ms(1), reduce: ms(0) };
+/// assert_eq!(
+///     overhead_changes(&last, &now, Some(50.0)),
+///     vec!["spawn overhead +120% vs last run".to_string(), "reduce overhead -100% vs last run".to_string()]
+/// );
+/// assert!(overhead_changes(&last, &now, None).is_empty());
+/// ```
+pub fn overhead_changes(last: &Overhead, now: &Overhead, threshold: Option<f64>) -> Vec<String> {
+    Overhead::PHASES
+        .iter()
+        .zip(last.phases().into_iter().zip(now.phases()))
+        .filter(|(_, (last, _))| !last.is_zero())
+        .filter_map(|(phase, (last, now))| {
+            let change = (now.as_secs_f64() - last.as_secs_f64()) / last.as_secs_f64() * 100.0;
+            threshold
+                .is_some_and(|percent| change.abs() > percent)
+                .then(|| format!("{} overhead {:+.0}% vs last run", phase, change))
+        })
+        .collect()
+}
+
+static SAMPLES: Mutex<Vec<SampleRecord>> = Mutex::new(Vec::new());
+
+/// Record a sample for the reports. Called by [`BenchRunner::run`](crate::runner::BenchRunner::run).
+pub fn record(sample: SampleRecord) {
+    SAMPLES.lock().unwrap().push(sample);
+}
+
+/// The time per iteration of a rank over the samples of a benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RankStatistics {
+    /// the mean time per iteration over all samples
+    pub mean: Duration,
+    /// the fastest time per iteration of a sample
+    pub min: Duration,
+    /// the slowest time per iteration of a sample
+    pub max: Duration,
+}
+
+/// The statistics of each rank over the given samples, ordered by rank. Samples without
+/// iterations are ignored.
+///
+/// # Example
+/// ```rust
+/// use mpirion::report::rank_statistics;
+/// use std::time::Duration;
+///
+/// let ms = Duration::from_millis;
+/// let samples = [(1, vec![ms(10), ms(20)]), (2, vec![ms(40), ms(20)])];
+/// let statistics = rank_statistics(&samples);
+/// assert_eq!(statistics.len(), 2);
+/// assert_eq!(statistics[0].mean, ms(15));
+/// assert_eq!((statistics[0].min, statistics[0].max), (ms(10), ms(20)));
+/// assert_eq!(statistics[1].mean, ms(15));
+/// assert_eq!((statistics[1].min, statistics[1].max), (ms(10), ms(20)));
+/// ```
+pub fn rank_statistics(samples: &[(u64, Vec<Duration>)]) -> Vec<RankStatistics> {
+    let ranks = samples
+        .iter()
+        .map(|(_, per_rank)| per_rank.len())
+        .max()
+        .unwrap_or(0);
+    (0..ranks)
+        .map(|rank| {
+            let per_iteration: Vec<Duration> = samples
+                .iter()
+                .filter(|(iterations, per_rank)| *iterations > 0 && rank < per_rank.len())
+                .map(|(iterations, per_rank)| per_iteration(per_rank[rank], *iterations))
+                .collect();
+            RankStatistics {
+                mean: crate::timing::mean(&per_iteration),
+                min: per_iteration.iter().min().copied().unwrap_or_default(),
+                max: per_iteration.iter().max().copied().unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+fn per_iteration(time: Duration, iterations: u64) -> Duration {
+    crate::timing::nanos_to_duration(
+        u64::try_from(time.as_nanos() / u128::from(iterations)).unwrap_or(u64::MAX),
+    )
+}
+
+/// The iterations and the times in nanoseconds of the samples in a ``sample.json`` of criterion, or
+/// ``None`` if the file is malformed.
+///
+/// # Example
+/// ```rust
+/// use mpirion::report::parse_samples;
+///
+/// let json = r#"{"sampling_mode":"Linear","iters":[1.0,2.0,3.0],"times":[105.0,198.0,311.0]}"#;
+/// assert_eq!(parse_samples(json), Some(vec![(1, 105), (2, 198), (3, 311)]));
+/// assert_eq!(parse_samples(r#"{"iters":[1.0],"times":[]}"#), None);
+/// assert_eq!(parse_samples("{}"), None);
+/// ```
+pub fn parse_samples(json: &str) -> Option<Vec<(u64, u64)>> {
+    let iterations = number_array(json, "iters")?;
+    let times = number_array(json, "times")?;
+    if iterations.len() != times.len() {
+        return None;
+    }
+    // criterion saves the durations as floating point nanoseconds
+    Some(
+        iterations
+            .into_iter()
+            .zip(times)
+            .map(|(i, t)| (i.round() as u64, t.round() as u64))
+            .collect(),
+    )
+}
+
+/// The numbers of the array in the given field of a flat JSON object.
+fn number_array(json: &str, field: &str) -> Option<Vec<f64>> {
+    let start = json.find(&format!("\"{}\":[", field))? + field.len() + 4;
+    let end = start + json[start..].find(']')?;
+    json[start..end]
+        .split(',')
+        .filter(|number| !number.trim().is_empty())
+        .map(|number| number.trim().parse().ok())
+        .collect()
+}
+
+/// The string in the given field of a flat JSON object, like the ``full_id`` in the
+/// ``benchmark.json`` of criterion.
+///
+/// # Example
+/// ```rust
+/// use mpirion::report::string_field;
+///
+/// let json = r#"{"group_id":"scan","full_id":"scan/\"large\" input","directory_name":"scan/_large_ input"}"#;
+/// assert_eq!(string_field(json, "full_id").as_deref(), Some("scan/\"large\" input"));
+/// assert_eq!(string_field(json, "title"), None);
+/// ```
+pub fn string_field(json: &str, field: &str) -> Option<String> {
+    let start = json.find(&format!("\"{}\":\"", field))? + field.len() + 4;
+    parse_string(&mut json[start..].chars())
+}
+
+/// The strings of the array in the given field of a flat JSON object.
+pub(crate) fn string_array(json: &str, field: &str) -> Option<Vec<String>> {
+    let start = json.find(&format!("\"{}\":[", field))? + field.len() + 4;
+    let mut chars = json[start..].chars();
+    let mut strings = Vec::new();
+    loop {
+        match chars.next()? {
+            ']' => return Some(strings),
+            '"' => strings.push(parse_string(&mut chars)?),
+            ',' => {}
+            c if c.is_whitespace() => {}
+            _ => return None,
+        }
+    }
+}
+
+/// The number in the given field of a flat JSON object, ``Some(None)`` if it is ``null``.
+pub(crate) fn number_field(json: &str, field: &str) -> Option<Option<f64>> {
+    let start = json.find(&format!("\"{}\":", field))? + field.len() + 3;
+    let end = start + json[start..].find([',', '}'])?;
+    match json[start..end].trim() {
+        "null" => Some(None),
+        number => number.parse().ok().map(Some),
+    }
+}
+
+/// The boolean in the given field of a flat JSON object, ``Some(None)`` if it is ``null``.
+fn bool_field(json: &str, field: &str) -> Option<Option<bool>> {
+    let start = json.find(&format!("\"{}\":", field))? + field.len() + 3;
+    let end = start + json[start..].find([',', '}'])?;
+    match json[start..end].trim() {
+        "null" => Some(None),
+        boolean => boolean.parse().ok().map(Some),
+    }
+}
+
+/// Parse a JSON string after its opening quote.
+fn parse_string(chars: &mut std::str::Chars) -> Option<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    value.push(
+                        u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)?,
+                    );
+                }
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// The output directory of criterion, see the module documentation.
+pub fn criterion_home() -> PathBuf {
+    match std::env::var_os("CRITERION_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => target_dir().join("criterion"),
+    }
+}
+
+/// The target directory of cargo: ``CARGO_TARGET_DIR``, the one the benchmark binary was built
+/// into, or ``target``.
+pub(crate) fn target_dir() -> PathBuf {
+    if let Some(target) = std::env::var_os("CARGO_TARGET_DIR") {
+        return PathBuf::from(target);
+    }
+    // benchmark binaries are built into <target>/<profile>/deps
+    let target = std::env::current_exe().ok().and_then(|exe| {
+        let deps = exe.parent()?;
+        if deps.file_name()? != "deps" {
+            return None;
+        }
+        Some(deps.parent()?.parent()?.to_path_buf())
+    });
+    target.unwrap_or_else(|| PathBuf::from("target"))
+}
+
+/// A benchmark of this run, with the samples mpirion recorded for it.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    /// the id of the benchmark in criterion
+    pub id: String,
+    /// the directory of the benchmark in the output directory of criterion
+    pub directory: PathBuf,
+    /// the samples of the benchmark, in the order of the ``sample.json`` of criterion
+    pub samples: Vec<SampleRecord>,
+    /// the ``sample.json`` of criterion the samples were matched with
+    pub sample_file: PathBuf,
+    /// the throughput per iteration set in criterion, if any
+    pub throughput: Option<Throughput>,
+}
+
+/// The throughput per iteration of a benchmark, as set with ``Throughput`` in criterion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Throughput {
+    /// the unit as criterion saves it: ``Bytes``, ``BytesDecimal`` or ``Elements``
+    pub unit: String,
+    /// the bytes or elements processed per iteration
+    pub per_iteration: u64,
+}
+
+/// The throughput in a ``benchmark.json`` of criterion, or ``None`` if none is set.
+///
+/// # Example
+/// ```rust
+/// use mpirion::report::{parse_throughput, Throughput};
+///
+/// let json = r#"{"full_id":"scan/p=8","throughput":{"Bytes":4096},"value_str":"p=8"}"#;
+/// assert_eq!(parse_throughput(json), Some(Throughput { unit: "Bytes".to_string(), per_iteration: 4096 }));
+/// assert_eq!(parse_throughput(r#"{"full_id":"scan/p=8","throughput":null}"#), None);
+/// ```
+pub fn parse_throughput(json: &str) -> Option<Throughput> {
+    let start = json.find("\"throughput\":{\"")? + "\"throughput\":{\"".len();
+    let unit_end = start + json[start..].find('"')?;
+    let unit = json[start..unit_end].to_string();
+    let per_iteration = number_field(&json[start - 1..], &unit)??;
+    Some(Throughput {
+        unit,
+        per_iteration: per_iteration as u64,
+    })
+}
+
+/// The argument a benchmark passed to the setup function of its children, so tools don't need to
+/// parse it from the id of the benchmark. Only arguments of primitive types and text can be
+/// rendered, because the argument types are only bound by ``Equivalence``; of other arguments,
+/// only the type is known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentValue {
+    /// the name of the type of the argument, ``text`` for arguments passed as text
+    pub type_name: String,
+    /// the argument formatted with ``Display``, if it can be rendered
+    pub display: Option<String>,
+    /// the argument formatted with ``Debug``, if it can be rendered
+    pub debug: Option<String>,
+    /// the argument as a number, if it is numeric
+    pub value: Option<f64>,
+}
+
+impl ArgumentValue {
+    /// Describe an argument. Arguments of the primitive numeric types and ``bool`` are rendered,
+    /// the numbers also as a value.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::report::ArgumentValue;
+    ///
+    /// let size = ArgumentValue::of(&1.5f64);
+    /// assert_eq!((size.display.as_deref(), size.debug.as_deref(), size.value), (Some("1.5"), Some("1.5"), Some(1.5)));
+    /// assert_eq!(ArgumentValue::of(&4096u32).value, Some(4096.0));
+    /// assert_eq!(ArgumentValue::of(&true).value, None);
+    ///
+    /// let buffer = ArgumentValue::of(&[0u8; 4]);
+    /// assert_eq!((buffer.type_name.as_str(), buffer.display, buffer.value), ("[u8; 4]", None, None));
+    /// ```
+    pub fn of<T>(argument: &T) -> Self {
+        macro_rules! describe_as {
+            ($($primitive:ty),*) => {
+                $(
+                if std::any::type_name::<T>() == std::any::type_name::<$primitive>() {
+                    // SAFETY: T and $primitive are the same type
+                    let argument: $primitive = unsafe { std::mem::transmute_copy(argument) };
+                    return Self {
+                        type_name: stringify!($primitive).to_string(),
+                        display: Some(argument.to_string()),
+                        debug: Some(format!("{:?}", argument)),
+                        value: Some(argument as f64),
+                    };
+                }
+                )*
+            };
+        }
+
+        describe_as!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+        if std::any::type_name::<T>() == std::any::type_name::<bool>() {
+            // SAFETY: T is bool
+            let argument: bool = unsafe { std::mem::transmute_copy(argument) };
+            return Self {
+                type_name: "bool".to_string(),
+                display: Some(argument.to_string()),
+                debug: Some(format!("{:?}", argument)),
+                value: None,
+            };
+        }
+        Self {
+            type_name: std::any::type_name::<T>().to_string(),
+            display: None,
+            debug: None,
+            value: None,
+        }
+    }
+
+    /// Describe an argument passed as text, see
+    /// [`BenchRunner::text_arg`](crate::runner::BenchRunner::text_arg). Its value is the number the
+    /// text parses to, if any.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::report::ArgumentValue;
+    ///
+    /// assert_eq!(ArgumentValue::text(" 64 ").value, Some(64.0));
+    /// assert_eq!(ArgumentValue::text("large").debug.as_deref(), Some("\"large\""));
+    /// ```
+    pub fn text(text: &str) -> Self {
+        Self {
+            type_name: "text".to_string(),
+            display: Some(text.to_string()),
+            debug: Some(format!("{:?}", text)),
+            value: text.trim().parse().ok(),
+        }
+    }
+}
+
+/// The metrics mpirion collected for a benchmark, which are written into its ``mpirion.json``. The
+/// times are per iteration, in nanoseconds in the file. The format is versioned with
+/// [`METRICS_VERSION`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    /// the version of the format the metrics were written with
+    pub version: u64,
+    /// the id of the benchmark in criterion
+    pub id: String,
+    /// the kernel of the benchmark
+    pub kernel: String,
+    /// the number of children
+    pub world_size: usize,
+    /// the number of samples
+    pub samples: usize,
+    /// the mean time per iteration that criterion received
+    pub mean: Duration,
+    /// the throughput per iteration set in criterion, if any
+    pub throughput: Option<Throughput>,
+    /// the share of the time of the samples spent spawning and connecting to the children, unless
+    /// the samples ran locally
+    pub spawn_share: Option<f64>,
+    /// the mean time spawning and connecting to the children took per sample, unless the samples
+    /// ran locally
+    pub spawn_overhead: Option<Duration>,
+    /// the mean time of each phase of the protocol per sample, unless the samples ran locally
+    pub overhead: Option<Overhead>,
+    /// the largest [coefficient of variation](crate::variance::coefficient_of_variation) of the
+    /// times of the ranks in a sample
+    pub max_variation: Option<f64>,
+    /// the time per iteration of each rank over the samples, ordered by rank
+    pub ranks: Vec<RankStatistics>,
+    /// the hosts the children of the first spawned sample ran on, ordered by rank, see
+    /// ``placement.json`` for all placements
+    pub hosts: Vec<String>,
+    /// the argument passed to the setup function of the children, if any
+    pub argument: Option<ArgumentValue>,
+    /// the number of samples that hit their ``max_sample_time`` and were extrapolated
+    pub truncated_samples: usize,
+    /// the hosts that ran more children than they have CPUs for in any sample, see
+    /// [`Placement::oversubscribed_hosts`]
+    pub oversubscribed_hosts: Vec<String>,
+    /// the worlds spawned for the samples, the children started in them and the CPU time they
+    /// reported, see [`launches`](crate::launches)
+    pub launches: Launches,
+    /// the size of the Rayon pool of each child, if it built one, see
+    /// [`BenchRunner::threads`](crate::runner::BenchRunner::threads)
+    pub threads: Option<usize>,
+    /// the environment variables the children were started with, see
+    /// [`BenchRunner::tuning`](crate::runner::BenchRunner::tuning)
+    pub tuning: Vec<(String, String)>,
+    /// the placement of the children requested from MPI, see
+    /// [`BenchRunner::placement`](crate::runner::BenchRunner::placement)
+    pub placement_mode: Option<PlacementMode>,
+    /// the placement the children of the first spawned sample achieved, see
+    /// [`PlacementMode::achieved`]
+    pub achieved_placement: Option<PlacementMode>,
+    /// whether MPI permuted the ranks of the Cartesian communicator of the kernel in any spawned
+    /// sample, if it was created with reordering, see [`topology`](crate::topology)
+    pub ranks_permuted: Option<bool>,
+    /// the mean time per iteration of each counter the kernel recorded, in the order they were
+    /// first recorded, see [`counters`](crate::counters)
+    pub counters: Vec<(String, Duration)>,
+}
+
+/// Empty metrics in the current format, to fill in only some of the fields.
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            version: METRICS_VERSION,
+            id: String::new(),
+            kernel: String::new(),
+            world_size: 0,
+            samples: 0,
+            mean: Duration::ZERO,
+            throughput: None,
+            spawn_share: None,
+            spawn_overhead: None,
+            overhead: None,
+            max_variation: None,
+            ranks: Vec::new(),
+            hosts: Vec::new(),
+            argument: None,
+            truncated_samples: 0,
+            oversubscribed_hosts: Vec::new(),
+            launches: Launches::NONE,
+            threads: None,
+            tuning: Vec::new(),
+            placement_mode: None,
+            achieved_placement: None,
+            ranks_permuted: None,
+            counters: Vec::new(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Collect the metrics of a benchmark of this run.
+    pub fn of(benchmark: &Benchmark) -> Self {
+        let samples: Vec<(u64, Vec<Duration>)> = benchmark
+            .samples
+            .iter()
+            .map(|sample| (sample.iterations, sample.per_rank.clone()))
+            .collect();
+        let overheads: Vec<Overhead> = benchmark
+            .samples
+            .iter()
+            .filter_map(|sample| sample.overhead)
+            .collect();
+        let spawns: Vec<Duration> = overheads.iter().map(Overhead::connect).collect();
+        let reported: Vec<Duration> = benchmark
+            .samples
+            .iter()
+            .filter(|sample| sample.iterations > 0)
+            .map(|sample| per_iteration(sample.reported, sample.iterations))
+            .collect();
+        let spawned: Vec<&SampleRecord> = benchmark
+            .samples
+            .iter()
+            .filter(|sample| sample.overhead.is_some())
+            .collect();
+        let spawn_total: f64 = spawns.iter().map(Duration::as_secs_f64).sum();
+        let measured_total: f64 = spawned
+            .iter()
+            .map(|sample| sample.reported.as_secs_f64())
+            .sum();
+        let hosts = benchmark
+            .samples
+            .iter()
+            .find_map(|sample| sample.placement.as_ref())
+            .map_or_else(Vec::new, |placement| {
+                placement
+                    .ranks
+                    .iter()
+                    .map(|rank| rank.host.clone())
+                    .collect()
+            });
+        let mut oversubscribed_hosts: Vec<String> = benchmark
+            .samples
+            .iter()
+            .filter_map(|sample| sample.placement.as_ref())
+            .flat_map(Placement::oversubscribed_hosts)
+            .collect();
+        oversubscribed_hosts.sort();
+        oversubscribed_hosts.dedup();
+        Self {
+            version: METRICS_VERSION,
+            id: benchmark.id.clone(),
+            kernel: benchmark
+                .samples
+                .first()
+                .map_or_else(String::new, |sample| sample.kernel.clone()),
+            world_size: benchmark
+                .samples
+                .iter()
+                .map(|sample| sample.per_rank.len())
+                .max()
+                .unwrap_or(0),
+            samples: benchmark.samples.len(),
+            mean: crate::timing::mean(&reported),
+            throughput: benchmark.throughput.clone(),
+            spawn_share: (spawn_total + measured_total > 0.0)
+                .then(|| spawn_total / (spawn_total + measured_total)),
+            spawn_overhead: (!spawns.is_empty()).then(|| crate::timing::mean(&spawns)),
+            overhead: Overhead::mean(&overheads),
+            max_variation: benchmark.max_variation(),
+            ranks: rank_statistics(&samples),
+            hosts,
+            argument: benchmark
+                .samples
+                .iter()
+                .find_map(|sample| sample.argument.clone()),
+            truncated_samples: benchmark
+                .samples
+                .iter()
+                .filter(|sample| sample.truncated)
+                .count(),
+            oversubscribed_hosts,
+            launches: Launches::sum(benchmark.samples.iter().map(|sample| &sample.launches)),
+            threads: benchmark.samples.iter().find_map(|sample| sample.threads),
+            tuning: benchmark
+                .samples
+                .first()
+                .map_or_else(Vec::new, |sample| sample.tuning.clone()),
+            placement_mode: benchmark
+                .samples
+                .first()
+                .and_then(|sample| sample.placement_mode),
+            achieved_placement: benchmark
+                .samples
+                .iter()
+                .find_map(|sample| sample.placement.as_ref())
+                .and_then(PlacementMode::achieved),
+            ranks_permuted: benchmark
+                .samples
+                .iter()
+                .filter_map(|sample| sample.ranks_permuted)
+                .reduce(|permuted, sample| permuted || sample),
+            counters: crate::counters::mean(
+                benchmark
+                    .samples
+                    .iter()
+                    .map(|sample| sample.counters.as_slice()),
+            ),
+        }
+    }
+
+    /// The metrics as JSON.
+    pub fn to_json(&self) -> String {
+        let nanos = |time: Duration| crate::timing::duration_to_nanos(time).to_string();
+        let column = |field: fn(&RankStatistics) -> Duration| {
+            self.ranks
+                .iter()
+                .map(|rank| nanos(field(rank)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let phase = |phase: fn(&Overhead) -> Duration| {
+            self.overhead
+                .as_ref()
+                .map_or_else(|| "null".to_string(), |overhead| nanos(phase(overhead)))
+        };
+        let hosts: Vec<String> = self
+            .hosts
+            .iter()
+            .map(|host| crate::manifest::json_string(host))
+            .collect();
+        let oversubscribed: Vec<String> = self
+            .oversubscribed_hosts
+            .iter()
+            .map(|host| crate::manifest::json_string(host))
+            .collect();
+        let tuning: Vec<String> = self
+            .tuning
+            .iter()
+            .map(|(name, value)| crate::manifest::json_string(&format!("{}={}", name, value)))
+            .collect();
+        let mode = |mode: Option<PlacementMode>| {
+            mode.map_or_else(
+                || "null".to_string(),
+                |mode| crate::manifest::json_string(&mode.to_string()),
+            )
+        };
+        let counter_names: Vec<String> = self
+            .counters
+            .iter()
+            .map(|(name, _)| crate::manifest::json_string(name))
+            .collect();
+        let argument = |field: fn(&ArgumentValue) -> Option<&str>| {
+            self.argument
+                .as_ref()
+                .and_then(field)
+                .map_or_else(|| "null".to_string(), crate::manifest::json_string)
+        };
+        format!(
+            r#"{{"version":{},"world_size":{},"argument_value":{},"samples":{},"mean_ns":{},"throughput_per_iteration":{},"spawn_share":{},"spawn_overhead_ns":{},"spawn_ns":{},"merge_ns":{},"broadcast_ns":{},"reduce_ns":{},"max_variation":{},"truncated_samples":{},"spawn_rounds":{},"children_started":{},"child_cpu_ns":{},"threads":{},"tuning":[{}],"placement_mode":{},"achieved_placement":{},"ranks_permuted":{},"counter_names":[{}],"counter_mean_ns":[{}],"rank_mean_ns":[{}],"rank_min_ns":[{}],"rank_max_ns":[{}],"hosts":[{}],"oversubscribed_hosts":[{}],"throughput_unit":{},"argument_type":{},"argument_display":{},"argument_debug":{},"kernel":{},"id":{}}}"#,
+            self.version,
+            self.world_size,
+            self.argument
+                .as_ref()
+                .and_then(|argument| argument.value)
+                .filter(|value| value.is_finite())
+                .map_or_else(|| "null".to_string(), |value| value.to_string()),
+            self.samples,
+            nanos(self.mean),
+            self.throughput.as_ref().map_or_else(
+                || "null".to_string(),
+                |throughput| throughput.per_iteration.to_string()
+            ),
+            self.spawn_share
+                .map_or_else(|| "null".to_string(), |share| share.to_string()),
+            self.spawn_overhead
+                .map_or_else(|| "null".to_string(), nanos),
+            phase(|overhead| overhead.spawn),
+            phase(|overhead| overhead.merge),
+            phase(|overhead| overhead.broadcast),
+            phase(|overhead| overhead.reduce),
+            self.max_variation
+                .map_or_else(|| "null".to_string(), |variation| variation.to_string()),
+            self.truncated_samples,
+            self.launches.spawn_rounds,
+            self.launches.children,
+            nanos(self.launches.child_cpu),
+            self.threads
+                .map_or_else(|| "null".to_string(), |threads| threads.to_string()),
+            tuning.join(","),
+            mode(self.placement_mode),
+            mode(self.achieved_placement),
+            self.ranks_permuted
+                .map_or_else(|| "null".to_string(), |permuted| permuted.to_string()),
+            counter_names.join(","),
+            self.counters
+                .iter()
+                .map(|(_, mean)| nanos(*mean))
+                .collect::<Vec<_>>()
+                .join(","),
+            column(|rank| rank.mean),
+            column(|rank| rank.min),
+            column(|rank| rank.max),
+            hosts.join(","),
+            oversubscribed.join(","),
+            self.throughput.as_ref().map_or_else(
+                || "null".to_string(),
+                |throughput| crate::manifest::json_string(&throughput.unit)
+            ),
+            argument(|argument| Some(&argument.type_name)),
+            argument(|argument| argument.display.as_deref()),
+            argument(|argument| argument.debug.as_deref()),
+            crate::manifest::json_string(&self.kernel),
+            crate::manifest::json_string(&self.id)
+        )
+    }
+
+    /// Parse metrics written by [`to_json`](Self::to_json). Fails if the JSON is malformed or was
+    /// written by a newer version of mpirion. Files of version 1 have no mean, throughput or spawn
+    /// share, which are read as zero and ``None``, files before version 3 have no overhead of the
+    /// phases of the protocol, files before version 4 no argument, files before version 5 no
+    /// truncated samples or oversubscribed hosts, files before version 6 no launches, which are
+    /// read as none, files before version 7 no size of the thread pool, files before version 8 no
+    /// counters, files before version 9 no tuning, files before version 10 no placement mode, and files
+    /// before version 11 no permutation of the ranks.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::launches::Launches;
+    /// use mpirion::placement::PlacementMode;
+    /// use mpirion::report::{ArgumentValue, Metrics, Overhead, RankStatistics, Throughput};
+    /// use std::time::Duration;
+    ///
+    /// let metrics = Metrics {
+    ///     id: "scan/8".to_string(),
+    ///     kernel: "scan_kernel".to_string(),
+    ///     world_size: 2,
+    ///     samples: 100,
+    ///     mean: Duration::from_nanos(10_500),
+    ///     throughput: Some(Throughput { unit: "Bytes".to_string(), per_iteration: 4096 }),
+    ///     spawn_share: Some(0.25),
+    ///     spawn_overhead: Some(Duration::from_millis(120)),
+    ///     overhead: Some(Overhead {
+    ///         spawn: Duration::from_millis(100),
+    ///         merge: Duration::from_millis(20),
+    ///         broadcast: Duration::from_micros(300),
+    ///         reduce: Duration::from_micros(200),
+    ///     }),
+    ///     max_variation: Some(0.125),
+    ///     ranks: vec![
+    ///         RankStatistics { mean: Duration::from_micros(10), min: Duration::from_micros(9), max: Duration::from_micros(12) },
+    ///         RankStatistics { mean: Duration::from_micros(11), min: Duration::from_micros(10), max: Duration::from_micros(14) },
+    ///     ],
+    ///     hosts: vec!["node01".to_string(), "node02".to_string()],
+    ///     argument: Some(ArgumentValue::of(&8usize)),
+    ///     truncated_samples: 3,
+    ///     oversubscribed_hosts: vec!["node02".to_string()],
+    ///     launches: Launches { spawn_rounds: 101, children: 202, child_cpu: Duration::from_millis(23_400) },
+    ///     threads: Some(8),
+    ///     tuning: vec![("OMPI_MCA_coll_tuned_bcast_algorithm".to_string(), "6".to_string())],
+    ///     placement_mode: Some(PlacementMode::PerNode),
+    ///     achieved_placement: Some(PlacementMode::Spread(2)),
+    ///     ranks_permuted: Some(true),
+    ///     counters: vec![("wait".to_string(), Duration::from_micros(4))],
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(Metrics::parse(&metrics.to_json()), Ok(metrics));
+    ///
+    /// let future = r#"{"version":999,"world_size":1}"#;
+    /// assert!(Metrics::parse(future).unwrap_err().contains("version 999"));
+    /// ```
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let malformed = |field: &str| format!("malformed {}: no valid `{}`", METRICS_JSON, field);
+        let integer = |field: &str| {
+            number_field(json, field)
+                .flatten()
+                .filter(|number| *number >= 0.0)
+                .map(|number| number as u64)
+                .ok_or_else(|| malformed(field))
+        };
+        let column = |field: &str| {
+            number_array(json, field)
+                .map(|nanos| {
+                    nanos
+                        .into_iter()
+                        .map(|nanos| Duration::from_nanos(nanos as u64))
+                        .collect::<Vec<_>>()
+                })
+                .ok_or_else(|| malformed(field))
+        };
+
+        let version = integer("version")?;
+        if version > METRICS_VERSION {
+            return Err(format!(
+                "{} has version {}, but this version of mpirion reads up to version {}",
+                METRICS_JSON, version, METRICS_VERSION
+            ));
+        }
+        let (mean, throughput, spawn_share) = if version >= 2 {
+            let throughput = match (
+                string_field(json, "throughput_unit"),
+                number_field(json, "throughput_per_iteration"),
+            ) {
+                (Some(unit), Some(Some(per_iteration))) => Some(Throughput {
+                    unit,
+                    per_iteration: per_iteration as u64,
+                }),
+                (None, Some(None)) => None,
+                _ => return Err(malformed("throughput_per_iteration")),
+            };
+            let spawn_share =
+                number_field(json, "spawn_share").ok_or_else(|| malformed("spawn_share"))?;
+            (
+                Duration::from_nanos(integer("mean_ns")?),
+                throughput,
+                spawn_share,
+            )
+        } else {
+            (Duration::ZERO, None, None)
+        };
+        let overhead = if version >= 3 {
+            let mut phases = [Duration::ZERO; 4];
+            let mut local = false;
+            for (phase, name) in phases.iter_mut().zip(Overhead::PHASES) {
+                let field = format!("{}_ns", name);
+                match number_field(json, &field).ok_or_else(|| malformed(&field))? {
+                    Some(nanos) => *phase = Duration::from_nanos(nanos as u64),
+                    None => local = true,
+                }
+            }
+            let [spawn, merge, broadcast, reduce] = phases;
+            (!local).then_some(Overhead {
+                spawn,
+                merge,
+                broadcast,
+                reduce,
+            })
+        } else {
+            None
+        };
+        let argument = if version >= 4 {
+            string_field(json, "argument_type").map(|type_name| ArgumentValue {
+                type_name,
+                display: string_field(json, "argument_display"),
+                debug: string_field(json, "argument_debug"),
+                value: number_field(json, "argument_value").flatten(),
+            })
+        } else {
+            None
+        };
+        let (truncated_samples, oversubscribed_hosts) = if version >= 5 {
+            (
+                integer("truncated_samples")? as usize,
+                string_array(json, "oversubscribed_hosts")
+                    .ok_or_else(|| malformed("oversubscribed_hosts"))?,
+            )
+        } else {
+            (0, Vec::new())
+        };
+        let launches = if version >= 6 {
+            Launches {
+                spawn_rounds: integer("spawn_rounds")?,
+                children: integer("children_started")?,
+                child_cpu: Duration::from_nanos(integer("child_cpu_ns")?),
+            }
+        } else {
+            Launches::NONE
+        };
+        let threads = if version >= 7 {
+            number_field(json, "threads")
+                .ok_or_else(|| malformed("threads"))?
+                .map(|threads| threads as usize)
+        } else {
+            None
+        };
+        let tuning = if version >= 9 {
+            string_array(json, "tuning")
+                .ok_or_else(|| malformed("tuning"))?
+                .into_iter()
+                .map(|var| match var.split_once('=') {
+                    Some((name, value)) => Ok((name.to_string(), value.to_string())),
+                    None => Err(malformed("tuning")),
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+        let (placement_mode, achieved_placement) = if version >= 10 {
+            let mode = |field: &str| {
+                string_field(json, field)
+                    .map(|mode| mode.parse::<PlacementMode>())
+                    .transpose()
+                    .map_err(|_| malformed(field))
+            };
+            (mode("placement_mode")?, mode("achieved_placement")?)
+        } else {
+            (None, None)
+        };
+        let ranks_permuted = if version >= 11 {
+            bool_field(json, "ranks_permuted").ok_or_else(|| malformed("ranks_permuted"))?
+        } else {
+            None
+        };
+        let counters = if version >= 8 {
+            let names =
+                string_array(json, "counter_names").ok_or_else(|| malformed("counter_names"))?;
+            let means = column("counter_mean_ns")?;
+            if names.len() != means.len() {
+                return Err(format!(
+                    "malformed {}: the counter names and means differ in length",
+                    METRICS_JSON
+                ));
+            }
+            names.into_iter().zip(means).collect()
+        } else {
+            Vec::new()
+        };
+        let (means, mins, maxs) = (
+            column("rank_mean_ns")?,
+            column("rank_min_ns")?,
+            column("rank_max_ns")?,
+        );
+        if means.len() != mins.len() || means.len() != maxs.len() {
+            return Err(format!(
+                "malformed {}: the rank columns differ in length",
+                METRICS_JSON
+            ));
+        }
+        Ok(Self {
+            version,
+            id: string_field(json, "id").ok_or_else(|| malformed("id"))?,
+            kernel: string_field(json, "kernel").ok_or_else(|| malformed("kernel"))?,
+            world_size: integer("world_size")? as usize,
+            samples: integer("samples")? as usize,
+            mean,
+            throughput,
+            spawn_share,
+            spawn_overhead: number_field(json, "spawn_overhead_ns")
+                .ok_or_else(|| malformed("spawn_overhead_ns"))?
+                .map(|nanos| Duration::from_nanos(nanos as u64)),
+            overhead,
+            max_variation: number_field(json, "max_variation")
+                .ok_or_else(|| malformed("max_variation"))?,
+            ranks: means
+                .into_iter()
+                .zip(mins)
+                .zip(maxs)
+                .map(|((mean, min), max)| RankStatistics { mean, min, max })
+                .collect(),
+            hosts: string_array(json, "hosts").ok_or_else(|| malformed("hosts"))?,
+            argument,
+            truncated_samples,
+            oversubscribed_hosts,
+            launches,
+            threads,
+            tuning,
+            placement_mode,
+            achieved_placement,
+            ranks_permuted,
+            counters,
+        })
+    }
+}
+
+/// Read the metrics from the ``mpirion.json`` in the given benchmark directory of criterion, e.g.
+/// ``target/criterion/scan/8``.
+pub fn load(directory: impl AsRef<Path>) -> Result<Metrics, String> {
+    let path = directory.as_ref().join(METRICS_JSON);
+    let json = std::fs::read_to_string(&path)
+        .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+    Metrics::parse(&json)
+}
+
+/// Write the metrics into the ``mpirion.json`` in the given benchmark directory. The file is
+/// replaced atomically, so readers never see a partially written file.
+pub fn save(directory: impl AsRef<Path>, metrics: &Metrics) -> std::io::Result<()> {
+    write_atomically(&directory.as_ref().join(METRICS_JSON), &metrics.to_json())
+}
+
+/// Write ``mpirion-samples.json`` next to the ``sample.json`` of criterion the samples of the
+/// benchmark were matched with. Every sample criterion saved has a recorded sample, see
+/// [`benchmarks`], so the file has an entry for each of them, and it is replaced atomically like
+/// ``mpirion.json``.
+pub fn save_samples(benchmark: &Benchmark, export: SampleExport) -> std::io::Result<PathBuf> {
+    let directory = benchmark
+        .sample_file
+        .parent()
+        .unwrap_or(&benchmark.directory);
+    let path = directory.join(SAMPLES_JSON);
+    write_atomically(&path, &samples_to_json(&benchmark.samples, export))?;
+    Ok(path)
+}
+
+/// Replace a file with the given contents, so readers see either the old or the new file.
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    let temporary = PathBuf::from(temporary);
+    std::fs::write(&temporary, contents)?;
+    if let Err(error) = std::fs::rename(&temporary, path) {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(error);
+    }
+    Ok(())
+}
+
+impl Benchmark {
+    /// The largest [coefficient of variation](crate::variance::coefficient_of_variation) of the
+    /// times of the ranks in a sample of the benchmark.
+    pub fn max_variation(&self) -> Option<f64> {
+        self.samples
+            .iter()
+            .filter_map(|sample| crate::variance::coefficient_of_variation(&sample.per_rank))
+            .reduce(f64::max)
+    }
+}
+
+/// Assign the recorded samples to the benchmarks in the output directory of criterion, by the id
+/// of the benchmark they were recorded for. Only benchmarks declared through the
+/// [`MpirionContext`](crate::MpirionContext) have an id, so the benchmarks declared directly with
+/// criterion are not assigned any samples.
+pub fn benchmarks(home: &Path) -> Vec<Benchmark> {
+    match_benchmarks(home, &SAMPLES.lock().unwrap().clone())
+}
+
+/// The number of samples recorded so far, which marks the start of a group for
+/// [`group_benchmarks`].
+pub fn samples_recorded() -> usize {
+    SAMPLES.lock().unwrap().len()
+}
+
+/// Assign the samples recorded since [`samples_recorded`] returned ``first_sample`` to the
+/// benchmarks in the output directory of criterion, like [`benchmarks`].
+pub fn group_benchmarks(home: &Path, first_sample: usize) -> Vec<Benchmark> {
+    let recorded = SAMPLES
+        .lock()
+        .unwrap()
+        .get(first_sample..)
+        .map(<[SampleRecord]>::to_vec)
+        .unwrap_or_default();
+    match_benchmarks(home, &recorded)
+}
+
+fn match_benchmarks(home: &Path, recorded: &[SampleRecord]) -> Vec<Benchmark> {
+    let mut by_benchmark: HashMap<&str, Vec<&SampleRecord>> = HashMap::new();
+    for sample in recorded {
+        if let Some(id) = &sample.benchmark {
+            by_benchmark.entry(id).or_default().push(sample);
+        }
+    }
+
+    // the newest saved samples of each benchmark directory of a benchmark that ran
+    let mut matched: HashMap<PathBuf, (SystemTime, Benchmark)> = HashMap::new();
+    for sample_file in find_files(home, "sample.json") {
+        let Some(saved) = sample_file
+            .parent()
+            .map(|parent| parent.join("benchmark.json"))
+        else {
+            continue;
+        };
+        let (Ok(samples), Ok(benchmark)) = (
+            std::fs::read_to_string(&sample_file),
+            std::fs::read_to_string(&saved),
+        ) else {
+            continue;
+        };
+        let (Some(samples), Some(id)) =
+            (parse_samples(&samples), string_field(&benchmark, "full_id"))
+        else {
+            continue;
+        };
+        let Some(ran) = by_benchmark.get(id.as_str()) else {
+            continue;
+        };
+        // criterion only saves the last samples, the calls of its warm-up come before them
+        let Some(warm_up) = ran.len().checked_sub(samples.len()) else {
+            continue;
+        };
+        let ran = &ran[warm_up..];
+        if samples.is_empty()
+            || samples
+                .iter()
+                .zip(ran)
+                .any(|((iterations, _), sample)| *iterations != sample.iterations)
+        {
+            continue;
+        }
+
+        let Some(directory) = sample_file
+            .parent()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+        else {
+            continue;
+        };
+        let modified = std::fs::metadata(&sample_file)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if matched
+            .get(&directory)
+            .is_some_and(|(newest, _)| *newest >= modified)
+        {
+            continue;
+        }
+        let benchmark = Benchmark {
+            throughput: parse_throughput(&benchmark),
+            id,
+            directory: directory.clone(),
+            samples: ran.iter().map(|sample| (*sample).clone()).collect(),
+            sample_file: sample_file.clone(),
+        };
+        matched.insert(directory, (modified, benchmark));
+    }
+
+    let mut benchmarks: Vec<Benchmark> = matched
+        .into_values()
+        .map(|(_, benchmark)| benchmark)
+        .collect();
+    benchmarks.sort_by(|a, b| a.id.cmp(&b.id));
+    benchmarks
+}
+
+/// All files with the given name below a directory.
+pub(crate) fn find_files(directory: &Path, name: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.file_name().is_some_and(|file| file == name) {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+/// Write the reports of all benchmarks of this run into the output directory of criterion. Errors
+/// are printed, but don't fail the run. Called by ``mpirion_main!`` after all groups ran.
+pub fn write() {
+    if crate::cli::options().quick || SAMPLES.lock().unwrap().is_empty() {
+        return;
+    }
+
+    let home = criterion_home();
+    crate::scaling::write(&home, &SAMPLES.lock().unwrap().clone());
+    let benchmarks = benchmarks(&home);
+    if benchmarks.is_empty() {
+        return;
+    }
+
+    let mut written = Vec::new();
+    for benchmark in &benchmarks {
+        match write_distribution(benchmark) {
+            Ok(()) => written.push(benchmark),
+            Err(error) => eprintln!(
+                "mpirion: could not write the reports of `{}` to {}: {}",
+                benchmark.id,
+                benchmark.directory.display(),
+                error
+            ),
+        }
+    }
+
+    let threshold = crate::variance::threshold();
+    if let Err(error) = std::fs::write(
+        home.join(INDEX_HTML),
+        render_index(&home, &written, threshold),
+    ) {
+        eprintln!(
+            "mpirion: could not write {}: {}",
+            home.join(INDEX_HTML).display(),
+            error
+        );
+    }
+
+    let varying: Vec<&str> = benchmarks
+        .iter()
+        .filter(|benchmark| {
+            benchmark
+                .max_variation()
+                .is_some_and(|variation| crate::variance::exceeds(variation, threshold))
+        })
+        .map(|benchmark| benchmark.id.as_str())
+        .collect();
+    if !varying.is_empty() {
+        eprintln!(
+            "mpirion: the times of the ranks varied by more than {}% in {} benchmark(s): {}",
+            threshold.unwrap_or_default(),
+            varying.len(),
+            varying.join(", ")
+        );
+    }
+}
+
+/// Print the phases of the protocol whose overhead changed since the ``mpirion.json`` of the last
+/// run, before it is replaced.
+pub(crate) fn compare_overhead(directory: &Path, metrics: &Metrics) {
+    let (
+        Ok(Metrics {
+            overhead: Some(last),
+            ..
+        }),
+        Some(now),
+    ) = (load(directory), &metrics.overhead)
+    else {
+        return;
+    };
+    for change in overhead_changes(&last, now, overhead_change_threshold()) {
+        eprintln!("mpirion: `{}`: {}", metrics.id, change);
+    }
+}
+
+fn write_distribution(benchmark: &Benchmark) -> std::io::Result<()> {
+    let samples: Vec<(u64, Vec<Duration>)> = benchmark
+        .samples
+        .iter()
+        .map(|sample| (sample.iterations, sample.per_rank.clone()))
+        .collect();
+    let statistics = rank_statistics(&samples);
+    std::fs::write(
+        benchmark.directory.join(DISTRIBUTION_SVG),
+        render_svg(&statistics),
+    )?;
+    std::fs::write(
+        benchmark.directory.join(DISTRIBUTION_HTML),
+        render_html(&benchmark.id, samples.len(), &statistics),
+    )?;
+
+    let placements: Vec<&Placement> = benchmark
+        .samples
+        .iter()
+        .filter_map(|sample| sample.placement.as_ref())
+        .collect();
+    if placements.is_empty() {
+        return Ok(());
+    }
+    std::fs::write(
+        benchmark.directory.join(PLACEMENT_JSON),
+        placements_to_json(&placements),
+    )
+}
+
+/// The times of the ranks in each sample, as written into ``mpirion-samples.json``. The entry of
+/// each sample has the ``index`` of the sample in the ``iters`` and ``times`` arrays of the
+/// ``sample.json`` of criterion, and its iterations and time, to check the join. With
+/// [`SampleExport::Raw`], it lists the total time of each rank over the iterations of the sample
+/// in ``rank_ns``, and with [`SampleExport::Summary`] the fastest, mean and slowest of these times
+/// and the slowest rank.
+///
+/// # Example
+/// ```rust
+/// use mpirion::launches::Launches;
+/// use mpirion::cli::SampleExport;
+/// use mpirion::report::{samples_to_json, SampleRecord};
+/// use std::time::Duration;
+///
+/// let sample = SampleRecord {
+///     benchmark: None,
+///     kernel: "scan_kernel".to_string(),
+///     iterations: 10,
+///     reported: Duration::from_nanos(1_100),
+///     per_rank: vec![Duration::from_nanos(1_000), Duration::from_nanos(1_200)],
+///     placement: None,
+///     overhead: None,
+///     argument: None,
+///     truncated: false,
+///     launches: Launches::NONE,
+///     threads: None,
+///     tuning: Vec::new(),
+///     placement_mode: None,
+///     ranks_permuted: None,
+///     counters: Vec::new(),
+/// };
+/// assert_eq!(
+///     samples_to_json(&[sample.clone()], SampleExport::Raw),
+///     r#"{"version":1,"export":"raw","samples":[{"index":0,"iterations":10,"time_ns":1100,"rank_ns":[1000,1200]}]}"#
+/// );
+/// assert_eq!(
+///     samples_to_json(&[sample], SampleExport::Summary),
+///     r#"{"version":1,"export":"summary","samples":[{"index":0,"iterations":10,"time_ns":1100,"rank_min_ns":1000,"rank_mean_ns":1100,"rank_max_ns":1200,"slowest_rank":1}]}"#
+/// );
+/// ```
+pub fn samples_to_json(samples: &[SampleRecord], export: SampleExport) -> String {
+    let nanos = crate::timing::duration_to_nanos;
+    let entries: Vec<String> = samples
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| {
+            let ranks = match export {
+                SampleExport::Raw => {
+                    let times: Vec<String> = sample
+                        .per_rank
+                        .iter()
+                        .map(|time| nanos(*time).to_string())
+                        .collect();
+                    format!(r#""rank_ns":[{}]"#, times.join(","))
+                }
+                SampleExport::Summary => {
+                    let slowest = sample
+                        .per_rank
+                        .iter()
+                        .enumerate()
+                        .max_by_key(|(_, time)| **time);
+                    format!(
+                        r#""rank_min_ns":{},"rank_mean_ns":{},"rank_max_ns":{},"slowest_rank":{}"#,
+                        nanos(sample.per_rank.iter().min().copied().unwrap_or_default()),
+                        nanos(crate::timing::mean(&sample.per_rank)),
+                        nanos(slowest.map(|(_, time)| *time).unwrap_or_default()),
+                        slowest.map_or_else(|| "null".to_string(), |(rank, _)| rank.to_string())
+                    )
+                }
+            };
+            format!(
+                r#"{{"index":{},"iterations":{},"time_ns":{},{}}}"#,
+                index,
+                sample.iterations,
+                nanos(sample.reported),
+                ranks
+            )
+        })
+        .collect();
+    let export = match export {
+        SampleExport::Raw => "raw",
+        SampleExport::Summary => "summary",
+    };
+    format!(
+        r#"{{"version":{},"export":"{}","samples":[{}]}}"#,
+        SAMPLES_VERSION,
+        export,
+        entries.join(",")
+    )
+}
+
+/// The distinct placements of the samples of a benchmark, in the order they first occurred, with
+/// the number of samples that ran with each. Usually, all samples of a benchmark run on the same
+/// ranks, so there is a single placement.
+///
+/// # Example
+/// ```rust
+/// use mpirion::placement::{Placement, RankPlacement};
+/// use mpirion::report::placements_to_json;
+///
+/// let on = |host: &str| Placement { ranks: vec![RankPlacement { host: host.to_string(), cpus: None }] };
+/// let (first, second) = (on("node01"), on("node02"));
+/// assert_eq!(
+///     placements_to_json(&[&first, &first, &second]),
+///     r#"{"placements":[{"samples":2,"ranks":[{"rank":0,"host":"node01","cpus":null}]},{"samples":1,"ranks":[{"rank":0,"host":"node02","cpus":null}]}]}"#
+/// );
+/// ```
+pub fn placements_to_json(placements: &[&Placement]) -> String {
+    let mut distinct: Vec<(&Placement, usize)> = Vec::new();
+    for placement in placements {
+        match distinct.iter_mut().find(|(seen, _)| seen == placement) {
+            Some((_, samples)) => *samples += 1,
+            None => distinct.push((placement, 1)),
+        }
+    }
+
+    let mut json = String::from(r#"{"placements":["#);
+    for (index, (placement, samples)) in distinct.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            r#"{{"samples":{},"ranks":{}}}"#,
+            samples,
+            placement.to_json()
+        );
+    }
+    json.push_str("]}");
+    json
+}
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 320.0;
+const MARGIN: f64 = 48.0;
+
+/// A bar chart of the mean time per iteration of each rank, with a whisker from the fastest to the
+/// slowest sample of the rank.
+pub fn render_svg(statistics: &[RankStatistics]) -> String {
+    let scale = statistics
+        .iter()
+        .map(|rank| rank.max)
+        .max()
+        .unwrap_or_default()
+        .as_secs_f64();
+    let plot_width = WIDTH - 2.0 * MARGIN;
+    let plot_height = HEIGHT - 2.0 * MARGIN;
+    let slot = plot_width / statistics.len().max(1) as f64;
+    let y = |time: Duration| {
+        let ratio = if scale > 0.0 {
+            time.as_secs_f64() / scale
+        } else {
+            0.0
+        };
+        HEIGHT - MARGIN - ratio * plot_height
+    };
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="11">"#
+    );
+    let _ = write!(
+        svg,
+        r#"<line x1="{MARGIN}" y1="{bottom}" x2="{right}" y2="{bottom}" stroke="black"/><line x1="{MARGIN}" y1="{MARGIN}" x2="{MARGIN}" y2="{bottom}" stroke="black"/>"#,
+        bottom = HEIGHT - MARGIN,
+        right = WIDTH - MARGIN
+    );
+    let _ = write!(
+        svg,
+        r#"<text x="{x}" y="{y}" text-anchor="end">{scale:?}</text><text x="{x}" y="{bottom}" text-anchor="end">0</text>"#,
+        x = MARGIN - 4.0,
+        y = MARGIN + 4.0,
+        scale = Duration::from_secs_f64(scale),
+        bottom = HEIGHT - MARGIN
+    );
+    // label at most about 16 ranks, so the labels don't overlap
+    let label_every = statistics.len().div_ceil(16).max(1);
+    for (rank, bar) in statistics.iter().enumerate() {
+        let left = MARGIN + rank as f64 * slot;
+        let center = left + slot / 2.0;
+        let _ = write!(
+            svg,
+            r##"<rect x="{x:.1}" y="{top:.1}" width="{width:.1}" height="{height:.1}" fill="#1f78b4"><title>rank {rank}: mean {mean:?}, min {min:?}, max {max:?}</title></rect>"##,
+            x = left + slot * 0.15,
+            top = y(bar.mean),
+            width = slot * 0.7,
+            height = HEIGHT - MARGIN - y(bar.mean),
+            mean = bar.mean,
+            min = bar.min,
+            max = bar.max
+        );
+        let _ = write!(
+            svg,
+            r#"<line x1="{center:.1}" y1="{min:.1}" x2="{center:.1}" y2="{max:.1}" stroke="black"/>"#,
+            min = y(bar.min),
+            max = y(bar.max)
+        );
+        if rank % label_every == 0 {
+            let _ = write!(
+                svg,
+                r#"<text x="{center:.1}" y="{y:.1}" text-anchor="middle">{rank}</text>"#,
+                y = HEIGHT - MARGIN + 14.0
+            );
+        }
+    }
+    let _ = write!(
+        svg,
+        r#"<text x="{x}" y="{y}" text-anchor="middle">rank</text></svg>"#,
+        x = WIDTH / 2.0,
+        y = HEIGHT - MARGIN + 32.0
+    );
+    svg
+}
+
+fn render_html(id: &str, samples: usize, statistics: &[RankStatistics]) -> String {
+    let mut rows = String::new();
+    for (rank, row) in statistics.iter().enumerate() {
+        let _ = write!(
+            rows,
+            "<tr><td>{}</td><td>{:?}</td><td>{:?}</td><td>{:?}</td></tr>",
+            rank, row.mean, row.min, row.max
+        );
+    }
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{id} - rank distribution</title></head><body>\
+         <h1>{id}</h1><p>Time per iteration of each rank over {samples} samples. The bars show the mean, the whiskers \
+         the fastest and the slowest sample. <a href=\"report/index.html\">Criterion report</a></p>\
+         <img src=\"{svg}\" alt=\"rank distribution\"><table><tr><th>rank</th><th>mean</th><th>min</th><th>max</th></tr>\
+         {rows}</table></body></html>\n",
+        id = escape_html(id),
+        svg = DISTRIBUTION_SVG
+    )
+}
+
+fn render_index(home: &Path, benchmarks: &[&Benchmark], threshold: Option<f64>) -> String {
+    let mut items = String::new();
+    for benchmark in benchmarks {
+        let page = benchmark.directory.join(DISTRIBUTION_HTML);
+        let link = page.strip_prefix(home).unwrap_or(&page);
+        let link: Vec<String> = link
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let variation = match benchmark.max_variation() {
+            Some(variation) if crate::variance::exceeds(variation, threshold) => {
+                format!(
+                    " <strong>high variance across ranks, up to {:.1}%</strong>",
+                    variation * 100.0
+                )
+            }
+            _ => String::new(),
+        };
+        let _ = write!(
+            items,
+            "<li><a href=\"{}\">{}</a>{}</li>",
+            escape_html(&link.join("/")),
+            escape_html(&benchmark.id),
+            variation
+        );
+    }
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Rank distributions</title></head><body>\
+         <h1>Rank distributions</h1><ul>{}</ul></body></html>\n",
+        items
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,237 @@
+//! Sinks for the results mpirion collects. Every benchmark of a run is passed to the reporters as
+//! a [`BenchRecord`] with its [`Metrics`] and raw samples once its group ends, and the whole run as
+//! a [`RunSummary`] at the end of ``mpirion_main!``. Teams that want the results in a database, a
+//! bucket or a dashboard implement [`MpirionReporter`] and register it with [`register_reporter`]
+//! from the ``init`` function of ``mpirion_main!``, which runs before the benchmarks.
+//!
+//! The ``mpirion.json`` and ``mpirion-samples.json`` of each benchmark and the table printed after
+//! each group are written by the built-in reporters [`JsonFileReporter`] and [`TableReporter`],
+//! which run before the registered ones, so a reporter receives everything mpirion writes itself.
+//!
+//! Benchmarks are only known by their criterion id once criterion saved their samples, see the
+//! [`report`](crate::report) module, so the reporters don't run in quick runs, see
+//! ``--mpirion-quick``, except for [`on_run_complete`](MpirionReporter::on_run_complete).
+//!
+//! # Example
+//! ```rust
+//! use mpirion::prelude::*;
+//! use mpirion::reporter::{BenchRecord, MpirionReporter, RunSummary};
+//!
+//! struct CsvReporter {
+//!     rows: Vec<String>,
+//! }
+//!
+//! impl MpirionReporter for CsvReporter {
+//!     fn on_benchmark_complete(&mut self, record: &BenchRecord) {
+//!         let mean = record.metrics.mean.as_nanos();
+//!         self.rows.push(format!("{},{},{}", record.metrics.id, record.metrics.world_size, mean));
+//!     }
+//!
+//!     fn on_run_complete(&mut self, summary: &RunSummary) {
+//!         let _ = std::fs::write("results.csv", self.rows.join("\n"));
+//!         eprintln!("{} benchmarks, {} failed", summary.benchmarks.len(), summary.failures.len());
+//!     }
+//! }
+//!
+//! fn register_reporters() {
+//!     mpirion::register_reporter(Box::new(CsvReporter { rows: Vec::new() }));
+//! }
+//!
+//! fn benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+//!     ctx.bench_function(c, "barrier", "barrier_kernel", |b| {
+//!         mpirion_bench!(barrier_kernel, b, ctx)
+//!     });
+//! }
+//!
+//! fn barrier_kernel(comm: &dyn Communicator) {
+//!     comm.barrier();
+//! }
+//!
+//! mpirion_kernel!(barrier_kernel);
+//! mpirion_group!(benches, benchmark);
+//! mpirion_main!(init = register_reporters; groups = benches; kernels = barrier_kernel);
+//! ```
+
+use std::sync::Mutex;
+
+use crate::failure::BenchmarkFailure;
+use crate::launches::Launches;
+use crate::meta::Environment;
+use crate::report::{Benchmark, Metrics};
+
+/// A sink for the results of the benchmarks, see the module documentation.
+pub trait MpirionReporter {
+    /// Called for each benchmark of a group once the group ends.
+    fn on_benchmark_complete(&mut self, record: &BenchRecord);
+
+    /// Called after [`on_benchmark_complete`](Self::on_benchmark_complete) was called for all
+    /// benchmarks of a group, with all of them. Does nothing by default.
+    fn on_group_complete(&mut self, group: &str, records: &[BenchRecord]) {
+        let _ = (group, records);
+    }
+
+    /// Called once at the end of the run, after all groups ran.
+    fn on_run_complete(&mut self, summary: &RunSummary);
+}
+
+/// A benchmark of this run.
+#[derive(Debug, Clone)]
+pub struct BenchRecord {
+    /// the name of the ``mpirion_group!`` the benchmark ran in
+    pub group: String,
+    /// the benchmark, with its directory in the output directory of criterion and its samples
+    pub benchmark: Benchmark,
+    /// the metrics of the benchmark, as written into its ``mpirion.json``
+    pub metrics: Metrics,
+}
+
+/// The results of a whole run.
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    /// the environment the benchmarks ran in
+    pub environment: Environment,
+    /// all benchmarks of the run, in the order they completed
+    pub benchmarks: Vec<BenchRecord>,
+    /// the benchmarks that failed
+    pub failures: Vec<BenchmarkFailure>,
+    /// the process launches of the whole run, see
+    /// [`launches::run_total`](crate::launches::run_total)
+    pub launches: Launches,
+}
+
+/// The reporters registered with [`register_reporter`].
+static REPORTERS: Mutex<Vec<Box<dyn MpirionReporter + Send>>> = Mutex::new(Vec::new());
+
+/// The benchmarks that completed so far in this run.
+static COMPLETED: Mutex<Vec<BenchRecord>> = Mutex::new(Vec::new());
+
+/// Register a reporter, which receives the results after the built-in reporters. Register it from
+/// the ``init`` function of ``mpirion_main!``.
+pub fn register_reporter(reporter: Box<dyn MpirionReporter + Send>) {
+    REPORTERS.lock().unwrap().push(reporter);
+}
+
+/// Call the built-in reporters, and then the registered ones in the order they were registered.
+fn notify(mut call: impl FnMut(&mut dyn MpirionReporter)) {
+    call(&mut JsonFileReporter);
+    call(&mut TableReporter);
+    for reporter in REPORTERS.lock().unwrap().iter_mut() {
+        call(reporter.as_mut());
+    }
+}
+
+/// Pass the benchmarks that recorded samples since
+/// [`report::samples_recorded`](crate::report::samples_recorded) returned ``first_sample`` to the
+/// reporters, and return them. Called by ``mpirion_group!`` before it returns.
+pub fn group_complete(group: &str, first_sample: usize) -> Vec<BenchRecord> {
+    if crate::cli::options().quick {
+        return Vec::new();
+    }
+    let records: Vec<BenchRecord> =
+        crate::report::group_benchmarks(&crate::report::criterion_home(), first_sample)
+            .into_iter()
+            .map(|benchmark| BenchRecord {
+                group: group.to_string(),
+                metrics: Metrics::of(&benchmark),
+                benchmark,
+            })
+            .collect();
+    if records.is_empty() {
+        return records;
+    }
+
+    notify(|reporter| {
+        for record in &records {
+            reporter.on_benchmark_complete(record);
+        }
+        reporter.on_group_complete(group, &records);
+    });
+    COMPLETED.lock().unwrap().extend(records.iter().cloned());
+    records
+}
+
+/// Pass the summary of the run to the reporters. Called by ``mpirion_main!`` after all groups ran.
+pub fn run_complete() {
+    let summary = RunSummary {
+        environment: Environment::collect(),
+        benchmarks: COMPLETED.lock().unwrap().clone(),
+        failures: crate::failure::recorded(),
+        launches: crate::launches::run_total(),
+    };
+    notify(|reporter| reporter.on_run_complete(&summary));
+}
+
+/// Writes the ``mpirion.json`` and ``mpirion-samples.json`` of each benchmark, see
+/// [`report::save`](crate::report::save) and
+/// [`report::save_samples`](crate::report::save_samples), moves the
+/// [flamegraph](crate::profile) of a profiled benchmark into its directory, and prints the phases
+/// of the protocol whose overhead changed since the last run. At the end of the run, it prints how
+/// the [fingerprint](crate::fingerprint) of the environment changed since the last run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFileReporter;
+
+impl MpirionReporter for JsonFileReporter {
+    fn on_benchmark_complete(&mut self, record: &BenchRecord) {
+        let directory = &record.benchmark.directory;
+        crate::report::compare_overhead(directory, &record.metrics);
+        crate::profile::claim(&record.metrics, directory);
+        if let Err(error) = crate::report::save(directory, &record.metrics) {
+            eprintln!(
+                "mpirion: could not write the metrics of `{}` to {}: {}",
+                record.metrics.id,
+                directory.display(),
+                error
+            );
+        }
+        if let Err(error) =
+            crate::report::save_samples(&record.benchmark, crate::cli::options().sample_export)
+        {
+            eprintln!(
+                "mpirion: could not write the samples of `{}` to {}: {}",
+                record.metrics.id,
+                directory.display(),
+                error
+            );
+        }
+    }
+
+    fn on_run_complete(&mut self, _summary: &RunSummary) {
+        crate::fingerprint::compare_with_last_run();
+    }
+}
+
+/// Prints the table of the benchmarks of each group, and at the end of the run the table of the
+/// process launches of all benchmarks, see the [`summary`](crate::summary) module.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableReporter;
+
+impl MpirionReporter for TableReporter {
+    fn on_benchmark_complete(&mut self, _record: &BenchRecord) {}
+
+    fn on_group_complete(&mut self, group: &str, records: &[BenchRecord]) {
+        let metrics: Vec<Metrics> = records
+            .iter()
+            .map(|record| record.metrics.clone())
+            .collect();
+        println!(
+            "\nmpirion: summary of group `{}`\n{}",
+            group,
+            crate::summary::table(&metrics)
+        );
+    }
+
+    fn on_run_complete(&mut self, summary: &RunSummary) {
+        if crate::cli::options().quick || summary.launches.spawn_rounds == 0 {
+            return;
+        }
+        let metrics: Vec<Metrics> = summary
+            .benchmarks
+            .iter()
+            .map(|record| record.metrics.clone())
+            .collect();
+        println!(
+            "\nmpirion: process launches of the run\n{}",
+            crate::summary::launches(&metrics, &summary.launches)
+        );
+    }
+}
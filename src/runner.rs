@@ -0,0 +1,1754 @@
+//! A non-macro API for the parent and child side of a benchmark, for suites that are assembled at
+//! runtime (e.g. from a configuration file). The ``mpirion_*`` macros are thin wrappers around
+//! these types, so both behave identically.
+//!
+//! # Example
+//! ```rust
+//! use criterion::Criterion;
+//! use mpi::traits::Communicator;
+//! use mpirion::registry::KernelRegistry;
+//! use mpirion::runner::{BenchRunner, ChildRunner};
+//!
+//! fn bench(c: &mut Criterion, world: &dyn Communicator, kernels: &[&str]) {
+//!     for &kernel in kernels {
+//!         c.bench_function(kernel, |b| {
+//!             b.iter_custom(|iterations| BenchRunner::new(kernel, world).world_size(8).run(iterations))
+//!         });
+//!     }
+//! }
+//!
+//! fn run_child(registry: KernelRegistry) {
+//!     if let Some(kernel) = ChildRunner::kernel_from_args() {
+//!         ChildRunner::new(registry).run(&kernel);
+//!     }
+//! }
+//! ```
+
+use crate::mpi::collective::Root;
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::topology::{MergeOrder, SimpleCommunicator};
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::panic::Location;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use criterion::{Bencher, Criterion};
+
+use crate::cli::Dispatch;
+use crate::clock::Clock;
+use crate::context::{Budget, ParentWorld};
+use crate::errhandler;
+use crate::launches::Launches;
+use crate::pair::{Report, Side};
+use crate::placement::{Placement, PlacementMode};
+use crate::protocol::{self, DynArgument, OutputCallback, Parent, TextArgument, TypeTag};
+use crate::registry::KernelRegistry;
+use crate::report::{ArgumentValue, Overhead, SampleRecord};
+use crate::timing::{self, mean};
+use crate::trace;
+use crate::Error;
+
+/// The environment variable that makes the benchmarks refuse to run if the children were built
+/// without optimizations, if it is set to ``1``.
+pub const REQUIRE_RELEASE_VAR: &str = "MPIRION_REQUIRE_RELEASE";
+
+/// The default number of child processes spawned for a benchmark.
+pub const DEFAULT_WORLD_SIZE: Rank = 4;
+
+/// Runs a kernel in a child process. The child process is started by [`BenchRunner`] with the
+/// arguments ``--mpirion-child <kernel>``.
+#[derive(Debug, Clone, Default)]
+pub struct ChildRunner {
+    registry: KernelRegistry,
+}
+
+impl ChildRunner {
+    /// Create a runner that looks up kernels in the given registry.
+    pub fn new(registry: KernelRegistry) -> Self {
+        Self { registry }
+    }
+
+    /// The kernel name if the current process was started as a child process, i.e. with
+    /// ``--mpirion-child <kernel>``, see [`cli::child_kernel`](crate::cli::child_kernel). Panics if
+    /// ``--mpirion-child`` is passed without a kernel name.
+    pub fn kernel_from_args() -> Option<String> {
+        match crate::cli::dispatch_from_env() {
+            Dispatch::Child(kernel) => Some(kernel),
+            _ => None,
+        }
+    }
+
+    /// Run the kernel with the given name. This takes part in the benchmark protocol with the
+    /// parent, so it must only be called in a process spawned by [`BenchRunner`]. Kernels
+    /// registered with [`register_kernel`](crate::register_kernel) are found as well. If the
+    /// kernel is not registered, the parent is told so, and fails the benchmark with the names of
+    /// the known kernels.
+    pub fn run(&self, name: &str) {
+        match self.registry.find(name) {
+            Some(run) => run(),
+            None if crate::registry::run_registered(name) => {}
+            None => self.reject(),
+        }
+    }
+
+    /// Take part in the protocol until the parent learns that the kernel is unknown.
+    fn reject(&self) {
+        let registered = crate::registry::registered_names();
+        let mut known = self.registry.names();
+        known.extend(registered.iter().map(String::as_str));
+
+        let universe = crate::threading::initialize();
+        let merged_comm = protocol::merge_with_parent(&universe.world());
+        protocol::receive_handshake(&merged_comm, cfg!(debug_assertions));
+        protocol::reject_kernel(&merged_comm, &known);
+    }
+}
+
+/// Gathers the outputs of the children, and passes them to the callback unless the flag is set.
+type Gather<'a> = Box<dyn FnMut(&dyn Communicator, bool) + 'a>;
+
+/// The output callback of a benchmark, with its output type erased.
+struct Output<'a> {
+    tag: TypeTag,
+    /// gathers the outputs, and passes them to the callback unless the flag is set
+    gather: Gather<'a>,
+    /// passes the output of a kernel that ran in the parent process to the callback
+    #[cfg_attr(not(feature = "local-fallback"), allow(dead_code))]
+    local: Box<dyn FnMut(Box<dyn Any>) + 'a>,
+}
+
+/// The consumers of a pair, spawned next to the children of the kernel, see [`pair`](crate::pair).
+struct Consumers {
+    /// the kernel the consumers run
+    kernel: String,
+    world_size: Rank,
+    report: Report,
+}
+
+/// The function that runs the samples of a kernel, generated by ``mpirion_kernel!`` as
+/// ``run_kernel_<name>``. It is only needed for the ``local-fallback`` feature.
+pub type LocalKernel = fn(&dyn Communicator, Parent<'_>);
+
+/// Spawns the child processes of a benchmark and measures a sample. The settings mirror the
+/// parameters of ``mpirion_bench!``.
+pub struct BenchRunner<'a> {
+    kernel: String,
+    world: &'a dyn Communicator,
+    world_size: Rank,
+    const_arg: Box<dyn DynArgument>,
+    arg: Box<dyn DynArgument>,
+    output: Option<Output<'a>>,
+    timeout: Option<Duration>,
+    /// the wall time after which the children stop a sample early
+    max_sample_time: Option<Duration>,
+    /// how often a timed-out sample is retried on a fresh child world
+    retry_on_timeout: u32,
+    /// the budget of the group the benchmark belongs to
+    budget: Option<Budget>,
+    /// the number of MADs beyond which the time of a rank is rejected as an outlier
+    reject_outliers: Option<f64>,
+    /// the call site that identifies the benchmark, if its first sample is preceded by a priming one
+    discard_first_sample: Option<&'static Location<'static>>,
+    /// whether the current sample is a priming one, whose outputs are discarded
+    priming: bool,
+    #[cfg_attr(not(feature = "local-fallback"), allow(dead_code))]
+    local_kernel: Option<LocalKernel>,
+    /// how long spawning and connecting to the children took in the last sample
+    spawn_time: Option<Duration>,
+    /// how long the phases of the protocol took in the last sample
+    overhead: Option<Overhead>,
+    /// the error that fails the whole group, if MPI could not spawn the children or failed in the
+    /// protocol
+    group_error: Option<Error>,
+    /// the clock the children measured the last sample with
+    clock: Option<Clock>,
+    /// the largest drift of the iteration times of a rank in the last sample
+    drift: Option<f64>,
+    /// the iterations the children ran in the last sample, if it hit the time cap
+    truncated: Option<u64>,
+    /// the hosts the children of the last sample ran on, if they were spawned
+    placement: Option<Placement>,
+    /// the launches since the last recorded sample, which include priming samples and retries
+    launches: Launches,
+    /// the child that profiles itself in the current sample, if it is a profiling one
+    profile: Option<crate::profile::Plan>,
+    /// the size of the Rayon pool of each child
+    threads: Option<usize>,
+    /// the environment variables of the children, see [`tuning`](crate::tuning)
+    tuning: Vec<(String, String)>,
+    /// the consumers spawned next to the children, see [`pair`](crate::pair)
+    consumers: Option<Consumers>,
+    /// the placement of the children requested from MPI
+    placement_mode: Option<PlacementMode>,
+    /// the time per iteration of each counter the kernel recorded in the last sample
+    counters: Vec<(String, Duration)>,
+    /// whether a kernel with ``reorder = compare`` runs on the communicator with reordered ranks
+    reorder: Option<bool>,
+    /// whether MPI permuted the ranks of the communicator of the kernel in the last sample
+    ranks_permuted: Option<bool>,
+}
+
+impl<'a> BenchRunner<'a> {
+    /// Create a runner for the kernel with the given name, which spawns its children from rank 0
+    /// of ``world``. The name must be the name the kernel is registered with on the child side.
+    pub fn new(kernel: impl Into<String>, world: &'a dyn Communicator) -> Self {
+        Self {
+            kernel: kernel.into(),
+            world,
+            world_size: DEFAULT_WORLD_SIZE,
+            const_arg: Box::new(()),
+            arg: Box::new(()),
+            output: None,
+            timeout: None,
+            max_sample_time: None,
+            retry_on_timeout: 0,
+            reject_outliers: None,
+            discard_first_sample: None,
+            priming: false,
+            budget: None,
+            local_kernel: None,
+            spawn_time: None,
+            overhead: None,
+            group_error: None,
+            clock: None,
+            drift: None,
+            truncated: None,
+            placement: None,
+            launches: Launches::NONE,
+            profile: None,
+            threads: None,
+            tuning: Vec::new(),
+            consumers: None,
+            placement_mode: None,
+            counters: Vec::new(),
+            reorder: None,
+            ranks_permuted: None,
+        }
+    }
+
+    /// Set the number of child processes to spawn. Defaults to [`DEFAULT_WORLD_SIZE`]. Any integer
+    /// type is accepted, see [`checked_world_size`]. Panics with the name of the kernel if the
+    /// world size is zero, negative or doesn't fit into a [`Rank`], before any MPI call. World
+    /// sizes above the sanity threshold of [`exceeds_world_size_warning`] print a warning.
+    #[track_caller]
+    pub fn world_size<N>(mut self, world_size: N) -> Self
+    where
+        N: TryInto<i128> + Copy + Display,
+    {
+        self.world_size = match checked_world_size(world_size) {
+            Ok(world_size) => world_size,
+            Err(message) => panic!(
+                "invalid world_size of kernel `{}`: {}",
+                self.kernel, message
+            ),
+        };
+
+        let max_world_size = crate::cli::options().max_world_size;
+        if exceeds_world_size_warning(self.world_size, max_world_size) {
+            warn_once(
+                &format!("{} world size", self.kernel),
+                format_args!(
+                    "kernel `{}` spawns {} children, more than the sanity threshold of {}. Raise it with --mpirion-max-world-size if this is intended.",
+                    self.kernel,
+                    self.world_size,
+                    max_world_size.unwrap_or(WORLD_SIZE_WARNING)
+                ),
+            );
+        }
+        self
+    }
+
+    /// Pass a constant argument to the setup function of the children.
+    pub fn const_arg<T: Equivalence + Clone + 'static>(mut self, const_arg: T) -> Self {
+        self.const_arg = Box::new((const_arg,));
+        self
+    }
+
+    /// Pass an argument to the setup function of the children.
+    pub fn arg<T: Equivalence + Clone + 'static>(mut self, arg: T) -> Self {
+        self.arg = Box::new((arg,));
+        self
+    }
+
+    /// Pass an argument as text, which the children parse into the argument type of the kernel.
+    /// This only works for primitive argument types, but the type doesn't need to be known on the
+    /// parent side.
+    pub fn text_arg(mut self, arg: impl Into<String>) -> Self {
+        self.arg = Box::new(TextArgument(arg.into()));
+        self
+    }
+
+    /// Collect the outputs of the children after each sample. The callback receives the outputs
+    /// ordered by child rank. The kernel must declare an output projection.
+    pub fn output<O, F>(mut self, callback: F) -> Self
+    where
+        O: Equivalence + Default + Clone + 'static,
+        F: FnMut(&[O]) + 'a,
+    {
+        let callback = Rc::new(RefCell::new(callback));
+        let local_callback = Rc::clone(&callback);
+        self.output = Some(Output {
+            tag: protocol::output_tag_of::<O>(),
+            gather: Box::new(move |comm, discard| {
+                let mut callback = callback.borrow_mut();
+                if discard {
+                    OutputCallback::<O>::discard((&mut *callback,), comm);
+                } else {
+                    OutputCallback::<O>::receive((&mut *callback,), comm);
+                }
+            }),
+            local: Box::new(move |output| {
+                let output = output
+                    .downcast::<O>()
+                    .expect("the kernel sent an output of another type");
+                let mut callback = local_callback.borrow_mut();
+                (*callback)(std::slice::from_ref(&*output));
+            }),
+        });
+        self
+    }
+
+    /// Abort the run if a sample takes longer than the timeout. A hanging child world cannot be
+    /// recovered, so the whole MPI job is aborted, unless the sample is retried, see
+    /// [`retry_on_timeout`](Self::retry_on_timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry a sample that exceeds the [`timeout`](Self::timeout) up to ``retries`` times with the
+    /// same number of iterations, e.g. because of a transient network problem. The hung child world
+    /// is abandoned: the parent stops sending heartbeats to its [watchdogs](crate::watchdog), so
+    /// its children exit, and a fresh child world is spawned. The run is only aborted if the last
+    /// retry times out as well. Each retry is printed, and counted in [`timeout_retries`] and the
+    /// ``--mpirion-verbose`` output.
+    ///
+    /// Whether the parent survives children that exit without finalizing MPI depends on the MPI
+    /// implementation. Without a watchdog, the abandoned children keep running.
+    pub fn retry_on_timeout(mut self, retries: u32) -> Self {
+        self.retry_on_timeout = retries;
+        self
+    }
+
+    /// Cap the wall time of a sample. Criterion raises the iteration count of the samples until
+    /// they fill its measurement time, which can make a single sample of a slow kernel take hours.
+    /// With a cap, the children stop after the iteration during which the cap was reached on any
+    /// rank, so all ranks run the same number of iterations. The times of the children are then
+    /// extrapolated to the requested iterations, see [`timing::extrapolate`], the benchmark warns
+    /// once that the samples are truncated, and ``--mpirion-verbose`` shows the iterations that
+    /// ran. At least one iteration always runs.
+    ///
+    /// The cap is checked with a reduction over the children after every iteration, outside of the
+    /// measured time of the kernel.
+    pub fn max_sample_time(mut self, max_sample_time: Duration) -> Self {
+        self.max_sample_time = Some(max_sample_time);
+        self
+    }
+
+    /// Reject the times of ranks that are more than ``mads`` median absolute deviations away from
+    /// the median of all ranks, e.g. a rank on a node with a noisy neighbor, and report the mean
+    /// of the remaining ranks, see [`robust_mean`]. The reported times are then no longer the raw
+    /// mean of all ranks, which the benchmark prints once. With ``--mpirion-verbose``, the number
+    /// of rejected ranks is printed for each sample.
+    pub fn reject_outliers(mut self, mads: f64) -> Self {
+        self.reject_outliers = Some(mads);
+        self
+    }
+
+    /// Run a throwaway sample of a single iteration before the first sample of the benchmark, if
+    /// ``discard`` is set. The first spawn of a run pays for loading the binary and the MPI
+    /// libraries and starting the daemons of the MPI runtime, so it is much slower than the later
+    /// ones. Criterion calls the benchmark with a new runner for every sample, so the benchmark is
+    /// identified by the kernel, the world size, the argument and the call site of this method: the
+    /// priming sample runs whenever the previous sample of the run came from another benchmark.
+    /// Benchmarks created in a loop from the same call site are therefore primed before each of
+    /// them that differs in its world size or argument.
+    ///
+    /// The outputs of the priming sample are discarded. The time it took is printed with
+    /// ``--mpirion-verbose`` and returned by [`priming_samples`].
+    #[track_caller]
+    pub fn discard_first_sample(mut self, discard: bool) -> Self {
+        let caller = Location::caller();
+        self.discard_first_sample = discard.then_some(caller);
+        self
+    }
+
+    /// Build a Rayon pool of ``threads`` threads in each child before the setup, for kernels that
+    /// parallelize each rank with Rayon, see [`threading`](crate::threading). The size is recorded
+    /// in the ``mpirion.json`` of the benchmark, so a sweep over ranks and threads can be told
+    /// apart. Panics with the name of the kernel if ``threads`` is zero, or if mpirion was built
+    /// without the ``rayon`` feature.
+    pub fn threads(mut self, threads: usize) -> Self {
+        if !cfg!(feature = "rayon") {
+            panic!(
+                "kernel `{}` sets threads, which needs the `rayon` feature of mpirion",
+                self.kernel
+            );
+        }
+        if threads == 0 {
+            panic!(
+                "invalid threads of kernel `{}`: the pool needs at least one thread",
+                self.kernel
+            );
+        }
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Set environment variables in each child before it initializes MPI, e.g. the MCA parameters
+    /// of Open MPI that select the algorithm of a collective, see [`tuning`](crate::tuning). They
+    /// are recorded in the ``mpirion.json`` of the benchmark. Panics with the name of the kernel if
+    /// a name is empty or contains ``=``.
+    pub fn tuning<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (name, value) in vars {
+            let name = name.into();
+            if let Err(message) = crate::tuning::check_name(&name) {
+                panic!("invalid tuning of kernel `{}`: {}", self.kernel, message);
+            }
+            self.tuning.push((name, value.into()));
+        }
+        self
+    }
+
+    /// Ask MPI to place the children on the nodes as ``mode`` says, see [`PlacementMode`]. The
+    /// parent warns if the children report a different placement, and records both in the
+    /// ``mpirion.json`` of the benchmark. Panics with the name of the kernel for ``Spread(0)``.
+    pub fn placement(mut self, mode: PlacementMode) -> Self {
+        if mode == PlacementMode::Spread(0) {
+            panic!(
+                "invalid placement of kernel `{}`: it spreads zero ranks on each node",
+                self.kernel
+            );
+        }
+        self.placement_mode = Some(mode);
+        self
+    }
+
+    /// Measure a kernel with ``topology = cart(.., reorder = compare)`` on the communicator with
+    /// reordered ranks if ``reorder`` is true, and on the one without otherwise, see
+    /// [`topology`](crate::topology). Each spawned world runs a sample on both communicators, the
+    /// one without reordering first. The sample on the communicator with reordered ranks of a
+    /// benchmark with ``reorder = false`` is replayed by the next benchmark of the kernel with
+    /// ``reorder = true``, at the same world size and argument, scaled to the iterations criterion
+    /// asks for. The ``mpirion.json`` of the benchmark records whether MPI actually permuted the
+    /// ranks.
+    ///
+    /// A sample fails if a kernel of its children doesn't declare ``reorder = compare``, just like a
+    /// sample of a kernel that declares it fails if ``reorder`` isn't set.
+    pub fn reorder(mut self, reorder: bool) -> Self {
+        self.reorder = Some(reorder);
+        self
+    }
+
+    /// Spawn ``world_size`` children that run the kernel ``consumer`` next to the children of this
+    /// kernel, the producers, and connect both groups with an inter-communicator, see
+    /// [`pair`](crate::pair). Any integer type is accepted for the world size, see
+    /// [`checked_world_size`]. Panics with the name of the kernel if the world size is invalid.
+    pub fn consumer<N>(mut self, consumer: impl Into<String>, world_size: N) -> Self
+    where
+        N: TryInto<i128> + Copy + Display,
+    {
+        let world_size = checked_world_size(world_size).unwrap_or_else(|message| {
+            panic!("invalid consumers of kernel `{}`: {}", self.kernel, message)
+        });
+        self.consumers = Some(Consumers {
+            kernel: consumer.into(),
+            world_size,
+            report: Report::default(),
+        });
+        self
+    }
+
+    /// Select the time of a pair that is reported, see [`Report`]. Defaults to the slower group.
+    /// Panics with the name of the kernel if no [`consumer`](Self::consumer) was set before.
+    pub fn report(mut self, report: Report) -> Self {
+        match &mut self.consumers {
+            Some(consumers) => consumers.report = report,
+            None => panic!(
+                "kernel `{}` sets report, which needs a consumer",
+                self.kernel
+            ),
+        }
+        self
+    }
+
+    /// The budget of the group, see [`Budget`], to warn if the benchmark was declared directly with
+    /// criterion and runs after the budget is used up. ``mpirion_bench!`` passes the budget of the
+    /// [`MpirionContext`](crate::MpirionContext).
+    pub fn budget(mut self, budget: Option<Budget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Set the function that runs the kernel in the parent process, which is used instead of
+    /// spawning children if the ``local-fallback`` feature is enabled. ``mpirion_bench!`` passes
+    /// the ``run_kernel_<name>`` function generated by ``mpirion_kernel!``.
+    pub fn local_kernel(mut self, local_kernel: LocalKernel) -> Self {
+        self.local_kernel = Some(local_kernel);
+        self
+    }
+
+    /// Spawn the children, run ``iterations`` iterations of the kernel and return the mean time
+    /// the children spent in the kernel. The result can be returned from criterion's
+    /// ``iter_custom``. If the children report a failure, the benchmark is failed with
+    /// [`fail`](crate::failure::fail).
+    ///
+    /// With the ``local-fallback`` feature, no children are spawned. Instead, the kernel runs in
+    /// the parent process on ``world``, see [`local_kernel`](Self::local_kernel).
+    ///
+    /// If the children cannot be spawned, the benchmark group is aborted with
+    /// [`Error::SpawnUnsupported`](crate::Error::SpawnUnsupported).
+    ///
+    /// With ``--mpirion-quick``, the children are only spawned once per benchmark, see
+    /// [`quick_config`]. With ``--mpirion-verbose``, a line with the timings of each sample is
+    /// printed.
+    ///
+    /// Panics if the benchmark was declared through the [`MpirionContext`](crate::MpirionContext)
+    /// with another kernel. A benchmark declared directly with criterion runs even if its kernel
+    /// is deselected or the budget of its group is used up, which is only warned about. In a run
+    /// with ``--mpirion-dry-run``, it fails instead of spawning the children.
+    pub fn run(&mut self, iterations: u64) -> Duration {
+        let declared = DECLARED.lock().unwrap().clone();
+        if declared.is_none() && crate::cli::options().dry_run {
+            crate::failure::fail(
+                &self.kernel,
+                "a dry run cannot skip a benchmark declared with criterion instead of the MpirionContext"
+                    .to_string(),
+            );
+        }
+        match &declared {
+            Some(declared) if declared.kernel != self.kernel => panic!(
+                "benchmark `{}` was declared with kernel `{}`, but runs kernel `{}`",
+                declared.id, declared.kernel, self.kernel
+            ),
+            Some(_) => {}
+            None if !crate::cli::options().runs_kernel(&self.kernel) => warn_once(
+                &self.kernel,
+                format_args!(
+                    "kernel `{}` is not selected with --mpirion-only-kernel, but runs anyway: its benchmark was declared with criterion instead of the MpirionContext",
+                    self.kernel
+                ),
+            ),
+            None => {
+                if let Some(budget) = self.budget.filter(Budget::exhausted) {
+                    warn_once(
+                        &self.kernel,
+                        format_args!(
+                            "kernel `{}` runs although the budget of {:?} of group `{}` is used up: its benchmark was declared with criterion instead of the MpirionContext",
+                            self.kernel,
+                            budget.duration(),
+                            budget.group()
+                        ),
+                    );
+                }
+            }
+        }
+
+        self.prime_if_first_sample();
+        self.profile_if_first_sample();
+
+        if crate::cli::options().quick {
+            return self.run_quick(iterations);
+        }
+
+        let start = Instant::now();
+        match self.try_run_per_rank(iterations) {
+            Ok(per_rank) => {
+                if let Some(mads) = self.reject_outliers {
+                    warn_once(
+                        &format!("{} outliers", self.kernel),
+                        format_args!(
+                            "kernel `{}`: outlier rejection is enabled, the reported times are the mean of the ranks within {} MADs of the median, not of all ranks",
+                            self.kernel, mads
+                        ),
+                    );
+                }
+                let (mean, rejected) = self.reported_mean(&per_rank);
+                self.warn_drift();
+                if self.consumers.is_some() {
+                    let (producers, consumers) = self.sides(&per_rank);
+                    self.warn_variance(producers);
+                    self.warn_variance(consumers);
+                    self.counters.extend(crate::pair::side_counters(
+                        &per_rank,
+                        self.world_size as usize,
+                        iterations,
+                    ));
+                } else {
+                    self.warn_variance(&per_rank);
+                }
+                if crate::cli::options().verbose {
+                    self.print_sample(iterations, start.elapsed(), &per_rank, mean, rejected);
+                }
+                crate::report::record(SampleRecord {
+                    benchmark: declared_id(),
+                    kernel: self.kernel.clone(),
+                    iterations,
+                    reported: mean,
+                    per_rank,
+                    placement: self.placement.clone(),
+                    overhead: self.overhead,
+                    argument: self.arg.describe(),
+                    truncated: self.truncated.is_some(),
+                    launches: std::mem::take(&mut self.launches),
+                    threads: self.threads,
+                    tuning: self.tuning.clone(),
+                    placement_mode: self.placement_mode,
+                    ranks_permuted: self.ranks_permuted,
+                    counters: std::mem::take(&mut self.counters),
+                });
+                mean
+            }
+            Err(message) => match self.group_error.take() {
+                Some(error) => crate::failure::fail_group(error),
+                None => crate::failure::fail(&self.kernel, message),
+            },
+        }
+    }
+
+    /// Run a priming sample of a single iteration and discard it, if the first sample of the
+    /// benchmark is discarded and the previous sample came from another benchmark, see
+    /// [`discard_first_sample`](Self::discard_first_sample).
+    fn prime_if_first_sample(&mut self) {
+        let benchmark = LastBenchmark {
+            kernel: self.kernel.clone(),
+            world_size: self.world_size,
+            argument: self.arg.describe(),
+            call_site: self.discard_first_sample,
+        };
+        let previous = LAST_BENCHMARK.lock().unwrap().replace(benchmark.clone());
+        if benchmark.call_site.is_none() || previous.as_ref() == Some(&benchmark) {
+            return;
+        }
+
+        let start = Instant::now();
+        self.priming = true;
+        let result = self.try_run_per_rank(1);
+        self.priming = false;
+        if let Err(message) = result {
+            match self.group_error.take() {
+                Some(error) => crate::failure::fail_group(error),
+                None => crate::failure::fail(&self.kernel, message),
+            }
+        }
+
+        let elapsed = start.elapsed();
+        PRIMINGS
+            .lock()
+            .unwrap()
+            .push((self.kernel.clone(), elapsed));
+        if crate::cli::options().verbose {
+            let spawn_time = self
+                .spawn_time
+                .map_or("-".to_string(), |time| format!("{:?}", time));
+            eprintln!(
+                "mpirion: {}: discarded a priming sample of 1 iteration on {} children, spawn {}, sample {:?}",
+                progress_name(&self.kernel), self.children(), spawn_time, elapsed
+            );
+        }
+    }
+
+    /// Run a profiling sample of [`profile::DURATION`](crate::profile::DURATION) and discard it, if
+    /// the kernel was selected with ``--mpirion-profile`` and this is the first sample of the
+    /// benchmark, see the [`profile`](crate::profile) module.
+    fn profile_if_first_sample(&mut self) {
+        let Some(plan) = crate::profile::plan(&self.kernel, self.world_size, self.arg.describe())
+        else {
+            return;
+        };
+        eprintln!(
+            "mpirion: kernel `{}`: profiling rank {} of {} children for {:?}",
+            self.kernel,
+            plan.rank,
+            self.world_size,
+            crate::profile::DURATION
+        );
+
+        let (max_sample_time, timeout) = (self.max_sample_time, self.timeout);
+        self.max_sample_time = Some(crate::profile::DURATION);
+        self.timeout = timeout.map(|timeout| timeout + crate::profile::DURATION);
+        self.profile = Some(plan);
+        self.priming = true;
+        let result = self.try_run_per_rank(crate::profile::ITERATIONS);
+        self.priming = false;
+        self.profile = None;
+        self.max_sample_time = max_sample_time;
+        self.timeout = timeout;
+        if let Err(message) = result {
+            match self.group_error.take() {
+                Some(error) => crate::failure::fail_group(error),
+                None => crate::failure::fail(&self.kernel, message),
+            }
+        }
+    }
+
+    /// Measure the kernel with a single spawn of [`QUICK_ITERATIONS`] iterations on the first call
+    /// of a benchmark, and replay the time per iteration for the remaining calls, see
+    /// [`quick_config`].
+    fn run_quick(&mut self, iterations: u64) -> Duration {
+        let argument = self.arg.describe();
+        let of_benchmark = |replay: &QuickReplay| {
+            replay.kernel == self.kernel
+                && replay.world_size == self.world_size
+                && replay.argument == argument
+        };
+        let mut replays = QUICK_REPLAYS.lock().unwrap();
+        if let Some(replay) = replays
+            .iter_mut()
+            .find(|replay| of_benchmark(replay) && replay.remaining > 0)
+        {
+            replay.remaining -= 1;
+            return replay.per_iteration.mul_f64(iterations as f64);
+        }
+        replays.retain(|replay| !of_benchmark(replay));
+        drop(replays);
+
+        let per_iteration = match self.try_run(QUICK_ITERATIONS) {
+            Ok(mean) => mean / QUICK_ITERATIONS as u32,
+            Err(message) => match self.group_error.take() {
+                Some(error) => crate::failure::fail_group(error),
+                None => crate::failure::fail(&self.kernel, message),
+            },
+        };
+        eprintln!(
+            "mpirion quick: kernel `{}`: {:?} per iteration, from a single spawn of {} iterations on {} children. \
+             This is not a statistical measurement, criterion's analysis below only replays it.",
+            self.kernel, per_iteration, QUICK_ITERATIONS, self.children()
+        );
+
+        QUICK_REPLAYS.lock().unwrap().push(QuickReplay {
+            kernel: self.kernel.clone(),
+            world_size: self.world_size,
+            argument,
+            per_iteration,
+            remaining: QUICK_SAMPLE_SIZE,
+        });
+        per_iteration.mul_f64(iterations as f64)
+    }
+
+    /// Like [`run`](Self::run), but returns the failure reported by the children instead of
+    /// failing the benchmark.
+    pub fn try_run(&mut self, iterations: u64) -> Result<Duration, String> {
+        self.try_run_per_rank(iterations)
+            .map(|per_rank| match &self.consumers {
+                Some(consumers) => {
+                    let (producers, consumer_times) = self.sides(&per_rank);
+                    crate::pair::reported(
+                        consumers.report,
+                        (mean(producers), 0),
+                        (mean(consumer_times), 0),
+                    )
+                    .0
+                }
+                None => mean(&per_rank),
+            })
+    }
+
+    /// The time a sample reports and the number of ranks rejected as outliers: the mean of all
+    /// ranks, or of the group of a pair selected with [`report`](Self::report), of the ranks within
+    /// the MADs of [`reject_outliers`](Self::reject_outliers) if it is set.
+    fn reported_mean(&self, per_rank: &[Duration]) -> (Duration, usize) {
+        let mean_of = |times: &[Duration]| match self.reject_outliers {
+            Some(mads) => robust_mean(times, mads),
+            None => (mean(times), 0),
+        };
+        match &self.consumers {
+            Some(consumers) => {
+                let (producers, consumer_times) = self.sides(per_rank);
+                crate::pair::reported(
+                    consumers.report,
+                    mean_of(producers),
+                    mean_of(consumer_times),
+                )
+            }
+            None => mean_of(per_rank),
+        }
+    }
+
+    /// The times of the producers and the consumers of a pair, from the times of all ranks.
+    fn sides<'t>(&self, per_rank: &'t [Duration]) -> (&'t [Duration], &'t [Duration]) {
+        per_rank.split_at((self.world_size as usize).min(per_rank.len()))
+    }
+
+    /// The host of each child in the hostfile of the job, by the placement of the benchmark, or
+    /// ``None`` without a hostfile or if the children don't fit into it, see
+    /// [`hostfile`](crate::hostfile).
+    fn assign_hosts(&self) -> Option<Vec<String>> {
+        let hostfile = crate::hostfile::available(self.world.size() as usize)?;
+        let mode = self.placement_mode.unwrap_or(PlacementMode::PerCore);
+        hostfile
+            .assign(mode, self.children() as usize)
+            .map_err(|message| {
+                warn_once(
+                    &format!("{} hostfile", self.kernel),
+                    format_args!(
+                        "kernel `{}`: {}, MPI places them instead",
+                        self.kernel, message
+                    ),
+                )
+            })
+            .ok()
+    }
+
+    /// The number of children spawned for a sample, including the consumers of a pair.
+    fn children(&self) -> Rank {
+        self.world_size
+            + self
+                .consumers
+                .as_ref()
+                .map_or(0, |consumers| consumers.world_size)
+    }
+
+    /// Like [`try_run`](Self::try_run), but returns the time each child spent in the kernel,
+    /// ordered by rank, instead of the mean.
+    pub fn try_run_per_rank(&mut self, iterations: u64) -> Result<Vec<Duration>, String> {
+        if let Some(per_rank) = self.replay_reordered(iterations) {
+            return Ok(per_rank);
+        }
+
+        #[cfg(feature = "local-fallback")]
+        let per_rank = self.run_local(iterations)?;
+
+        #[cfg(not(feature = "local-fallback"))]
+        let per_rank = self.run_spawned(iterations)?;
+
+        Ok(per_rank)
+    }
+
+    #[cfg_attr(feature = "local-fallback", allow(dead_code))]
+    fn run_spawned(&mut self, iterations: u64) -> Result<Vec<Duration>, String> {
+        let mut attempt = 0;
+        loop {
+            let retry = attempt < self.retry_on_timeout;
+            if let Some(outcome) = self.spawn_sample(iterations, retry)? {
+                return Ok(outcome);
+            }
+
+            attempt += 1;
+            RETRIES.lock().unwrap().push(self.kernel.clone());
+            eprintln!(
+                "kernel `{}`: the sample did not finish within {:?}, retrying on a fresh child world ({} of {})",
+                self.kernel,
+                self.timeout.unwrap_or_default(),
+                attempt,
+                self.retry_on_timeout
+            );
+        }
+    }
+
+    /// Spawn a child world and run a sample on it. If the sample times out and ``retry`` is set, the
+    /// child world is abandoned and ``None`` is returned, otherwise the MPI job is aborted.
+    #[cfg_attr(feature = "local-fallback", allow(dead_code))]
+    fn spawn_sample(
+        &mut self,
+        iterations: u64,
+        retry: bool,
+    ) -> Result<Option<Vec<Duration>>, String> {
+        let _span = trace::parent_span(&self.kernel);
+
+        // create child processes
+        let world_id = crate::watchdog::next_world();
+        let child_command = |kernel: &str| {
+            let mut child_exe = std::process::Command::new(
+                std::env::current_exe().expect("failed to retrieve benchmark executable path"),
+            );
+            child_exe.arg(crate::cli::CHILD_FLAG);
+            child_exe.arg(kernel);
+            // the consumers of a pair run another kernel than the selected one
+            if let Some(only) = crate::cli::options()
+                .only_kernel
+                .as_ref()
+                .filter(|only| kernel == self.kernel && *only == kernel)
+            {
+                // the children check that they run the selected kernel
+                child_exe.arg("--mpirion-only-kernel");
+                child_exe.arg(only);
+            }
+            child_exe.args(crate::threading::child_args());
+            child_exe.args(crate::tuning::child_args(&self.tuning));
+            if let Some(address) = crate::watchdog::parent_address() {
+                let grace = crate::cli::options()
+                    .watchdog_grace
+                    .unwrap_or(crate::watchdog::DEFAULT_GRACE);
+                child_exe.args(crate::watchdog::child_args(address, grace, world_id));
+            }
+            child_exe
+        };
+
+        let log_directory = crate::logs::kernel_directory(&self.kernel);
+
+        trace::debug!(world_size = self.children(), "spawning children");
+        self.overhead = None;
+        let spawn_start = Instant::now();
+        // errors while spawning are returned by MPI, all later ones abort the job with a report
+        errhandler::enter(&self.kernel, errhandler::SPAWN);
+        let mut commands = vec![child_command(&self.kernel)];
+        let mut world_sizes = vec![self.world_size];
+        if let Some(consumers) = &self.consumers {
+            commands[0].args(crate::pair::child_args(Side::Producer));
+            let mut consumer_exe = child_command(&consumers.kernel);
+            consumer_exe.args(crate::pair::child_args(Side::Consumer));
+            commands.push(consumer_exe);
+            world_sizes.push(consumers.world_size);
+        }
+        let assigned_hosts = self.assign_hosts();
+        let mut first_rank = 0;
+        let infos: Vec<_> = world_sizes
+            .iter()
+            .map(|world_size| {
+                let ranks = first_rank..first_rank + *world_size as usize;
+                first_rank = ranks.end;
+                crate::placement::spawn_info(
+                    self.placement_mode,
+                    assigned_hosts.as_ref().map(|hosts| &hosts[ranks]),
+                )
+            })
+            .collect();
+        let root = self.world.process_at_rank(0);
+        let spawned_children = if infos.iter().any(|info| !info.is_empty()) {
+            crate::placement::spawn(self.world, &commands, &world_sizes, &infos)
+        } else if commands.len() == 1 {
+            root.spawn(&commands[0], world_sizes[0])
+        } else {
+            root.spawn_multiple(&commands, &world_sizes)
+        };
+        let child_inter_comm = match spawned_children {
+            Ok(child_inter_comm) => child_inter_comm,
+            Err(error) => {
+                // the world size was validated before, so MPI refused to spawn a valid number
+                let message = format!(
+                    "MPI refused to spawn {} children: {:?}",
+                    self.children(),
+                    error
+                );
+                self.group_error = Some(Error::SpawnUnsupported {
+                    message: message.clone(),
+                });
+                return Err(message);
+            }
+        };
+        let spawned = Instant::now();
+        errhandler::enter(&self.kernel, errhandler::MERGE);
+        errhandler::install(&child_inter_comm).map_err(|error| self.mpi_failed(error))?;
+        let child_world_size = child_inter_comm.remote_size();
+        assert_eq!(child_world_size, self.children());
+        trace::debug!(world_size = child_world_size, "spawned children");
+
+        // create intracomm for parent and the children
+        let merged_comm = child_inter_comm.merge(MergeOrder::Low);
+        errhandler::install(&merged_comm).map_err(|error| self.mpi_failed(error))?;
+        let merged = Instant::now();
+        self.spawn_time = Some(merged - spawn_start);
+        trace::debug!("merged with children");
+        errhandler::enter(&self.kernel, errhandler::HANDSHAKE);
+        let handshake = protocol::send_handshake(
+            &merged_comm,
+            log_directory.as_deref(),
+            &crate::io::file_path(&self.kernel, world_id),
+        );
+        if handshake.unoptimized > 0 {
+            self.warn_unoptimized(&merged_comm, handshake.unoptimized);
+        }
+        self.clock = Some(handshake.clock);
+        crate::placement::note(&self.kernel, &handshake.placement);
+        if let Some(mode) = self
+            .placement_mode
+            .filter(|mode| !mode.holds(&handshake.placement))
+        {
+            warn_once(
+                &format!("{} placement", self.kernel),
+                format_args!(
+                    "kernel `{}`: requested the placement {}, but MPI placed the children differently: {}",
+                    self.kernel,
+                    mode,
+                    handshake.placement.summary()
+                ),
+            );
+        }
+        if let Some(hosts) =
+            assigned_hosts.filter(|hosts| !crate::hostfile::matches(hosts, &handshake.placement))
+        {
+            warn_once(
+                &format!("{} hosts", self.kernel),
+                format_args!(
+                    "kernel `{}`: the children were assigned to {} of the hostfile, but MPI placed them differently: {}",
+                    self.kernel,
+                    crate::hostfile::host_value(&hosts),
+                    handshake.placement.summary()
+                ),
+            );
+        }
+        crate::fingerprint::record(&handshake.fingerprints);
+        self.placement = Some(handshake.placement);
+        if handshake.clock != Clock::Instant {
+            warn_once(
+                &format!("{} clock", self.kernel),
+                format_args!(
+                    "kernel `{}`: Instant failed the clock self test of the children, they measure with {}",
+                    self.kernel, handshake.clock
+                ),
+            );
+        }
+        protocol::send_kernel_name(&merged_comm, &self.kernel)?;
+        crate::threading::check_children(&merged_comm, &self.kernel)?;
+
+        crate::topology::check_children(&merged_comm, &self.kernel, self.reorder.is_some())?;
+
+        // a kernel with reorder = compare runs a sample on each of its communicators in this world
+        let mut sample = None;
+        let mut child_cpu = Duration::ZERO;
+        let mut round_start = merged;
+        for reordered in crate::topology::rounds(self.reorder.is_some()) {
+            let own = reordered == self.reorder;
+            errhandler::enter(&self.kernel, errhandler::ARGUMENTS);
+            protocol::send_iterations(&merged_comm, iterations)
+                .map_err(|error| self.mpi_failed(error))?;
+            timing::send_time_cap(&merged_comm, self.max_sample_time);
+            crate::threading::send_threads(&merged_comm, self.threads);
+            self.const_arg.send(&merged_comm);
+            self.arg.send(&merged_comm);
+            let output_tag = self
+                .output
+                .as_ref()
+                .map_or(TypeTag::none(), |output| output.tag);
+            protocol::send_output_tag(&merged_comm, output_tag);
+            crate::profile::send(&merged_comm, self.profile.as_ref());
+            let broadcast = round_start.elapsed();
+
+            trace::debug!("waiting for children");
+            errhandler::enter(&self.kernel, errhandler::KERNEL);
+            let finished = protocol::wait_for_children(&merged_comm, self.timeout)
+                .map_err(|error| self.mpi_failed(error))?;
+            if !finished {
+                if retry {
+                    // the abandoned children never report their CPU time
+                    self.launched(Launches::world(self.children() as u64, Duration::ZERO));
+                    crate::watchdog::abandon(world_id);
+                    // freeing the communicators could block on the hung children, so they are leaked
+                    std::mem::forget(merged_comm);
+                    std::mem::forget(child_inter_comm);
+                    return Ok(None);
+                }
+                eprintln!(
+                    "kernel `{}` failed: the sample did not finish within {:?}, aborting",
+                    self.kernel,
+                    self.timeout.unwrap_or_default()
+                );
+                crate::logs::print_paths(&self.kernel);
+                merged_comm.abort(1);
+            }
+
+            errhandler::enter(&self.kernel, errhandler::OUTCOME);
+            let outcome_start = Instant::now();
+            let outcome =
+                protocol::receive_outcome(&merged_comm).map_err(|error| self.mpi_failed(error))?;
+            trace::debug!(failed = outcome.is_err(), "received outcome");
+            let drift = timing::receive_drift(&merged_comm);
+            let iterations_run = timing::receive_iterations_run(&merged_comm);
+            let ranks_permuted = crate::topology::receive_ranks_permuted(&merged_comm);
+            if let Some(output) = &mut self.output {
+                // the children send their output regardless of the outcome
+                (output.gather)(&merged_comm, outcome.is_err() || self.priming || !own);
+            }
+            let counters = crate::counters::per_iteration(
+                &crate::counters::receive(&merged_comm),
+                iterations_run,
+            );
+            child_cpu = crate::launches::receive_cpu_time(&merged_comm);
+            let per_rank = match outcome {
+                Ok(per_rank) => per_rank,
+                Err(message) => {
+                    if reordered == Some(false) {
+                        // the children wait for the sample on the communicator with reordered ranks
+                        protocol::send_shutdown(&merged_comm);
+                    }
+                    self.launched(Launches::world(self.children() as u64, child_cpu));
+                    return Err(message);
+                }
+            };
+            if own {
+                self.drift = drift;
+                self.ranks_permuted = ranks_permuted;
+                self.counters = counters;
+                self.overhead = Some(Overhead {
+                    spawn: spawned - spawn_start,
+                    merge: merged - spawned,
+                    broadcast,
+                    reduce: outcome_start.elapsed(),
+                });
+                sample = Some(self.extrapolate(per_rank, iterations_run, iterations));
+            } else if !self.priming {
+                REORDER_REPLAYS.lock().unwrap().push(ReorderReplay {
+                    kernel: self.kernel.clone(),
+                    world_size: self.world_size,
+                    argument: self.arg.describe(),
+                    iterations,
+                    per_rank: per_rank
+                        .into_iter()
+                        .map(|time| timing::extrapolate(time, iterations_run, iterations))
+                        .collect(),
+                    drift,
+                    ranks_permuted,
+                    counters,
+                    placement: self.placement.clone(),
+                });
+            }
+            round_start = Instant::now();
+        }
+        self.launched(Launches::world(self.children() as u64, child_cpu));
+
+        Ok(sample)
+    }
+
+    /// Replay a sample of a kernel with ``reorder = compare`` on the communicator with reordered
+    /// ranks, which was run in the world of a sample on the one without, scaled to ``iterations``.
+    /// Returns ``None`` if no such sample is left.
+    fn replay_reordered(&mut self, iterations: u64) -> Option<Vec<Duration>> {
+        if self.reorder != Some(true) || self.priming {
+            return None;
+        }
+        let argument = self.arg.describe();
+        let mut replays = REORDER_REPLAYS.lock().unwrap();
+        let position = replays.iter().position(|replay| {
+            replay.kernel == self.kernel
+                && replay.world_size == self.world_size
+                && replay.argument == argument
+        })?;
+        let replay = replays.remove(position);
+        drop(replays);
+
+        self.drift = replay.drift;
+        self.ranks_permuted = replay.ranks_permuted;
+        self.counters = replay.counters;
+        self.placement = replay.placement;
+        self.truncated = None;
+        self.spawn_time = None;
+        self.overhead = None;
+        Some(
+            replay
+                .per_rank
+                .into_iter()
+                .map(|time| timing::extrapolate(time, replay.iterations, iterations))
+                .collect(),
+        )
+    }
+
+    /// Count the launches of a spawned world towards the next recorded sample and the run.
+    fn launched(&mut self, launches: Launches) {
+        self.launches = Launches::sum([&self.launches, &launches]);
+        crate::launches::note(launches);
+    }
+
+    /// Warn once per kernel if the iteration times of a rank drifted more than the
+    /// [threshold](crate::drift::threshold) over the last sample.
+    fn warn_drift(&self) {
+        let Some(drift) = self
+            .drift
+            .filter(|drift| crate::drift::exceeds(*drift, crate::drift::threshold()))
+        else {
+            return;
+        };
+        warn_once(
+            &format!("{} drift", self.kernel),
+            format_args!(
+                "kernel `{}`: the iterations of a sample got {:.1}% {} from the first to the last quartile, the mean time may be misleading. \
+                 Set {} to change the threshold of this warning.",
+                self.kernel,
+                drift.abs() * 100.0,
+                if drift > 0.0 { "slower" } else { "faster" },
+                crate::drift::DRIFT_THRESHOLD_VAR
+            ),
+        );
+    }
+
+    /// Warn if the times of the ranks of a sample vary more than the threshold of
+    /// ``MPIRION_VARIANCE_WARN``, once per kernel and world size.
+    fn warn_variance(&self, per_rank: &[Duration]) {
+        let Some(variation) = crate::variance::coefficient_of_variation(per_rank)
+            .filter(|variation| crate::variance::exceeds(*variation, crate::variance::threshold()))
+        else {
+            return;
+        };
+        warn_once(
+            &format!("{} variance {}", self.kernel, per_rank.len()),
+            format_args!(
+                "WARNING: kernel `{}` on {} ranks: the times of the ranks of a sample vary by {:.1}% (coefficient of variation). \
+                 This usually means a missing barrier or work that is not split evenly, not an interesting result. \
+                 Set {} to change the threshold of this warning.",
+                self.kernel,
+                per_rank.len(),
+                variation * 100.0,
+                crate::variance::VARIANCE_WARN_VAR
+            ),
+        );
+    }
+
+    /// Keep an MPI error of the protocol to fail the group with, see the
+    /// [`errhandler`](crate::errhandler) module, and return its message for the caller.
+    fn mpi_failed(&mut self, error: Error) -> String {
+        let message = error.to_string();
+        self.group_error = Some(error);
+        message
+    }
+
+    /// Warn that the children were built without optimizations, or abort if
+    /// ``MPIRION_REQUIRE_RELEASE`` is set.
+    fn warn_unoptimized(&self, merged_comm: &dyn Communicator, unoptimized: u64) {
+        if std::env::var(REQUIRE_RELEASE_VAR).is_ok_and(|value| value == "1") {
+            eprintln!(
+                "kernel `{}`: the children were built without optimizations, and {}=1 requires an optimized build, aborting. \
+                 Run the benchmarks with `cargo bench` or build them with `--release`.",
+                self.kernel, REQUIRE_RELEASE_VAR
+            );
+            merged_comm.abort(1);
+        }
+
+        warn_once(
+            "unoptimized children",
+            format_args!(
+                "WARNING: the children are built without optimizations, the timings are not representative. \
+                 Run the benchmarks with `cargo bench` or build them with `--release`."
+            ),
+        );
+        warn_once(
+            &format!("{} unoptimized", self.kernel),
+            format_args!(
+                "kernel `{}`: {} of {} children run an unoptimized build",
+                self.kernel,
+                unoptimized,
+                self.children()
+            ),
+        );
+    }
+
+    /// Print the diagnostics of a sample for ``--mpirion-verbose``.
+    fn print_sample(
+        &self,
+        iterations: u64,
+        elapsed: Duration,
+        per_rank: &[Duration],
+        mean: Duration,
+        rejected: usize,
+    ) {
+        let spawn_time = self
+            .spawn_time
+            .map_or("-".to_string(), |time| format!("{:?}", time));
+        let clock = self.clock.unwrap_or_else(crate::clock::selected);
+        let total: Duration = per_rank.iter().sum();
+        let min = per_rank.iter().min().copied().unwrap_or_default();
+        let max = per_rank.iter().max().copied().unwrap_or_default();
+        let imbalance = if min.is_zero() {
+            "-".to_string()
+        } else {
+            format!("{:.2}", max.as_secs_f64() / min.as_secs_f64())
+        };
+        let rejected = match self.reject_outliers {
+            Some(_) => format!(
+                " of {} ranks, rejected {} outliers",
+                per_rank.len() - rejected,
+                rejected
+            ),
+            None => String::new(),
+        };
+        let retries = match timeout_retries(&self.kernel) {
+            0 => String::new(),
+            retries => format!(", {} timed-out samples retried so far", retries),
+        };
+        let drift = self
+            .drift
+            .map_or("-".to_string(), |drift| format!("{:+.1}%", drift * 100.0));
+        let variation = crate::variance::coefficient_of_variation(per_rank)
+            .map_or("-".to_string(), |variation| {
+                format!("{:.1}%", variation * 100.0)
+            });
+        let truncated = match self.truncated {
+            Some(iterations_run) => format!(" (truncated after {}, extrapolated)", iterations_run),
+            None => String::new(),
+        };
+        eprintln!(
+            "mpirion: {}: {} iterations{} on {} children, spawn {}, sample {:?}, child total {:?}, mean {:?}{}, imbalance {} (max/min), variation {}, drift {}, clock {}{}",
+            progress_name(&self.kernel), iterations, truncated, per_rank.len(), spawn_time, elapsed, total, mean, rejected, imbalance, variation, drift, clock, retries
+        );
+    }
+
+    /// Run the kernel in the parent process instead of spawning children.
+    #[cfg(feature = "local-fallback")]
+    fn run_local(&mut self, iterations: u64) -> Result<Vec<Duration>, String> {
+        let run = self.local_kernel.unwrap_or_else(|| {
+            panic!(
+                "kernel `{}` has no local fallback, see BenchRunner::local_kernel",
+                self.kernel
+            )
+        });
+        if let Some(consumers) = &self.consumers {
+            return Err(format!(
+                "kernel `{}` runs in a pair with `{}`, which needs spawned children and cannot run with local-fallback",
+                self.kernel, consumers.kernel
+            ));
+        }
+        warn_once(
+            &self.kernel,
+            format_args!(
+                "mpirion: local-fallback is enabled, kernel `{}` runs in the parent process on {} rank(s) \
+                 instead of {} spawned children. The timings are NOT representative of an MPI run.",
+                self.kernel,
+                self.world.size(),
+                self.world_size
+            ),
+        );
+        if !self.tuning.is_empty() {
+            warn_once(
+                &format!("{} tuning", self.kernel),
+                format_args!(
+                    "kernel `{}`: local-fallback runs it in the parent, whose MPI is already initialized, so its tuning has no effect",
+                    self.kernel
+                ),
+            );
+        }
+        if let Some(mode) = self.placement_mode {
+            warn_once(
+                &format!("{} placement", self.kernel),
+                format_args!(
+                    "kernel `{}`: local-fallback runs it in the parent, so its placement {} has no effect",
+                    self.kernel, mode
+                ),
+            );
+        }
+
+        crate::io::set_scratch_path(crate::io::file_path(
+            &self.kernel,
+            crate::watchdog::next_world(),
+        ));
+        let output_tag = self
+            .output
+            .as_ref()
+            .map_or(TypeTag::none(), |output| output.tag);
+        let mut local = protocol::LocalRun::new(
+            iterations,
+            self.max_sample_time,
+            self.world.size() as u64,
+            &*self.const_arg,
+            &*self.arg,
+            output_tag,
+        );
+        crate::topology::select(self.reorder);
+        run(self.world, Parent::Local(&mut local));
+        crate::topology::select(None);
+
+        self.drift = local.drift();
+        self.placement = None;
+        self.ranks_permuted = None;
+        self.spawn_time = None;
+        self.overhead = None;
+        let iterations_run = local.iterations_run();
+        self.counters = crate::counters::per_iteration(&local.counters(), iterations_run);
+        let (outcome, output) = local.finish();
+        if let (Some(callback), Some(output), Ok(_)) = (&mut self.output, output, &outcome) {
+            if !self.priming {
+                (callback.local)(output);
+            }
+        }
+
+        outcome.map(|per_rank| self.extrapolate(per_rank, iterations_run, iterations))
+    }
+
+    /// Extrapolate the times of a sample that hit its time cap after ``iterations_run`` of the
+    /// ``requested`` iterations, and warn once that the samples of the kernel are truncated.
+    fn extrapolate(
+        &mut self,
+        per_rank: Vec<Duration>,
+        iterations_run: u64,
+        requested: u64,
+    ) -> Vec<Duration> {
+        if iterations_run >= requested {
+            self.truncated = None;
+            return per_rank;
+        }
+
+        self.truncated = Some(iterations_run);
+        warn_once(
+            &format!("{} truncated", self.kernel),
+            format_args!(
+                "kernel `{}`: a sample of {} iterations hit the max_sample_time of {:?} after {} iterations, \
+                 its times are extrapolated from the iterations that ran",
+                self.kernel,
+                requested,
+                self.max_sample_time.unwrap_or_default(),
+                iterations_run
+            ),
+        );
+        per_rank
+            .into_iter()
+            .map(|time| timing::extrapolate(time, iterations_run, requested))
+            .collect()
+    }
+}
+
+/// The number of iterations of the single spawn of a benchmark with ``--mpirion-quick``.
+pub const QUICK_ITERATIONS: u64 = 10;
+
+/// The sample size of criterion with ``--mpirion-quick``, which is the smallest it accepts.
+const QUICK_SAMPLE_SIZE: usize = 10;
+
+/// The time per iteration measured by the spawn of a benchmark with ``--mpirion-quick``, which is
+/// replayed for its remaining calls. Benchmarks are told apart by their kernel, world size and
+/// argument, and the replays of a benchmark declared through the
+/// [`MpirionContext`](crate::MpirionContext) end with it.
+struct QuickReplay {
+    kernel: String,
+    world_size: Rank,
+    argument: Option<ArgumentValue>,
+    per_iteration: Duration,
+    /// the calls of the benchmark that are still to come
+    remaining: usize,
+}
+
+static QUICK_REPLAYS: Mutex<Vec<QuickReplay>> = Mutex::new(Vec::new());
+
+/// A sample of a kernel with ``reorder = compare`` on the communicator with reordered ranks, which
+/// the children ran in the world of a sample on the communicator without, see
+/// [`BenchRunner::reorder`]. Benchmarks are told apart by their kernel, world size and argument.
+struct ReorderReplay {
+    kernel: String,
+    world_size: Rank,
+    argument: Option<ArgumentValue>,
+    iterations: u64,
+    per_rank: Vec<Duration>,
+    drift: Option<f64>,
+    ranks_permuted: Option<bool>,
+    counters: Vec<(String, Duration)>,
+    placement: Option<Placement>,
+}
+
+static REORDER_REPLAYS: Mutex<Vec<ReorderReplay>> = Mutex::new(Vec::new());
+
+/// A benchmark that criterion runs after it was declared through the
+/// [`MpirionContext`](crate::MpirionContext), see [`run_declared`].
+#[derive(Clone)]
+struct Declared {
+    /// the id of the benchmark in criterion
+    id: String,
+    kernel: String,
+}
+
+static DECLARED: Mutex<Option<Declared>> = Mutex::new(None);
+
+/// The id of the running benchmark, if it was declared through the
+/// [`MpirionContext`](crate::MpirionContext).
+fn declared_id() -> Option<String> {
+    DECLARED
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|declared| declared.id.clone())
+}
+
+/// The name of the running benchmark in the progress lines of ``--mpirion-verbose``: its id if it
+/// was declared through the [`MpirionContext`](crate::MpirionContext), as a kernel runs in several
+/// benchmarks, and the kernel otherwise.
+fn progress_name(kernel: &str) -> String {
+    match declared_id() {
+        Some(id) => format!("benchmark `{}`", id),
+        None => format!("kernel `{}`", kernel),
+    }
+}
+
+/// Run a benchmark that was declared through the [`MpirionContext`](crate::MpirionContext) with
+/// its id and kernel, so its runners know which benchmark they measure.
+pub(crate) fn run_declared(id: String, kernel: &str, bench: impl FnOnce()) {
+    // the benchmark may fail and unwind to the group, which must not leave it declared
+    struct Finished;
+    impl Drop for Finished {
+        fn drop(&mut self) {
+            *DECLARED.lock().unwrap() = None;
+        }
+    }
+
+    *DECLARED.lock().unwrap() = Some(Declared {
+        id,
+        kernel: kernel.to_string(),
+    });
+    QUICK_REPLAYS.lock().unwrap().clear();
+    let _finished = Finished;
+    bench();
+}
+
+/// The configuration of criterion with ``--mpirion-quick``, which is applied by ``mpirion_group!``
+/// on top of all other configuration. Criterion cannot skip its statistics, but it only calls the
+/// benchmark once for the warm-up, because the warm-up time is shorter than a spawn, and
+/// ten times for the samples. [`BenchRunner::run`] spawns the children on the
+/// warm-up call and replays the measured time for the samples, so each benchmark spawns once.
+/// The long measurement time only keeps criterion from warning that it cannot finish the samples in
+/// time, the replayed samples take no time at all. Criterion doesn't save the replayed samples as a
+/// baseline, see [`without_baseline`](crate::cli::without_baseline).
+pub fn quick_config(criterion: Criterion) -> Criterion {
+    criterion
+        .sample_size(QUICK_SAMPLE_SIZE)
+        .warm_up_time(Duration::from_nanos(1))
+        .measurement_time(Duration::from_secs(3600))
+        .nresamples(1000)
+        .without_plots()
+}
+
+/// The kernels of the samples that were retried after a timeout, once per retry.
+static RETRIES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// How often samples of the given kernel were retried after a timeout during this run, see
+/// [`BenchRunner::retry_on_timeout`].
+pub fn timeout_retries(kernel: &str) -> u32 {
+    RETRIES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|retried| *retried == kernel)
+        .count() as u32
+}
+
+/// The benchmark of the previous sample, see [`BenchRunner::discard_first_sample`].
+#[derive(Clone, PartialEq)]
+struct LastBenchmark {
+    kernel: String,
+    world_size: Rank,
+    argument: Option<ArgumentValue>,
+    /// the call site of ``discard_first_sample``, if the first sample is discarded
+    call_site: Option<&'static Location<'static>>,
+}
+
+static LAST_BENCHMARK: Mutex<Option<LastBenchmark>> = Mutex::new(None);
+
+/// The kernels of the priming samples run during this run, with the time each took.
+static PRIMINGS: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// The times of the priming samples that were run and discarded for the given kernel during this
+/// run, including spawning the children, see [`BenchRunner::discard_first_sample`].
+pub fn priming_samples(kernel: &str) -> Vec<Duration> {
+    PRIMINGS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(primed, _)| primed == kernel)
+        .map(|(_, elapsed)| *elapsed)
+        .collect()
+}
+
+/// Whether the ranks of the parent world other than rank 0 idle in [`idle_non_root`].
+static RANKS_IDLE: AtomicBool = AtomicBool::new(false);
+
+/// Convert a world size of any integer type to a [`Rank`], which is the type MPI counts processes
+/// in. Fails if the world size is zero, negative or doesn't fit into a [`Rank`], instead of
+/// truncating it, with a message that says which of them it is.
+///
+/// # Example
+/// ```rust
+/// use mpirion::runner::checked_world_size;
+///
+/// assert_eq!(checked_world_size(8usize), Ok(8));
+/// assert_eq!(checked_world_size(8u32), Ok(8));
+/// assert_eq!(checked_world_size(8i32), Ok(8));
+/// assert!(checked_world_size(0).unwrap_err().contains("is 0"));
+/// assert!(checked_world_size(-1i64).unwrap_err().contains("negative"));
+/// assert!(checked_world_size(-5_000_000_000i64).unwrap_err().contains("negative"));
+/// assert!(checked_world_size(u64::MAX).unwrap_err().contains("larger than"));
+/// assert!(checked_world_size(u128::MAX).unwrap_err().contains("larger than"));
+/// ```
+pub fn checked_world_size<N>(world_size: N) -> Result<Rank, String>
+where
+    N: TryInto<i128> + Copy + Display,
+{
+    let too_large = || {
+        format!(
+            "the world size is {}, which is larger than the maximum of {}",
+            world_size,
+            Rank::MAX
+        )
+    };
+    let wide: i128 = world_size.try_into().map_err(|_| too_large())?;
+    match wide {
+        0 => Err("the world size is 0, but at least one child is needed".to_string()),
+        wide if wide < 0 => Err(format!("the world size is negative ({})", world_size)),
+        wide => Rank::try_from(wide).map_err(|_| too_large()),
+    }
+}
+
+/// The world size above which a benchmark warns that it spawns an unusual number of children,
+/// unless it is set with ``--mpirion-max-world-size``.
+pub const WORLD_SIZE_WARNING: Rank = 256;
+
+/// Whether a world size is above the sanity threshold, which is ``max_world_size`` or
+/// [`WORLD_SIZE_WARNING`].
+///
+/// # Example
+/// ```rust
+/// use mpirion::runner::{exceeds_world_size_warning, WORLD_SIZE_WARNING};
+///
+/// assert!(!exceeds_world_size_warning(WORLD_SIZE_WARNING, None));
+/// assert!(exceeds_world_size_warning(WORLD_SIZE_WARNING + 1, None));
+/// assert!(!exceeds_world_size_warning(4096, Some(4096)));
+/// ```
+pub fn exceeds_world_size_warning(world_size: Rank, max_world_size: Option<Rank>) -> bool {
+    world_size > max_world_size.unwrap_or(WORLD_SIZE_WARNING)
+}
+
+/// Benchmark a kernel that is chosen by name at runtime, like ``mpirion_bench!``. The kernel must be
+/// registered on the child side, e.g. with [`register_kernel`](crate::register_kernel). Since the
+/// argument type of such a kernel isn't known at compile time, the argument is passed as text, see
+/// [`BenchRunner::text_arg`].
+pub fn bench_dyn<W: ParentWorld + ?Sized>(
+    kernel: &str,
+    bencher: &mut Bencher<'_>,
+    world: &W,
+    world_size: Rank,
+    arg: Option<&str>,
+) {
+    bencher.iter_custom(|iterations| {
+        let mut runner = BenchRunner::new(kernel, world.parent_world())
+            .world_size(world_size)
+            .budget(world.budget());
+        if let Some(arg) = arg {
+            runner = runner.text_arg(arg);
+        }
+        runner.run(iterations)
+    })
+}
+
+/// Idle on the ranks of a parent world other than rank 0, e.g. if the benchmark was launched with
+/// ``mpiexec -n 4`` instead of ``mpiexec -n 1``. The benchmarks only run on rank 0. The other ranks
+/// block until rank 0 calls [`release_idle_ranks`] and then return ``true``, so the caller can
+/// finalize MPI and exit. Rank 0 returns ``false`` immediately.
+pub fn idle_non_root(world: &dyn Communicator) -> bool {
+    if world.size() == 1 {
+        return false;
+    }
+
+    if world.rank() == 0 {
+        eprintln!(
+            "mpirion: the benchmark was launched on {} ranks, but only runs on rank 0. The other ranks \
+             idle until it finishes. Launch it with `mpiexec -n 1` instead.",
+            world.size()
+        );
+        RANKS_IDLE.store(true, Ordering::Relaxed);
+        return false;
+    }
+
+    // poll instead of blocking in the barrier, which keeps a core busy in some MPI implementations
+    let mut request = world.immediate_barrier();
+    loop {
+        match request.test() {
+            Ok(_) => return true,
+            Err(pending) => request = pending,
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Release the ranks idling in [`idle_non_root`]. Must be called on rank 0 once the benchmarks are
+/// done.
+pub fn release_idle_ranks(world: &dyn Communicator) {
+    if world.size() > 1 {
+        // the idle ranks wait in a nonblocking barrier, which only matches a nonblocking one
+        world.immediate_barrier().wait();
+    }
+}
+
+/// Check that a benchmark group runs on rank 0 of the parent world, and that the other ranks, if
+/// any, idle in [`idle_non_root`].
+pub fn check_parent_world(world: &dyn Communicator) -> Result<(), Error> {
+    if world.rank() != 0 {
+        return Err(Error::WrongRank { rank: world.rank() });
+    }
+    if world.size() > 1 && !RANKS_IDLE.load(Ordering::Relaxed) {
+        return Err(Error::WorldSize { size: world.size() });
+    }
+
+    Ok(())
+}
+
+/// The communicator from which rank 0 of the parent world spawns the children. Spawning is
+/// collective, so if other ranks idle in [`idle_non_root`], rank 0 spawns on its own.
+pub fn spawn_world(world: SimpleCommunicator) -> SimpleCommunicator {
+    if world.size() == 1 {
+        world
+    } else {
+        let self_comm = SimpleCommunicator::self_comm();
+        if let Err(error) = crate::errhandler::install(&self_comm) {
+            panic!(
+                "mpirion could not switch the spawning communicator to MPI_ERRORS_RETURN: {}",
+                error
+            );
+        }
+        self_comm
+    }
+}
+
+/// The mean of the times of the children that are at most ``mads`` median absolute deviations
+/// (MAD) away from the median, and the number of rejected children. If the MAD is zero, i.e. most
+/// ranks took exactly the same time, no rank is rejected.
+///
+/// # Example
+/// ```rust
+/// use mpirion::runner::robust_mean;
+/// use std::time::Duration;
+///
+/// let per_rank = [100, 102, 98, 101, 400].map(Duration::from_micros);
+/// let (mean, rejected) = robust_mean(&per_rank, 3.0);
+/// assert_eq!(rejected, 1);
+/// assert_eq!(mean, Duration::from_nanos(100_250));
+///
+/// // without outliers, all ranks are kept
+/// assert_eq!(robust_mean(&per_rank[..4], 3.0).1, 0);
+/// ```
+pub fn robust_mean(per_rank: &[Duration], mads: f64) -> (Duration, usize) {
+    let median_of = |mut values: Vec<f64>| {
+        values.sort_by(f64::total_cmp);
+        let middle = values.len() / 2;
+        if values.len() % 2 == 1 {
+            values[middle]
+        } else {
+            (values[middle - 1] + values[middle]) / 2.0
+        }
+    };
+
+    if per_rank.is_empty() {
+        return (Duration::ZERO, 0);
+    }
+
+    let nanos: Vec<f64> = per_rank
+        .iter()
+        .map(|duration| duration.as_nanos() as f64)
+        .collect();
+    let median = median_of(nanos.clone());
+    let mad = median_of(nanos.iter().map(|value| (value - median).abs()).collect());
+    if mad == 0.0 {
+        return (mean(per_rank), 0);
+    }
+
+    let kept: Vec<Duration> = per_rank
+        .iter()
+        .zip(&nanos)
+        .filter(|(_, value)| (*value - median).abs() <= mads * mad)
+        .map(|(duration, _)| *duration)
+        .collect();
+    (mean(&kept), per_rank.len() - kept.len())
+}
+
+/// Print a message once per key, usually the kernel or the id of a benchmark, so it doesn't repeat
+/// for every sample.
+pub(crate) fn warn_once(key: &str, message: std::fmt::Arguments) {
+    static WARNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    let mut warned = WARNED.lock().unwrap();
+    if !warned.iter().any(|warned| warned == key) {
+        eprintln!("{}", message);
+        warned.push(key.to_string());
+    }
+}
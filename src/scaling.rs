@@ -0,0 +1,258 @@
+//! Scaling summaries of kernels that were measured at several world sizes. At the end of a run,
+//! ``scaling-<kernel>.csv`` and ``scaling-<kernel>.md`` are written into the output directory of
+//! criterion for each kernel of the run, with the mean time per iteration at each world size, its
+//! standard deviation over the samples, and the speedup and parallel efficiency relative to the
+//! smallest world size, see [`ScalingRow`].
+//!
+//! The times come from the samples that [`report`](crate::report) records. A sweep often doesn't
+//! fit into one job allocation, so the rows of an existing file are kept, and only the world sizes
+//! measured in this run are replaced. A kernel that is benchmarked with several inputs at the same
+//! world size is averaged over them, so a sweep should measure each kernel with a single input.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::report::SampleRecord;
+
+/// The header of the CSV file.
+pub const CSV_HEADER: &str = "world_size,mean_ns,stddev_ns,speedup,efficiency";
+
+/// The time per iteration of a kernel at a world size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScalingRow {
+    /// the number of children
+    pub world_size: usize,
+    /// the mean time per iteration over the samples
+    pub mean: Duration,
+    /// the standard deviation of the time per iteration over the samples
+    pub stddev: Duration,
+}
+
+/// The time per iteration of the given kernel at each world size it was measured at, ordered by
+/// world size.
+///
+/// # Example
+/// ```rust
+/// use mpirion::launches::Launches;
+/// use mpirion::report::SampleRecord;
+/// use mpirion::scaling::measure;
+/// use std::time::Duration;
+///
+/// let sample = |ranks, iterations, micros| SampleRecord {
+///     benchmark: None,
+///     kernel: "scan_kernel".to_string(),
+///     iterations,
+///     reported: Duration::from_micros(micros),
+///     per_rank: vec![Duration::from_micros(micros); ranks],
+///     placement: None,
+///     overhead: None,
+///     argument: None,
+///     truncated: false,
+///     launches: Launches::NONE,
+///     threads: None,
+///     tuning: Vec::new(),
+///     placement_mode: None,
+///     ranks_permuted: None,
+///     counters: Vec::new(),
+/// };
+/// let rows = measure(&[sample(4, 10, 1000), sample(2, 10, 2000), sample(4, 20, 2400)], "scan_kernel");
+/// assert_eq!(rows.len(), 2);
+/// assert_eq!((rows[0].world_size, rows[0].mean, rows[0].stddev), (2, Duration::from_micros(200), Duration::ZERO));
+/// assert_eq!((rows[1].world_size, rows[1].mean, rows[1].stddev), (4, Duration::from_micros(110), Duration::from_micros(10)));
+/// ```
+pub fn measure(samples: &[SampleRecord], kernel: &str) -> Vec<ScalingRow> {
+    let mut world_sizes: Vec<usize> = samples
+        .iter()
+        .filter(|sample| sample.kernel == kernel && sample.iterations > 0)
+        .map(|sample| sample.per_rank.len())
+        .collect();
+    world_sizes.sort_unstable();
+    world_sizes.dedup();
+
+    world_sizes
+        .into_iter()
+        .map(|world_size| {
+            // in nanoseconds, which keeps whole numbers exact
+            let times: Vec<f64> = samples
+                .iter()
+                .filter(|sample| {
+                    sample.kernel == kernel
+                        && sample.iterations > 0
+                        && sample.per_rank.len() == world_size
+                })
+                .map(|sample| sample.reported.as_nanos() as f64 / sample.iterations as f64)
+                .collect();
+            let mean = times.iter().sum::<f64>() / times.len() as f64;
+            let variance =
+                times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / times.len() as f64;
+            ScalingRow {
+                world_size,
+                mean: Duration::from_nanos(mean.round() as u64),
+                stddev: Duration::from_nanos(variance.sqrt().round() as u64),
+            }
+        })
+        .collect()
+}
+
+/// Replace the rows of the world sizes measured in this run, and keep the others, ordered by world
+/// size.
+///
+/// # Example
+/// ```rust
+/// use mpirion::scaling::{merge, ScalingRow};
+/// use std::time::Duration;
+///
+/// let row = |world_size, millis| ScalingRow { world_size, mean: Duration::from_millis(millis), stddev: Duration::ZERO };
+/// let merged = merge(vec![row(2, 100), row(4, 60)], vec![row(8, 40), row(4, 55)]);
+/// assert_eq!(merged, vec![row(2, 100), row(4, 55), row(8, 40)]);
+/// ```
+pub fn merge(previous: Vec<ScalingRow>, current: Vec<ScalingRow>) -> Vec<ScalingRow> {
+    let mut rows: Vec<ScalingRow> = previous
+        .into_iter()
+        .filter(|row| {
+            !current
+                .iter()
+                .any(|measured| measured.world_size == row.world_size)
+        })
+        .collect();
+    rows.extend(current);
+    rows.sort_by_key(|row| row.world_size);
+    rows
+}
+
+/// The speedup and the parallel efficiency of a row relative to the smallest world size, or
+/// ``None`` if the row took no measurable time.
+pub(crate) fn speedup(smallest: &ScalingRow, row: &ScalingRow) -> Option<(f64, f64)> {
+    if row.mean.is_zero() || row.world_size == 0 {
+        return None;
+    }
+    let speedup = smallest.mean.as_secs_f64() / row.mean.as_secs_f64();
+    Some((
+        speedup,
+        speedup * smallest.world_size as f64 / row.world_size as f64,
+    ))
+}
+
+/// The rows as CSV, with the speedup and efficiency relative to the first row.
+///
+/// # Example
+/// ```rust
+/// use mpirion::scaling::{to_csv, ScalingRow};
+/// use std::time::Duration;
+///
+/// let row = |world_size, millis| ScalingRow { world_size, mean: Duration::from_millis(millis), stddev: Duration::from_millis(1) };
+/// assert_eq!(
+///     to_csv(&[row(2, 100), row(4, 60), row(8, 50)]),
+///     "world_size,mean_ns,stddev_ns,speedup,efficiency\n\
+///      2,100000000,1000000,1.000,1.000\n\
+///      4,60000000,1000000,1.667,0.833\n\
+///      8,50000000,1000000,2.000,0.500\n"
+/// );
+/// ```
+pub fn to_csv(rows: &[ScalingRow]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for row in rows {
+        let (speedup, efficiency) = match rows.first().and_then(|smallest| speedup(smallest, row)) {
+            Some((speedup, efficiency)) => {
+                (format!("{:.3}", speedup), format!("{:.3}", efficiency))
+            }
+            None => (String::new(), String::new()),
+        };
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{}",
+            row.world_size,
+            crate::timing::duration_to_nanos(row.mean),
+            crate::timing::duration_to_nanos(row.stddev),
+            speedup,
+            efficiency
+        );
+    }
+    csv
+}
+
+/// Parse the rows of a CSV file written by [`to_csv`]. Lines that are not rows are skipped, and the
+/// speedup and efficiency are recomputed when the file is written again.
+///
+/// # Example
+/// ```rust
+/// use mpirion::scaling::{parse_csv, to_csv, ScalingRow};
+/// use std::time::Duration;
+///
+/// let rows = [ScalingRow { world_size: 16, mean: Duration::from_nanos(1234), stddev: Duration::from_nanos(5) }];
+/// assert_eq!(parse_csv(&to_csv(&rows)), rows);
+/// assert!(parse_csv("not a scaling file").is_empty());
+/// ```
+pub fn parse_csv(csv: &str) -> Vec<ScalingRow> {
+    csv.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(',');
+            Some(ScalingRow {
+                world_size: fields.next()?.trim().parse().ok()?,
+                mean: Duration::from_nanos(fields.next()?.trim().parse().ok()?),
+                stddev: Duration::from_nanos(fields.next()?.trim().parse().ok()?),
+            })
+        })
+        .collect()
+}
+
+/// The rows as a Markdown table.
+pub fn to_markdown(kernel: &str, rows: &[ScalingRow]) -> String {
+    let mut markdown = format!(
+        "# Scaling of `{}`\n\n| world size | mean | stddev | speedup | efficiency |\n|---:|---:|---:|---:|---:|\n",
+        kernel
+    );
+    for row in rows {
+        let (speedup, efficiency) = match rows.first().and_then(|smallest| speedup(smallest, row)) {
+            Some((speedup, efficiency)) => (
+                format!("{:.2}×", speedup),
+                format!("{:.0}%", efficiency * 100.0),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let _ = writeln!(
+            markdown,
+            "| {} | {:?} | {:?} | {} | {} |",
+            row.world_size, row.mean, row.stddev, speedup, efficiency
+        );
+    }
+    markdown
+}
+
+/// Write the scaling summaries of the kernels of the given samples into the output directory of
+/// criterion, merged with the files of previous runs, which are
+/// [checked for regressions](crate::regression) first. Called by
+/// [`report::write`](crate::report::write).
+pub fn write(home: &Path, samples: &[SampleRecord]) {
+    let mut kernels: Vec<&str> = samples
+        .iter()
+        .map(|sample| sample.kernel.as_str())
+        .collect();
+    kernels.sort_unstable();
+    kernels.dedup();
+
+    for kernel in kernels {
+        let csv = home.join(format!("scaling-{}.csv", kernel));
+        let previous = std::fs::read_to_string(&csv)
+            .map(|csv| parse_csv(&csv))
+            .unwrap_or_default();
+        let measured = measure(samples, kernel);
+        crate::regression::check(kernel, &previous, &measured);
+        let rows = merge(previous, measured);
+        let result = std::fs::write(&csv, to_csv(&rows)).and_then(|_| {
+            std::fs::write(
+                home.join(format!("scaling-{}.md", kernel)),
+                to_markdown(kernel, &rows),
+            )
+        });
+        if let Err(error) = result {
+            eprintln!(
+                "mpirion: could not write the scaling summary of `{}` to {}: {}",
+                kernel,
+                home.display(),
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! A check of the MPI setup before the benchmarks run. ``mpirion_main!`` enters this mode with
+//! ``--mpirion-selfcheck``. It spawns a built-in kernel on two children, which takes part in the
+//! whole protocol with the parent and reduces over the children, and checks the times they report.
+//! It does so twice, so the second spawn shows that the children of the first one were torn down.
+//! Job scripts can run it first and bail out early if MPI cannot spawn processes.
+//!
+//! The kernel is registered in every binary, see [`KERNEL`].
+
+use std::time::{Duration, Instant};
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+use crate::registry::KernelEntry;
+use crate::runner::BenchRunner;
+
+/// The name of the built-in kernel of the self check.
+pub const KERNEL: &str = "mpirion_selfcheck";
+
+/// The number of children the self check spawns.
+const WORLD_SIZE: Rank = 2;
+
+/// The time after which a sample of the self check is considered hung.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The longest time a child may report for the kernel, which does a single reduction.
+const SANE_TIME: Duration = Duration::from_secs(1);
+
+fn mpirion_selfcheck_kernel(comm: &dyn Communicator) -> Result<(), String> {
+    let mut children = 0u64;
+    comm.all_reduce_into(&1u64, &mut children, SystemOperation::sum());
+    if children != comm.size() as u64 {
+        return Err(format!(
+            "the reduction over {} children returned {}",
+            comm.size(),
+            children
+        ));
+    }
+    Ok(())
+}
+
+crate::mpirion_kernel!(kernel = mpirion_selfcheck_kernel as mpirion_selfcheck);
+
+/// The entry of the built-in kernel, which the [`registry`](crate::registry) always finds.
+pub(crate) fn entry() -> KernelEntry {
+    KernelEntry {
+        name: KERNEL,
+        run: execute_kernel_mpirion_selfcheck,
+        info: kernel_info_mpirion_selfcheck,
+    }
+}
+
+/// Check that the times of the children are plausible for the built-in kernel.
+///
+/// # Example
+/// ```rust
+/// use mpirion::selfcheck::check_times;
+/// use std::time::Duration;
+///
+/// assert!(check_times(&[Duration::from_micros(20), Duration::from_micros(25)], 2).is_ok());
+/// assert!(check_times(&[Duration::from_micros(20)], 2).unwrap_err().contains("1 of 2"));
+/// assert!(check_times(&[Duration::ZERO, Duration::from_micros(25)], 2).unwrap_err().contains("zero"));
+/// assert!(check_times(&[Duration::from_secs(30), Duration::from_micros(25)], 2).is_err());
+/// ```
+pub fn check_times(per_rank: &[Duration], world_size: Rank) -> Result<(), String> {
+    if per_rank.len() != world_size as usize {
+        return Err(format!(
+            "{} of {} children reported a time",
+            per_rank.len(),
+            world_size
+        ));
+    }
+    for (rank, time) in per_rank.iter().enumerate() {
+        if time.is_zero() {
+            return Err(format!(
+                "rank {} reported zero time, is the clock of the children broken?",
+                rank
+            ));
+        }
+        if *time > SANE_TIME {
+            return Err(format!(
+                "rank {} reported {:?} for a single reduction",
+                rank, time
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Run the self check and print a diagnosis. Returns the exit code of the process.
+pub fn run(world: &dyn Communicator) -> i32 {
+    eprintln!("mpirion selfcheck: {}", crate::meta::Environment::collect());
+    eprintln!(
+        "mpirion selfcheck: threading level {:?}, parent world of {} rank(s)",
+        crate::threading::granted(),
+        world.size()
+    );
+
+    for attempt in ["spawn, merge and protocol", "teardown and second spawn"] {
+        let start = Instant::now();
+        let result = BenchRunner::new(KERNEL, world)
+            .world_size(WORLD_SIZE)
+            .timeout(TIMEOUT)
+            .try_run_per_rank(1)
+            .and_then(|per_rank| check_times(&per_rank, WORLD_SIZE).map(|_| per_rank));
+
+        match result {
+            Ok(per_rank) => eprintln!(
+                "mpirion selfcheck: {}: ok in {:?}, kernel times {:?}",
+                attempt,
+                start.elapsed(),
+                per_rank
+            ),
+            Err(message) => {
+                eprintln!("mpirion selfcheck: {}: {}", attempt, message);
+                eprintln!("mpirion selfcheck: FAIL");
+                return 1;
+            }
+        }
+    }
+
+    eprintln!("mpirion selfcheck: PASS");
+    0
+}
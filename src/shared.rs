@@ -0,0 +1,202 @@
+//! Windows of memory shared by the ranks of a node, allocated with ``MPI_Win_allocate_shared``,
+//! for benchmarks of shared-memory algorithms that copy from the memory of other ranks instead of
+//! sending messages. Allocating the window is part of the setup, so it fits the ``init`` and
+//! ``teardown`` functions of ``mpirion_kernel!``, like the requests of the
+//! [`persistent`](crate::persistent) module: the init function [allocates](SharedWindow::allocate)
+//! the window, the kernel reads and writes it, and the teardown function [frees](SharedWindow::free)
+//! it.
+//!
+//! The communicator is split by shared memory first, so every node has its own window, and the
+//! slices of a window are addressed by the rank in the communicator of the node, see
+//! [`node`](SharedWindow::node). Each rank has a slice of the same length. Its own slice is
+//! writable with [`local_mut`](SharedWindow::local_mut), the slices of all ranks are readable with
+//! [`slice`](SharedWindow::slice). Like the buffer of a persistent request, the slices are borrowed
+//! through a shared reference, and the window checks like a ``RefCell`` that the own slice is not
+//! read while it is written.
+//!
+//! The window checks nothing across processes. A rank that reads the slice of another rank must
+//! [synchronize](SharedWindow::synchronize) with it between the write and the read, and again
+//! before it is written the next time.
+//!
+//! # Example
+//! ```rust
+//! use mpirion::prelude::*;
+//! use mpirion::shared::SharedWindow;
+//!
+//! fn init(comm: &dyn Communicator) -> SharedWindow<u64> {
+//!     SharedWindow::allocate(comm, 1024, 0)
+//! }
+//!
+//! fn reset<'s>(_comm: &dyn Communicator, window: &'s SharedWindow<u64>) -> &'s SharedWindow<u64> {
+//!     window
+//! }
+//!
+//! fn teardown(_comm: &dyn Communicator, window: SharedWindow<u64>) {
+//!     window.free();
+//! }
+//!
+//! // every rank writes its slice and sums the slice of the next rank of its node
+//! fn ring_kernel(_comm: &dyn Communicator, window: &&SharedWindow<u64>) {
+//!     let (rank, size) = (window.node().rank(), window.node().size());
+//!     window.local_mut().fill(rank as u64);
+//!     window.synchronize();
+//!     let sum: u64 = window.slice((rank + 1) % size).iter().sum();
+//!     std::hint::black_box(sum);
+//!     window.synchronize();
+//! }
+//!
+//! mpirion_kernel!(kernel = ring_kernel, init = init, reset = reset, teardown = teardown, data = ref);
+//! ```
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::mem::{align_of, size_of};
+use std::os::raw::{c_int, c_void};
+
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::topology::SimpleCommunicator;
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+
+/// A window of shared memory with a slice of ``T`` for each rank of a node, see the module
+/// documentation. It is freed when it is dropped.
+pub struct SharedWindow<T> {
+    raw: ffi::MPI_Win,
+    /// the address and length of the slice of each rank of the node
+    slices: Vec<(*mut T, usize)>,
+    /// the borrows of the slices, only the own one is borrowed mutably
+    borrows: Vec<RefCell<()>>,
+    /// dropped after the window in [`Drop`]
+    node: SimpleCommunicator,
+}
+
+impl<T: Copy> SharedWindow<T> {
+    /// Split ``comm`` by shared memory and allocate a window on each node with a slice of ``len``
+    /// values for each rank, all set to ``value``. Collective over ``comm``, and the slices of all
+    /// ranks of the node are set when it returns. Panics if the slice is longer than MPI can
+    /// address, or if MPI returns memory that is not aligned for ``T``.
+    pub fn allocate(comm: &dyn Communicator, len: usize, value: T) -> Self {
+        let node = comm.split_shared(comm.rank());
+        let bytes = len
+            .checked_mul(size_of::<T>())
+            .and_then(|bytes| ffi::MPI_Aint::try_from(bytes).ok())
+            .expect("the slice of a shared window is longer than MPI can address");
+        let unit = c_int::try_from(size_of::<T>().max(1))
+            .expect("the values of a shared window are too large");
+        let mut base: *mut T = std::ptr::null_mut();
+        let mut raw = std::mem::MaybeUninit::uninit();
+        // SAFETY: MPI writes the address of the own slice into base and the window into raw
+        let raw = unsafe {
+            ffi::MPI_Win_allocate_shared(
+                bytes,
+                unit,
+                ffi::RSMPI_INFO_NULL,
+                node.as_raw(),
+                &mut base as *mut *mut T as *mut c_void,
+                raw.as_mut_ptr(),
+            );
+            // all slices stay accessible until the window is freed, ordered by synchronize
+            ffi::MPI_Win_lock_all(0, raw.assume_init());
+            raw.assume_init()
+        };
+        let slices: Vec<_> = (0..node.size())
+            .map(|rank| {
+                let (mut size, mut displacement_unit, mut address) =
+                    (0, 0, std::ptr::null_mut::<T>());
+                // SAFETY: the rank is in the group of the window
+                unsafe {
+                    ffi::MPI_Win_shared_query(
+                        raw,
+                        rank,
+                        &mut size,
+                        &mut displacement_unit,
+                        &mut address as *mut *mut T as *mut c_void,
+                    );
+                }
+                assert!(
+                    (address as usize).is_multiple_of(align_of::<T>()),
+                    "MPI returned a shared slice that is not aligned for the values"
+                );
+                (address, len)
+            })
+            .collect();
+        let borrows = slices.iter().map(|_| RefCell::new(())).collect();
+        let window = SharedWindow {
+            raw,
+            slices,
+            borrows,
+            node,
+        };
+        window.local_mut().fill(value);
+        window.synchronize();
+        window
+    }
+}
+
+impl<T> SharedWindow<T> {
+    /// The communicator of the ranks that share the window, split from the communicator of
+    /// [`allocate`](Self::allocate). The slices are addressed by the ranks in it.
+    pub fn node(&self) -> &SimpleCommunicator {
+        &self.node
+    }
+
+    /// The slice of this rank, to write into. Panics if it is borrowed.
+    pub fn local_mut(&self) -> RefMut<'_, [T]> {
+        let rank = self.node.rank() as usize;
+        let (address, len) = self.slices[rank];
+        let guard = self.borrows[rank]
+            .try_borrow_mut()
+            .expect("the slice of this rank in a shared window is borrowed");
+        // SAFETY: the slice was allocated for this rank, and the guard keeps it from being read
+        RefMut::map(guard, |_| unsafe {
+            std::slice::from_raw_parts_mut(address, len)
+        })
+    }
+
+    /// The slice of ``rank`` in the communicator of the node, including this one. Panics if the
+    /// rank is not on the node, or if the slice of this rank is written. The values of other ranks
+    /// are only complete after a [`synchronize`](Self::synchronize) that follows their writes.
+    pub fn slice(&self, rank: Rank) -> Ref<'_, [T]> {
+        let index = usize::try_from(rank)
+            .ok()
+            .filter(|index| *index < self.slices.len())
+            .unwrap_or_else(|| panic!("rank {} is not on the node of the shared window", rank));
+        let (address, len) = self.slices[index];
+        let guard = self.borrows[index]
+            .try_borrow()
+            .expect("the slice of this rank in a shared window is written");
+        // SAFETY: other processes only write the slice between synchronizations, see the module
+        // documentation
+        Ref::map(guard, |_| unsafe {
+            std::slice::from_raw_parts(address, len)
+        })
+    }
+
+    /// Make the writes of all ranks of the node visible to each other, like ``MPI_Win_sync``
+    /// around a barrier of the node. Collective over the node.
+    pub fn synchronize(&self) {
+        // SAFETY: the window is locked for all ranks since it was allocated
+        unsafe {
+            ffi::MPI_Win_sync(self.raw);
+        }
+        self.node.barrier();
+        // SAFETY: as above
+        unsafe {
+            ffi::MPI_Win_sync(self.raw);
+        }
+    }
+
+    /// Free the window, the same as dropping it. Collective over the node. Meant for the teardown
+    /// function of a kernel, so the window is visibly freed before the communicator.
+    pub fn free(self) {}
+}
+
+impl<T> Drop for SharedWindow<T> {
+    fn drop(&mut self) {
+        // SAFETY: the slices are not borrowed, since the window is dropped
+        unsafe {
+            ffi::MPI_Win_unlock_all(self.raw);
+            ffi::MPI_Win_free(&mut self.raw);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Sub-communicators for kernels whose ranks work in groups, like the row and column
+//! communicators of SUMMA. A kernel declares how the world of the children is split with
+//! ``split = by_rows_and_columns(4)`` in the named parameters syntax of ``mpirion_kernel!``, or
+//! with its own function ``split = my_split`` that takes a ``&dyn Communicator`` and returns a
+//! [`SplitSpec`].
+//!
+//! The split is applied once per spawned world, before the setup, and the sub-communicators are
+//! passed to the kernel as its context, a shared reference to a [`Split`]. The kernel is timed on
+//! the whole world of the children as usual, so the barrier before each iteration and the reduction
+//! of the times span all ranks, not only those of one sub-communicator.
+
+use crate::mpi::topology::{Color, SimpleCommunicator};
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+
+/// How the world is split: the color of this rank in each sub-communicator. Ranks with the same
+/// color in the same part end up in the same sub-communicator, ordered by their rank in the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitSpec {
+    /// the color of this rank in each part of the split, which must not be negative
+    pub colors: Vec<Rank>,
+}
+
+impl SplitSpec {
+    /// A split into the given parts.
+    pub fn new(colors: impl IntoIterator<Item = Rank>) -> Self {
+        Self {
+            colors: colors.into_iter().collect(),
+        }
+    }
+
+    /// The row of ``rank`` in a grid with ``columns`` columns, in row-major order. The last row is
+    /// shorter if the world size is not a multiple of ``columns``.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::split::SplitSpec;
+    ///
+    /// // 6 ranks in rows of 3: [0, 1, 2] and [3, 4, 5]
+    /// assert_eq!(SplitSpec::rows(4, 3).colors, [1]);
+    /// // and in columns: [0, 3], [1, 4] and [2, 5]
+    /// assert_eq!(SplitSpec::columns(4, 3).colors, [1]);
+    /// assert_eq!(SplitSpec::rows_and_columns(4, 3).colors, [1, 1]);
+    /// assert_eq!(SplitSpec::rows_and_columns(2, 3).colors, [0, 2]);
+    /// ```
+    pub fn rows(rank: Rank, columns: Rank) -> Self {
+        Self::new([rank / columns])
+    }
+
+    /// The column of ``rank`` in a grid with ``columns`` columns, in row-major order.
+    pub fn columns(rank: Rank, columns: Rank) -> Self {
+        Self::new([rank % columns])
+    }
+
+    /// The row and the column of ``rank`` in a grid with ``columns`` columns, in this order.
+    pub fn rows_and_columns(rank: Rank, columns: Rank) -> Self {
+        Self::new([rank / columns, rank % columns])
+    }
+}
+
+/// Split the world into rows of ``columns`` consecutive ranks. Panics if ``columns`` is not
+/// positive.
+pub fn by_rows(columns: Rank) -> impl Fn(&dyn Communicator) -> SplitSpec {
+    assert!(
+        columns > 0,
+        "a split needs at least one column, not {}",
+        columns
+    );
+    move |world| SplitSpec::rows(world.rank(), columns)
+}
+
+/// Split the world into the columns of a grid with ``columns`` columns, in row-major order.
+/// Panics if ``columns`` is not positive.
+pub fn by_columns(columns: Rank) -> impl Fn(&dyn Communicator) -> SplitSpec {
+    assert!(
+        columns > 0,
+        "a split needs at least one column, not {}",
+        columns
+    );
+    move |world| SplitSpec::columns(world.rank(), columns)
+}
+
+/// Split the world into the rows and the columns of a grid with ``columns`` columns, in row-major
+/// order. The row communicator is part 0 of the [`Split`], the column communicator part 1.
+/// Panics if ``columns`` is not positive.
+pub fn by_rows_and_columns(columns: Rank) -> impl Fn(&dyn Communicator) -> SplitSpec {
+    assert!(
+        columns > 0,
+        "a split needs at least one column, not {}",
+        columns
+    );
+    move |world| SplitSpec::rows_and_columns(world.rank(), columns)
+}
+
+/// The sub-communicators of this rank, one per part of its [`SplitSpec`], passed to the kernel as
+/// its context.
+pub struct Split {
+    parts: Vec<SimpleCommunicator>,
+}
+
+impl Split {
+    /// Split ``world`` as ``spec`` says. Collective over ``world``, and every rank must return the
+    /// same number of parts. Called by the code ``mpirion_kernel!`` generates for a kernel with a
+    /// ``split``, once per spawned world. Panics with the name of the kernel if a color is negative.
+    pub fn new<F>(kernel: &str, world: &dyn Communicator, spec: F) -> Self
+    where
+        F: Fn(&dyn Communicator) -> SplitSpec,
+    {
+        let spec = spec(world);
+        let parts = spec
+            .colors
+            .iter()
+            .map(|&color| {
+                if color < 0 {
+                    panic!(
+                        "kernel `{}`: rank {} has the negative split color {}",
+                        kernel,
+                        world.rank(),
+                        color
+                    );
+                }
+                world
+                    .split_by_color(Color::with_value(color))
+                    .expect("every rank has a color")
+            })
+            .collect();
+        Self { parts }
+    }
+
+    /// The sub-communicator of the given part of the split. Panics if the split has fewer parts.
+    pub fn part(&self, index: usize) -> &dyn Communicator {
+        match self.parts.get(index) {
+            Some(part) => part,
+            None => panic!(
+                "the split has {} parts, there is no part {}",
+                self.parts.len(),
+                index
+            ),
+        }
+    }
+
+    /// The number of parts of the split.
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Whether the split has no parts at all.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+}
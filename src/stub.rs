@@ -0,0 +1,844 @@
+//! A stand-in for the mpi crate, used instead of it with the ``stub`` feature. It has the parts of
+//! the mpi API that mpirion and its macros use, so mpirion and the crates depending on it build
+//! without an MPI installation, e.g. on docs.rs. Nothing can run: [`initialize`] panics, and so
+//! would every other function if it could be reached.
+//!
+//! The modules mirror the layout of the mpi crate, so the paths in mpirion are the same for both.
+
+use std::marker::PhantomData;
+
+/// The rank of a process in a communicator.
+pub type Rank = i32;
+
+fn unsupported() -> ! {
+    panic!("mpirion was built with the `stub` feature, which cannot run MPI programs")
+}
+
+/// Panics, the stub cannot initialize MPI.
+pub fn initialize() -> Option<environment::Universe> {
+    unsupported()
+}
+
+/// The threading levels of MPI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threading {
+    Single,
+    Funneled,
+    Serialized,
+    Multiple,
+}
+
+/// Panics, the stub cannot initialize MPI.
+pub fn initialize_with_threading(
+    _threading: Threading,
+) -> Option<(environment::Universe, Threading)> {
+    unsupported()
+}
+
+pub mod environment {
+    use super::topology::SimpleCommunicator;
+
+    /// Panics, the stub has no MPI library.
+    pub fn version() -> (i32, i32) {
+        super::unsupported()
+    }
+
+    /// Panics, the stub has no MPI library.
+    pub fn library_version() -> Result<String, std::string::FromUtf8Error> {
+        super::unsupported()
+    }
+
+    /// Panics, the stub has no MPI library.
+    pub fn processor_name() -> Result<String, std::string::FromUtf8Error> {
+        super::unsupported()
+    }
+
+    /// Panics, the stub has no MPI clock.
+    pub fn time() -> f64 {
+        super::unsupported()
+    }
+
+    pub struct Universe(());
+
+    // like in the mpi crate, dropping the universe finalizes MPI
+    impl Drop for Universe {
+        fn drop(&mut self) {}
+    }
+
+    impl Universe {
+        pub fn world(&self) -> SimpleCommunicator {
+            super::unsupported()
+        }
+    }
+}
+
+pub mod datatype {
+    use super::ffi::MPI_Datatype;
+    use super::raw::AsRaw;
+
+    /// The MPI datatype of a type that implements [`Equivalence`].
+    pub struct DatatypeRef(());
+
+    unsafe impl AsRaw for DatatypeRef {
+        type Raw = MPI_Datatype;
+
+        fn as_raw(&self) -> MPI_Datatype {
+            super::unsupported()
+        }
+    }
+
+    /// Types that can be transferred with MPI.
+    ///
+    /// # Safety
+    /// Like in the mpi crate, the type must have a fixed memory layout.
+    pub unsafe trait Equivalence {
+        fn equivalent_datatype() -> DatatypeRef {
+            super::unsupported()
+        }
+    }
+
+    macro_rules! equivalence {
+        ($($ty:ty),*) => {
+            $(unsafe impl Equivalence for $ty {})*
+        };
+    }
+
+    equivalence!(bool, char, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+    unsafe impl<T: Equivalence, const N: usize> Equivalence for [T; N] {}
+
+    /// A buffer that can be sent.
+    pub trait Buffer {}
+
+    impl<T: Equivalence> Buffer for T {}
+    impl<T: Equivalence> Buffer for [T] {}
+    impl<T: Equivalence> Buffer for Vec<T> {}
+
+    /// A buffer that can be received into.
+    pub trait BufferMut {}
+
+    impl<T: Equivalence> BufferMut for T {}
+    impl<T: Equivalence> BufferMut for [T] {}
+    impl<T: Equivalence> BufferMut for Vec<T> {}
+}
+
+pub mod point_to_point {
+    pub struct Status(());
+}
+
+pub mod request {
+    use super::point_to_point::Status;
+
+    pub struct Request(());
+
+    // like in the mpi crate, a request must be completed or forgotten before it is dropped
+    impl Drop for Request {
+        fn drop(&mut self) {}
+    }
+
+    impl Request {
+        pub fn test(self) -> Result<Status, Self> {
+            super::unsupported()
+        }
+
+        pub fn wait(self) -> Status {
+            super::unsupported()
+        }
+    }
+}
+
+pub mod topology {
+    use super::collective::Process;
+    use super::ffi::MPI_Comm;
+    use super::raw::AsRaw;
+    use super::Rank;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MergeOrder {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Color(i32);
+
+    impl Color {
+        pub fn with_value(value: i32) -> Self {
+            Color(value)
+        }
+    }
+
+    pub trait Communicator: AsRaw<Raw = MPI_Comm> {
+        fn rank(&self) -> Rank;
+
+        fn size(&self) -> Rank;
+
+        fn process_at_rank(&self, rank: Rank) -> Process<'_> {
+            Process::new(rank)
+        }
+
+        fn this_process(&self) -> Process<'_> {
+            self.process_at_rank(self.rank())
+        }
+
+        fn abort(&self, _error_code: i32) -> ! {
+            super::unsupported()
+        }
+
+        fn split_shared(&self, _key: i32) -> SimpleCommunicator {
+            super::unsupported()
+        }
+
+        fn split_by_color(&self, _color: Color) -> Option<SimpleCommunicator> {
+            super::unsupported()
+        }
+
+        fn create_cartesian_communicator(
+            &self,
+            _dims: &[Rank],
+            _periods: &[bool],
+            _reorder: bool,
+        ) -> Option<CartesianCommunicator> {
+            super::unsupported()
+        }
+    }
+
+    pub struct SimpleCommunicator(());
+
+    // like in the mpi crate, dropping a communicator frees it
+    impl Drop for SimpleCommunicator {
+        fn drop(&mut self) {}
+    }
+
+    impl SimpleCommunicator {
+        pub fn self_comm() -> Self {
+            super::unsupported()
+        }
+
+        /// # Safety
+        /// ``raw`` must be a valid communicator, which the returned one frees when it is dropped.
+        pub unsafe fn from_raw(_raw: MPI_Comm) -> Self {
+            super::unsupported()
+        }
+
+        pub fn parent(&self) -> Option<InterCommunicator> {
+            super::unsupported()
+        }
+    }
+
+    impl Communicator for SimpleCommunicator {
+        fn rank(&self) -> Rank {
+            super::unsupported()
+        }
+
+        fn size(&self) -> Rank {
+            super::unsupported()
+        }
+    }
+
+    pub struct CartesianCommunicator(());
+
+    impl Communicator for CartesianCommunicator {
+        fn rank(&self) -> Rank {
+            super::unsupported()
+        }
+
+        fn size(&self) -> Rank {
+            super::unsupported()
+        }
+    }
+
+    pub struct InterCommunicator(());
+
+    // like in the mpi crate, dropping a communicator frees it
+    impl Drop for InterCommunicator {
+        fn drop(&mut self) {}
+    }
+
+    impl InterCommunicator {
+        /// # Safety
+        /// ``raw`` must be a valid inter-communicator, which the returned one frees when it is
+        /// dropped.
+        pub unsafe fn from_raw(_raw: MPI_Comm) -> Self {
+            super::unsupported()
+        }
+
+        pub fn remote_size(&self) -> Rank {
+            super::unsupported()
+        }
+
+        pub fn merge(&self, _order: MergeOrder) -> SimpleCommunicator {
+            super::unsupported()
+        }
+    }
+
+    impl Communicator for InterCommunicator {
+        fn rank(&self) -> Rank {
+            super::unsupported()
+        }
+
+        fn size(&self) -> Rank {
+            super::unsupported()
+        }
+    }
+}
+
+pub mod collective {
+    use super::datatype::{Buffer, BufferMut};
+    use super::request::Request;
+    use super::topology::{Communicator, InterCommunicator};
+    use super::{unsupported, PhantomData, Rank};
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct SystemOperation(());
+
+    impl SystemOperation {
+        pub fn max() -> Self {
+            Self(())
+        }
+
+        pub fn min() -> Self {
+            Self(())
+        }
+
+        pub fn sum() -> Self {
+            Self(())
+        }
+
+        pub fn product() -> Self {
+            Self(())
+        }
+    }
+
+    /// A process of a communicator, which is the root of rooted collectives.
+    pub struct Process<'a> {
+        rank: Rank,
+        _comm: PhantomData<&'a ()>,
+    }
+
+    impl Process<'_> {
+        pub(super) fn new(rank: Rank) -> Self {
+            Self {
+                rank,
+                _comm: PhantomData,
+            }
+        }
+
+        pub fn rank(&self) -> Rank {
+            self.rank
+        }
+    }
+
+    pub trait CommunicatorCollectives {
+        fn barrier(&self) {
+            unsupported()
+        }
+
+        fn immediate_barrier(&self) -> Request {
+            unsupported()
+        }
+
+        fn all_reduce_into<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+            _op: SystemOperation,
+        ) {
+            unsupported()
+        }
+
+        fn all_gather_into<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+        ) {
+            unsupported()
+        }
+
+        fn all_to_all_into<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+        ) {
+            unsupported()
+        }
+
+        fn scan_into<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+            _op: SystemOperation,
+        ) {
+            unsupported()
+        }
+
+        fn exclusive_scan_into<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+            _op: SystemOperation,
+        ) {
+            unsupported()
+        }
+    }
+
+    impl<C: Communicator + ?Sized> CommunicatorCollectives for C {}
+
+    pub trait Root {
+        fn broadcast_into<B: BufferMut + ?Sized>(&self, _buffer: &mut B) {
+            unsupported()
+        }
+
+        fn reduce_into<S: Buffer + ?Sized>(&self, _send: &S, _op: SystemOperation) {
+            unsupported()
+        }
+
+        fn reduce_into_root<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+            _op: SystemOperation,
+        ) {
+            unsupported()
+        }
+
+        fn gather_into<S: Buffer + ?Sized>(&self, _send: &S) {
+            unsupported()
+        }
+
+        fn gather_into_root<S: Buffer + ?Sized, R: BufferMut + ?Sized>(
+            &self,
+            _send: &S,
+            _recv: &mut R,
+        ) {
+            unsupported()
+        }
+
+        fn spawn(
+            &self,
+            _command: &std::process::Command,
+            _max_procs: Rank,
+        ) -> Result<InterCommunicator, i32> {
+            unsupported()
+        }
+
+        fn spawn_multiple(
+            &self,
+            _commands: &[std::process::Command],
+            _max_procs: &[Rank],
+        ) -> Result<InterCommunicator, i32> {
+            unsupported()
+        }
+    }
+
+    impl Root for Process<'_> {}
+}
+
+#[allow(
+    non_camel_case_types,
+    non_snake_case,
+    clippy::missing_safety_doc,
+    clippy::too_many_arguments
+)]
+pub mod ffi {
+    use std::os::raw::{c_char, c_int, c_void};
+
+    /// The handle of a communicator.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Comm(());
+
+    /// The handle of an error handler.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Errhandler(());
+
+    /// The handle of a datatype.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Datatype(());
+
+    /// The handle of an info object.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Info(());
+
+    pub static RSMPI_INFO_NULL: MPI_Info = MPI_Info(());
+
+    /// The handle of a window.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Win(());
+
+    /// An address or a size in bytes.
+    pub type MPI_Aint = isize;
+
+    /// The handle of a reduction operation.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Op(());
+
+    pub static RSMPI_MAX: MPI_Op = MPI_Op(());
+    pub static RSMPI_MIN: MPI_Op = MPI_Op(());
+
+    pub type MPI_User_function =
+        Option<unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_int, *mut MPI_Datatype)>;
+
+    /// The handle of a file.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_File(());
+
+    /// An offset into a file in bytes.
+    pub type MPI_Offset = i64;
+
+    pub const MPI_MODE_CREATE: u32 = 1;
+    pub const MPI_MODE_RDWR: u32 = 8;
+    pub const MPI_SEEK_SET: u32 = 600;
+
+    /// The handle of a request.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Request(());
+
+    /// The status of a completed request.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MPI_Status(());
+
+    pub static mut RSMPI_STATUS_IGNORE: *mut MPI_Status = std::ptr::null_mut();
+
+    pub const MPI_SUCCESS: u32 = 0;
+
+    pub static RSMPI_ERRORS_RETURN: MPI_Errhandler = MPI_Errhandler(());
+
+    pub unsafe fn MPI_Comm_set_errhandler(_comm: MPI_Comm, _errhandler: MPI_Errhandler) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Error_class(_code: c_int, _class: *mut c_int) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Error_string(
+        _code: c_int,
+        _string: *mut c_char,
+        _length: *mut c_int,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Abort(_comm: MPI_Comm, _code: c_int) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Send_init(
+        _buffer: *const c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _destination: c_int,
+        _tag: c_int,
+        _comm: MPI_Comm,
+        _request: *mut MPI_Request,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Recv_init(
+        _buffer: *mut c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _source: c_int,
+        _tag: c_int,
+        _comm: MPI_Comm,
+        _request: *mut MPI_Request,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Start(_request: *mut MPI_Request) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Wait(_request: *mut MPI_Request, _status: *mut MPI_Status) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Test(
+        _request: *mut MPI_Request,
+        _flag: *mut c_int,
+        _status: *mut MPI_Status,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Ibarrier(_comm: MPI_Comm, _request: *mut MPI_Request) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Request_free(_request: *mut MPI_Request) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Intercomm_create(
+        _local_comm: MPI_Comm,
+        _local_leader: c_int,
+        _peer_comm: MPI_Comm,
+        _remote_leader: c_int,
+        _tag: c_int,
+        _inter_comm: *mut MPI_Comm,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Dist_graph_create_adjacent(
+        _comm: MPI_Comm,
+        _indegree: c_int,
+        _sources: *const c_int,
+        _source_weights: *const c_int,
+        _outdegree: c_int,
+        _destinations: *const c_int,
+        _destination_weights: *const c_int,
+        _info: MPI_Info,
+        _reorder: c_int,
+        _graph_comm: *mut MPI_Comm,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Dist_graph_neighbors_count(
+        _comm: MPI_Comm,
+        _indegree: *mut c_int,
+        _outdegree: *mut c_int,
+        _weighted: *mut c_int,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Dist_graph_neighbors(
+        _comm: MPI_Comm,
+        _max_indegree: c_int,
+        _sources: *mut c_int,
+        _source_weights: *mut c_int,
+        _max_outdegree: c_int,
+        _destinations: *mut c_int,
+        _destination_weights: *mut c_int,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Neighbor_alltoall(
+        _send: *const c_void,
+        _send_count: c_int,
+        _send_type: MPI_Datatype,
+        _receive: *mut c_void,
+        _receive_count: c_int,
+        _receive_type: MPI_Datatype,
+        _comm: MPI_Comm,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Info_create(_info: *mut MPI_Info) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Info_set(
+        _info: MPI_Info,
+        _key: *const c_char,
+        _value: *const c_char,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Info_free(_info: *mut MPI_Info) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Comm_spawn_multiple(
+        _count: c_int,
+        _commands: *mut *mut c_char,
+        _argvs: *mut *mut *mut c_char,
+        _max_procs: *const c_int,
+        _infos: *const MPI_Info,
+        _root: c_int,
+        _comm: MPI_Comm,
+        _inter_comm: *mut MPI_Comm,
+        _error_codes: *mut c_int,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Win_allocate_shared(
+        _size: MPI_Aint,
+        _disp_unit: c_int,
+        _info: MPI_Info,
+        _comm: MPI_Comm,
+        _baseptr: *mut c_void,
+        _win: *mut MPI_Win,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Win_shared_query(
+        _win: MPI_Win,
+        _rank: c_int,
+        _size: *mut MPI_Aint,
+        _disp_unit: *mut c_int,
+        _baseptr: *mut c_void,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Win_lock_all(_assert: c_int, _win: MPI_Win) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Win_unlock_all(_win: MPI_Win) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Win_sync(_win: MPI_Win) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Win_free(_win: *mut MPI_Win) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Op_create(
+        _function: MPI_User_function,
+        _commute: c_int,
+        _op: *mut MPI_Op,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Op_free(_op: *mut MPI_Op) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Bcast(
+        _buffer: *mut c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _root: c_int,
+        _comm: MPI_Comm,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Allreduce(
+        _send: *const c_void,
+        _receive: *mut c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _op: MPI_Op,
+        _comm: MPI_Comm,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_Reduce(
+        _send: *const c_void,
+        _receive: *mut c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _op: MPI_Op,
+        _root: c_int,
+        _comm: MPI_Comm,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_open(
+        _comm: MPI_Comm,
+        _filename: *const c_char,
+        _amode: c_int,
+        _info: MPI_Info,
+        _fh: *mut MPI_File,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_get_size(_fh: MPI_File, _size: *mut MPI_Offset) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_set_size(_fh: MPI_File, _size: MPI_Offset) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_seek(_fh: MPI_File, _offset: MPI_Offset, _whence: c_int) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_write_all(
+        _fh: MPI_File,
+        _buf: *const c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _status: *mut MPI_Status,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_write_at_all(
+        _fh: MPI_File,
+        _offset: MPI_Offset,
+        _buf: *const c_void,
+        _count: c_int,
+        _datatype: MPI_Datatype,
+        _status: *mut MPI_Status,
+    ) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_sync(_fh: MPI_File) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_close(_fh: *mut MPI_File) -> c_int {
+        super::unsupported()
+    }
+
+    pub unsafe fn MPI_File_delete(_filename: *const c_char, _info: MPI_Info) -> c_int {
+        super::unsupported()
+    }
+}
+
+pub mod raw {
+    use super::ffi::MPI_Comm;
+    use super::topology::{CartesianCommunicator, InterCommunicator, SimpleCommunicator};
+
+    /// Access to the raw handle of an MPI object.
+    ///
+    /// # Safety
+    /// The handle must be valid for as long as the object lives.
+    pub unsafe trait AsRaw {
+        type Raw;
+
+        fn as_raw(&self) -> Self::Raw;
+    }
+
+    unsafe impl AsRaw for SimpleCommunicator {
+        type Raw = MPI_Comm;
+
+        fn as_raw(&self) -> MPI_Comm {
+            super::unsupported()
+        }
+    }
+
+    unsafe impl AsRaw for CartesianCommunicator {
+        type Raw = MPI_Comm;
+
+        fn as_raw(&self) -> MPI_Comm {
+            super::unsupported()
+        }
+    }
+
+    unsafe impl AsRaw for InterCommunicator {
+        type Raw = MPI_Comm;
+
+        fn as_raw(&self) -> MPI_Comm {
+            super::unsupported()
+        }
+    }
+}
+
+pub mod traits {
+    pub use super::collective::{CommunicatorCollectives, Root};
+    pub use super::datatype::{Buffer, BufferMut, Equivalence};
+    pub use super::topology::Communicator;
+}
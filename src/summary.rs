@@ -0,0 +1,186 @@
+//! The table printed at the end of a group. Criterion prints the results of each benchmark as it
+//! goes, but spotting the odd one out among dozens of MPI benchmarks needs them side by side.
+//! ``mpirion_group!`` prints a table of the [metrics](crate::report::Metrics) of the benchmarks of
+//! the group before it returns, through the built-in
+//! [`TableReporter`](crate::reporter::TableReporter).
+//!
+//! At the end of the run, it prints a table of the [process launches](crate::launches) of all
+//! benchmarks, see [`launches`].
+//!
+//! Like the [reports](crate::report), the tables are left out in runs with ``--mpirion-quick``.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::launches::Launches;
+use crate::report::{Metrics, Throughput};
+
+/// The table of the given benchmarks, with a row per benchmark: its id, the world size, the mean
+/// time per iteration, the throughput if set, the imbalance between the slowest and the fastest
+/// rank, and the share of the time spent spawning the children.
+///
+/// # Example
+/// ```rust
+/// use mpirion::report::{Metrics, RankStatistics, Throughput};
+/// use mpirion::summary::table;
+/// use std::time::Duration;
+///
+/// let rank = |micros| RankStatistics {
+///     mean: Duration::from_micros(micros),
+///     min: Duration::from_micros(micros),
+///     max: Duration::from_micros(micros),
+/// };
+/// let metrics = Metrics {
+///     id: "gossiping/all-to-all/p=2".to_string(),
+///     kernel: "simple_kernel".to_string(),
+///     world_size: 2,
+///     samples: 100,
+///     mean: Duration::from_micros(100),
+///     throughput: Some(Throughput { unit: "Bytes".to_string(), per_iteration: 1 << 20 }),
+///     spawn_share: Some(0.375),
+///     spawn_overhead: Some(Duration::from_millis(150)),
+///     ranks: vec![rank(80), rank(100)],
+///     ..Default::default()
+/// };
+/// let table = table(&[metrics]);
+/// let row = table.lines().nth(1).unwrap();
+/// assert!(row.starts_with("gossiping/all-to-all/p=2"));
+/// assert!(row.contains("100.00µs"));
+/// assert!(row.contains("9.77 GiB/s"));
+/// assert!(row.contains("1.25"));
+/// assert!(row.ends_with("37.5%"));
+/// ```
+pub fn table(benchmarks: &[Metrics]) -> String {
+    let header = [
+        "benchmark",
+        "ranks",
+        "mean",
+        "throughput",
+        "imbalance",
+        "spawn",
+    ];
+    let rows: Vec<[String; 6]> = benchmarks
+        .iter()
+        .map(|metrics| {
+            [
+                metrics.id.clone(),
+                metrics.world_size.to_string(),
+                format!("{:.2?}", metrics.mean),
+                metrics.throughput.as_ref().map_or_else(
+                    || "-".to_string(),
+                    |throughput| format_throughput(throughput, metrics.mean),
+                ),
+                imbalance(metrics)
+                    .map_or_else(|| "-".to_string(), |imbalance| format!("{:.2}", imbalance)),
+                metrics
+                    .spawn_share
+                    .map_or_else(|| "-".to_string(), |share| format!("{:.1}%", share * 100.0)),
+            ]
+        })
+        .collect();
+    render(header, &rows)
+}
+
+/// The table of the process launches of the given benchmarks, with a row per benchmark: its id,
+/// the worlds spawned for its samples, the children started in them and the CPU-seconds they
+/// reported. The last row holds the totals of the run, which also count the launches outside of the
+/// samples of a benchmark, see [`launches`](crate::launches).
+///
+/// # Example
+/// ```rust
+/// use mpirion::launches::Launches;
+/// use mpirion::report::Metrics;
+/// use mpirion::summary::launches;
+/// use std::time::Duration;
+///
+/// let metrics = Metrics {
+///     id: "bcast/p=32".to_string(),
+///     kernel: "bcast_kernel".to_string(),
+///     world_size: 32,
+///     samples: 100,
+///     mean: Duration::from_micros(40),
+///     launches: Launches { spawn_rounds: 100, children: 3200, child_cpu: Duration::from_millis(912_500) },
+///     ..Default::default()
+/// };
+/// let run = Launches { spawn_rounds: 104, children: 3328, child_cpu: Duration::from_millis(950_000) };
+/// let table = launches(&[metrics], &run);
+/// let rows: Vec<&str> = table.lines().collect();
+/// assert!(rows[1].starts_with("bcast/p=32"));
+/// assert!(rows[1].ends_with("100      3200        912.5"));
+/// assert!(rows[2].starts_with("total"));
+/// assert!(rows[2].ends_with("104      3328        950.0"));
+/// ```
+pub fn launches(benchmarks: &[Metrics], run: &Launches) -> String {
+    let header = ["benchmark", "spawn rounds", "children", "CPU-seconds"];
+    let row = |id: &str, launches: &Launches| {
+        [
+            id.to_string(),
+            launches.spawn_rounds.to_string(),
+            launches.children.to_string(),
+            format!("{:.1}", launches.child_cpu.as_secs_f64()),
+        ]
+    };
+    let rows: Vec<[String; 4]> = benchmarks
+        .iter()
+        .map(|metrics| row(&metrics.id, &metrics.launches))
+        .chain(std::iter::once(row("total", run)))
+        .collect();
+    render(header, &rows)
+}
+
+/// Render a table with the given header, the first column aligned left and the others right.
+fn render<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    let header = header.map(String::from);
+    for row in std::iter::once(&header).chain(rows) {
+        let mut line = String::new();
+        for (column, (cell, width)) in row.iter().zip(widths).enumerate() {
+            let padding = " ".repeat(width - cell.chars().count());
+            match column {
+                // the id is aligned left, the numbers right
+                0 => {
+                    let _ = write!(line, "{}{}", cell, padding);
+                }
+                _ => {
+                    let _ = write!(line, "  {}{}", padding, cell);
+                }
+            }
+        }
+        let _ = writeln!(table, "{}", line);
+    }
+    table
+}
+
+/// The ratio of the mean time of the slowest rank to that of the fastest one.
+pub(crate) fn imbalance(metrics: &Metrics) -> Option<f64> {
+    let slowest = metrics.ranks.iter().map(|rank| rank.mean).max()?;
+    let fastest = metrics.ranks.iter().map(|rank| rank.mean).min()?;
+    (!fastest.is_zero()).then(|| slowest.as_secs_f64() / fastest.as_secs_f64())
+}
+
+/// A throughput per second, in the units criterion uses for it.
+pub(crate) fn format_throughput(throughput: &Throughput, mean: Duration) -> String {
+    if mean.is_zero() {
+        return "-".to_string();
+    }
+    let per_second = throughput.per_iteration as f64 / mean.as_secs_f64();
+    let (base, units): (f64, [&str; 4]) = match throughput.unit.as_str() {
+        "Bytes" => (1024.0, ["B/s", "KiB/s", "MiB/s", "GiB/s"]),
+        "BytesDecimal" => (1000.0, ["B/s", "KB/s", "MB/s", "GB/s"]),
+        _ => (1000.0, ["elem/s", "Kelem/s", "Melem/s", "Gelem/s"]),
+    };
+    let mut value = per_second;
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, units[unit])
+}
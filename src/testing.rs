@@ -0,0 +1,49 @@
+//! Running kernel tests declared with ``mpirion_test!``. The output mimics the built-in test
+//! harness, so test binaries launched with ``mpiexec`` read like ``cargo test``.
+
+use crate::mpi::traits::Communicator;
+
+/// A test declared with ``mpirion_test!``. It spawns the children, runs the kernel once and
+/// returns the failure reported by the children, if any.
+pub type KernelTest = fn(&dyn Communicator) -> Result<(), String>;
+
+/// Run all tests on ``world`` and print their results. Returns the exit code of the test binary,
+/// which is non-zero if any test failed.
+pub fn run_tests(world: &dyn Communicator, tests: &[(&str, KernelTest)]) -> i32 {
+    println!(
+        "\nrunning {} test{}",
+        tests.len(),
+        if tests.len() == 1 { "" } else { "s" }
+    );
+
+    let mut failures = Vec::new();
+    for &(name, test) in tests {
+        match test(world) {
+            Ok(()) => println!("test {} ... ok", name),
+            Err(message) => {
+                println!("test {} ... FAILED", name);
+                failures.push((name, message));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, message) in &failures {
+            println!("    {}: {}", name, message);
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        tests.len() - failures.len(),
+        failures.len()
+    );
+
+    if failures.is_empty() {
+        0
+    } else {
+        101
+    }
+}
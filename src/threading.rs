@@ -0,0 +1,394 @@
+//! The threading level MPI is initialized with. Criterion runs threads for plotting and reports
+//! next to the benchmark thread, and with ``MPI_THREAD_SINGLE`` some MPI builds leave any MPI call
+//! undefined once other threads exist. The parent and the children therefore request
+//! [`Threading::Funneled`] by default, which allows other threads as long as only the main thread
+//! calls MPI. The watchdog and the timeouts of mpirion don't call MPI from other threads, so they
+//! need nothing more.
+//!
+//! Kernels that call MPI from several threads can request a higher level with the
+//! ``MPIRION_THREADING`` environment variable (``funneled``, ``serialized`` or ``multiple``) when
+//! starting the benchmark. The parent passes it on to the children on the command line. If MPI
+//! cannot provide the requested level, the process panics with an explanation instead of running
+//! with a level the benchmarks are not safe under. Kernels can check the granted level with
+//! [`granted`].
+//!
+//! A kernel that always calls MPI from several threads declares its level with
+//! ``threading = multiple`` (or ``serialized``) in ``mpirion_kernel!``, so it doesn't depend on the
+//! environment of the run. Its children request that level, or the one of ``MPIRION_THREADING`` if
+//! it is higher. After the kernel name, the parent and the children agree on whether MPI granted it
+//! to all children, and if not, the children exit and the benchmark fails with the level MPI
+//! provides, see [`check_children`].
+//!
+//! Even then, only the main thread of a child takes part in the protocol of mpirion: the barrier
+//! before each iteration, the agreement on failures and the time cap run on the thread that
+//! initialized MPI, which the generated code asserts, see [`enter_kernel`]. The threads of a kernel
+//! must be joined before the kernel returns, e.g. with ``std::thread::scope``, so their calls never
+//! overlap with the collectives of the protocol on the same communicator. The communicator a kernel
+//! receives can only be shared with its threads through [`ThreadSafe`], which checks that MPI
+//! granted ``multiple``.
+//!
+//! Hybrid kernels that parallelize each rank with Rayon get a pool of a given size in every child
+//! with ``threads = 8`` in ``mpirion_bench!``, enabled by the ``rayon`` feature. The parent
+//! broadcasts the size with the other settings of the sample, and each child builds the global
+//! Rayon pool with [`build_pool`] before the setup runs, so ranks and threads can be swept
+//! together. The threads of the pool must not call MPI: the parallel iterators of the kernel run on
+//! them while the main thread waits, which [`Threading::Funneled`] permits.
+
+use std::sync::OnceLock;
+use std::thread::ThreadId;
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::environment::Universe;
+use crate::mpi::traits::*;
+pub use crate::mpi::Threading;
+
+/// The environment variable that sets the requested threading level.
+pub const THREADING_VAR: &str = "MPIRION_THREADING";
+
+/// The flag that passes the requested threading level to the children.
+const THREADING_FLAG: &str = "--mpirion-threading";
+
+/// The level mpirion needs at least, see the module documentation.
+pub const MINIMUM: Threading = Threading::Funneled;
+
+static GRANTED: OnceLock<Threading> = OnceLock::new();
+
+/// The level the kernel of this child needs, see [`initialize_kernel`].
+static REQUIRED: OnceLock<Threading> = OnceLock::new();
+
+/// The thread that initialized MPI, which runs the protocol.
+static MAIN_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Parse the name of a threading level, as used in ``MPIRION_THREADING``.
+///
+/// # Example
+/// ```rust
+/// use mpirion::threading::{level_name, parse_level, Threading};
+///
+/// assert_eq!(parse_level("multiple"), Ok(Threading::Multiple));
+/// assert_eq!(parse_level("Funneled"), Ok(Threading::Funneled));
+/// assert!(parse_level("single").is_err());
+/// assert!(parse_level("many").is_err());
+/// assert_eq!(level_name(Threading::Serialized), "serialized");
+/// ```
+pub fn parse_level(name: &str) -> Result<Threading, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "funneled" => Ok(Threading::Funneled),
+        "serialized" => Ok(Threading::Serialized),
+        "multiple" => Ok(Threading::Multiple),
+        "single" => Err(format!(
+            "{} cannot be `single`, criterion runs threads next to the benchmark, which needs at least `funneled`",
+            THREADING_VAR
+        )),
+        _ => Err(format!(
+            "unknown threading level `{}`, expected `funneled`, `serialized` or `multiple`",
+            name
+        )),
+    }
+}
+
+/// The name of a threading level, as used in ``MPIRION_THREADING``.
+pub fn level_name(level: Threading) -> &'static str {
+    match level {
+        Threading::Single => "single",
+        Threading::Funneled => "funneled",
+        Threading::Serialized => "serialized",
+        Threading::Multiple => "multiple",
+    }
+}
+
+/// Whether the level ``granted`` permits everything ``required`` permits.
+///
+/// # Example
+/// ```rust
+/// use mpirion::threading::{satisfies, Threading};
+///
+/// assert!(satisfies(Threading::Multiple, Threading::Funneled));
+/// assert!(satisfies(Threading::Funneled, Threading::Funneled));
+/// assert!(!satisfies(Threading::Single, Threading::Funneled));
+/// ```
+pub fn satisfies(granted: Threading, required: Threading) -> bool {
+    order(granted) >= order(required)
+}
+
+fn order(level: Threading) -> u8 {
+    match level {
+        Threading::Single => 0,
+        Threading::Funneled => 1,
+        Threading::Serialized => 2,
+        Threading::Multiple => 3,
+    }
+}
+
+fn from_order(order: u64) -> Threading {
+    match order {
+        0 => Threading::Single,
+        1 => Threading::Funneled,
+        2 => Threading::Serialized,
+        _ => Threading::Multiple,
+    }
+}
+
+/// The higher of two levels.
+///
+/// # Example
+/// ```rust
+/// use mpirion::threading::{max_level, Threading};
+///
+/// assert_eq!(max_level(Threading::Funneled, Threading::Multiple), Threading::Multiple);
+/// assert_eq!(max_level(Threading::Serialized, Threading::Funneled), Threading::Serialized);
+/// ```
+pub fn max_level(first: Threading, second: Threading) -> Threading {
+    if satisfies(first, second) {
+        first
+    } else {
+        second
+    }
+}
+
+/// The requested threading level: the level passed by the parent on the command line, or
+/// ``MPIRION_THREADING``, or [`MINIMUM`]. Panics if the level is malformed.
+pub fn requested() -> Threading {
+    let args: Vec<String> = std::env::args().collect();
+    let from_parent = args
+        .iter()
+        .position(|arg| arg == THREADING_FLAG)
+        .and_then(|index| args.get(index + 1).cloned());
+
+    match from_parent.or_else(|| std::env::var(THREADING_VAR).ok()) {
+        Some(name) => parse_level(&name).unwrap_or_else(|message| panic!("{}", message)),
+        None => MINIMUM,
+    }
+}
+
+/// The arguments that pass the requested threading level to a child.
+pub fn child_args() -> [String; 2] {
+    [
+        THREADING_FLAG.to_string(),
+        level_name(requested()).to_string(),
+    ]
+}
+
+/// Initialize MPI with the [`requested`] threading level and switch the world to
+/// ``MPI_ERRORS_RETURN``, see the [`errhandler`](crate::errhandler) module. Panics if MPI is already
+/// initialized, or if it cannot provide the level. Called by the code generated by the macros
+/// instead of ``mpi::initialize``.
+pub fn initialize() -> Universe {
+    initialize_kernel(MINIMUM)
+}
+
+/// Initialize MPI like [`initialize`], but with at least the level a kernel declared with
+/// ``threading`` in ``mpirion_kernel!``. Called by the children of the kernel. If MPI cannot
+/// provide the level of the kernel, the child doesn't panic, but tells the parent so in
+/// [`confirm_level`], which fails the benchmark.
+pub fn initialize_kernel(kernel_level: Threading) -> Universe {
+    let requested = requested();
+    let required = max_level(requested, kernel_level);
+    let (universe, granted) =
+        crate::mpi::initialize_with_threading(required).expect("MPI was already initialized");
+    if !satisfies(granted, requested) {
+        panic!(
+            "MPI provides the threading level `{}`, but mpirion needs `{}`: {}",
+            level_name(granted),
+            level_name(requested),
+            if requested == MINIMUM {
+                "criterion runs threads for plotting and reports next to the benchmark thread, \
+                 and some MPI builds leave MPI calls undefined once other threads exist"
+            } else {
+                "it was requested with MPIRION_THREADING"
+            }
+        );
+    }
+
+    let _ = GRANTED.set(granted);
+    let _ = REQUIRED.set(required);
+    let _ = MAIN_THREAD.set(std::thread::current().id());
+    if let Err(error) = crate::errhandler::install(&universe.world()) {
+        panic!(
+            "mpirion could not switch the world to MPI_ERRORS_RETURN: {}",
+            error
+        );
+    }
+    universe
+}
+
+/// The threading level MPI was initialized with by [`initialize`]. Kernels that call MPI from
+/// several threads can assert their requirements with it. Panics if MPI was not initialized by
+/// mpirion.
+pub fn granted() -> Threading {
+    *GRANTED.get().expect("MPI was not initialized by mpirion")
+}
+
+/// Let the children tell the parent whether MPI granted them the level their kernel needs, see
+/// [`initialize_kernel`]. Called by the parent right after
+/// [`send_kernel_name`](crate::protocol::send_kernel_name). Returns an error naming the levels if
+/// any child lacks it, in which case the children exit.
+pub fn check_children(comm: &dyn Communicator, kernel: &str) -> Result<(), String> {
+    let (required, granted) = agree_on_levels(comm, Threading::Single, Threading::Multiple);
+    if satisfies(granted, required) {
+        return Ok(());
+    }
+    Err(format!(
+        "kernel `{}` needs the threading level `{}`, but MPI only provides `{}` in the children",
+        kernel,
+        level_name(required),
+        level_name(granted)
+    ))
+}
+
+/// Tell the parent whether MPI granted this child the level its kernel needs, see
+/// [`check_children`]. Called by the children after they acknowledged the kernel. Returns false if
+/// any child lacks it, in which case the child must exit.
+pub fn confirm_level(comm: &dyn Communicator) -> bool {
+    let required = REQUIRED.get().copied().unwrap_or(MINIMUM);
+    let (required, granted) = agree_on_levels(comm, required, granted());
+    satisfies(granted, required)
+}
+
+/// The highest level any process needs and the lowest level any process was granted.
+fn agree_on_levels(
+    comm: &dyn Communicator,
+    required: Threading,
+    granted: Threading,
+) -> (Threading, Threading) {
+    let (mut highest_required, mut lowest_granted) = (0u64, 0u64);
+    comm.all_reduce_into(
+        &u64::from(order(required)),
+        &mut highest_required,
+        SystemOperation::max(),
+    );
+    comm.all_reduce_into(
+        &u64::from(order(granted)),
+        &mut lowest_granted,
+        SystemOperation::min(),
+    );
+    (from_order(highest_required), from_order(lowest_granted))
+}
+
+/// Check that the kernel ``kernel`` can run in this process before its first sample: MPI must
+/// provide the level the kernel declared, and the protocol must run on the thread that initialized
+/// MPI. Called by the code ``mpirion_kernel!`` generates, so it also covers kernels that run in the
+/// parent with the ``local-fallback`` feature, whose MPI was initialized without the level of the
+/// kernel.
+pub fn enter_kernel(kernel: &str, kernel_level: Threading) {
+    let granted = granted();
+    if !satisfies(granted, kernel_level) {
+        panic!(
+            "kernel `{}` needs the threading level `{}`, but MPI was initialized with `{}`, \
+             set {}={} to run it in the parent",
+            kernel,
+            level_name(kernel_level),
+            level_name(granted),
+            THREADING_VAR,
+            level_name(kernel_level)
+        );
+    }
+    if MAIN_THREAD
+        .get()
+        .is_some_and(|main| *main != std::thread::current().id())
+    {
+        panic!(
+            "kernel `{}` runs on another thread than the one that initialized MPI, but the protocol of mpirion must stay on it",
+            kernel
+        );
+    }
+}
+
+/// A communicator that can be shared with other threads, because MPI was initialized with
+/// ``multiple``, see the module documentation. It dereferences to the communicator.
+///
+/// # Example
+/// ```rust
+/// use mpi::point_to_point::send_receive_into_with_tags;
+/// use mpirion::prelude::*;
+/// use mpirion::threading::ThreadSafe;
+///
+/// // both threads of a rank exchange their rank with the same thread of the partner rank at the
+/// // same time, told apart by the tag, on worlds of even size
+/// fn two_threads_kernel(comm: &dyn Communicator) {
+///     let comm = ThreadSafe::new(comm);
+///     std::thread::scope(|scope| {
+///         for tag in 0..2 {
+///             scope.spawn(move || {
+///                 let partner = comm.process_at_rank(comm.rank() ^ 1);
+///                 let mut received = -1;
+///                 send_receive_into_with_tags(&comm.rank(), &partner, tag, &mut received, &partner, tag);
+///                 assert_eq!(received, comm.rank() ^ 1);
+///             });
+///         }
+///     });
+/// }
+///
+/// mpirion_kernel!(kernel = two_threads_kernel, threading = multiple);
+/// ```
+#[derive(Clone, Copy)]
+pub struct ThreadSafe<'c> {
+    comm: &'c dyn Communicator,
+}
+
+// SAFETY: with MPI_THREAD_MULTIPLE, MPI may be called on a communicator from any thread at any time
+unsafe impl Send for ThreadSafe<'_> {}
+unsafe impl Sync for ThreadSafe<'_> {}
+
+impl<'c> ThreadSafe<'c> {
+    /// Wrap ``comm`` to share it with other threads. Panics unless MPI was initialized with
+    /// ``multiple``, e.g. because the kernel declared ``threading = multiple``.
+    pub fn new(comm: &'c dyn Communicator) -> Self {
+        let granted = granted();
+        if granted != Threading::Multiple {
+            panic!(
+                "a communicator can only be shared with other threads with the threading level `multiple`, \
+                 but MPI was initialized with `{}`, declare `threading = multiple` in mpirion_kernel!",
+                level_name(granted)
+            );
+        }
+        Self { comm }
+    }
+}
+
+impl<'c> std::ops::Deref for ThreadSafe<'c> {
+    type Target = dyn Communicator + 'c;
+
+    fn deref(&self) -> &Self::Target {
+        self.comm
+    }
+}
+
+/// Broadcast the size of the thread pool of the children to them, see [`build_pool`]. Must be
+/// called right after [`send_time_cap`](crate::timing::send_time_cap). No size is sent as 0.
+pub fn send_threads(comm: &dyn Communicator, threads: Option<usize>) {
+    let mut threads = threads.map_or(0, |threads| threads as u64);
+    comm.this_process().broadcast_into(&mut threads);
+}
+
+/// Receive the size of the thread pool from the parent, see [`send_threads`].
+pub fn receive_threads(comm: &dyn Communicator) -> Option<usize> {
+    let mut threads = 0u64;
+    comm.process_at_rank(0).broadcast_into(&mut threads);
+    (threads != 0).then_some(threads as usize)
+}
+
+/// Build the global Rayon pool of this child with ``threads`` threads. Called by the code
+/// ``mpirion_kernel!`` generates, before the setup. Does nothing without a size or without the
+/// ``rayon`` feature. The global pool can only be built once per process, so if the kernel already
+/// used Rayon before, the existing pool is kept and a pool of another size is reported.
+pub fn build_pool(kernel: &str, threads: Option<usize>) {
+    #[cfg(not(feature = "rayon"))]
+    let _ = (kernel, threads);
+
+    #[cfg(feature = "rayon")]
+    if let Some(threads) = threads {
+        let built = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("mpirion-rayon-{}", index))
+            .build_global();
+        if built.is_err() && rayon::current_num_threads() != threads {
+            eprintln!(
+                "mpirion: kernel `{}`: the Rayon pool already runs {} threads, {} were requested",
+                kernel,
+                rayon::current_num_threads(),
+                threads
+            );
+        }
+    }
+}
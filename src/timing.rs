@@ -0,0 +1,230 @@
+//! The exchange of the timings of a sample between the children and the parent. The values are
+//! converted from and to their wire format here and nowhere else: the time a child spent in the
+//! kernel and the time cap of a sample are sent as ``u64``s of nanoseconds, the drift of its
+//! iteration times as an ``i64`` of parts per million, and the number of iterations as a ``u64``.
+//! The code generated by the macros only handles [`Duration`]s and never converts between integer
+//! types.
+//!
+//! # Supported heterogeneity
+//! The children run the binary of the parent, and the handshake rejects children of any other
+//! build, so the parent and the children always agree on the layout of every Rust type. What may
+//! differ between their nodes is the hardware, e.g. a 32-bit front-end node driving 64-bit compute
+//! nodes with a binary built for the front end, or nodes of different endianness if the MPI
+//! library supports it:
+//! - Everything the protocol exchanges has a fixed width, and no ``usize`` is ever sent, so the
+//!   values mean the same on every node. MPI converts the representation of these types between
+//!   nodes like for any other predefined datatype.
+//! - On 32-bit targets, a ``usize`` of nanoseconds would overflow after 4.3 seconds. The times are
+//!   therefore kept in [`Duration`]s and ``u64``s, which hold more than 500 years. Longer times
+//!   saturate instead of wrapping, see [`duration_to_nanos`].
+//! - The arguments and outputs of the kernels are transferred in their in-memory layout, like with
+//!   any other MPI call of the kernels. Since all processes run the same binary, the layouts match.
+//!
+//! Different binaries on the parent and the children, e.g. a 32-bit parent spawning a 64-bit build
+//! of the benchmark, are not supported.
+
+use std::time::{Duration, Instant};
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::traits::*;
+
+/// Convert a duration to nanoseconds for the wire. Durations beyond ``u64::MAX`` nanoseconds,
+/// which is more than 584 years, saturate.
+///
+/// # Example
+/// ```rust
+/// use mpirion::timing::{duration_to_nanos, nanos_to_duration};
+/// use std::time::Duration;
+///
+/// assert_eq!(duration_to_nanos(Duration::from_micros(15)), 15_000);
+/// // longer than a u32 of nanoseconds, which is where a 32-bit usize would wrap
+/// assert_eq!(duration_to_nanos(Duration::from_secs(5)), 5_000_000_000);
+/// assert_eq!(duration_to_nanos(Duration::MAX), u64::MAX);
+/// assert_eq!(nanos_to_duration(5_000_000_000), Duration::from_secs(5));
+/// ```
+pub fn duration_to_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Convert nanoseconds from the wire to a duration.
+pub fn nanos_to_duration(nanos: u64) -> Duration {
+    Duration::from_nanos(nanos)
+}
+
+/// Convert a relative value, like a drift, to parts per million for the wire. Values beyond the
+/// range of ``i64`` saturate, and NaN becomes 0.
+///
+/// # Example
+/// ```rust
+/// use mpirion::timing::{parts_per_million_to_relative, relative_to_parts_per_million};
+///
+/// assert_eq!(relative_to_parts_per_million(0.25), 250_000);
+/// assert_eq!(relative_to_parts_per_million(-0.0000014), -1);
+/// assert_eq!(relative_to_parts_per_million(f64::INFINITY), i64::MAX);
+/// assert_eq!(relative_to_parts_per_million(f64::NAN), 0);
+/// assert_eq!(parts_per_million_to_relative(-125_000), -0.125);
+/// ```
+pub fn relative_to_parts_per_million(relative: f64) -> i64 {
+    // float to integer casts saturate, and map NaN to 0
+    (relative * 1e6).round() as i64
+}
+
+/// Convert parts per million from the wire to a relative value.
+pub fn parts_per_million_to_relative(parts_per_million: i64) -> f64 {
+    parts_per_million as f64 / 1e6
+}
+
+/// The mean of the times of the children, rounded down to whole nanoseconds. The sum cannot
+/// overflow, however many children there are. The mean of no times is zero.
+///
+/// # Example
+/// ```rust
+/// use mpirion::timing::mean;
+/// use std::time::Duration;
+///
+/// assert_eq!(mean(&[100, 102, 98, 101].map(Duration::from_micros)), Duration::from_nanos(100_250));
+/// assert_eq!(mean(&[Duration::from_nanos(1), Duration::from_nanos(2)]), Duration::from_nanos(1));
+/// assert_eq!(mean(&[Duration::from_secs(u64::MAX / 2); 4]), Duration::from_secs(u64::MAX / 2));
+/// assert_eq!(mean(&[]), Duration::ZERO);
+/// ```
+pub fn mean(per_rank: &[Duration]) -> Duration {
+    if per_rank.is_empty() {
+        return Duration::ZERO;
+    }
+    let total: u128 = per_rank.iter().map(Duration::as_nanos).sum();
+    let mean = total / per_rank.len() as u128;
+    // the mean is at most the longest time, so the seconds always fit
+    Duration::new(
+        u64::try_from(mean / 1_000_000_000).unwrap_or(u64::MAX),
+        u32::try_from(mean % 1_000_000_000).unwrap_or_default(),
+    )
+}
+
+/// Send the time this child spent in the kernel during a sample to the parent, which is rank 0 of
+/// ``comm``.
+pub fn send_time(comm: &dyn Communicator, total: Duration) {
+    comm.process_at_rank(0)
+        .gather_into(&duration_to_nanos(total));
+}
+
+/// Receive the times the children spent in the kernel during a sample on the parent, ordered by
+/// rank.
+pub fn receive_times(comm: &dyn Communicator) -> Vec<Duration> {
+    let ranks = usize::try_from(comm.size()).expect("the size of a communicator is positive");
+    let mut nanos = vec![0u64; ranks];
+    comm.process_at_rank(0)
+        .gather_into_root(&0u64, &mut nanos[..]);
+    // the first value is the dummy value of the parent
+    nanos.into_iter().skip(1).map(nanos_to_duration).collect()
+}
+
+/// Send the relative drift of the iteration times of this rank to the parent, see
+/// [`DriftTracker`](crate::drift::DriftTracker). Ranks without a drift send 0.
+pub fn send_drift(comm: &dyn Communicator, drift: Option<f64>) {
+    let parts_per_million = drift.map_or(0, relative_to_parts_per_million);
+    let root = comm.process_at_rank(0);
+    root.reduce_into(&parts_per_million, SystemOperation::max());
+    root.reduce_into(&parts_per_million, SystemOperation::min());
+}
+
+/// Receive the drift of the rank whose iteration times drifted most, in either direction, on the
+/// parent. Returns ``None`` if no rank measured a drift.
+pub fn receive_drift(comm: &dyn Communicator) -> Option<f64> {
+    let root = comm.process_at_rank(0);
+    let mut max = 0i64;
+    root.reduce_into_root(&0i64, &mut max, SystemOperation::max());
+    let mut min = 0i64;
+    root.reduce_into_root(&0i64, &mut min, SystemOperation::min());
+
+    let parts_per_million = if max >= min.saturating_neg() {
+        max
+    } else {
+        min
+    };
+    (parts_per_million != 0).then(|| parts_per_million_to_relative(parts_per_million))
+}
+
+/// Broadcast the cap on the wall time of the next sample to the children, see [`Deadline`]. Must be
+/// called right after [`send_iterations`](crate::protocol::send_iterations). No cap is sent as 0.
+pub fn send_time_cap(comm: &dyn Communicator, cap: Option<Duration>) {
+    let mut nanos = cap.map_or(0, |cap| duration_to_nanos(cap).max(1));
+    comm.this_process().broadcast_into(&mut nanos);
+}
+
+/// Receive the cap on the wall time of the sample from the parent, see [`send_time_cap`].
+pub fn receive_time_cap(comm: &dyn Communicator) -> Option<Duration> {
+    let mut nanos = 0u64;
+    comm.process_at_rank(0).broadcast_into(&mut nanos);
+    (nanos != 0).then(|| nanos_to_duration(nanos))
+}
+
+/// The end of the wall time a sample may take, after which the children stop running iterations.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Start the wall time of a sample with the given cap, or without one.
+    pub fn start(cap: Option<Duration>) -> Self {
+        Self(cap.and_then(|cap| Instant::now().checked_add(cap)))
+    }
+
+    /// Whether the wall time of the sample is up on any rank of ``world``. All ranks must call
+    /// this after the same iteration, and all of them receive the same answer, so they stop at the
+    /// same iteration. Without a cap, this returns false without communicating.
+    pub fn reached(&self, world: &dyn Communicator) -> bool {
+        let Some(deadline) = self.0 else {
+            return false;
+        };
+        let reached = u64::from(Instant::now() >= deadline);
+        let mut any = 0u64;
+        world.all_reduce_into(&reached, &mut any, SystemOperation::max());
+        any > 0
+    }
+}
+
+/// Send the number of iterations the children ran in the sample to the parent. All children ran
+/// the same number of iterations.
+pub fn send_iterations_run(comm: &dyn Communicator, iterations: u64) {
+    comm.process_at_rank(0)
+        .reduce_into(&iterations, SystemOperation::min());
+}
+
+/// Receive the number of iterations the children ran in the sample on the parent.
+pub fn receive_iterations_run(comm: &dyn Communicator) -> u64 {
+    let mut iterations = 0u64;
+    comm.process_at_rank(0)
+        .reduce_into_root(&u64::MAX, &mut iterations, SystemOperation::min());
+    iterations
+}
+
+/// Extrapolate the time of ``run`` iterations to the time of ``requested`` iterations, for a sample
+/// that was truncated by its time cap. The time is returned unchanged if no iteration ran, and
+/// saturates at [`Duration::MAX`].
+///
+/// # Example
+/// ```rust
+/// use mpirion::timing::extrapolate;
+/// use std::time::Duration;
+///
+/// assert_eq!(extrapolate(Duration::from_secs(60), 1_000, 4_000), Duration::from_secs(240));
+/// assert_eq!(extrapolate(Duration::from_nanos(10), 3, 10), Duration::from_nanos(33));
+/// assert_eq!(extrapolate(Duration::from_secs(1), 7, 7), Duration::from_secs(1));
+/// assert_eq!(extrapolate(Duration::from_secs(1), 0, 7), Duration::from_secs(1));
+/// assert_eq!(extrapolate(Duration::MAX, 1, u64::MAX), Duration::MAX);
+/// ```
+pub fn extrapolate(time: Duration, run: u64, requested: u64) -> Duration {
+    if run == 0 || run == requested {
+        return time;
+    }
+    let Some(nanos) = time.as_nanos().checked_mul(u128::from(requested)) else {
+        return Duration::MAX;
+    };
+    let nanos = nanos / u128::from(run);
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(seconds) => Duration::new(
+            seconds,
+            u32::try_from(nanos % 1_000_000_000).unwrap_or_default(),
+        ),
+        Err(_) => Duration::MAX,
+    }
+}
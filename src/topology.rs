@@ -0,0 +1,663 @@
+//! Process grids for kernels that need a Cartesian communicator, like a halo exchange or a 2D
+//! transpose. Creating the communicator in the kernel would be measured, and the setup function
+//! returns data, not communicators, so a kernel declares its grid with
+//! ``topology = cart(dims = [0, 0], periodic = [true, true])`` in the named parameters syntax of
+//! ``mpirion_kernel!``.
+//!
+//! The Cartesian communicator is created once per spawned world, before the first iteration, and
+//! passed to the setup function, the kernel and the validation function in place of the plain world
+//! of the children. The dimensions that are zero are inferred from the world size like
+//! ``MPI_Dims_create`` does, see [`dims_create`], so the same kernel runs at every world size. By
+//! default, the ranks are not reordered, so every child keeps its rank of the plain world.
+//!
+//! ``reorder = true`` after the periodicities lets MPI reorder the ranks to fit the grid to the
+//! hardware, and ``reorder = compare`` measures whether that pays off: every spawned world creates
+//! both communicators, without and with reordering, and runs a sample on each, in this order.
+//! [`compare_reorder`] declares a benchmark for each, with the ids suffixed ``/noreorder`` and
+//! ``/reorder``, which pass ``reorder = false`` and ``reorder = true`` to ``mpirion_bench!``. The
+//! samples of ``/reorder`` are the ones run in the worlds of ``/noreorder``, scaled to the
+//! iterations criterion asks for, see [`BenchRunner::reorder`](crate::runner::BenchRunner::reorder).
+//! A benchmark of such a kernel without ``reorder = ..`` fails, and so does a benchmark with
+//! ``reorder = ..`` of any other kernel. Since many MPI libraries ignore the hint, the children
+//! compare the ranks of both communicators, and the ``mpirion.json`` of the benchmark records
+//! whether MPI actually permuted them, see
+//! [`Metrics::ranks_permuted`](crate::report::Metrics::ranks_permuted).
+//!
+//! The kernel receives the communicator as a ``&dyn Communicator``, so it finds its neighbours with
+//! the [`CartesianGrid`] returned by [`grid`] instead of asking MPI.
+//!
+//! Kernels of neighborhood collectives over an application-defined graph declare it with
+//! ``topology = graph(my_neighbours)`` instead, with a function that takes a rank and the world
+//! size and returns the ranks the rank sends to. The function is evaluated for every rank, so each
+//! rank knows the ranks it receives from as well, and a distributed graph communicator is created
+//! from both lists like ``MPI_Dist_graph_create_adjacent`` does, again once per spawned world and
+//! without reordering. The kernel finds its [`Neighbours`] with [`graph`], in the order of the blocks
+//! of [`neighbor_all_to_all_into`], and can check them against the ones MPI reports with
+//! [`reported_neighbours`].
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::os::raw::{c_int, c_void};
+use std::sync::Mutex;
+
+use criterion::Bencher;
+
+use crate::mpi::collective::SystemOperation;
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::ffi;
+use crate::mpi::raw::AsRaw;
+use crate::mpi::topology::{CartesianCommunicator, SimpleCommunicator};
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+use crate::MpirionGroup;
+
+/// The grid of the kernel of this child process, once its communicator was created.
+static GRID: Mutex<Option<CartesianGrid>> = Mutex::new(None);
+
+/// Whether MPI permuted the ranks of this child process when it created the communicator of its
+/// kernel with reordering, or ``None`` if the kernel doesn't reorder its ranks.
+static PERMUTED: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Whether the current sample of a kernel with ``reorder = compare`` runs on the communicator
+/// with reordered ranks, or ``None`` if the benchmark selects none, see [`select`].
+static ROUND: Mutex<Option<bool>> = Mutex::new(None);
+
+thread_local! {
+    /// The communicator with reordered ranks of a kernel with ``reorder = compare``, which is
+    /// created next to the one without in the first sample of a world and used in the second.
+    static REORDERED: RefCell<Option<CartesianCommunicator>> = const { RefCell::new(None) };
+}
+
+/// The suffixes of the ids of the benchmarks of [`compare_reorder`], without and with reordering.
+const REORDER_SUFFIXES: [&str; 2] = ["noreorder", "reorder"];
+
+/// The neighbours of this child process in the graph of its kernel, once its communicator was
+/// created.
+static GRAPH: Mutex<Option<Neighbours>> = Mutex::new(None);
+
+/// The shape of a Cartesian process grid. The ranks are laid out in row-major order, like
+/// ``MPI_Cart_create`` lays them out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartesianGrid {
+    /// the number of processes along each dimension
+    pub dims: Vec<Rank>,
+    /// whether each dimension wraps around
+    pub periodic: Vec<bool>,
+}
+
+impl CartesianGrid {
+    /// The grid of ``size`` processes with the given dimensions, where zeros are inferred with
+    /// [`dims_create`]. Fails if there are not as many periodicities as dimensions, or the
+    /// dimensions don't fit the size.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::topology::CartesianGrid;
+    ///
+    /// let grid = CartesianGrid::new(12, &[0, 0], &[true, false]).unwrap();
+    /// assert_eq!(grid.dims, [4, 3]);
+    /// assert_eq!(grid.coordinates(7), [2, 1]);
+    /// assert_eq!(grid.rank(&[2, 1]), Some(7));
+    ///
+    /// // the first dimension wraps around, the second one doesn't
+    /// assert_eq!(grid.shift(0, 0, 1), (Some(9), Some(3)));
+    /// assert_eq!(grid.shift(0, 1, 1), (None, Some(1)));
+    ///
+    /// assert!(CartesianGrid::new(12, &[5, 0], &[true, true]).is_err());
+    /// assert!(CartesianGrid::new(12, &[0, 0], &[true]).is_err());
+    /// ```
+    pub fn new(size: Rank, dims: &[Rank], periodic: &[bool]) -> Result<Self, String> {
+        if dims.len() != periodic.len() {
+            return Err(format!(
+                "the topology has {} dimensions, but {} periodicities",
+                dims.len(),
+                periodic.len()
+            ));
+        }
+        Ok(Self {
+            dims: dims_create(size, dims)?,
+            periodic: periodic.to_vec(),
+        })
+    }
+
+    /// The number of processes in the grid.
+    pub fn size(&self) -> Rank {
+        self.dims.iter().product()
+    }
+
+    /// The coordinates of a rank in the grid.
+    pub fn coordinates(&self, rank: Rank) -> Vec<Rank> {
+        let mut coordinates = vec![0; self.dims.len()];
+        let mut rest = rank;
+        for (coordinate, dim) in coordinates.iter_mut().zip(&self.dims).rev() {
+            *coordinate = rest % dim;
+            rest /= dim;
+        }
+        coordinates
+    }
+
+    /// The rank at the given coordinates, or ``None`` if they are outside of a dimension that
+    /// doesn't wrap around. Coordinates in periodic dimensions wrap around.
+    pub fn rank(&self, coordinates: &[Rank]) -> Option<Rank> {
+        let mut rank = 0;
+        for ((&coordinate, &dim), &periodic) in
+            coordinates.iter().zip(&self.dims).zip(&self.periodic)
+        {
+            let coordinate = if periodic {
+                coordinate.rem_euclid(dim)
+            } else if (0..dim).contains(&coordinate) {
+                coordinate
+            } else {
+                return None;
+            };
+            rank = rank * dim + coordinate;
+        }
+        Some(rank)
+    }
+
+    /// The ranks ``displacement`` steps before and after ``rank`` along a dimension, like
+    /// ``MPI_Cart_shift``: the rank to receive from and the rank to send to in a shift. Either is
+    /// ``None`` at the border of a dimension that doesn't wrap around.
+    pub fn shift(
+        &self,
+        rank: Rank,
+        dimension: usize,
+        displacement: Rank,
+    ) -> (Option<Rank>, Option<Rank>) {
+        let neighbour = |displacement: Rank| {
+            let mut coordinates = self.coordinates(rank);
+            coordinates[dimension] += displacement;
+            self.rank(&coordinates)
+        };
+        (neighbour(-displacement), neighbour(displacement))
+    }
+}
+
+/// Infer the dimensions that are zero, so the grid has ``size`` processes, like
+/// ``MPI_Dims_create``. The inferred dimensions are as close to each other as possible, in
+/// non-increasing order. Fails if the dimensions that are set don't divide the size.
+///
+/// # Example
+/// ```rust
+/// use mpirion::topology::dims_create;
+///
+/// assert_eq!(dims_create(16, &[0, 0]), Ok(vec![4, 4]));
+/// assert_eq!(dims_create(12, &[0, 0]), Ok(vec![4, 3]));
+/// assert_eq!(dims_create(6, &[0, 0, 0]), Ok(vec![3, 2, 1]));
+/// assert_eq!(dims_create(7, &[0, 0]), Ok(vec![7, 1]));
+/// assert_eq!(dims_create(24, &[0, 2, 0]), Ok(vec![4, 2, 3]));
+/// assert!(dims_create(12, &[5, 0]).is_err());
+/// assert!(dims_create(12, &[3, 2]).is_err());
+/// ```
+pub fn dims_create(size: Rank, dims: &[Rank]) -> Result<Vec<Rank>, String> {
+    let fixed: Rank = dims.iter().filter(|dim| **dim != 0).product();
+    let mismatch = || format!("the topology {:?} does not fit {} processes", dims, size);
+    if size <= 0 || dims.iter().any(|dim| *dim < 0) || size % fixed != 0 {
+        return Err(mismatch());
+    }
+    let free = dims.iter().filter(|dim| **dim == 0).count();
+    if free == 0 && fixed != size {
+        return Err(mismatch());
+    }
+
+    // hand the largest prime factors out first, each to the smallest inferred dimension so far
+    let mut factors = Vec::new();
+    let (mut rest, mut factor) = (size / fixed, 2);
+    while rest > 1 {
+        if rest % factor == 0 {
+            factors.push(factor);
+            rest /= factor;
+        } else {
+            factor += 1;
+        }
+    }
+    let mut inferred = vec![1; free];
+    for factor in factors.into_iter().rev() {
+        if let Some(smallest) = inferred.iter_mut().min() {
+            *smallest *= factor;
+        }
+    }
+    inferred.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut inferred = inferred.into_iter();
+    Ok(dims
+        .iter()
+        .map(|dim| {
+            if *dim == 0 {
+                inferred.next().unwrap_or(1)
+            } else {
+                *dim
+            }
+        })
+        .collect())
+}
+
+/// The grid of the kernel of this child process. Panics if the kernel declares no topology.
+pub fn grid() -> CartesianGrid {
+    GRID.lock().unwrap().clone().expect(
+        "the kernel declares no topology, add `topology = cart(dims = [..], periodic = [..])` to mpirion_kernel!",
+    )
+}
+
+/// Whether MPI may reorder the ranks of a Cartesian communicator, declared with ``reorder = ..``
+/// in ``topology = cart(..)``, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reorder {
+    /// every child keeps its rank of the plain world, ``reorder = false``
+    #[default]
+    Never,
+    /// MPI may reorder the ranks, ``reorder = true``
+    Always,
+    /// both communicators are created, and the benchmark selects one, ``reorder = compare``
+    Compare,
+}
+
+/// The communicators a kernel runs a sample on in each spawned world, in this order: ``None`` for
+/// the one of its topology, or both communicators of a kernel with ``reorder = compare``, the one
+/// without reordering first, see [`select`].
+///
+/// # Example
+/// ```rust
+/// use mpirion::topology::rounds;
+///
+/// assert_eq!(rounds(false), [None]);
+/// assert_eq!(rounds(true), [Some(false), Some(true)]);
+/// ```
+pub fn rounds(compare: bool) -> Vec<Option<bool>> {
+    if compare {
+        vec![Some(false), Some(true)]
+    } else {
+        vec![None]
+    }
+}
+
+/// Select the communicator the next sample of a kernel with ``reorder = compare`` runs on, one of
+/// [`rounds`]. Called by the code ``mpirion_kernel!`` generates before each sample, and by the
+/// parent around a sample with the ``local-fallback`` feature.
+pub fn select(reordered: Option<bool>) {
+    *ROUND.lock().unwrap() = reordered;
+    if reordered != Some(true) {
+        REORDERED.with(|reordered| reordered.borrow_mut().take());
+    }
+}
+
+/// Let the children tell the parent whether their kernel declares ``reorder = compare``, so it
+/// fails a benchmark that selects no communicator of such a kernel, or one of another kernel.
+/// Called by the parent right after
+/// [`threading::check_children`](crate::threading::check_children) with whether the benchmark
+/// selects one. Returns an error naming the mistake otherwise, in which case the children exit.
+pub fn check_children(comm: &dyn Communicator, kernel: &str, compared: bool) -> Result<(), String> {
+    let mut compared = compared as u64;
+    comm.this_process().broadcast_into(&mut compared);
+    match agree_on_reorder(comm, 0) {
+        0 => Ok(()),
+        1 => Err(format!(
+            "kernel `{}` declares `reorder = compare`, but its benchmark selects no communicator: \
+             declare it with mpirion::topology::compare_reorder, or pass `reorder = ..` to mpirion_bench!",
+            kernel
+        )),
+        _ => Err(format!(
+            "the benchmark of kernel `{}` passes `reorder = ..` to mpirion_bench!, \
+             but the kernel doesn't declare `topology = cart(.., reorder = compare)`",
+            kernel
+        )),
+    }
+}
+
+/// Tell the parent whether the kernel of this child declares ``reorder = compare``, see
+/// [`check_children`]. Called by the children after they confirmed their threading level. Returns
+/// false if the benchmark doesn't select a communicator exactly when the kernels compare them, in
+/// which case the child must exit.
+pub fn confirm_reorder(comm: &dyn Communicator, declared: Reorder) -> bool {
+    let mut compared = 0u64;
+    comm.process_at_rank(0).broadcast_into(&mut compared);
+    let mismatch = match (declared == Reorder::Compare, compared == 1) {
+        (true, false) => 1,
+        (false, true) => 2,
+        _ => 0,
+    };
+    agree_on_reorder(comm, mismatch) == 0
+}
+
+/// The mistake any process found, ``1`` for a kernel with ``reorder = compare`` whose benchmark
+/// selects no communicator and ``2`` for a benchmark that selects one of another kernel.
+fn agree_on_reorder(comm: &dyn Communicator, mismatch: u64) -> u64 {
+    let mut agreed = 0u64;
+    comm.all_reduce_into(&mismatch, &mut agreed, SystemOperation::max());
+    agreed
+}
+
+/// Create the Cartesian communicator of a kernel on the world of the children. Called by the code
+/// ``mpirion_kernel!`` generates for a kernel with a ``topology``, before each sample. With
+/// [`Reorder::Compare`], both communicators are created in the first sample of a world, and the
+/// one [selected](select) for the sample is returned. Panics with the name of the kernel if the
+/// topology doesn't fit the world, or if a communicator is selected exactly when the kernel doesn't
+/// compare them.
+pub fn cartesian(
+    kernel: &str,
+    world: &dyn Communicator,
+    dims: &[Rank],
+    periodic: &[bool],
+    reorder: Reorder,
+) -> CartesianCommunicator {
+    let grid = CartesianGrid::new(world.size(), dims, periodic)
+        .unwrap_or_else(|message| panic!("kernel `{}`: {}", kernel, message));
+    let create = |reorder: bool| {
+        world
+            .create_cartesian_communicator(&grid.dims, &grid.periodic, reorder)
+            .unwrap_or_else(|| {
+                panic!(
+                    "kernel `{}`: MPI did not place rank {} in the topology",
+                    kernel,
+                    world.rank()
+                )
+            })
+    };
+    let selected = *ROUND.lock().unwrap();
+    if selected.is_some() != (reorder == Reorder::Compare) {
+        panic!(
+            "kernel `{}`: {}",
+            kernel,
+            if selected.is_some() {
+                "its benchmark passes `reorder = ..` to mpirion_bench!, but the kernel doesn't declare `reorder = compare`"
+            } else {
+                "the kernel declares `reorder = compare`, but its benchmark doesn't pass `reorder = ..` to mpirion_bench!"
+            }
+        );
+    }
+    // the communicator without reordering has the ranks of the plain world
+    let communicator = match (reorder, selected) {
+        (Reorder::Never, _) => create(false),
+        (Reorder::Always, _) => {
+            let reordered = create(true);
+            *PERMUTED.lock().unwrap() = Some(reordered.rank() != world.rank());
+            reordered
+        }
+        // the second sample of a world runs on the communicator created in the first
+        (Reorder::Compare, Some(true)) => REORDERED
+            .with(|reordered| reordered.borrow_mut().take())
+            .unwrap_or_else(|| {
+                let reordered = create(true);
+                *PERMUTED.lock().unwrap() = Some(reordered.rank() != world.rank());
+                reordered
+            }),
+        (Reorder::Compare, _) => {
+            let (plain, reordered) = (create(false), create(true));
+            *PERMUTED.lock().unwrap() = Some(reordered.rank() != plain.rank());
+            REORDERED.with(|stored| *stored.borrow_mut() = Some(reordered));
+            plain
+        }
+    };
+    *GRID.lock().unwrap() = Some(grid);
+    communicator
+}
+
+/// Send whether MPI permuted the ranks of the communicator of the kernel to the parent, see
+/// [`receive_ranks_permuted`].
+pub fn send_ranks_permuted(comm: &dyn Communicator) {
+    let code = match *PERMUTED.lock().unwrap() {
+        None => 0u64,
+        Some(false) => 1,
+        Some(true) => 2,
+    };
+    comm.process_at_rank(0)
+        .reduce_into(&code, SystemOperation::max());
+}
+
+/// Receive whether MPI permuted the rank of any child when it created the communicator of the
+/// kernel with reordering, on the parent. ``None`` if the kernel doesn't reorder its ranks.
+pub fn receive_ranks_permuted(comm: &dyn Communicator) -> Option<bool> {
+    let mut code = 0u64;
+    comm.process_at_rank(0)
+        .reduce_into_root(&0u64, &mut code, SystemOperation::max());
+    match code {
+        0 => None,
+        code => Some(code == 2),
+    }
+}
+
+/// The id of the benchmark of [`compare_reorder`] that runs with or without reordering.
+///
+/// # Example
+/// ```rust
+/// use mpirion::topology::reorder_id;
+///
+/// assert_eq!(reorder_id(16, false), "16/noreorder");
+/// assert_eq!(reorder_id("halo", true), "halo/reorder");
+/// ```
+pub fn reorder_id(id: impl Display, reorder: bool) -> String {
+    format!("{}/{}", id, REORDER_SUFFIXES[reorder as usize])
+}
+
+/// Declare the two benchmarks of a kernel with ``reorder = compare`` in ``group``, on the
+/// communicator without and on the one with reordered ranks, with the ids of [`reorder_id`]. The
+/// routine receives whether to reorder and passes it on with ``reorder = ..`` to
+/// ``mpirion_bench!`` for ``kernel``. The benchmark without reordering runs first, and spawns the
+/// worlds whose samples on the communicator with reordered ranks the second one replays.
+///
+/// # Example
+/// ```rust
+/// use mpirion::prelude::*;
+/// use mpirion::topology::compare_reorder;
+///
+/// fn halo_kernel(comm: &dyn Communicator) {
+///     let grid = mpirion::topology::grid();
+///     let _neighbours = grid.shift(comm.rank(), 0, 1);
+/// }
+///
+/// mpirion_kernel!(kernel = halo_kernel, topology = cart(dims = [0, 0], periodic = [true, true], reorder = compare));
+///
+/// fn halo_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+///     let mut group = ctx.benchmark_group(c, "halo");
+///     compare_reorder(&mut group, 16, "halo_kernel", |b, reorder| {
+///         mpirion_bench!(halo_kernel, b, ctx; world_size = 16, reorder = reorder)
+///     });
+///     group.finish();
+/// }
+/// ```
+pub fn compare_reorder(
+    group: &mut MpirionGroup<'_>,
+    id: impl Display,
+    kernel: &str,
+    mut routine: impl FnMut(&mut Bencher<'_>, bool),
+) {
+    for reorder in [false, true] {
+        group.bench_function(reorder_id(&id, reorder), kernel, |b| routine(b, reorder));
+    }
+}
+
+/// The neighbours of a rank in a directed graph, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Neighbours {
+    /// the ranks this rank receives from, in ascending order
+    pub sources: Vec<Rank>,
+    /// the ranks this rank sends to, in the order the function of the graph returned them
+    pub destinations: Vec<Rank>,
+}
+
+impl Neighbours {
+    /// The neighbours of ``rank`` in the graph of ``size`` processes in which each rank sends to
+    /// the ranks ``destinations`` returns for it. A rank that appears several times in the
+    /// destinations of another rank is its neighbour as often. Fails if ``destinations`` returns a
+    /// rank outside of the world for any rank.
+    ///
+    /// # Example
+    /// ```rust
+    /// use mpirion::topology::Neighbours;
+    ///
+    /// // every rank sends to the next two ranks of a ring
+    /// let ring = |rank, size| vec![(rank + 1) % size, (rank + 2) % size];
+    /// let neighbours = Neighbours::of(0, 5, ring).unwrap();
+    /// assert_eq!(neighbours.destinations, [1, 2]);
+    /// assert_eq!(neighbours.sources, [3, 4]);
+    ///
+    /// assert!(Neighbours::of(0, 5, |rank, size| vec![rank + size]).is_err());
+    /// ```
+    pub fn of(
+        rank: Rank,
+        size: Rank,
+        destinations: impl Fn(Rank, Rank) -> Vec<Rank>,
+    ) -> Result<Self, String> {
+        let mut sources = Vec::new();
+        let mut own = Vec::new();
+        for source in 0..size {
+            let targets = destinations(source, size);
+            if let Some(target) = targets.iter().find(|target| !(0..size).contains(*target)) {
+                return Err(format!(
+                    "rank {} of the graph sends to rank {}, which is not in the world of {} processes",
+                    source, target, size
+                ));
+            }
+            sources.extend(
+                targets
+                    .iter()
+                    .filter(|target| **target == rank)
+                    .map(|_| source),
+            );
+            if source == rank {
+                own = targets;
+            }
+        }
+        Ok(Self {
+            sources,
+            destinations: own,
+        })
+    }
+}
+
+/// The neighbours of this child process in the graph of its kernel. Panics if the kernel declares
+/// no graph.
+pub fn graph() -> Neighbours {
+    GRAPH.lock().unwrap().clone().expect(
+        "the kernel declares no graph, add `topology = graph(my_neighbours)` to mpirion_kernel!",
+    )
+}
+
+/// Create the distributed graph communicator of a kernel on the world of the children. Called by the
+/// code ``mpirion_kernel!`` generates for a kernel with a ``topology = graph(..)``, once per spawned
+/// world. Panics with the name of the kernel if the graph doesn't fit the world, or if MPI reports
+/// other neighbours than the graph has.
+pub fn distributed_graph(
+    kernel: &str,
+    world: &dyn Communicator,
+    destinations: impl Fn(Rank, Rank) -> Vec<Rank>,
+) -> SimpleCommunicator {
+    let neighbours = Neighbours::of(world.rank(), world.size(), destinations)
+        .unwrap_or_else(|message| panic!("kernel `{}`: {}", kernel, message));
+    let degree = |ranks: &[Rank]| {
+        c_int::try_from(ranks.len())
+            .unwrap_or_else(|_| panic!("kernel `{}`: the graph has too many edges", kernel))
+    };
+    // the edges are weighted with 1, MPI_UNWEIGHTED is not exposed by the mpi crate
+    let source_weights = vec![1; neighbours.sources.len()];
+    let destination_weights = vec![1; neighbours.destinations.len()];
+    let mut raw = std::mem::MaybeUninit::uninit();
+    // SAFETY: the lists have the lengths passed with them, and MPI initializes the communicator
+    let communicator = unsafe {
+        ffi::MPI_Dist_graph_create_adjacent(
+            world.as_raw(),
+            degree(&neighbours.sources),
+            neighbours.sources.as_ptr(),
+            source_weights.as_ptr(),
+            degree(&neighbours.destinations),
+            neighbours.destinations.as_ptr(),
+            destination_weights.as_ptr(),
+            ffi::RSMPI_INFO_NULL,
+            0,
+            raw.as_mut_ptr(),
+        );
+        SimpleCommunicator::from_raw(raw.assume_init())
+    };
+
+    let reported = reported_neighbours(&communicator);
+    if reported != neighbours {
+        panic!(
+            "kernel `{}`: rank {} has the neighbours {:?} in the graph, but MPI reports {:?}",
+            kernel,
+            world.rank(),
+            neighbours,
+            reported
+        );
+    }
+    *GRAPH.lock().unwrap() = Some(neighbours);
+    communicator
+}
+
+/// The neighbours of this rank that MPI reports for a distributed graph communicator, like
+/// ``MPI_Dist_graph_neighbors``. The sources are sorted, so they compare equal to the ones of
+/// [`graph`] if MPI placed the edges as given.
+pub fn reported_neighbours(comm: &dyn Communicator) -> Neighbours {
+    let (mut indegree, mut outdegree, mut weighted) = (0, 0, 0);
+    // SAFETY: the lists have room for the degrees MPI reported
+    unsafe {
+        ffi::MPI_Dist_graph_neighbors_count(
+            comm.as_raw(),
+            &mut indegree,
+            &mut outdegree,
+            &mut weighted,
+        );
+        let mut sources = vec![0; indegree as usize];
+        let mut destinations = vec![0; outdegree as usize];
+        let mut source_weights = vec![0; indegree as usize];
+        let mut destination_weights = vec![0; outdegree as usize];
+        ffi::MPI_Dist_graph_neighbors(
+            comm.as_raw(),
+            indegree,
+            sources.as_mut_ptr(),
+            source_weights.as_mut_ptr(),
+            outdegree,
+            destinations.as_mut_ptr(),
+            destination_weights.as_mut_ptr(),
+        );
+        sources.sort_unstable();
+        Neighbours {
+            sources,
+            destinations,
+        }
+    }
+}
+
+/// Send the ``i``-th block of ``send`` to the ``i``-th destination and receive the block of the
+/// ``i``-th source into the ``i``-th block of ``receive``, like ``MPI_Neighbor_alltoall`` on the
+/// graph of the kernel. Panics if the kernel declares no graph, or if the buffers don't hold a block
+/// of the same length for each neighbour.
+pub fn neighbor_all_to_all_into<T: Equivalence>(
+    comm: &dyn Communicator,
+    send: &[T],
+    receive: &mut [T],
+) {
+    let neighbours = GRAPH
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|neighbours| (neighbours.sources.len(), neighbours.destinations.len()));
+    let (indegree, outdegree) = neighbours.expect(
+        "the kernel declares no graph, add `topology = graph(my_neighbours)` to mpirion_kernel!",
+    );
+    let block = send
+        .len()
+        .checked_div(outdegree)
+        .unwrap_or(receive.len() / indegree.max(1));
+    assert!(
+        send.len() == block * outdegree && receive.len() == block * indegree,
+        "neighbor_all_to_all_into needs a block for each of the {} destinations and {} sources, but got {} and {} elements",
+        outdegree,
+        indegree,
+        send.len(),
+        receive.len()
+    );
+    let count = c_int::try_from(block)
+        .expect("the blocks of neighbor_all_to_all_into are longer than i32::MAX");
+    // SAFETY: the buffers hold a block of ``count`` elements for each neighbour
+    unsafe {
+        ffi::MPI_Neighbor_alltoall(
+            send.as_ptr() as *const c_void,
+            count,
+            T::equivalent_datatype().as_raw(),
+            receive.as_mut_ptr() as *mut c_void,
+            count,
+            T::equivalent_datatype().as_raw(),
+            comm.as_raw(),
+        );
+    }
+}
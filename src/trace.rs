@@ -0,0 +1,76 @@
+//! Instrumentation of the protocol phases with ``tracing``, enabled by the ``tracing`` feature.
+//! Without the feature, all functions in this module do nothing.
+//!
+//! The parent emits events when it spawns the children, merges the communicators, broadcasts the
+//! iterations and receives the outcome, all inside a span with the name of the kernel. The
+//! children emit events when they merge with the parent, receive the iterations, start each
+//! iteration and send the outcome, inside a span with the name of the kernel and their rank. If a
+//! sample hangs, the last event of each process shows which phase it is stuck in.
+//!
+//! ``mpirion_main!`` installs a subscriber that logs to stderr if the ``MPIRION_LOG`` environment
+//! variable is set, e.g. ``MPIRION_LOG=debug``. Its value is an ``EnvFilter`` directive of
+//! ``tracing_subscriber``. If the benchmark installs its own subscriber, it takes precedence. The
+//! children also write the events into their [log files](crate::logs).
+
+use crate::mpi::traits::Communicator;
+
+/// The environment variable with the filter of the subscriber installed by [`init_from_env`].
+pub const LOG_VAR: &str = "MPIRION_LOG";
+
+/// Emit a debug event if the ``tracing`` feature is enabled.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+pub(crate) use debug;
+
+/// Keeps a span entered until it is dropped.
+#[must_use = "the span is exited when the guard is dropped"]
+pub struct SpanGuard {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+/// Install a subscriber that logs to stderr with the filter in [`LOG_VAR`], if the variable is set
+/// and no subscriber is installed yet. Called by ``mpirion_main!`` in the parent and the children.
+pub fn init_from_env() {
+    #[cfg(feature = "tracing")]
+    if let Ok(filter) = std::env::var(LOG_VAR) {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::new(filter))
+            .with_writer(|| crate::logs::TeeWriter)
+            .try_init();
+    }
+}
+
+/// Enter the span of a sample on the parent.
+pub fn parent_span(kernel: &str) -> SpanGuard {
+    #[cfg(not(feature = "tracing"))]
+    let _ = kernel;
+
+    SpanGuard {
+        #[cfg(feature = "tracing")]
+        _span: tracing::debug_span!("parent", kernel).entered(),
+    }
+}
+
+/// Enter the span of a sample on a child, tagged with its rank in the child world.
+pub fn child_span(kernel: &str, world: &dyn Communicator) -> SpanGuard {
+    #[cfg(not(feature = "tracing"))]
+    let _ = (kernel, world);
+
+    SpanGuard {
+        #[cfg(feature = "tracing")]
+        _span: tracing::debug_span!("child", kernel, rank = world.rank()).entered(),
+    }
+}
+
+/// Emit the event of a child starting an iteration of the kernel.
+pub fn iteration(iteration: u64) {
+    #[cfg(not(feature = "tracing"))]
+    let _ = iteration;
+
+    debug!(iteration, "starting kernel iteration");
+}
@@ -0,0 +1,88 @@
+//! Broadcasts of buffers of arbitrary length. MPI counts are ``int``s, so a single broadcast can
+//! transfer at most ``i32::MAX`` elements. The functions in this module transparently split larger
+//! transfers into multiple broadcasts of bounded chunks. They can be used from setup functions to
+//! distribute large inputs, and mpirion uses them for its own variable-length transfers.
+
+use crate::mpi::datatype::Equivalence;
+use crate::mpi::traits::*;
+use crate::mpi::Rank;
+use std::ops::Range;
+
+/// The maximum number of elements transferred by a single broadcast.
+pub const MAX_CHUNK_LEN: usize = i32::MAX as usize;
+
+/// Broadcast ``buffer`` from ``root`` to all ranks of ``comm``. The buffer must have the same length
+/// on all ranks. Buffers longer than [`MAX_CHUNK_LEN`] are split into multiple broadcasts.
+pub fn broadcast_slice<T: Equivalence>(comm: &dyn Communicator, root: Rank, buffer: &mut [T]) {
+    broadcast_slice_chunked(comm, root, buffer, MAX_CHUNK_LEN);
+}
+
+/// Broadcast ``buffer`` from ``root`` to all ranks of ``comm`` in chunks of at most ``chunk_len``
+/// elements. This is the same as [`broadcast_slice`] with a custom chunk size.
+///
+/// # Example
+/// ```rust
+/// use mpirion::mpi::traits::*;
+/// use mpirion::transfer::broadcast_slice_chunked;
+///
+/// let universe = mpirion::mpi::initialize().unwrap();
+/// let world = universe.world();
+/// // ten elements in chunks of three, so the last broadcast transfers a single element
+/// let mut buffer: Vec<u64> = if world.rank() == 0 {
+///     (0..10).collect()
+/// } else {
+///     vec![0; 10]
+/// };
+/// broadcast_slice_chunked(&world, 0, &mut buffer, 3);
+/// assert_eq!(buffer, (0..10).collect::<Vec<u64>>());
+/// ```
+pub fn broadcast_slice_chunked<T: Equivalence>(
+    comm: &dyn Communicator,
+    root: Rank,
+    buffer: &mut [T],
+    chunk_len: usize,
+) {
+    let process = comm.process_at_rank(root);
+    for range in chunk_ranges(buffer.len(), chunk_len) {
+        process.broadcast_into(&mut buffer[range]);
+    }
+}
+
+/// Broadcast a vector from ``root`` to all ranks of ``comm``. Unlike [`broadcast_slice`], the
+/// length only needs to be known at the root; the vectors of all other ranks are resized to it.
+pub fn broadcast_vec<T: Equivalence + Clone + Default>(
+    comm: &dyn Communicator,
+    root: Rank,
+    buffer: &mut Vec<T>,
+) {
+    let mut len = buffer.len() as u64;
+    comm.process_at_rank(root).broadcast_into(&mut len);
+    buffer.resize(len as usize, T::default());
+    broadcast_slice(comm, root, buffer);
+}
+
+/// Split ``len`` elements into consecutive ranges of at most ``chunk_len`` elements.
+///
+/// # Example
+/// ```rust
+/// use mpirion::transfer::chunk_ranges;
+///
+/// let ranges: Vec<_> = chunk_ranges(10, 3).collect();
+/// assert_eq!(ranges, vec![0..3, 3..6, 6..9, 9..10]);
+/// assert_eq!(chunk_ranges(0, 3).count(), 0);
+/// assert_eq!(chunk_ranges(6, 3).count(), 2);
+///
+/// // reassembling a buffer from its chunks restores the original
+/// let source: Vec<u64> = (0..100_003).collect();
+/// let mut target = vec![0u64; source.len()];
+/// for range in chunk_ranges(source.len(), 4096) {
+///     target[range.clone()].copy_from_slice(&source[range]);
+/// }
+/// assert_eq!(source, target);
+/// ```
+pub fn chunk_ranges(len: usize, chunk_len: usize) -> impl Iterator<Item = Range<usize>> {
+    assert!(chunk_len > 0, "chunk length must be positive");
+    (0..len)
+        .step_by(chunk_len)
+        .map(move |start| start..len.min(start + chunk_len))
+}
@@ -0,0 +1,177 @@
+//! Environment variables for the children of a benchmark, to select the algorithms of the
+//! collectives. Open MPI reads its MCA parameters, like ``OMPI_MCA_coll_tuned_bcast_algorithm``,
+//! and MPICH its control variables, like ``MPIR_CVAR_BCAST_INTRA_ALGORITHM``, from the environment
+//! when MPI is initialized. The children are spawned by MPI, which does not pass the environment
+//! of the parent on, so a benchmark sets them with ``tuning = [("OMPI_MCA_..", "6")]`` in
+//! ``mpirion_bench!``.
+//!
+//! The parent passes the variables to the children on the command line, and each child sets them
+//! in its environment before it initializes MPI, so every benchmark point runs with its own
+//! algorithm. The variables are written into the ``mpirion.json`` of the benchmark, see
+//! [`Metrics::tuning`](crate::report::Metrics::tuning). Local runs don't spawn children and
+//! ignore them.
+//!
+//! To benchmark a kernel under several algorithm choices, name each choice as a [`TuningSet`] and
+//! iterate the cross product of the sets and the inputs with [`sweep`].
+//!
+//! # Example
+//! ```rust
+//! use mpirion::prelude::*;
+//! use mpirion::tuning::{sweep, TuningSet};
+//!
+//! fn bcast_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+//!     let algorithms = [
+//!         TuningSet::new("default", Vec::<(String, String)>::new()),
+//!         TuningSet::new("binomial", [("OMPI_MCA_coll_tuned_bcast_algorithm", "6")]),
+//!     ];
+//!     let mut g = ctx.benchmark_group(c, "bcast");
+//!     for (algorithm, size) in sweep(&algorithms, [1usize << 10, 1 << 20]) {
+//!         let id = format!("{}/{}", algorithm.name, size);
+//!         g.bench_with_input(id, "bcast_kernel", &size, |b, &size| {
+//!             mpirion_bench!(bcast_kernel, b, ctx; arg = size, tuning = algorithm)
+//!         });
+//!     }
+//!     g.finish();
+//! }
+//!
+//! fn setup(_comm: &dyn Communicator, size: usize) -> Vec<u8> {
+//!     vec![0; size]
+//! }
+//!
+//! fn bcast_kernel(comm: &dyn Communicator, data: &mut Vec<u8>) {
+//!     comm.process_at_rank(0).broadcast_into(&mut data[..]);
+//! }
+//!
+//! mpirion_kernel!(bcast_kernel, setup, usize);
+//! ```
+
+/// The flag that passes a variable of the tuning to a child, followed by ``NAME=VALUE``.
+const TUNING_FLAG: &str = "--mpirion-tuning";
+
+/// A named set of environment variables for the children, e.g. the choice of an algorithm.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TuningSet {
+    /// the name of the set, e.g. to tell the benchmarks of the sets apart
+    pub name: String,
+    /// the variables and their values
+    pub vars: Vec<(String, String)>,
+}
+
+impl TuningSet {
+    /// A set with the given name and variables.
+    pub fn new<K, V>(name: impl Into<String>, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            vars: vars
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+impl IntoIterator for TuningSet {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vars.into_iter()
+    }
+}
+
+/// The cross product of the tuning sets and the inputs of a benchmark, with the inputs varying
+/// fastest, so the benchmarks of a set are grouped.
+///
+/// # Example
+/// ```rust
+/// use mpirion::tuning::{sweep, TuningSet};
+///
+/// let sets = [TuningSet::new("linear", [("ALGORITHM", "1")]), TuningSet::new("binomial", [("ALGORITHM", "6")])];
+/// let points: Vec<(String, u32)> = sweep(&sets, [8, 64]).map(|(set, size)| (set.name.clone(), size)).collect();
+/// assert_eq!(
+///     points,
+///     [("linear".to_string(), 8), ("linear".to_string(), 64), ("binomial".to_string(), 8), ("binomial".to_string(), 64)]
+/// );
+/// ```
+pub fn sweep<'s, A: Clone + 's>(
+    sets: &'s [TuningSet],
+    inputs: impl IntoIterator<Item = A>,
+) -> impl Iterator<Item = (&'s TuningSet, A)> + 's {
+    let inputs: Vec<A> = inputs.into_iter().collect();
+    sets.iter()
+        .flat_map(move |set| inputs.clone().into_iter().map(move |input| (set, input)))
+}
+
+/// Check that ``name`` can be set as an environment variable.
+///
+/// # Example
+/// ```rust
+/// use mpirion::tuning::check_name;
+///
+/// assert!(check_name("OMPI_MCA_coll_tuned_bcast_algorithm").is_ok());
+/// assert!(check_name("").is_err());
+/// assert!(check_name("A=B").is_err());
+/// ```
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(format!(
+            "`{}` is not a valid name of an environment variable",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// The arguments that pass the tuning of a benchmark to a child.
+pub fn child_args(vars: &[(String, String)]) -> Vec<String> {
+    vars.iter()
+        .flat_map(|(name, value)| [TUNING_FLAG.to_string(), format!("{}={}", name, value)])
+        .collect()
+}
+
+/// The variables of the tuning in the arguments of a child. Fails if a flag has no ``NAME=VALUE``
+/// after it.
+///
+/// # Example
+/// ```rust
+/// use mpirion::tuning::parse_child_args;
+///
+/// let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+/// assert_eq!(
+///     parse_child_args(&args(&["--mpirion-child", "bcast", "--mpirion-tuning", "ALGORITHM=6"])),
+///     Ok(vec![("ALGORITHM".to_string(), "6".to_string())])
+/// );
+/// assert_eq!(parse_child_args(&args(&["--mpirion-tuning", "EMPTY="])), Ok(vec![("EMPTY".to_string(), String::new())]));
+/// assert!(parse_child_args(&args(&["--mpirion-tuning"])).is_err());
+/// assert!(parse_child_args(&args(&["--mpirion-tuning", "ALGORITHM"])).is_err());
+/// ```
+pub fn parse_child_args(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg != TUNING_FLAG {
+            continue;
+        }
+        let var = args
+            .next()
+            .ok_or_else(|| format!("{} needs NAME=VALUE", TUNING_FLAG))?;
+        let (name, value) = var
+            .split_once('=')
+            .ok_or_else(|| format!("{} needs NAME=VALUE, not `{}`", TUNING_FLAG, var))?;
+        check_name(name)?;
+        vars.push((name.to_string(), value.to_string()));
+    }
+    Ok(vars)
+}
+
+/// Set the variables of the tuning passed by the parent in the environment of this child. Called
+/// on dispatch to a child, before MPI is initialized. Panics if the arguments are malformed.
+pub fn apply_child_args(args: &[String]) {
+    for (name, value) in parse_child_args(args).unwrap_or_else(|message| panic!("{}", message)) {
+        std::env::set_var(name, value);
+    }
+}
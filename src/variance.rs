@@ -0,0 +1,90 @@
+//! Detection of ranks that disagree about the time of a sample. If the times of the ranks vary
+//! widely, the benchmark usually has a methodology problem, like a missing barrier before the
+//! measured region or work that is not split evenly, rather than an interesting result.
+//!
+//! The parent computes the [coefficient of variation](coefficient_of_variation) of the times of
+//! the ranks of each sample, and warns once per kernel and world size if it exceeds the threshold
+//! set with ``MPIRION_VARIANCE_WARN``, see [`threshold`]. The [report](crate::report) marks the
+//! benchmarks whose samples exceeded it.
+
+use std::time::Duration;
+
+/// The environment variable that sets the variance threshold in percent, or ``off`` to disable
+/// the warning.
+pub const VARIANCE_WARN_VAR: &str = "MPIRION_VARIANCE_WARN";
+
+/// The variance threshold in percent if ``MPIRION_VARIANCE_WARN`` is not set.
+pub const DEFAULT_THRESHOLD: f64 = 25.0;
+
+/// The standard deviation of the times of the ranks relative to their mean. ``None`` if there are
+/// fewer than two ranks, or the ranks took no measurable time.
+///
+/// # Example
+/// ```rust
+/// use mpirion::variance::coefficient_of_variation;
+/// use std::time::Duration;
+///
+/// let even = [100, 100, 100, 100].map(Duration::from_micros);
+/// assert_eq!(coefficient_of_variation(&even), Some(0.0));
+///
+/// // a mean of 100 µs and a standard deviation of 50 µs
+/// let uneven = [50, 150, 50, 150].map(Duration::from_micros);
+/// assert!((coefficient_of_variation(&uneven).unwrap() - 0.5).abs() < 1e-9);
+///
+/// assert_eq!(coefficient_of_variation(&[Duration::from_micros(100)]), None);
+/// assert_eq!(coefficient_of_variation(&[Duration::ZERO; 4]), None);
+/// ```
+pub fn coefficient_of_variation(per_rank: &[Duration]) -> Option<f64> {
+    if per_rank.len() < 2 {
+        return None;
+    }
+    let ranks = per_rank.len() as f64;
+    let mean = per_rank.iter().map(Duration::as_secs_f64).sum::<f64>() / ranks;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = per_rank
+        .iter()
+        .map(|time| (time.as_secs_f64() - mean).powi(2))
+        .sum::<f64>()
+        / ranks;
+    Some(variance.sqrt() / mean)
+}
+
+/// Parse a variance threshold in percent, as used in ``MPIRION_VARIANCE_WARN``. ``off`` disables
+/// the warning.
+///
+/// # Example
+/// ```rust
+/// use mpirion::variance::parse_threshold;
+///
+/// assert_eq!(parse_threshold("40"), Ok(Some(40.0)));
+/// assert_eq!(parse_threshold("OFF"), Ok(None));
+/// assert!(parse_threshold("0").is_err());
+/// ```
+pub fn parse_threshold(value: &str) -> Result<Option<f64>, String> {
+    crate::drift::parse_percent(VARIANCE_WARN_VAR, value)
+}
+
+/// The variance threshold in percent, from ``MPIRION_VARIANCE_WARN`` or [`DEFAULT_THRESHOLD`], or
+/// ``None`` if the warning is disabled. Panics if the variable is malformed.
+pub fn threshold() -> Option<f64> {
+    match std::env::var(VARIANCE_WARN_VAR) {
+        Ok(value) => parse_threshold(&value).unwrap_or_else(|message| panic!("{}", message)),
+        Err(_) => Some(DEFAULT_THRESHOLD),
+    }
+}
+
+/// Whether a coefficient of variation exceeds a threshold in percent.
+///
+/// # Example
+/// ```rust
+/// use mpirion::variance::exceeds;
+///
+/// assert!(exceeds(0.3, Some(25.0)));
+/// assert!(!exceeds(0.2, Some(25.0)));
+/// assert!(!exceeds(0.3, None));
+/// ```
+pub fn exceeds(variation: f64, threshold: Option<f64>) -> bool {
+    threshold.is_some_and(|percent| variation * 100.0 > percent)
+}
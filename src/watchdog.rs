@@ -0,0 +1,178 @@
+//! Keeps the children from outliving a crashed parent. If the parent dies between spawning the
+//! children and collecting their outcome, the children would otherwise block in a collective
+//! forever.
+//!
+//! The liveness check doesn't use MPI, so MPI doesn't need to be initialized with a threading
+//! level that permits calls from another thread. Instead, the parent listens on a TCP socket and
+//! passes its address to the children on the command line. Each child connects from a watchdog
+//! thread, and the parent sends a heartbeat over the connection every second. If the connection
+//! is closed, or no heartbeat arrives within the grace period, the child exits.
+//!
+//! The parent listens on the loopback interface, which only reaches children on the same node. If
+//! the children run on other nodes, set ``MPIRION_WATCHDOG_HOST`` to an address of the parent
+//! that they can reach. Children that cannot connect run without a watchdog.
+//!
+//! Each child world is identified by a number, which its children send when they connect. The
+//! parent can [`abandon`] a hung child world by closing the connections of its children, which
+//! makes them exit while the parent keeps running.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// The environment variable that sets the address the parent listens on for the watchdogs.
+pub const HOST_VAR: &str = "MPIRION_WATCHDOG_HOST";
+
+/// How long a child waits for a heartbeat of the parent before it exits, unless it is set with
+/// ``--mpirion-watchdog-grace``.
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+/// How often the parent sends a heartbeat. The grace period should be a few times longer.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The flag that passes the address of the parent to the children.
+const ADDRESS_FLAG: &str = "--mpirion-watchdog";
+
+/// The flag that passes the grace period in milliseconds to the children.
+const GRACE_FLAG: &str = "--mpirion-watchdog-grace-ms";
+
+/// The flag that passes the number of the child world to the children.
+const WORLD_FLAG: &str = "--mpirion-watchdog-world";
+
+/// The child worlds whose children no longer receive heartbeats.
+static ABANDONED: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+/// A new number for a child world, which is passed to its children with [`child_args`].
+pub fn next_world() -> u64 {
+    static NEXT_WORLD: AtomicU64 = AtomicU64::new(0);
+    NEXT_WORLD.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Stop the heartbeats to the children of the given world, so they exit within a heartbeat
+/// interval. Used to get rid of a child world that hangs, since MPI cannot cancel it.
+pub fn abandon(world: u64) {
+    ABANDONED.lock().unwrap().push(world);
+}
+
+fn is_abandoned(world: u64) -> bool {
+    ABANDONED.lock().unwrap().contains(&world)
+}
+
+/// The address the parent accepts watchdog connections on, or ``None`` if it cannot listen. The
+/// socket is opened on the first call and shared by all benchmarks of the process.
+pub fn parent_address() -> Option<&'static str> {
+    static ADDRESS: OnceLock<Option<String>> = OnceLock::new();
+    ADDRESS.get_or_init(listen).as_deref()
+}
+
+fn listen() -> Option<String> {
+    let host = std::env::var(HOST_VAR).unwrap_or_else(|_| "127.0.0.1".to_string());
+    let listener = match TcpListener::bind((host.as_str(), 0)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!(
+                "mpirion: cannot listen for the watchdogs of the children on {}: {}. Children may outlive a crashed parent.",
+                host, error
+            );
+            return None;
+        }
+    };
+    let address = listener.local_addr().ok()?.to_string();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            std::thread::spawn(move || heartbeat(stream));
+        }
+    });
+    Some(address)
+}
+
+/// Send heartbeats to a child until it closes the connection, or its world is abandoned.
+fn heartbeat(mut stream: TcpStream) {
+    let mut world = [0u8; 8];
+    if stream.read_exact(&mut world).is_err() {
+        return;
+    }
+    let world = u64::from_le_bytes(world);
+
+    while !is_abandoned(world) && stream.write_all(&[0]).is_ok() {
+        std::thread::sleep(HEARTBEAT_INTERVAL);
+    }
+}
+
+/// The arguments that pass the address of the parent, the grace period and the number of the
+/// child world to a child.
+pub fn child_args(address: &str, grace: Duration, world: u64) -> [String; 6] {
+    [
+        ADDRESS_FLAG.to_string(),
+        address.to_string(),
+        GRACE_FLAG.to_string(),
+        grace.as_millis().to_string(),
+        WORLD_FLAG.to_string(),
+        world.to_string(),
+    ]
+}
+
+/// Start the watchdog of a child, if the parent passed its address on the command line. Called by
+/// the children when they merge with the parent.
+pub fn start() {
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+            .cloned()
+    };
+
+    let Some(address) = value_of(ADDRESS_FLAG) else {
+        return;
+    };
+    let grace = value_of(GRACE_FLAG)
+        .and_then(|millis| millis.parse().ok())
+        .map_or(DEFAULT_GRACE, Duration::from_millis);
+    let world = value_of(WORLD_FLAG)
+        .and_then(|world| world.parse().ok())
+        .unwrap_or(0);
+
+    std::thread::spawn(move || watch(&address, grace, world));
+}
+
+/// Wait until the parent is gone or abandons the world of this child, then exit the process.
+fn watch(address: &str, grace: Duration, world: u64) {
+    let mut stream = match TcpStream::connect(address) {
+        Ok(stream) => stream,
+        Err(error) => {
+            eprintln!(
+                "mpirion: the watchdog cannot reach the parent at {}: {}",
+                address, error
+            );
+            return;
+        }
+    };
+    if stream.set_read_timeout(Some(grace)).is_err()
+        || stream.write_all(&world.to_le_bytes()).is_err()
+    {
+        return;
+    }
+
+    let mut heartbeat = [0u8; 1];
+    let closed = loop {
+        match stream.read(&mut heartbeat) {
+            Ok(1) => {}
+            Ok(_) => break true,
+            Err(_) => break false,
+        }
+    };
+
+    if closed {
+        eprintln!("mpirion: the parent closed the watchdog connection, exiting");
+    } else {
+        eprintln!(
+            "mpirion: the parent did not send a heartbeat within {:?}, exiting",
+            grace
+        );
+    }
+    std::process::exit(1);
+}
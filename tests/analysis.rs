@@ -0,0 +1,45 @@
+// Two kernels are compared from the samples criterion saves, so the comparison is checked against
+// real criterion output of a kernel and one that does ten times the work.
+use mpirion::analysis::{compare, comparison_file, save, Verdict};
+use mpirion::baseline::world_size_id;
+use mpirion::criterion::Criterion;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[test]
+fn more_work_is_slower_with_confidence() {
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("analysis");
+    let _ = std::fs::remove_dir_all(&home);
+    let mut criterion = Criterion::default()
+        .output_directory(&home)
+        .sample_size(20)
+        .warm_up_time(Duration::from_millis(10))
+        .measurement_time(Duration::from_millis(100))
+        .without_plots();
+    let mut group = criterion.benchmark_group("scan");
+    for (kernel, work) in [("ring", 1_000u32), ("tree", 10_000)] {
+        group.bench_with_input(world_size_id(kernel, 16), &work, |b, &work| {
+            b.iter(|| (0..work).map(std::hint::black_box).sum::<u32>())
+        });
+    }
+    group.finish();
+
+    let comparison = compare(&home, "scan/ring/p=16", "scan/tree/p=16").unwrap();
+    assert_eq!(comparison.verdict, Verdict::Slower, "{}", comparison);
+    assert!(comparison.lower > 0.0 && comparison.lower <= comparison.difference);
+    assert!(comparison.mean_b > comparison.mean_a);
+
+    let reverse = compare(&home, "scan/tree/p=16", "scan/ring/p=16").unwrap();
+    assert_eq!(reverse.verdict, Verdict::Faster, "{}", reverse);
+
+    let path = save(&home, &comparison).unwrap();
+    assert_eq!(
+        path,
+        comparison_file(&home, "scan/ring/p=16", "scan/tree/p=16")
+    );
+    let json = std::fs::read_to_string(path).unwrap();
+    assert!(json.contains(r#""verdict":"slower""#), "{}", json);
+
+    let missing = compare(&home, "scan/ring/p=16", "scan/hypercube/p=16").unwrap_err();
+    assert!(missing.contains("scan/hypercube/p=16"), "{}", missing);
+}
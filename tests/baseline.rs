@@ -0,0 +1,56 @@
+// mpirion benchmarks save ordinary criterion baselines, which critcmp compares. Two baselines of
+// a world size sweep are saved with criterion, read back like critcmp reads them, and compared.
+use mpirion::baseline::{compare, label, load, world_size_id};
+use mpirion::criterion::Criterion;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn save_sweep(home: &Path, baseline: &str, work: u32) {
+    let mut criterion = Criterion::default()
+        .output_directory(home)
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(10))
+        .measurement_time(Duration::from_millis(50))
+        .without_plots()
+        .save_baseline(baseline.to_string());
+    let mut group = criterion.benchmark_group("gossiping");
+    for world_size in [2u32, 4] {
+        group.bench_with_input(
+            world_size_id("all_to_all", world_size),
+            &world_size,
+            |b, &world_size| {
+                b.iter(|| {
+                    (0..work * world_size)
+                        .map(std::hint::black_box)
+                        .sum::<u32>()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+#[test]
+fn baselines_of_two_machines_compare_by_world_size() {
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("baseline");
+    let _ = std::fs::remove_dir_all(&home);
+    let (before, after) = (label("sweep", "node01"), label("sweep", "node02"));
+    save_sweep(&home, &before, 1_000);
+    save_sweep(&home, &after, 10_000);
+
+    let old = load(&home, &before);
+    let new = load(&home, &after);
+    let ids: Vec<&str> = new.iter().map(|entry| entry.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        ["gossiping/all_to_all/p=2", "gossiping/all_to_all/p=4"]
+    );
+    assert_eq!(old.len(), 2);
+
+    let ratios = compare(&old, &new);
+    assert_eq!(ratios.len(), 2);
+    for (id, ratio) in ratios {
+        // ten times the work takes longer on any machine
+        assert!(ratio > 1.0, "{} got faster by {}", id, ratio);
+    }
+}
@@ -0,0 +1,96 @@
+// REPORT.md is pasted into merge requests, so it is generated from the files in the output
+// directory of criterion alone, and the same files must always give the same report.
+use mpirion::campaign::{Campaign, REPORT_MD};
+use mpirion::report::{save, Metrics, RankStatistics};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn criterion_home(name: &str) -> PathBuf {
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("campaign")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    home
+}
+
+fn benchmark(home: &Path, id: &str, world_size: usize, mean_nanos: u64) -> PathBuf {
+    let directory = home.join(id);
+    std::fs::create_dir_all(directory.join("new")).unwrap();
+    let metrics = Metrics {
+        id: id.to_string(),
+        kernel: "scan_kernel".to_string(),
+        world_size,
+        samples: 10,
+        mean: Duration::from_nanos(mean_nanos),
+        spawn_share: Some(0.5),
+        max_variation: Some(0.01),
+        ranks: vec![
+            RankStatistics {
+                mean: Duration::from_nanos(mean_nanos),
+                min: Duration::ZERO,
+                max: Duration::ZERO
+            };
+            world_size
+        ],
+        ..Default::default()
+    };
+    save(&directory, &metrics).unwrap();
+    std::fs::write(
+        directory.join("new").join("estimates.json"),
+        format!(
+            r#"{{"mean":{{"confidence_interval":{{"confidence_level":0.95,"lower_bound":{},"upper_bound":{}}},"point_estimate":{},"standard_error":1.0}}}}"#,
+            mean_nanos - 100,
+            mean_nanos + 100,
+            mean_nanos
+        ),
+    )
+    .unwrap();
+    directory
+}
+
+#[test]
+fn report_lists_groups_scaling_environment_and_warnings() {
+    let home = criterion_home("complete");
+    benchmark(&home, "scan/p=4", 4, 5_000);
+    benchmark(&home, "scan/p=2", 2, 9_000);
+    let broadcast = benchmark(&home, "broadcast/p=2", 2, 1_000);
+    let mut truncated = mpirion::report::load(&broadcast).unwrap();
+    truncated.truncated_samples = 4;
+    save(&broadcast, &truncated).unwrap();
+    std::fs::write(
+        home.join("scaling-scan_kernel.csv"),
+        "world_size,mean_ns,stddev_ns,speedup,efficiency\n2,9000,0,1.000,1.000\n4,5000,0,1.800,0.900\n",
+    )
+    .unwrap();
+    std::fs::write(
+        home.join("mpirion-meta.json"),
+        r#"{"mpi_library":"Open MPI v4.1.5","mpi_version":"3.1","mpirion_version":"0.1.0","rustc_version":"rustc 1.80.0","profile":"release"}"#,
+    )
+    .unwrap();
+
+    let report = Campaign::load(&home).to_markdown(Some(25.0));
+    assert!(report.contains("3 benchmarks in 2 groups."));
+    assert!(report.contains("- MPI library: Open MPI v4.1.5\n"));
+    // groups and benchmarks are ordered by name
+    let broadcast = report.find("## Group `broadcast`").unwrap();
+    let scan = report.find("## Group `scan`").unwrap();
+    assert!(broadcast < scan);
+    assert!(report.find("[scan/p=2]").unwrap() < report.find("[scan/p=4]").unwrap());
+    assert!(report.contains("| [scan/p=4](scan/p=4/rank-distribution.svg) | 4 | - | 5.00µs | 4.90µs – 5.10µs | - | 1.00 | 50.0% |\n"));
+    assert!(report.contains("### `scan_kernel`"));
+    assert!(report.contains("1.80x"));
+    assert!(report.contains("- `broadcast/p=2`: 4 of 10 samples hit their max_sample_time and were extrapolated (truncated)\n"));
+}
+
+#[test]
+fn report_is_stable() {
+    let home = criterion_home("stable");
+    benchmark(&home, "gossip/p=8", 8, 12_345);
+    benchmark(&home, "gossip/p=2", 2, 23_456);
+    let first = Campaign::load(&home).to_markdown(Some(25.0));
+    std::fs::write(home.join(REPORT_MD), &first).unwrap();
+    assert_eq!(Campaign::load(&home).to_markdown(Some(25.0)), first);
+    assert!(first.ends_with("## Warnings\n\nNone.\n"));
+    assert!(first.contains("`mpirion-meta.json` was not found."));
+}
@@ -0,0 +1,75 @@
+// cargo criterion runs the benchmark binaries with --bench, the arguments after `--` and the port
+// of its IPC connection in CARGO_CRITERION_PORT. The arguments must reach criterion unchanged, the
+// mpirion flags must still be recognized, and the children must not see the connection.
+use mpirion::cli::{
+    dispatch, scrub_child_environment, split_flags, BenchOptions, Dispatch, CARGO_CRITERION_VARS,
+};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn cargo_criterion_arguments_run_the_benchmarks() {
+    let invocations: &[&[&str]] = &[
+        &["--bench"],
+        &["--bench", "prefix-sum"],
+        &["--bench", "--save-baseline", "main"],
+        &["--bench", "--profile-time", "5", "scan"],
+    ];
+
+    for invocation in invocations {
+        let invocation = args(invocation);
+        assert_eq!(
+            dispatch(&invocation),
+            Ok(Dispatch::Bench(BenchOptions::default())),
+            "{:?}",
+            invocation
+        );
+        let (flags, criterion_args) = split_flags(&invocation).unwrap();
+        assert!(flags.is_empty());
+        assert_eq!(criterion_args, invocation);
+    }
+}
+
+#[test]
+fn mpirion_flags_are_separated_from_cargo_criterion_arguments() {
+    let invocation = args(&[
+        "--bench",
+        "--mpirion-only-kernel",
+        "scan",
+        "scan",
+        "--save-baseline",
+        "main",
+    ]);
+    let only = BenchOptions {
+        only_kernel: Some("scan".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(dispatch(&invocation), Ok(Dispatch::Bench(only)));
+
+    let (flags, criterion_args) = split_flags(&invocation).unwrap();
+    assert_eq!(flags, args(&["--mpirion-only-kernel", "scan"]));
+    assert_eq!(
+        criterion_args,
+        args(&["--bench", "scan", "--save-baseline", "main"])
+    );
+}
+
+#[test]
+fn children_are_recognized_and_drop_the_connection() {
+    let child = args(&["--mpirion-child", "scan"]);
+    assert_eq!(dispatch(&child), Ok(Dispatch::Child("scan".to_string())));
+
+    for var in CARGO_CRITERION_VARS {
+        std::env::set_var(var, "40000");
+    }
+    scrub_child_environment();
+    for var in CARGO_CRITERION_VARS {
+        assert!(
+            std::env::var(var).is_err(),
+            "{} was inherited by the child",
+            var
+        );
+    }
+}
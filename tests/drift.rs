@@ -0,0 +1,54 @@
+// the children measure the drift of the iteration times of a sample with a DriftTracker. Synthetic
+// kernels with known iteration times stand in for kernels that get slower or faster over a sample.
+use mpirion::drift::{exceeds, DriftTracker, DEFAULT_THRESHOLD};
+use std::time::Duration;
+
+/// Measure the drift of a synthetic kernel, which returns the time of each iteration.
+fn drift_of(iterations: u64, kernel: impl Fn(u64) -> Duration) -> Option<f64> {
+    let mut drift = DriftTracker::new(iterations);
+    for iteration in 0..iterations {
+        drift.record(iteration, kernel(iteration));
+    }
+    drift.relative()
+}
+
+#[test]
+fn fragmenting_kernel_drifts_upwards() {
+    // every iteration is 1% slower than the first
+    let drift = drift_of(1000, |iteration| {
+        Duration::from_nanos(10_000 + 100 * iteration)
+    })
+    .unwrap();
+    assert!(drift > 0.0);
+    assert!(exceeds(drift, Some(DEFAULT_THRESHOLD)));
+}
+
+#[test]
+fn lazily_initialized_kernel_drifts_downwards() {
+    // the first tenth of the iterations pays for initialization
+    let drift = drift_of(1000, |iteration| {
+        Duration::from_micros(if iteration < 100 { 50 } else { 10 })
+    })
+    .unwrap();
+    assert!(drift < 0.0);
+    assert!(exceeds(drift, Some(DEFAULT_THRESHOLD)));
+}
+
+#[test]
+fn noisy_steady_kernel_stays_below_the_threshold() {
+    // alternating iteration times average out within each quartile
+    let drift = drift_of(1000, |iteration| {
+        Duration::from_micros(if iteration % 2 == 0 { 9 } else { 11 })
+    })
+    .unwrap();
+    assert!(!exceeds(drift, Some(DEFAULT_THRESHOLD)));
+}
+
+#[test]
+fn short_samples_have_no_drift() {
+    assert_eq!(
+        drift_of(15, |iteration| Duration::from_micros(10 * (iteration + 1))),
+        None
+    );
+    assert_eq!(drift_of(0, |_| Duration::from_micros(10)), None);
+}
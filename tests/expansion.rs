@@ -0,0 +1,16 @@
+// Kernels and setup functions with mismatching signatures must fail with errors that name the
+// mismatch. The expected errors are in the .stderr files next to the cases, regenerate them with
+// TRYBUILD=overwrite after intentional changes.
+#[test]
+fn signature_mismatches() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
+
+// The expansions of the macros must compile without any traits of criterion or mpi in scope, and
+// with the parameters in every type they accept.
+#[test]
+fn expansions_need_no_imports() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/pass/*.rs");
+}
@@ -0,0 +1,54 @@
+// The fingerprints of the hosts are stored in mpirion-meta.json next to the environment, where the
+// campaign report and the comparison with the next run read them back.
+use mpirion::campaign::Campaign;
+use mpirion::fingerprint::{differences, parse_meta, HostFingerprint};
+use mpirion::meta::{to_json, Environment, META_JSON};
+use std::path::PathBuf;
+
+fn node(host: &str, ucx: &str) -> HostFingerprint {
+    HostFingerprint {
+        host: host.to_string(),
+        cpu_model: Some("Intel(R) Xeon(R) Platinum 8360Y CPU @ 2.40GHz".to_string()),
+        cpus: Some(72),
+        memory: Some(256 << 30),
+        kernel: Some("4.18.0-477.el8.x86_64".to_string()),
+        variables: vec![
+            ("OMP_NUM_THREADS".to_string(), "1".to_string()),
+            ("UCX_TLS".to_string(), ucx.to_string()),
+        ],
+        interconnect: vec!["mlx5_0".to_string()],
+    }
+}
+
+#[test]
+fn fingerprints_are_read_back_from_the_metadata() {
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("fingerprint");
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    let environment = Environment {
+        mpi_library: "Open MPI v4.1.5".to_string(),
+        mpi_version: (3, 1),
+        mpirion_version: "0.1.0",
+        rustc_version: "rustc 1.80.0",
+        profile: "release",
+    };
+    let last = [node("node01", "rc,sm"), node("node02", "rc,sm")];
+    std::fs::write(home.join(META_JSON), to_json(&environment, &last)).unwrap();
+
+    let meta = std::fs::read_to_string(home.join(META_JSON)).unwrap();
+    assert_eq!(parse_meta(&meta), last);
+    let report = Campaign::load(&home).to_markdown(None);
+    assert!(
+        report.contains("- MPI library: Open MPI v4.1.5\n"),
+        "{}",
+        report
+    );
+    assert!(report.contains("- host `node02`: 72 CPUs, Intel(R) Xeon(R) Platinum 8360Y CPU @ 2.40GHz, 256.0 GiB, kernel 4.18.0-477.el8.x86_64, interconnect mlx5_0, OMP_NUM_THREADS=1, UCX_TLS=rc,sm\n"), "{}", report);
+
+    // another allocation of the same nodes is the same environment, other UCX transports are not
+    assert!(differences(&last, &[node("node07", "rc,sm")]).is_empty());
+    assert_eq!(
+        differences(&last, &[node("node07", "tcp")]),
+        ["UCX_TLS: tcp instead of rc,sm"]
+    );
+}
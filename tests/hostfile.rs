@@ -0,0 +1,44 @@
+// job scripts pass the hostfile of the job in MPIRION_HOSTFILE. It is read once per process, so this
+// binary has a single test that sets it before the first read. The slots of a Slurm nodelist come
+// from SLURM_TASKS_PER_NODE, which the job script inherits from Slurm.
+use mpirion::hostfile::{hostfile, Host, HOSTFILE_VAR, TASKS_PER_NODE_VAR};
+use mpirion::placement::PlacementMode;
+
+#[test]
+fn nodelist_of_the_job_is_read_with_the_slots_of_slurm() {
+    let path = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("hostfile")
+        .join("nodelist");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "# written by the job script\nnode[07-09]\n").unwrap();
+    std::env::set_var(HOSTFILE_VAR, &path);
+    std::env::set_var(TASKS_PER_NODE_VAR, "2(x2),1");
+
+    let hostfile = hostfile().expect("the hostfile is set");
+    assert_eq!(
+        hostfile.hosts,
+        [
+            Host {
+                name: "node07".to_string(),
+                slots: 2
+            },
+            Host {
+                name: "node08".to_string(),
+                slots: 2
+            },
+            Host {
+                name: "node09".to_string(),
+                slots: 1
+            },
+        ]
+    );
+    assert_eq!(hostfile.slots(), 5);
+
+    // the parent occupies a slot of its host, so the children fill the rest
+    let available = hostfile.without("node07", 1);
+    assert_eq!(
+        available.assign(PlacementMode::PerCore, 4).unwrap(),
+        ["node07", "node08", "node08", "node09"]
+    );
+    assert!(available.assign(PlacementMode::Spread(2), 4).is_err());
+}
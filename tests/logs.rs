@@ -0,0 +1,65 @@
+// long benchmarking campaigns create a log directory per run. Pruning keeps the most recent runs
+// and must never touch anything in the log root that isn't a run directory.
+use mpirion::logs::{prune, run_name};
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn log_root(name: &str) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("logs")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn runs(count: u64) -> Vec<String> {
+    (0..count)
+        .map(|day| run_name(UNIX_EPOCH + Duration::from_secs(1_792_067_405 + day * 86_400)))
+        .collect()
+}
+
+#[test]
+fn pruning_keeps_the_most_recent_runs() {
+    let root = log_root("recent");
+    for run in runs(5) {
+        std::fs::create_dir_all(root.join(&run).join("scan_kernel")).unwrap();
+        std::fs::write(
+            root.join(&run).join("scan_kernel").join("rank-0.log"),
+            "log",
+        )
+        .unwrap();
+    }
+
+    let removed = prune(&root, 3);
+    assert_eq!(
+        removed,
+        runs(2).iter().map(|run| root.join(run)).collect::<Vec<_>>()
+    );
+    let mut left: Vec<String> = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    left.sort();
+    assert_eq!(left, runs(5)[2..]);
+}
+
+#[test]
+fn pruning_leaves_other_files_alone() {
+    let root = log_root("other");
+    for run in runs(2) {
+        std::fs::create_dir_all(root.join(run)).unwrap();
+    }
+    std::fs::create_dir_all(root.join("notes")).unwrap();
+    std::fs::write(root.join("20200101-000000.txt"), "not a run").unwrap();
+
+    assert_eq!(prune(&root, 0).len(), 2);
+    assert!(root.join("notes").is_dir());
+    assert!(root.join("20200101-000000.txt").is_file());
+}
+
+#[test]
+fn missing_root_is_not_an_error() {
+    let root = log_root("missing").join("never created");
+    assert!(prune(&root, 1).is_empty());
+}
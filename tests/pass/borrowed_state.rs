@@ -0,0 +1,33 @@
+// the state of init may borrow from the communicator, and is handed to the teardown function
+use mpi::Rank;
+use mpirion::prelude::*;
+
+struct Handle<'a> {
+    comm: &'a dyn Communicator,
+    released: bool,
+}
+
+fn init(comm: &dyn Communicator) -> Handle<'_> {
+    Handle { comm, released: false }
+}
+
+fn reset(_comm: &dyn Communicator, state: &Handle<'_>) -> Rank {
+    state.comm.rank()
+}
+
+fn teardown(_comm: &dyn Communicator, mut state: Handle<'_>) {
+    state.released = true;
+    assert!(state.released);
+}
+
+fn rank_kernel(comm: &dyn Communicator, rank: &Rank) {
+    assert_eq!(comm.rank(), *rank);
+}
+
+fn benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    c.bench_function("borrowed", |b| mpirion_bench!(rank_kernel, b, ctx));
+}
+
+mpirion_kernel!(kernel = rank_kernel, init = init, reset = reset, teardown = teardown, data = ref);
+mpirion_group!(benches, benchmark);
+mpirion_main!(benches, rank_kernel);
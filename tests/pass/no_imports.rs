@@ -0,0 +1,88 @@
+// only the macros are imported, types are named by their full path and no traits are in scope
+use mpirion::*;
+
+fn setup(_comm: &dyn mpi::traits::Communicator, len: usize) -> Vec<u64> {
+    vec![0; len]
+}
+
+fn sum_kernel(_comm: &dyn mpi::traits::Communicator, data: &mut Vec<u64>) {
+    data.iter_mut().for_each(|value| *value += 1);
+}
+
+fn noop_kernel(_comm: &dyn mpi::traits::Communicator) {}
+
+fn rows_kernel(_comm: &dyn mpi::traits::Communicator, split: &mpirion::split::Split) {
+    assert_eq!(split.len(), 1);
+}
+
+fn threaded_kernel(comm: &dyn mpi::traits::Communicator) {
+    let comm = mpirion::threading::ThreadSafe::new(comm);
+    std::thread::scope(|scope| {
+        scope.spawn(move || mpi::traits::Communicator::size(&*comm));
+    });
+}
+
+fn grid_kernel(_comm: &dyn mpi::traits::Communicator) {
+    assert_eq!(mpirion::topology::grid().dims.len(), 2);
+}
+
+fn reordered_grid_kernel(_comm: &dyn mpi::traits::Communicator) {
+    assert_eq!(mpirion::topology::grid().dims.len(), 2);
+}
+
+fn ring(rank: mpi::Rank, size: mpi::Rank) -> Vec<mpi::Rank> {
+    vec![(rank + 1) % size]
+}
+
+fn ring_kernel(comm: &dyn mpi::traits::Communicator) {
+    let (send, mut receive) = ([1u64], [0u64]);
+    mpirion::topology::neighbor_all_to_all_into(comm, &send, &mut receive);
+}
+
+fn benchmark(c: &mut criterion::Criterion, ctx: &mpirion::MpirionContext) {
+    c.bench_function("sum", |b| mpirion_bench!(sum_kernel, b, ctx, 16usize));
+    c.bench_function("sum-sized", |b| mpirion_bench!(sum_kernel, b, ctx; world_size = 2, arg = 16usize));
+    c.bench_function("noop", |b| mpirion_bench!(noop_kernel, b, ctx));
+    c.bench_function("noop-retried", |b| {
+        mpirion_bench!(noop_kernel, b, ctx; timeout = std::time::Duration::from_secs(10), retry_on_timeout = 1)
+    });
+    c.bench_function("noop-capped", |b| {
+        mpirion_bench!(noop_kernel, b, ctx; max_sample_time = std::time::Duration::from_secs(30))
+    });
+    c.bench_function("noop-primed", |b| mpirion_bench!(noop_kernel, b, ctx; discard_first_sample = true));
+    c.bench_function("grid", |b| mpirion_bench!(grid_kernel, b, ctx));
+    c.bench_function("grid-reordered", |b| {
+        mpirion_bench!(reordered_grid_kernel, b, ctx; reorder = true)
+    });
+    c.bench_function("rows", |b| mpirion_bench!(rows_kernel, b, ctx));
+    c.bench_function("ring", |b| mpirion_bench!(ring_kernel, b, ctx));
+    c.bench_function("threaded", |b| mpirion_bench!(threaded_kernel, b, ctx));
+    c.bench_function("noop-per-node", |b| {
+        mpirion_bench!(noop_kernel, b, ctx; world_size = 2, placement = mpirion::placement::PlacementMode::PerNode)
+    });
+    c.bench_function("noop-pair", |b| {
+        mpirion_bench!(noop_kernel, b, ctx; world_size = 2, consumer = noop_kernel, consumers = 1, report = mpirion::pair::Report::Max)
+    });
+}
+
+mpirion_kernel!(sum_kernel, setup, usize);
+mpirion_kernel!(noop_kernel);
+mpirion_kernel!(kernel = grid_kernel, topology = cart(dims = [0, 0], periodic = [true, false]));
+mpirion_kernel!(
+    kernel = reordered_grid_kernel,
+    topology = cart(dims = [0, 0], periodic = [true, false], reorder = compare)
+);
+mpirion_kernel!(kernel = rows_kernel, split = mpirion::split::by_rows(2));
+mpirion_kernel!(kernel = ring_kernel, topology = graph(ring));
+mpirion_kernel!(kernel = threaded_kernel, threading = multiple);
+mpirion_group!(benches, benchmark);
+mpirion_main!(
+    benches,
+    sum_kernel,
+    noop_kernel,
+    grid_kernel,
+    reordered_grid_kernel,
+    rows_kernel,
+    ring_kernel,
+    threaded_kernel
+);
@@ -0,0 +1,37 @@
+// kernels take the data of the setup function by shared reference with `data = ref`, and by
+// mutable reference by default or with `data = mut`, also as a slice of a vector
+use mpirion::prelude::*;
+
+fn setup(comm: &dyn Communicator) -> Vec<u64> {
+    vec![comm.rank() as u64; 16]
+}
+
+fn sum_kernel(_comm: &dyn Communicator, data: &Vec<u64>) {
+    assert!(data.iter().sum::<u64>() < u64::MAX);
+}
+
+fn slice_kernel(_comm: &dyn Communicator, data: &[u64]) {
+    assert!(data.iter().sum::<u64>() < u64::MAX);
+}
+
+fn increment_kernel(_comm: &dyn Communicator, data: &mut Vec<u64>) {
+    data.push(1);
+}
+
+fn fill_kernel(_comm: &dyn Communicator, data: &mut [u64]) {
+    data.fill(1);
+}
+
+fn benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    c.bench_function("ref", |b| mpirion_bench!(sum_kernel, b, ctx));
+    c.bench_function("slice", |b| mpirion_bench!(slice_kernel, b, ctx));
+    c.bench_function("mut", |b| mpirion_bench!(increment_kernel, b, ctx));
+    c.bench_function("mut-slice", |b| mpirion_bench!(fill_kernel, b, ctx));
+}
+
+mpirion_kernel!(kernel = sum_kernel, setup = setup, data = ref);
+mpirion_kernel!(kernel = slice_kernel, setup = setup, data = ref);
+mpirion_kernel!(kernel = increment_kernel, setup = setup);
+mpirion_kernel!(kernel = fill_kernel, setup = setup, data = mut);
+mpirion_group!(benches, benchmark);
+mpirion_main!(benches, sum_kernel, slice_kernel, increment_kernel, fill_kernel);
@@ -0,0 +1,43 @@
+// kernels with the same function name in sibling modules are registered under distinct names
+use mpirion::*;
+
+mod scan {
+    use mpirion::*;
+
+    pub fn kernel(_comm: &dyn mpi::traits::Communicator) {}
+
+    mpirion_kernel!(kernel = kernel as scan_kernel);
+    export_kernels!(scan_kernel);
+}
+
+mod reduce {
+    use mpirion::*;
+
+    pub fn kernel(_comm: &dyn mpi::traits::Communicator) {}
+
+    mpirion_kernel!(kernel = kernel as reduce_kernel);
+    export_kernels!(reduce_kernel);
+}
+
+mod gather {
+    use mpirion::*;
+
+    #[mpirion::kernel(name = gather_kernel)]
+    pub fn kernel(_comm: &dyn mpi::traits::Communicator) {}
+
+    pub fn benchmark(c: &mut criterion::Criterion, ctx: &MpirionContext) {
+        c.bench_function("gather", |b| mpirion_bench!(gather_kernel, b, ctx));
+    }
+}
+
+import_kernels!(scan);
+import_kernels!(reduce);
+
+fn benchmark(c: &mut criterion::Criterion, ctx: &MpirionContext) {
+    c.bench_function("scan", |b| mpirion_bench!(scan_kernel, b, ctx));
+    c.bench_function("reduce", |b| mpirion_bench!(reduce_kernel, b, ctx));
+    gather::benchmark(c, ctx);
+}
+
+mpirion_group!(benches, benchmark);
+mpirion_main!(benches, scan_kernel, reduce_kernel);
@@ -0,0 +1,20 @@
+// world_size accepts any integer type, e.g. the loop variable of a sweep over world sizes
+use mpirion::*;
+
+fn noop_kernel(_comm: &dyn mpi::traits::Communicator) {}
+
+fn benchmark(c: &mut criterion::Criterion, ctx: &MpirionContext) {
+    for size in [2usize, 4, 8] {
+        c.bench_function("usize", |b| mpirion_bench!(noop_kernel, b, ctx; world_size = size));
+    }
+    for size in [2u32, 4, 8] {
+        c.bench_function("u32", |b| mpirion_bench!(noop_kernel, b, ctx; world_size = size));
+    }
+    for size in [2i32, 4, 8] {
+        c.bench_function("i32", |b| mpirion_bench!(kernel = noop_kernel, bencher = b, world = ctx, world_size = size));
+    }
+}
+
+mpirion_kernel!(noop_kernel);
+mpirion_group!(benches, benchmark);
+mpirion_main!(benches, noop_kernel);
@@ -0,0 +1,265 @@
+// downstream tools read the mpirion.json of a benchmark with report::load. The file must round-trip
+// the metrics, be replaced as a whole, and refuse files of a newer format. mpirion-samples.json is
+// joined with the sample.json of criterion by index, so it is written next to it, and the samples
+// are assigned to the benchmarks by the id they were recorded for.
+use mpirion::cli::SampleExport;
+use mpirion::launches::Launches;
+use mpirion::nb::{INITIATION, WAIT};
+use mpirion::placement::PlacementMode;
+use mpirion::report::{
+    benchmarks, load, record, save, save_samples, ArgumentValue, Benchmark, Metrics, Overhead,
+    RankStatistics, SampleRecord, Throughput, METRICS_JSON, METRICS_VERSION, SAMPLES_JSON,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn benchmark_directory(name: &str) -> PathBuf {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("report")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn metrics(world_size: usize) -> Metrics {
+    Metrics {
+        id: "scan/\"large\" input".to_string(),
+        kernel: "scan_kernel".to_string(),
+        world_size,
+        samples: 100,
+        mean: Duration::from_nanos(1_520),
+        throughput: Some(Throughput {
+            unit: "Elements".to_string(),
+            per_iteration: 1 << 20,
+        }),
+        spawn_share: Some(0.4),
+        spawn_overhead: Some(Duration::from_millis(250)),
+        overhead: Some(Overhead {
+            spawn: Duration::from_millis(200),
+            merge: Duration::from_millis(50),
+            broadcast: Duration::from_micros(120),
+            reduce: Duration::from_micros(80),
+        }),
+        max_variation: Some(0.031),
+        ranks: (0..world_size)
+            .map(|rank| RankStatistics {
+                mean: Duration::from_nanos(1_500 + rank as u64),
+                min: Duration::from_nanos(1_400),
+                max: Duration::from_nanos(2_000),
+            })
+            .collect(),
+        hosts: (0..world_size)
+            .map(|rank| format!("node{:02}", rank / 4))
+            .collect(),
+        argument: Some(ArgumentValue::of(&(1u64 << 20))),
+        truncated_samples: 2,
+        oversubscribed_hosts: vec!["node00".to_string()],
+        launches: Launches {
+            spawn_rounds: 104,
+            children: 104 * world_size as u64,
+            child_cpu: Duration::from_millis(61_250),
+        },
+        threads: Some(4),
+        tuning: vec![
+            (
+                "OMPI_MCA_coll_tuned_use_dynamic_rules".to_string(),
+                "1".to_string(),
+            ),
+            (
+                "OMPI_MCA_coll_tuned_bcast_algorithm".to_string(),
+                "6".to_string(),
+            ),
+        ],
+        placement_mode: Some(PlacementMode::PerNode),
+        achieved_placement: Some(PlacementMode::PerNode),
+        ranks_permuted: Some(false),
+        counters: vec![
+            (INITIATION.to_string(), Duration::from_nanos(90)),
+            (WAIT.to_string(), Duration::from_nanos(1_210)),
+        ],
+        ..Default::default()
+    }
+}
+
+fn sample(benchmark: &str, iterations: u64, reported: u64, per_rank: [u64; 2]) -> SampleRecord {
+    SampleRecord {
+        benchmark: Some(benchmark.to_string()),
+        kernel: "scan_kernel".to_string(),
+        iterations,
+        reported: Duration::from_nanos(reported),
+        per_rank: per_rank.map(Duration::from_nanos).to_vec(),
+        placement: None,
+        overhead: None,
+        argument: None,
+        truncated: false,
+        launches: Launches::NONE,
+        threads: None,
+        tuning: Vec::new(),
+        placement_mode: None,
+        ranks_permuted: None,
+        counters: Vec::new(),
+    }
+}
+
+#[test]
+fn saved_metrics_load_unchanged() {
+    let directory = benchmark_directory("round_trip");
+    save(&directory, &metrics(8)).unwrap();
+    assert_eq!(load(&directory), Ok(metrics(8)));
+}
+
+#[test]
+fn saving_replaces_the_whole_file() {
+    let directory = benchmark_directory("replace");
+    save(&directory, &metrics(16)).unwrap();
+    save(&directory, &metrics(2)).unwrap();
+    assert_eq!(load(&directory), Ok(metrics(2)));
+
+    // no temporary file is left behind
+    let files: Vec<_> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(files, [METRICS_JSON]);
+}
+
+#[test]
+fn local_samples_have_no_spawn_overhead_or_hosts() {
+    let directory = benchmark_directory("local");
+    let full = metrics(4);
+    let local = Metrics {
+        id: full.id,
+        kernel: full.kernel,
+        world_size: full.world_size,
+        samples: full.samples,
+        mean: full.mean,
+        ranks: full.ranks,
+        ..Default::default()
+    };
+    save(&directory, &local).unwrap();
+    assert_eq!(load(&directory), Ok(local));
+}
+
+#[test]
+fn newer_formats_and_missing_files_are_errors() {
+    let directory = benchmark_directory("newer");
+    let json = metrics(2).to_json().replacen(
+        &format!("\"version\":{}", METRICS_VERSION),
+        &format!("\"version\":{}", METRICS_VERSION + 1),
+        1,
+    );
+    std::fs::write(directory.join(METRICS_JSON), json).unwrap();
+    assert!(load(&directory)
+        .unwrap_err()
+        .contains("reads up to version"));
+
+    assert!(load(benchmark_directory("missing"))
+        .unwrap_err()
+        .contains("could not read"));
+}
+
+#[test]
+fn files_of_older_runs_have_no_phases() {
+    let directory = benchmark_directory("older");
+    let json = metrics(2).to_json().replacen(
+        &format!("\"version\":{}", METRICS_VERSION),
+        "\"version\":2",
+        1,
+    );
+    std::fs::write(directory.join(METRICS_JSON), json).unwrap();
+    let older = load(&directory).unwrap();
+    assert_eq!(older.overhead, None);
+    assert_eq!(older.launches, Launches::NONE);
+    assert_eq!(older.threads, None);
+    assert!(older.tuning.is_empty());
+    assert_eq!(older.placement_mode, None);
+    assert_eq!(older.ranks_permuted, None);
+    assert!(older.counters.is_empty());
+    assert_eq!(older.spawn_overhead, metrics(2).spawn_overhead);
+}
+
+#[test]
+fn samples_are_written_next_to_the_samples_of_criterion() {
+    let directory = benchmark_directory("samples");
+    let sample_file = directory.join("new").join("sample.json");
+    std::fs::create_dir_all(sample_file.parent().unwrap()).unwrap();
+    std::fs::write(
+        &sample_file,
+        r#"{"sampling_mode":"Linear","iters":[10.0,20.0],"times":[1100.0,2300.0]}"#,
+    )
+    .unwrap();
+    let benchmark = Benchmark {
+        id: "scan/p=2".to_string(),
+        directory: directory.clone(),
+        samples: vec![
+            sample("scan/p=2", 10, 1_100, [1_000, 1_200]),
+            sample("scan/p=2", 20, 2_300, [2_400, 2_200]),
+        ],
+        sample_file: sample_file.clone(),
+        throughput: None,
+    };
+
+    let path = save_samples(&benchmark, SampleExport::Raw).unwrap();
+    assert_eq!(path, sample_file.with_file_name(SAMPLES_JSON));
+    let json = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        json.contains(r#"{"index":1,"iterations":20,"time_ns":2300,"rank_ns":[2400,2200]}"#),
+        "{}",
+        json
+    );
+
+    save_samples(&benchmark, SampleExport::Summary).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+    assert!(json.contains(r#""slowest_rank":0}"#), "{}", json);
+    let files: Vec<_> = std::fs::read_dir(sample_file.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(files.len(), 2, "{:?}", files);
+}
+
+#[test]
+fn samples_belong_to_the_benchmark_they_were_recorded_for() {
+    let home = benchmark_directory("assigned");
+    for (directory, id) in [("ring", "scan/ring"), ("tree", "scan/tree")] {
+        let saved = home.join(directory).join("new");
+        std::fs::create_dir_all(&saved).unwrap();
+        std::fs::write(
+            saved.join("sample.json"),
+            r#"{"sampling_mode":"Linear","iters":[10.0,20.0],"times":[1100.0,2200.0]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            saved.join("benchmark.json"),
+            format!(r#"{{"group_id":"scan","full_id":"{}"}}"#, id),
+        )
+        .unwrap();
+    }
+    // both benchmarks report the same times, and criterion calls each once for the warm-up
+    for id in ["scan/ring", "scan/tree"] {
+        record(sample(id, 1, 110, [100, 120]));
+        record(sample(id, 10, 1_100, [1_000, 1_200]));
+        record(sample(id, 20, 2_200, [2_100, 2_300]));
+    }
+
+    let benchmarks = benchmarks(&home);
+    let assigned: Vec<(&str, Vec<(&str, u64)>)> = benchmarks
+        .iter()
+        .map(|benchmark| {
+            let samples = benchmark
+                .samples
+                .iter()
+                .map(|sample| (sample.benchmark.as_deref().unwrap(), sample.iterations))
+                .collect();
+            (benchmark.id.as_str(), samples)
+        })
+        .collect();
+    assert_eq!(
+        assigned,
+        [
+            ("scan/ring", vec![("scan/ring", 10), ("scan/ring", 20)]),
+            ("scan/tree", vec![("scan/tree", 10), ("scan/tree", 20)]),
+        ]
+    );
+}
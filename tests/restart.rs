@@ -0,0 +1,36 @@
+// A benchmark run with mpirion flags restarts itself without them and hands them over in
+// MPIRION_FLAGS. Runs the simple example with an unknown group: the restarted process must find
+// the group in the flags it was handed, and reject it before MPI is initialized, while criterion
+// never sees the flag.
+#[test]
+fn mpirion_flags_survive_the_restart() {
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let target_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/target/restart-test");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = std::process::Command::new(cargo)
+        .args([
+            "run",
+            "--quiet",
+            "--example",
+            "benchmark_simple",
+            "--manifest-path",
+            manifest,
+            "--target-dir",
+            target_dir,
+            "--",
+            "--bench",
+            "--mpirion-group",
+            "missing",
+        ])
+        .env_remove("MPIRION_FLAGS")
+        .output()
+        .expect("failed to run cargo");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr);
+    assert!(
+        stderr.contains("unknown group `missing` passed to --mpirion-group"),
+        "{}",
+        stderr
+    );
+}
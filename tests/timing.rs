@@ -0,0 +1,61 @@
+// the children send their times as u64 nanoseconds and their drift as i64 parts per million. The
+// conversions must round-trip the values a benchmark can produce, and saturate instead of wrapping
+// on values it cannot, whatever the pointer width of the target.
+use mpirion::timing::{
+    duration_to_nanos, mean, nanos_to_duration, parts_per_million_to_relative,
+    relative_to_parts_per_million,
+};
+use std::time::Duration;
+
+#[test]
+fn times_round_trip_through_nanoseconds() {
+    let times = [
+        Duration::ZERO,
+        Duration::from_nanos(1),
+        Duration::from_micros(999),
+        // u32::MAX nanoseconds and just beyond, where a 32-bit usize would wrap
+        Duration::from_nanos(u64::from(u32::MAX)),
+        Duration::from_nanos(u64::from(u32::MAX) + 1),
+        Duration::from_secs(3600),
+        Duration::from_nanos(u64::MAX),
+    ];
+    for time in times {
+        assert_eq!(nanos_to_duration(duration_to_nanos(time)), time);
+    }
+}
+
+#[test]
+fn times_beyond_the_wire_format_saturate() {
+    assert_eq!(
+        duration_to_nanos(Duration::from_nanos(u64::MAX) + Duration::from_nanos(1)),
+        u64::MAX
+    );
+    assert_eq!(duration_to_nanos(Duration::MAX), u64::MAX);
+}
+
+#[test]
+fn drift_round_trips_through_parts_per_million() {
+    for relative in [0.0, 0.1, -0.1, 0.123456, -0.999999, 2.5, 1000.0] {
+        let wire = relative_to_parts_per_million(relative);
+        assert!(
+            (parts_per_million_to_relative(wire) - relative).abs() <= 0.5e-6,
+            "{}",
+            relative
+        );
+    }
+    assert_eq!(relative_to_parts_per_million(f64::NEG_INFINITY), i64::MIN);
+    assert_eq!(relative_to_parts_per_million(1e300), i64::MAX);
+}
+
+#[test]
+fn mean_is_exact_for_long_times_on_many_ranks() {
+    // the sum of these times overflows a u64 of nanoseconds
+    let per_rank = vec![Duration::from_secs(3_000_000_000); 1024];
+    assert_eq!(mean(&per_rank), Duration::from_secs(3_000_000_000));
+
+    let per_rank = [
+        Duration::from_nanos(u64::MAX),
+        Duration::from_nanos(u64::MAX - 2),
+    ];
+    assert_eq!(mean(&per_rank), Duration::from_nanos(u64::MAX - 1));
+}
@@ -0,0 +1,13 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+fn setup(_comm: &dyn Communicator, len: u64) -> Vec<u64> {
+    vec![0; len as usize]
+}
+
+fn kernel(_comm: &dyn Communicator, _data: &mut Vec<u64>) {}
+
+// the declared argument type doesn't match the parameter of the setup function
+mpirion_kernel!(kernel, setup, u32);
+
+fn main() {}
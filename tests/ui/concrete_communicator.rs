@@ -0,0 +1,9 @@
+use mpi::topology::SimpleCommunicator;
+use mpirion::mpirion_kernel;
+
+// kernels take a &dyn Communicator, not a concrete communicator type
+fn kernel(_comm: &SimpleCommunicator) {}
+
+mpirion_kernel!(kernel);
+
+fn main() {}
@@ -0,0 +1,15 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+fn setup(_comm: &dyn Communicator) -> u64 {
+    0
+}
+
+// the kernel writes to the data, which `data = ref` only passes by shared reference
+fn kernel(_comm: &dyn Communicator, data: &mut u64) {
+    *data += 1;
+}
+
+mpirion_kernel!(kernel = kernel, setup = setup, data = ref);
+
+fn main() {}
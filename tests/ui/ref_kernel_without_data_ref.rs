@@ -0,0 +1,13 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+fn setup(_comm: &dyn Communicator) -> u64 {
+    0
+}
+
+// the kernel only reads the data, but the data is passed mutably unless `data = ref` is declared
+fn kernel(_comm: &dyn Communicator, _data: &u64) {}
+
+mpirion_kernel!(kernel, setup);
+
+fn main() {}
@@ -0,0 +1,13 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+fn setup(_comm: &dyn Communicator) -> Vec<u64> {
+    vec![0; 16]
+}
+
+// a replicated setup is broadcast as an MPI datatype, which a Vec<u64> is not
+fn kernel(_comm: &dyn Communicator, _data: &Vec<u64>) {}
+
+mpirion_kernel!(kernel = kernel, replicated_setup = setup, data = ref);
+
+fn main() {}
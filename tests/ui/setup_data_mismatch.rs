@@ -0,0 +1,13 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+fn setup(_comm: &dyn Communicator) -> Vec<u64> {
+    vec![0; 16]
+}
+
+// the setup function returns a Vec<u64>, but the kernel takes a u64
+fn kernel(_comm: &dyn Communicator, _data: &mut u64) {}
+
+mpirion_kernel!(kernel, setup);
+
+fn main() {}
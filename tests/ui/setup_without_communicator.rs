@@ -0,0 +1,13 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+// setup functions take a &dyn Communicator first
+fn setup() -> Vec<u64> {
+    vec![0; 16]
+}
+
+fn kernel(_comm: &dyn Communicator, _data: &mut Vec<u64>) {}
+
+mpirion_kernel!(kernel, setup);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use mpi::traits::Communicator;
+use mpirion::mpirion_kernel;
+
+// criterion runs threads next to the kernel, so a kernel cannot lower the level below funneled
+fn kernel(_comm: &dyn Communicator) {}
+
+mpirion_kernel!(kernel = kernel, threading = single);
+
+fn main() {}
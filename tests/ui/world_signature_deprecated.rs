@@ -0,0 +1,12 @@
+#![deny(deprecated)]
+
+use criterion::Criterion;
+use mpi::traits::Communicator;
+use mpirion::mpirion_group;
+
+// target functions that take the world instead of the context are deprecated
+fn world_benchmark(_c: &mut Criterion, _world: &dyn Communicator) {}
+
+mpirion_group!(benches, world_benchmark);
+
+fn main() {}
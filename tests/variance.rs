@@ -0,0 +1,34 @@
+// the parent warns when the times of the ranks of a sample vary more than the threshold. Synthetic
+// per-rank times stand in for benchmarks with and without a methodology problem.
+use mpirion::variance::{coefficient_of_variation, exceeds, DEFAULT_THRESHOLD};
+use std::time::Duration;
+
+#[test]
+fn balanced_ranks_stay_below_the_threshold() {
+    // a few percent of noise between 64 ranks
+    let per_rank: Vec<Duration> = (0..64)
+        .map(|rank| Duration::from_micros(1000 + rank % 5 * 10))
+        .collect();
+    let variation = coefficient_of_variation(&per_rank).unwrap();
+    assert!(!exceeds(variation, Some(DEFAULT_THRESHOLD)));
+}
+
+#[test]
+fn missing_barrier_exceeds_the_threshold() {
+    // rank 0 starts the clock late and misses most of the work of the others
+    let mut per_rank = vec![Duration::from_micros(1000); 8];
+    per_rank[0] = Duration::from_micros(10);
+    let variation = coefficient_of_variation(&per_rank).unwrap();
+    assert!(exceeds(variation, Some(DEFAULT_THRESHOLD)));
+}
+
+#[test]
+fn asymmetric_work_exceeds_the_threshold() {
+    // half of the ranks get twice the work
+    let per_rank: Vec<Duration> = (0..16)
+        .map(|rank| Duration::from_micros(if rank % 2 == 0 { 2000 } else { 1000 }))
+        .collect();
+    let variation = coefficient_of_variation(&per_rank).unwrap();
+    assert!((variation - 1.0 / 3.0).abs() < 1e-9);
+    assert!(exceeds(variation, Some(DEFAULT_THRESHOLD)));
+}
@@ -0,0 +1,24 @@
+// Kernels exported from a library crate with export_kernels! must be usable from the benchmark
+// binaries of another crate. Builds the workspace in tests/workspace, which has a kernels library
+// and two benchmark binaries.
+#[test]
+fn kernels_across_crates() {
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/workspace/Cargo.toml");
+    let target_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/target/workspace-test");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = std::process::Command::new(cargo)
+        .args([
+            "build",
+            "--benches",
+            "--manifest-path",
+            manifest,
+            "--target-dir",
+            target_dir,
+        ])
+        .status()
+        .expect("failed to run cargo");
+    assert!(
+        status.success(),
+        "the workspace in tests/workspace failed to build"
+    );
+}
@@ -0,0 +1,19 @@
+use mpirion::prelude::*;
+
+import_kernels!(shared_kernels);
+
+fn all_reduce_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "all-reduce", "all_reduce_kernel", |b| {
+        mpirion_bench!(all_reduce_kernel, b, ctx)
+    });
+}
+
+// register all kernels of the library instead of listing them
+fn register_shared_kernels() {
+    for kernel in shared_kernels::mpirion_kernels::KERNELS {
+        mpirion::register_kernel(Box::new(*kernel));
+    }
+}
+
+mpirion_group!(benches, all_reduce_benchmark);
+mpirion_main!(init = register_shared_kernels; groups = benches);
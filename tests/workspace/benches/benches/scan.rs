@@ -0,0 +1,12 @@
+use mpirion::prelude::*;
+
+import_kernels!(shared_kernels);
+
+fn scan_benchmark(c: &mut Criterion, ctx: &MpirionContext) {
+    ctx.bench_function(c, "prefix-sum", "scan_kernel", |b| {
+        mpirion_bench!(scan_kernel, b, ctx)
+    });
+}
+
+mpirion_group!(benches, scan_benchmark);
+mpirion_main!(benches, scan_kernel);
@@ -0,0 +1,21 @@
+//! Kernels shared by the benchmark binaries of the workspace.
+
+use mpirion::prelude::*;
+
+fn setup(comm: &dyn Communicator) -> u64 {
+    comm.rank() as u64
+}
+
+fn scan_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.scan_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+fn all_reduce_kernel(comm: &dyn Communicator, data: &u64) {
+    let mut recv_buffer = 0u64;
+    comm.all_reduce_into(data, &mut recv_buffer, SystemOperation::sum());
+}
+
+mpirion_kernel!(kernel = scan_kernel, setup = setup, data = ref);
+mpirion_kernel!(kernel = all_reduce_kernel, setup = setup, data = ref);
+export_kernels!(scan_kernel, all_reduce_kernel);